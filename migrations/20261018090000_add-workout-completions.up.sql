@@ -0,0 +1,18 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS workout_completions (
+    id bigserial NOT NULL PRIMARY KEY,
+    user_id integer NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    activity_id integer NOT NULL UNIQUE REFERENCES activities (id) ON DELETE CASCADE,
+    workout_instance_id integer NOT NULL REFERENCES workout_instances (id) ON DELETE CASCADE,
+    occurence_date timestamp with time zone NOT NULL,
+    manual boolean NOT NULL DEFAULT FALSE,
+    planned_duration integer,
+    planned_distance integer,
+    planned_load integer,
+    actual_duration integer NOT NULL,
+    actual_distance integer,
+    actual_load integer,
+    UNIQUE (workout_instance_id, occurence_date)
+);
+
+CREATE INDEX IF NOT EXISTS IX_workout_completions_user_date ON workout_completions (user_id, occurence_date);
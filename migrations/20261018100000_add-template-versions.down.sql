@@ -0,0 +1,19 @@
+-- Add down migration script here
+ALTER TABLE workout_instances
+    DROP COLUMN IF EXISTS valid_from,
+    DROP COLUMN IF EXISTS valid_until;
+
+DROP INDEX IF EXISTS UX_workout_templates_user_name;
+
+DELETE FROM workout_templates
+WHERE superseded;
+
+ALTER TABLE workout_templates
+    DROP COLUMN IF EXISTS version,
+    DROP COLUMN IF EXISTS previous_version_id,
+    DROP COLUMN IF EXISTS superseded,
+    DROP COLUMN IF EXISTS archived;
+
+ALTER TABLE workout_templates
+    ADD CONSTRAINT workout_templates_template_name_key UNIQUE (template_name);
+
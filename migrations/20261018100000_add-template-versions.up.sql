@@ -0,0 +1,17 @@
+-- Add up migration script here
+ALTER TABLE workout_templates DROP CONSTRAINT IF EXISTS workout_templates_template_name_key;
+
+ALTER TABLE workout_templates
+    ADD COLUMN version integer NOT NULL DEFAULT 1,
+    ADD COLUMN previous_version_id integer REFERENCES workout_templates (id) ON DELETE SET NULL,
+    ADD COLUMN superseded boolean NOT NULL DEFAULT FALSE,
+    ADD COLUMN archived boolean NOT NULL DEFAULT FALSE;
+
+CREATE UNIQUE INDEX IF NOT EXISTS UX_workout_templates_user_name ON workout_templates (user_id, template_name)
+WHERE
+    NOT superseded;
+
+ALTER TABLE workout_instances
+    ADD COLUMN valid_from timestamp with time zone,
+    ADD COLUMN valid_until timestamp with time zone;
+
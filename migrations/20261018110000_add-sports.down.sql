@@ -0,0 +1,18 @@
+-- Add down migration script here
+ALTER TABLE slope_speed
+    DROP COLUMN IF EXISTS sport_id;
+
+ALTER TABLE laps
+    DROP COLUMN IF EXISTS sub_sport,
+    DROP COLUMN IF EXISTS sport_id;
+
+ALTER TABLE sessions
+    DROP COLUMN IF EXISTS sub_sport,
+    DROP COLUMN IF EXISTS sport_id;
+
+ALTER TABLE workout_templates
+    DROP COLUMN IF EXISTS sport_id;
+
+DROP TABLE IF EXISTS sport_fit_mappings;
+
+DROP TABLE IF EXISTS sports;
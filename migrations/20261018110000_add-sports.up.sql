@@ -0,0 +1,225 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS sports (
+    id bigserial NOT NULL PRIMARY KEY,
+    -- NULL for the built-in sports shared by all users
+    user_id integer REFERENCES users (id) ON DELETE CASCADE,
+    parent_id bigint REFERENCES sports (id) ON DELETE CASCADE,
+    name text NOT NULL,
+    workout_type workout_type NOT NULL
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS UX_sports_builtin_name ON sports (name)
+WHERE
+    user_id IS NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS UX_sports_user_name ON sports (user_id, name)
+WHERE
+    user_id IS NOT NULL;
+
+CREATE INDEX IF NOT EXISTS IX_sports_parent ON sports (parent_id);
+
+-- sport and sub_sport as named in the FIT profile, a NULL sub_sport matches any sub sport
+CREATE TABLE IF NOT EXISTS sport_fit_mappings (
+    id bigserial NOT NULL PRIMARY KEY,
+    sport_id bigint NOT NULL REFERENCES sports (id) ON DELETE CASCADE,
+    fit_sport varchar(50) NOT NULL,
+    fit_sub_sport varchar(50)
+);
+
+CREATE INDEX IF NOT EXISTS IX_sport_fit_mappings ON sport_fit_mappings (fit_sport, fit_sub_sport);
+
+INSERT INTO sports (name, workout_type)
+    VALUES ('Running', 'run'),
+    ('Cycling', 'cycling'),
+    ('Swimming', 'endurance'),
+    ('Rowing', 'endurance'),
+    ('Paddling', 'endurance'),
+    ('Skiing', 'endurance'),
+    ('Hiking', 'hiking'),
+    ('Training', 'strength'),
+    ('Climbing', 'strength'),
+    ('Inline Skating', 'endurance');
+
+INSERT INTO sports (parent_id, name, workout_type)
+SELECT
+    p.id,
+    c.name,
+    p.workout_type
+FROM (
+    VALUES ('Running', 'Trail Running'),
+        ('Running', 'Treadmill Running'),
+        ('Running', 'Track Running'),
+        ('Running', 'Indoor Running'),
+        ('Running', 'Ultra Running'),
+        ('Cycling', 'Road Cycling'),
+        ('Cycling', 'Mountain Biking'),
+        ('Cycling', 'Gravel Cycling'),
+        ('Cycling', 'Cyclocross'),
+        ('Cycling', 'Indoor Cycling'),
+        ('Cycling', 'Virtual Cycling'),
+        ('Cycling', 'E-Biking'),
+        ('Swimming', 'Pool Swimming'),
+        ('Swimming', 'Open Water Swimming'),
+        ('Rowing', 'Indoor Rowing'),
+        ('Paddling', 'Kayaking'),
+        ('Paddling', 'Stand Up Paddleboarding'),
+        ('Skiing', 'Alpine Skiing'),
+        ('Skiing', 'Cross-Country Skiing'),
+        ('Skiing', 'Snowboarding'),
+        ('Hiking', 'Walking'),
+        ('Hiking', 'Mountaineering'),
+        ('Hiking', 'Snowshoeing'),
+        ('Training', 'Strength Training'),
+        ('Training', 'Cardio Training'),
+        ('Training', 'HIIT'),
+        ('Training', 'Yoga'),
+        ('Training', 'Pilates'),
+        ('Training', 'Flexibility Training'),
+        ('Training', 'Fitness Equipment'),
+        ('Climbing', 'Indoor Climbing'),
+        ('Climbing', 'Bouldering')) AS c (parent, name)
+    INNER JOIN sports p ON p.name = c.parent
+        AND p.user_id IS NULL;
+
+INSERT INTO sports (parent_id, name, workout_type)
+SELECT
+    p.id,
+    c.name,
+    p.workout_type
+FROM (
+    VALUES ('Alpine Skiing', 'Backcountry Skiing'),
+        ('Cross-Country Skiing', 'Skate Skiing'),
+        ('Fitness Equipment', 'Elliptical'),
+        ('Fitness Equipment', 'Stair Climbing')) AS c (parent, name)
+    INNER JOIN sports p ON p.name = c.parent
+        AND p.user_id IS NULL;
+
+INSERT INTO sport_fit_mappings (sport_id, fit_sport, fit_sub_sport)
+SELECT
+    s.id,
+    m.fit_sport,
+    m.fit_sub_sport
+FROM (
+    VALUES ('Running', 'running', NULL),
+        ('Trail Running', 'running', 'trail'),
+        ('Treadmill Running', 'running', 'treadmill'),
+        ('Treadmill Running', 'fitness_equipment', 'treadmill'),
+        ('Track Running', 'running', 'track'),
+        ('Indoor Running', 'running', 'indoor_running'),
+        ('Ultra Running', 'running', 'ultra'),
+        ('Cycling', 'cycling', NULL),
+        ('Road Cycling', 'cycling', 'road'),
+        ('Mountain Biking', 'cycling', 'mountain'),
+        ('Mountain Biking', 'cycling', 'downhill'),
+        ('Gravel Cycling', 'cycling', 'gravel_cycling'),
+        ('Cyclocross', 'cycling', 'cyclocross'),
+        ('Indoor Cycling', 'cycling', 'indoor_cycling'),
+        ('Indoor Cycling', 'cycling', 'spin'),
+        ('Indoor Cycling', 'fitness_equipment', 'indoor_cycling'),
+        ('Virtual Cycling', 'cycling', 'virtual_activity'),
+        ('E-Biking', 'e_biking', NULL),
+        ('Swimming', 'swimming', NULL),
+        ('Pool Swimming', 'swimming', 'lap_swimming'),
+        ('Open Water Swimming', 'swimming', 'open_water'),
+        ('Rowing', 'rowing', NULL),
+        ('Indoor Rowing', 'rowing', 'indoor_rowing'),
+        ('Indoor Rowing', 'fitness_equipment', 'indoor_rowing'),
+        ('Paddling', 'paddling', NULL),
+        ('Kayaking', 'kayaking', NULL),
+        ('Stand Up Paddleboarding', 'stand_up_paddleboarding', NULL),
+        ('Alpine Skiing', 'alpine_skiing', NULL),
+        ('Backcountry Skiing', 'alpine_skiing', 'backcountry'),
+        ('Cross-Country Skiing', 'cross_country_skiing', NULL),
+        ('Skate Skiing', 'cross_country_skiing', 'skate_skiing'),
+        ('Snowboarding', 'snowboarding', NULL),
+        ('Hiking', 'hiking', NULL),
+        ('Walking', 'walking', NULL),
+        ('Mountaineering', 'mountaineering', NULL),
+        ('Snowshoeing', 'snowshoeing', NULL),
+        ('Training', 'training', NULL),
+        ('Strength Training', 'training', 'strength_training'),
+        ('Cardio Training', 'training', 'cardio_training'),
+        ('HIIT', 'training', 'hiit'),
+        ('HIIT', 'hiit', NULL),
+        ('Yoga', 'training', 'yoga'),
+        ('Pilates', 'training', 'pilates'),
+        ('Flexibility Training', 'training', 'flexibility_training'),
+        ('Fitness Equipment', 'fitness_equipment', NULL),
+        ('Elliptical', 'fitness_equipment', 'elliptical'),
+        ('Stair Climbing', 'fitness_equipment', 'stair_climbing'),
+        ('Climbing', 'rock_climbing', NULL),
+        ('Indoor Climbing', 'rock_climbing', 'indoor_climbing'),
+        ('Bouldering', 'rock_climbing', 'bouldering'),
+        ('Inline Skating', 'inline_skating', NULL)) AS m (name, fit_sport, fit_sub_sport)
+    INNER JOIN sports s ON s.name = m.name
+        AND s.user_id IS NULL;
+
+ALTER TABLE workout_templates
+    ADD COLUMN sport_id bigint REFERENCES sports (id) ON DELETE SET NULL;
+
+ALTER TABLE sessions
+    ADD COLUMN sub_sport varchar(50),
+    ADD COLUMN sport_id bigint REFERENCES sports (id) ON DELETE SET NULL;
+
+ALTER TABLE laps
+    ADD COLUMN sub_sport varchar(50),
+    ADD COLUMN sport_id bigint REFERENCES sports (id) ON DELETE SET NULL;
+
+ALTER TABLE slope_speed
+    ADD COLUMN sport_id bigint REFERENCES sports (id) ON DELETE SET NULL;
+
+-- general endurance templates stay without a sport and are fulfilled by any activity
+UPDATE
+    workout_templates t
+SET
+    sport_id = s.id
+FROM
+    sports s
+WHERE
+    s.user_id IS NULL
+    AND s.name = CASE t.workout_type
+    WHEN 'run' THEN
+        'Running'
+    WHEN 'cycling' THEN
+        'Cycling'
+    WHEN 'hiking' THEN
+        'Hiking'
+    WHEN 'strength' THEN
+        'Training'
+    END;
+
+UPDATE
+    sessions
+SET
+    sport_id = (
+        SELECT
+            m.sport_id
+        FROM
+            sport_fit_mappings m
+        WHERE
+            m.fit_sport = sessions.sport
+            AND m.fit_sub_sport IS NULL);
+
+UPDATE
+    laps
+SET
+    sport_id = (
+        SELECT
+            m.sport_id
+        FROM
+            sport_fit_mappings m
+        WHERE
+            m.fit_sport = laps.sport
+            AND m.fit_sub_sport IS NULL);
+
+UPDATE
+    slope_speed
+SET
+    sport_id = (
+        SELECT
+            m.sport_id
+        FROM
+            sport_fit_mappings m
+        WHERE
+            m.fit_sport = slope_speed.sport
+            AND m.fit_sub_sport IS NULL);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS strength_sets;
+
+DROP TABLE IF EXISTS exercises;
@@ -0,0 +1,28 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS exercises (
+    id bigserial NOT NULL PRIMARY KEY,
+    user_id integer NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name text NOT NULL,
+    -- exercise category as named in the FIT profile, e.g. bench_press
+    fit_category varchar(50),
+    UNIQUE (user_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS strength_sets (
+    id bigserial NOT NULL PRIMARY KEY,
+    activity_id integer NOT NULL REFERENCES activities (id) ON DELETE CASCADE,
+    exercise_id integer REFERENCES exercises (id) ON DELETE SET NULL,
+    position integer NOT NULL,
+    start_time timestamp with time zone,
+    -- seconds
+    duration float8,
+    repetitions integer,
+    -- kilograms
+    weight float8,
+    -- seconds of rest after the set
+    rest float8
+);
+
+CREATE INDEX IF NOT EXISTS IX_strength_sets_activity ON strength_sets (activity_id);
+
+CREATE INDEX IF NOT EXISTS IX_strength_sets_exercise ON strength_sets (exercise_id);
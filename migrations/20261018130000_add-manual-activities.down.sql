@@ -0,0 +1,8 @@
+-- Add down migration script here
+UPDATE sessions SET sport = 'generic' WHERE sport IS NULL;
+
+ALTER TABLE sessions
+    ALTER COLUMN sport SET NOT NULL;
+
+ALTER TABLE activities
+    DROP COLUMN IF EXISTS rpe;
@@ -0,0 +1,8 @@
+-- Add up migration script here
+ALTER TABLE activities
+    -- perceived exertion on the CR-10 scale
+    ADD COLUMN rpe smallint CHECK (rpe BETWEEN 0 AND 10);
+
+-- sessions entered by hand have no FIT sport, only a sport_id
+ALTER TABLE sessions
+    ALTER COLUMN sport DROP NOT NULL;
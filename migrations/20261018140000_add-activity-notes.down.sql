@@ -0,0 +1,14 @@
+-- Add down migration script here
+ALTER TABLE user_preferences
+    DROP COLUMN IF EXISTS load_model;
+
+DROP TYPE IF EXISTS load_model;
+
+DROP INDEX IF EXISTS IX_activities_tags;
+
+ALTER TABLE activities
+    DROP COLUMN IF EXISTS title,
+    DROP COLUMN IF EXISTS notes,
+    DROP COLUMN IF EXISTS feeling,
+    DROP COLUMN IF EXISTS tags,
+    DROP COLUMN IF EXISTS measured_load;
@@ -0,0 +1,27 @@
+-- Add up migration script here
+ALTER TABLE activities
+    ADD COLUMN title text,
+    ADD COLUMN notes text,
+    -- how the activity felt, from 1 (very weak) to 5 (very strong)
+    ADD COLUMN feeling smallint CHECK (feeling BETWEEN 1 AND 5),
+    ADD COLUMN tags text[] NOT NULL DEFAULT '{}',
+    -- load calculated from the recorded data, load is what the user's load model makes of it
+    ADD COLUMN measured_load int;
+
+UPDATE
+    activities
+SET
+    measured_load = LOAD
+WHERE
+    rpe IS NULL
+    OR avg_heartrate IS NOT NULL;
+
+CREATE INDEX IF NOT EXISTS IX_activities_tags ON activities USING GIN (tags);
+
+CREATE TYPE load_model AS ENUM (
+    'heartrate',
+    'session_rpe'
+);
+
+ALTER TABLE user_preferences
+    ADD COLUMN load_model load_model NOT NULL DEFAULT 'heartrate';
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS IX_activities_user_device;
+
+ALTER TABLE activities
+    DROP COLUMN IF EXISTS device;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+ALTER TABLE activities
+    -- device that recorded the activity, e.g. Garmin Fenix6
+    ADD COLUMN device text;
+
+CREATE INDEX IF NOT EXISTS IX_activities_user_device ON activities (user_id, device);
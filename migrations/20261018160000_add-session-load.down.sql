@@ -0,0 +1,9 @@
+-- Add down migration script here
+DROP VIEW IF EXISTS session_loads;
+
+DELETE FROM sports
+WHERE name = 'Transition'
+    AND user_id IS NULL;
+
+ALTER TABLE sessions
+    DROP COLUMN IF EXISTS load;
@@ -0,0 +1,46 @@
+-- Add up migration script here
+ALTER TABLE sessions
+    ADD COLUMN load integer;
+
+INSERT INTO sports (name, workout_type)
+    VALUES ('Transition', 'endurance');
+
+INSERT INTO sport_fit_mappings (sport_id, fit_sport, fit_sub_sport)
+SELECT
+    id,
+    'transition',
+    NULL
+FROM
+    sports
+WHERE
+    name = 'Transition'
+    AND user_id IS NULL;
+
+UPDATE
+    sessions
+SET
+    sport_id = (
+        SELECT
+            id
+        FROM
+            sports
+        WHERE
+            name = 'Transition'
+            AND user_id IS NULL)
+WHERE
+    sport = 'transition';
+
+-- the load of an activity split up between its sessions, by the heart rate load of each session
+-- or, without one, by the duration of each session
+CREATE VIEW session_loads AS
+SELECT
+    s.id AS session_id,
+    s.activity_id,
+    a.user_id,
+    s.start_time,
+    s.sport_id,
+    a.load * COALESCE(COALESCE(s.load, 0)::float8 / NULLIF(SUM(s.load) OVER w, 0), extract(epoch FROM s.end_time - s.start_time) / NULLIF(SUM(extract(epoch FROM s.end_time - s.start_time)) OVER w, 0), 1.0 / COUNT(*) OVER w) AS load
+FROM
+    sessions s
+    INNER JOIN activities a ON a.id = s.activity_id
+WINDOW w AS (PARTITION BY s.activity_id);
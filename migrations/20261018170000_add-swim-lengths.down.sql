@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS swim_lengths;
+
+ALTER TABLE user_preferences DROP COLUMN IF EXISTS critical_swim_speed;
+
+ALTER TABLE sessions DROP COLUMN IF EXISTS pool_length;
@@ -0,0 +1,24 @@
+-- Add up migration script here
+ALTER TABLE sessions
+    -- pool length in meters for pool swims
+    ADD COLUMN pool_length float8;
+
+ALTER TABLE user_preferences
+    -- critical swim speed in m/s, swim load is calculated relative to it
+    ADD COLUMN critical_swim_speed float8 NOT NULL DEFAULT 1.0;
+
+CREATE TABLE IF NOT EXISTS swim_lengths (
+    id bigserial PRIMARY KEY,
+    activity_id integer NOT NULL REFERENCES activities(id) ON DELETE CASCADE,
+    -- order of the length within the activity
+    position integer NOT NULL,
+    start_time timestamptz NOT NULL,
+    -- duration in seconds
+    duration float8 NOT NULL,
+    -- false for rests at the wall
+    active boolean NOT NULL,
+    stroke varchar(50),
+    strokes integer
+);
+
+CREATE INDEX IF NOT EXISTS IX_swim_lengths_activity_id ON swim_lengths (activity_id, position);
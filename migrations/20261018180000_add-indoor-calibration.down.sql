@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE activities
+    DROP COLUMN IF EXISTS gps_distance_ratio,
+    DROP COLUMN IF EXISTS footpod,
+    DROP COLUMN IF EXISTS distance_calibration,
+    DROP COLUMN IF EXISTS indoor;
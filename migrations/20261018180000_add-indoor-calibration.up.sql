@@ -0,0 +1,26 @@
+-- Add up migration script here
+ALTER TABLE activities
+    -- indoor activities have no usable GPS track and are left out of maps and slope-speed
+    ADD COLUMN indoor boolean NOT NULL DEFAULT false,
+    -- factor the recorded sensor distance and speed are scaled with
+    ADD COLUMN distance_calibration float8 NOT NULL DEFAULT 1.0,
+    -- footpod the distance of a run was recorded with
+    ADD COLUMN footpod text,
+    -- GPS distance divided by footpod distance for runs with both, to learn the calibration per
+    -- footpod and sport
+    ADD COLUMN gps_distance_ratio float8;
+
+UPDATE activities
+SET indoor = true
+WHERE EXISTS (
+    SELECT 1
+    FROM sessions
+    WHERE sessions.activity_id = activities.id
+        AND sessions.sub_sport IN ('treadmill', 'indoor_running', 'indoor_walking', 'indoor_cycling',
+            'indoor_rowing', 'indoor_skiing', 'indoor_climbing', 'virtual_activity')
+);
+
+DELETE FROM slope_speed
+USING activities
+WHERE activities.id = slope_speed.activity_id
+    AND activities.indoor;
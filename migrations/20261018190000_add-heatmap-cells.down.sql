@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS heatmap_cells;
+
+DROP FUNCTION IF EXISTS geohash_encode(float8, float8, integer);
@@ -0,0 +1,61 @@
+-- Add up migration script here
+-- standard geohash of a position, shared by the upload and the backfill below
+CREATE OR REPLACE FUNCTION geohash_encode(latitude float8, longitude float8, chars integer)
+RETURNS text AS $$
+DECLARE
+    base32 text := '0123456789bcdefghjkmnpqrstuvwxyz';
+    lat_min float8 := -90;
+    lat_max float8 := 90;
+    lon_min float8 := -180;
+    lon_max float8 := 180;
+    mid float8;
+    hash text := '';
+    bits integer := 0;
+    ch integer := 0;
+    even boolean := true;
+BEGIN
+    WHILE length(hash) < chars LOOP
+        IF even THEN
+            mid := (lon_min + lon_max) / 2;
+            IF longitude >= mid THEN
+                ch := ch * 2 + 1;
+                lon_min := mid;
+            ELSE
+                ch := ch * 2;
+                lon_max := mid;
+            END IF;
+        ELSE
+            mid := (lat_min + lat_max) / 2;
+            IF latitude >= mid THEN
+                ch := ch * 2 + 1;
+                lat_min := mid;
+            ELSE
+                ch := ch * 2;
+                lat_max := mid;
+            END IF;
+        END IF;
+        even := NOT even;
+        bits := bits + 1;
+        IF bits = 5 THEN
+            hash := hash || substr(base32, ch + 1, 1);
+            bits := 0;
+            ch := 0;
+        END IF;
+    END LOOP;
+    RETURN hash;
+END;
+$$ LANGUAGE plpgsql IMMUTABLE STRICT;
+
+-- number of recorded positions of an activity per geohash, the heatmap aggregates these
+CREATE TABLE IF NOT EXISTS heatmap_cells (
+    activity_id integer NOT NULL REFERENCES activities(id) ON DELETE CASCADE,
+    geohash varchar(12) NOT NULL,
+    points integer NOT NULL,
+    PRIMARY KEY (activity_id, geohash)
+);
+
+INSERT INTO heatmap_cells (activity_id, geohash, points)
+SELECT activity_id, geohash_encode(latitude, longitude, 7), COUNT(*)
+FROM records
+WHERE latitude IS NOT NULL AND longitude IS NOT NULL
+GROUP BY activity_id, geohash_encode(latitude, longitude, 7);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS segment_efforts;
+
+DROP TABLE IF EXISTS segments;
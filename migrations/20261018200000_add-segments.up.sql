@@ -0,0 +1,29 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS segments (
+    id bigserial NOT NULL PRIMARY KEY,
+    user_id integer NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name text NOT NULL,
+    -- track of the segment, thinned out to a point every 20m
+    latitudes float8[] NOT NULL,
+    longitudes float8[] NOT NULL,
+    -- meters
+    distance float8 NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS IX_segments_user ON segments (user_id);
+
+CREATE TABLE IF NOT EXISTS segment_efforts (
+    id bigserial NOT NULL PRIMARY KEY,
+    segment_id bigint NOT NULL REFERENCES segments (id) ON DELETE CASCADE,
+    activity_id integer NOT NULL REFERENCES activities (id) ON DELETE CASCADE,
+    start_time timestamp with time zone NOT NULL,
+    end_time timestamp with time zone NOT NULL,
+    -- seconds
+    duration float8 NOT NULL,
+    average_heartrate smallint,
+    average_power smallint
+);
+
+CREATE INDEX IF NOT EXISTS IX_segment_efforts_segment ON segment_efforts (segment_id, duration);
+
+CREATE INDEX IF NOT EXISTS IX_segment_efforts_activity ON segment_efforts (activity_id);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+UPDATE records
+SET altitude = raw_altitude
+WHERE raw_altitude IS NOT NULL;
+
+ALTER TABLE records
+    DROP COLUMN raw_altitude;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE records
+    ADD COLUMN raw_altitude NUMERIC(6, 1);
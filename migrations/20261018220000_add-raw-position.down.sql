@@ -0,0 +1,9 @@
+-- Add down migration script here
+UPDATE records
+SET latitude = raw_latitude,
+    longitude = raw_longitude
+WHERE raw_latitude IS NOT NULL OR raw_longitude IS NOT NULL;
+
+ALTER TABLE records
+    DROP COLUMN raw_latitude,
+    DROP COLUMN raw_longitude;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE records
+    ADD COLUMN raw_latitude float8,
+    ADD COLUMN raw_longitude float8;
@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS heartrate_recoveries (
+    activity_id integer NOT NULL PRIMARY KEY REFERENCES activities (id) ON DELETE CASCADE,
+    -- hard efforts followed by an easy period
+    efforts integer NOT NULL,
+    -- average drop in bpm one and two minutes after the efforts
+    recovery_60 float8,
+    recovery_120 float8,
+    -- percent the heart rate rose relative to speed or power from the first to the second half
+    cardiac_drift float8
+);
@@ -0,0 +1,14 @@
+-- Add up migration script here
+-- a sport and all of its sub sports, for filtering by sport
+CREATE OR REPLACE FUNCTION sport_subtree (root bigint)
+    RETURNS SETOF bigint
+    LANGUAGE sql
+    STABLE
+    AS $$
+    WITH RECURSIVE sport_tree AS (
+        SELECT id FROM sports WHERE id = root
+        UNION
+        SELECT sports.id FROM sports INNER JOIN sport_tree ON sports.parent_id = sport_tree.id
+    )
+    SELECT id FROM sport_tree
+$$;
@@ -5,9 +5,12 @@ use crate::{
         activity_overview::ActivityList,
         auth::{login::Login, signup::Signup},
         fit_upload::FitUploadForm,
+        heatmap::Heatmap,
         home::Home,
         landing::Landing,
         overview::Overview,
+        route_planner::RoutePlanner,
+        segments::Segments,
         user::UserSettings,
         workout_schedule::WorkoutCalendar,
     },
@@ -129,6 +132,18 @@ pub fn App() -> impl IntoView {
                                                         <A href="/calendar" class="navbar-item">
                                                             Calendar
                                                         </A>
+
+                                                        <A href="/heatmap" class="navbar-item">
+                                                            Heatmap
+                                                        </A>
+
+                                                        <A href="/segments" class="navbar-item">
+                                                            Segments
+                                                        </A>
+
+                                                        <A href="/planner" class="navbar-item">
+                                                            Route planner
+                                                        </A>
                                                         <a
                                                             href="#"
                                                             class="navbar-item"
@@ -178,6 +193,9 @@ pub fn App() -> impl IntoView {
 
                     <Route path="/activities" view=ActivityList/>
                     <Route path="/calendar" view=WorkoutCalendar/>
+                    <Route path="/heatmap" view=Heatmap/>
+                    <Route path="/segments" view=Segments/>
+                    <Route path="/planner" view=RoutePlanner/>
 
                 </Route>
             </Routes>
@@ -6,6 +6,8 @@ cfg_if::cfg_if! {
         };
         use once_cell::sync::OnceCell;
         use serde::Deserialize;
+        use std::path::PathBuf;
+        use crate::models::track_cleanup::TrackCleanup;
     }
 }
 
@@ -24,6 +26,11 @@ cfg_if::cfg_if! {
 #[derive(Deserialize, Debug)]
 pub struct Config {
     pub database_url: String,
+    /// Directory of SRTM HGT tiles to correct the altitude of GPS activities with.
+    #[serde(default)]
+    pub dem_path: Option<PathBuf>,
+    #[serde(default)]
+    pub gps_cleanup: TrackCleanup,
 }
 
 #[cfg(feature = "ssr")]
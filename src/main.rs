@@ -8,8 +8,10 @@ cfg_if! {
         use toedirs::app::*;
         use toedirs::authentication::*;
         use toedirs::pages::fit_upload::upload_fit_file;
+        use toedirs::pages::activity_overview::activity_export::export_activity;
         use toedirs::state::AppState;
         use toedirs::config::Config;
+        use toedirs::models::heartrate_recovery::backfill_heartrate_recovery;
         use toedirs::fileserv::file_and_error_handler;
         use sqlx::{PgPool,ConnectOptions, migrate, {postgres::{PgPoolOptions,PgConnectOptions}}};
 
@@ -85,6 +87,16 @@ async fn main() {
 
     simple_logger::init_with_level(log::Level::Warn).expect("couldn't initialize logging");
 
+    // activities uploaded before heart rate recovery was tracked
+    tokio::spawn({
+        let pool = pool.clone();
+        async move {
+            if let Err(e) = backfill_heartrate_recovery(&pool).await {
+                log::error!("couldn't backfill heart rate recovery: {}", e);
+            }
+        }
+    });
+
     // Setting get_configuration(None) means we'll be using cargo-leptos's env values
     // For deployment these variables are:
     // <https://github.com/leptos-rs/start-axum#executing-a-server-on-a-remote-machine-without-the-toolchain>
@@ -107,6 +119,10 @@ async fn main() {
             get(server_fn_handler).post(server_fn_handler),
         )
         .route("/api/upload_fit_file", post(upload_fit_file))
+        .route(
+            "/api/export_activity/:activity_id/:format",
+            get(export_activity),
+        )
         .leptos_routes_with_handler(routes, get(leptos_routes_handler))
         .fallback(file_and_error_handler)
         .layer(NewSentryLayer::new_from_top())
@@ -1,11 +1,13 @@
 use chrono::{DateTime, Duration, Local};
 use fitparser::{profile::MesgNum, FitDataRecord, Value};
 #[cfg(feature = "ssr")]
-use sqlx::{query, Row};
+use sqlx::{query, PgConnection, Row};
 
 #[cfg(feature = "ssr")]
 use super::base::Stored;
-use super::base::{DatabaseEntry, ModelError, New};
+use super::base::{display_name, DatabaseEntry, ModelError, New};
+#[cfg(feature = "ssr")]
+use super::user_preferences::{get_user_preferences, LoadModel};
 
 #[non_exhaustive]
 #[derive(Debug, Clone)]
@@ -16,6 +18,20 @@ pub struct Activity {
     pub duration: f64,
     pub load: Option<u32>,
     pub avg_heartrate: Option<u16>,
+    /// Perceived exertion on the CR-10 scale.
+    pub rpe: Option<i16>,
+    /// The device that recorded the activity, e.g. `Garmin Fenix6`.
+    pub device: Option<String>,
+    pub indoor: bool,
+    /// The footpod that recorded the distance, e.g. `Stryd 123456`.
+    pub footpod: Option<String>,
+    /// GPS distance divided by the footpod distance, for outdoor runs with both.
+    pub gps_distance_ratio: Option<f64>,
+}
+
+/// Session-RPE load after Foster, the perceived exertion times the duration in minutes.
+pub fn session_rpe_load(rpe: i16, duration: f64) -> u32 {
+    (rpe as f64 * duration / 60.0).round() as u32
 }
 impl TryFrom<FitDataRecord> for DatabaseEntry<New, Activity> {
     type Error = ModelError;
@@ -62,12 +78,42 @@ impl TryFrom<FitDataRecord> for DatabaseEntry<New, Activity> {
                 duration,
                 load: None,
                 avg_heartrate: None,
+                rpe: None,
+                device: None,
+                indoor: false,
+                footpod: None,
+                gps_distance_ratio: None,
             }),
             extra: New,
         })
     }
 }
 
+/// Name of the device that created a FIT file, from its `file_id` message.
+pub fn device_name(value: &FitDataRecord) -> Option<String> {
+    if !matches!(value.kind(), MesgNum::FileId) {
+        return None;
+    }
+    let text = |name: &str| {
+        value
+            .fields()
+            .iter()
+            .find(|&f| f.name() == name)
+            .and_then(|f| match f.value() {
+                Value::String(val) if !val.trim().is_empty() => Some(val.trim().to_string()),
+                _ => None,
+            })
+    };
+    let manufacturer = text("manufacturer").map(|m| display_name(&m));
+    let product = text("product_name")
+        .or(text("garmin_product").map(|p| display_name(&p)))
+        .or(text("product").map(|p| display_name(&p)));
+    match (manufacturer, product) {
+        (Some(manufacturer), Some(product)) => Some(format!("{} {}", manufacturer, product)),
+        (manufacturer, product) => manufacturer.or(product),
+    }
+}
+
 #[cfg(feature = "ssr")]
 pub async fn insert_activity(
     activity: DatabaseEntry<New, Activity>,
@@ -76,8 +122,8 @@ pub async fn insert_activity(
 ) -> Result<DatabaseEntry<Stored, Activity>, ModelError> {
     let result = query(
         r#"
-        INSERT INTO activities (user_id, start_time, end_time, duration,avg_heartrate,load)
-        VALUES ($1, $2::timestamptz, $3::timestamptz,$4,$5,$6)
+        INSERT INTO activities (user_id, start_time, end_time, duration,avg_heartrate,load,measured_load,rpe,device,indoor,footpod,gps_distance_ratio)
+        VALUES ($1, $2::timestamptz, $3::timestamptz,$4,$5,$6,$6,$7,$8,$9,$10,$11)
         RETURNING id
         "#,
     )
@@ -87,6 +133,11 @@ pub async fn insert_activity(
     .bind(activity.state.duration)
     .bind(activity.state.avg_heartrate.map(|v| v as i32))
     .bind(activity.state.load.map(|v| v as i32))
+    .bind(activity.state.rpe)
+    .bind(activity.state.device.clone())
+    .bind(activity.state.indoor)
+    .bind(activity.state.footpod.clone())
+    .bind(activity.state.gps_distance_ratio)
     .fetch_one(executor)
     .await
     .map_err(|e| ModelError::InsertError(format!("Couldn't insert activity: {}", e)))?;
@@ -99,3 +150,77 @@ pub async fn insert_activity(
         extra: Stored { activity_id },
     })
 }
+
+/// Recalculates the training load of all of a user's activities with a perceived exertion, the
+/// only ones whose load depends on the load model, the same way as
+/// [`UserPreferences::activity_load`](super::user_preferences::UserPreferences::activity_load).
+#[cfg(feature = "ssr")]
+pub async fn update_rpe_loads(
+    user_id: i64,
+    load_model: LoadModel,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    sqlx::query!(
+        r#"
+        UPDATE activities
+        SET load = CASE
+            WHEN $2::load_model = 'session_rpe' THEN COALESCE(rpe_loads.load, activities.measured_load)
+            ELSE COALESCE(activities.measured_load, rpe_loads.load)
+        END
+        FROM (
+            SELECT id, ROUND((rpe * duration / 60.0)::numeric)::int4 as load
+            FROM activities
+            WHERE user_id=$1::bigint and rpe IS NOT NULL
+        ) rpe_loads
+        WHERE activities.id = rpe_loads.id
+        "#,
+        user_id,
+        load_model as _
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't update loads: {}", e)))?;
+    Ok(())
+}
+
+/// Recalculates the training load of an activity with the user's current load model.
+///
+/// The load model applies to all activities, so it's taken from the current preferences.
+#[cfg(feature = "ssr")]
+pub async fn update_activity_load(
+    activity_id: i64,
+    user_id: i64,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    let activity = sqlx::query!(
+        r#"
+        SELECT measured_load, rpe, duration::float8 as "duration!"
+        FROM activities
+        WHERE id=$1 and user_id=$2::bigint
+        "#,
+        activity_id,
+        user_id
+    )
+    .fetch_one(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load activity: {}", e)))?;
+    let preferences = get_user_preferences(user_id, Local::now(), &mut *executor).await;
+    let load = preferences.activity_load(
+        activity.measured_load.map(|l| l as u32),
+        activity.rpe,
+        activity.duration,
+    );
+    sqlx::query!(
+        r#"
+        UPDATE activities
+        SET load=$2
+        WHERE id=$1
+        "#,
+        activity_id,
+        load.map(|l| l as i32)
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't update load: {}", e)))?;
+    Ok(())
+}
@@ -0,0 +1,834 @@
+//! Fixes for bad recordings: trimming, splitting and merging activities.
+//!
+//! All operations move or delete the stored rows and then rebuild the derived data of the
+//! affected activities, see [`rebuild_activity`].
+use chrono::{DateTime, Local};
+#[cfg(feature = "ssr")]
+use sqlx::PgConnection;
+
+#[cfg(feature = "ssr")]
+use super::{
+    activity::update_activity_load,
+    base::{DatabaseEntry, ModelError, New},
+    elevation::{correct_activity_altitude, update_activity_climb},
+    heartrate_recovery::update_heartrate_recovery,
+    heatmap::update_heatmap_cells,
+    record::Record,
+    segment::match_activity_segments,
+    session::{update_session_loads, Session},
+    slope_speed::{insert_slopes, slopes_from_records},
+    sport::assign_sports,
+    strength_set::{strength_load, StrengthSet},
+    swim_length::activity_swim_load,
+    user_preferences::get_user_preferences,
+    workout_completion::update_completion_actuals,
+};
+
+/// Gaps between records longer than this are pauses and don't count towards the duration.
+const MAX_RECORD_GAP: f64 = 15.0;
+
+/// Time in seconds covered by records, leaving out pauses in the recording.
+pub fn moving_duration(timestamps: &[DateTime<Local>]) -> f64 {
+    timestamps
+        .windows(2)
+        .map(|w| (w[1] - w[0]).num_milliseconds() as f64 / 1000.0)
+        .filter(|gap| *gap <= MAX_RECORD_GAP)
+        .sum()
+}
+
+/// Start and end of an activity, checking that it belongs to the user.
+#[cfg(feature = "ssr")]
+async fn activity_bounds(
+    activity_id: i64,
+    user_id: i64,
+    executor: &mut PgConnection,
+) -> Result<(DateTime<Local>, DateTime<Local>), ModelError> {
+    let bounds = sqlx::query!(
+        r#"
+        SELECT start_time, end_time
+        FROM activities
+        WHERE id=$1 and user_id=$2::bigint
+        "#,
+        activity_id,
+        user_id
+    )
+    .fetch_optional(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load activity: {}", e)))?
+    .ok_or(ModelError::QueryError("Activity not found".to_string()))?;
+    Ok((bounds.start_time.into(), bounds.end_time.into()))
+}
+
+/// Cuts an activity down to the time between `start` and `end`.
+#[cfg(feature = "ssr")]
+pub async fn trim_activity(
+    activity_id: i64,
+    user_id: i64,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    let (activity_start, activity_end) = activity_bounds(activity_id, user_id, executor).await?;
+    let start = start.max(activity_start);
+    let end = end.min(activity_end);
+    if start >= end {
+        return Err(ModelError::ParseError(
+            "Nothing of the activity is left after trimming".to_string(),
+        ));
+    }
+    rebuild_activity(activity_id, user_id, start, end, true, executor).await
+}
+
+/// Splits an activity in two at `at`, returning the id of the new second activity.
+///
+/// The second activity keeps the title, tags and device of the original one. Sessions and laps
+/// that span `at` end up in both activities.
+#[cfg(feature = "ssr")]
+pub async fn split_activity(
+    activity_id: i64,
+    user_id: i64,
+    at: DateTime<Local>,
+    executor: &mut PgConnection,
+) -> Result<i64, ModelError> {
+    let (start, end) = activity_bounds(activity_id, user_id, executor).await?;
+    if at <= start || at >= end {
+        return Err(ModelError::ParseError(
+            "The split has to be within the activity".to_string(),
+        ));
+    }
+    let new_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO activities (user_id, start_time, end_time, duration, rpe, title, tags, feeling, device,
+            indoor, footpod, distance_calibration)
+        SELECT user_id, $2, end_time, duration, rpe, title, tags, feeling, device, indoor, footpod,
+            distance_calibration
+        FROM activities
+        WHERE id=$1
+        RETURNING id
+        "#,
+        activity_id,
+        at
+    )
+    .fetch_one(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't insert activity: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE records
+        SET activity_id=$2
+        WHERE activity_id=$1 and timestamp >= $3
+        "#,
+        activity_id as i32,
+        new_id as i32,
+        at
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't move records: {}", e)))?;
+    // sessions and laps spanning the split are copied, the rebuild cuts them to size
+    sqlx::query!(
+        r#"
+        INSERT INTO sessions (activity_id, start_time, end_time, sport, sub_sport, sport_id, distance, calories,
+            average_heartrate, min_heartrate, max_heartrate, average_speed, min_speed, max_speed, ascent,
+            descent, average_power, pool_length)
+        SELECT $2, start_time, end_time, sport, sub_sport, sport_id, distance, calories, average_heartrate,
+            min_heartrate, max_heartrate, average_speed, min_speed, max_speed, ascent, descent, average_power,
+            pool_length
+        FROM sessions
+        WHERE activity_id=$1 and start_time < $3 and end_time > $3
+        "#,
+        activity_id as i32,
+        new_id as i32,
+        at
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't copy sessions: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET activity_id=$2
+        WHERE activity_id=$1 and start_time >= $3
+        "#,
+        activity_id as i32,
+        new_id as i32,
+        at
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't move sessions: {}", e)))?;
+    sqlx::query!(
+        r#"
+        INSERT INTO laps (activity_id, start_time, end_time, sport, sub_sport, sport_id, distance, calories,
+            average_heartrate, min_heartrate, max_heartrate, average_speed, min_speed, max_speed, ascent,
+            descent, average_power)
+        SELECT $2, start_time, end_time, sport, sub_sport, sport_id, distance, calories, average_heartrate,
+            min_heartrate, max_heartrate, average_speed, min_speed, max_speed, ascent, descent, average_power
+        FROM laps
+        WHERE activity_id=$1 and start_time < $3 and end_time > $3
+        "#,
+        activity_id as i32,
+        new_id as i32,
+        at
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't copy laps: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE laps
+        SET activity_id=$2
+        WHERE activity_id=$1 and start_time >= $3
+        "#,
+        activity_id as i32,
+        new_id as i32,
+        at
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't move laps: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE strength_sets
+        SET activity_id=$2
+        WHERE activity_id=$1 and start_time >= $3
+        "#,
+        activity_id as i32,
+        new_id as i32,
+        at
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't move strength sets: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE swim_lengths
+        SET activity_id=$2
+        WHERE activity_id=$1 and start_time >= $3
+        "#,
+        activity_id as i32,
+        new_id as i32,
+        at
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't move swim lengths: {}", e)))?;
+    rebuild_activity(activity_id, user_id, start, at, true, executor).await?;
+    rebuild_activity(new_id, user_id, at, end, true, executor).await?;
+    Ok(new_id)
+}
+
+/// Merges the activity `second_id` into `first_id`, which has to start before it.
+///
+/// Distances of the second activity's records are continued from the end of the first one.
+#[cfg(feature = "ssr")]
+pub async fn merge_activities(
+    first_id: i64,
+    second_id: i64,
+    user_id: i64,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    let (first_start, first_end) = activity_bounds(first_id, user_id, executor).await?;
+    let (second_start, second_end) = activity_bounds(second_id, user_id, executor).await?;
+    if first_id == second_id || second_start < first_start {
+        return Err(ModelError::ParseError(
+            "Only a later activity can be merged into an activity".to_string(),
+        ));
+    }
+    if second_start < first_end {
+        return Err(ModelError::ParseError(
+            "Overlapping activities can't be merged".to_string(),
+        ));
+    }
+    sqlx::query!(
+        r#"
+        UPDATE records
+        SET
+            activity_id=$1,
+            distance=records.distance + COALESCE((
+                SELECT MAX(first.distance) FROM records first WHERE first.activity_id=$1
+            ), 0)
+        WHERE activity_id=$2
+        "#,
+        first_id as i32,
+        second_id as i32
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't move records: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET activity_id=$1
+        WHERE activity_id=$2
+        "#,
+        first_id as i32,
+        second_id as i32
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't move sessions: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE laps
+        SET activity_id=$1
+        WHERE activity_id=$2
+        "#,
+        first_id as i32,
+        second_id as i32
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't move laps: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE strength_sets
+        SET
+            activity_id=$1,
+            position=strength_sets.position + COALESCE((
+                SELECT MAX(first.position) + 1 FROM strength_sets first WHERE first.activity_id=$1
+            ), 0)
+        WHERE activity_id=$2
+        "#,
+        first_id as i32,
+        second_id as i32
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't move strength sets: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE swim_lengths
+        SET
+            activity_id=$1,
+            position=swim_lengths.position + COALESCE((
+                SELECT MAX(first.position) + 1 FROM swim_lengths first WHERE first.activity_id=$1
+            ), 0)
+        WHERE activity_id=$2
+        "#,
+        first_id as i32,
+        second_id as i32
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't move swim lengths: {}", e)))?;
+    // without records the rebuild keeps the duration and load, so they are added up here
+    sqlx::query!(
+        r#"
+        UPDATE activities first
+        SET
+            duration=first.duration + second.duration,
+            measured_load=CASE
+                WHEN first.measured_load IS NULL AND second.measured_load IS NULL THEN NULL
+                ELSE COALESCE(first.measured_load, 0) + COALESCE(second.measured_load, 0)
+            END
+        FROM activities second
+        WHERE first.id=$1 and second.id=$2
+        "#,
+        first_id,
+        second_id
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't merge activities: {}", e)))?;
+    sqlx::query!(
+        r#"
+        DELETE FROM activities
+        WHERE id=$1
+        "#,
+        second_id
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't delete activity: {}", e)))?;
+    rebuild_activity(
+        first_id,
+        user_id,
+        first_start,
+        first_end.max(second_end),
+        true,
+        executor,
+    )
+    .await
+}
+
+/// Marks an activity as done indoors or outdoors, indoor activities are left out of slope-speed.
+#[cfg(feature = "ssr")]
+pub async fn set_indoor(
+    activity_id: i64,
+    user_id: i64,
+    indoor: bool,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    let (start, end) = activity_bounds(activity_id, user_id, executor).await?;
+    sqlx::query!(
+        r#"
+        UPDATE activities
+        SET indoor=$2
+        WHERE id=$1
+        "#,
+        activity_id,
+        indoor
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't update activity: {}", e)))?;
+    rebuild_activity(activity_id, user_id, start, end, false, executor).await
+}
+
+/// Takes the altitude of an activity from the elevation model and rebuilds it, returns whether the
+/// model covered the activity.
+#[cfg(feature = "ssr")]
+pub async fn correct_elevation(
+    activity_id: i64,
+    user_id: i64,
+    executor: &mut PgConnection,
+) -> Result<bool, ModelError> {
+    let (start, end) = activity_bounds(activity_id, user_id, executor).await?;
+    if !correct_activity_altitude(activity_id, executor).await? {
+        return Ok(false);
+    }
+    rebuild_activity(activity_id, user_id, start, end, false, executor).await?;
+    Ok(true)
+}
+
+/// Recalculates distance, heart rate and speed statistics of an activity's sessions and laps from
+/// its records.
+#[cfg(feature = "ssr")]
+pub async fn recalculate_session_stats(
+    activity_id: i64,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    sqlx::query!(
+        r#"
+        WITH samples AS (
+            SELECT
+                timestamp,
+                heartrate,
+                distance,
+                speed
+            FROM records
+            WHERE activity_id=$1
+        ), stats AS (
+            SELECT
+                sessions.id,
+                MAX(samples.distance) - MIN(samples.distance) as distance,
+                AVG(samples.heartrate) as average_heartrate,
+                MIN(samples.heartrate) as min_heartrate,
+                MAX(samples.heartrate) as max_heartrate,
+                AVG(samples.speed) as average_speed,
+                MIN(samples.speed) as min_speed,
+                MAX(samples.speed) as max_speed
+            FROM sessions
+            INNER JOIN samples ON samples.timestamp >= sessions.start_time
+                AND samples.timestamp <= sessions.end_time
+            WHERE sessions.activity_id=$1
+            GROUP BY sessions.id
+        )
+        UPDATE sessions
+        SET
+            distance=COALESCE(stats.distance, sessions.distance),
+            average_heartrate=COALESCE(stats.average_heartrate::smallint, sessions.average_heartrate),
+            min_heartrate=COALESCE(stats.min_heartrate, sessions.min_heartrate),
+            max_heartrate=COALESCE(stats.max_heartrate, sessions.max_heartrate),
+            average_speed=COALESCE(stats.average_speed, sessions.average_speed),
+            min_speed=COALESCE(stats.min_speed, sessions.min_speed),
+            max_speed=COALESCE(stats.max_speed, sessions.max_speed)
+        FROM stats
+        WHERE sessions.id=stats.id
+        "#,
+        activity_id as i32
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't update sessions: {}", e)))?;
+    sqlx::query!(
+        r#"
+        WITH samples AS (
+            SELECT
+                timestamp,
+                heartrate,
+                distance,
+                speed
+            FROM records
+            WHERE activity_id=$1
+        ), stats AS (
+            SELECT
+                laps.id,
+                MAX(samples.distance) - MIN(samples.distance) as distance,
+                AVG(samples.heartrate) as average_heartrate,
+                MIN(samples.heartrate) as min_heartrate,
+                MAX(samples.heartrate) as max_heartrate,
+                AVG(samples.speed) as average_speed,
+                MIN(samples.speed) as min_speed,
+                MAX(samples.speed) as max_speed
+            FROM laps
+            INNER JOIN samples ON samples.timestamp >= laps.start_time
+                AND samples.timestamp <= laps.end_time
+            WHERE laps.activity_id=$1
+            GROUP BY laps.id
+        )
+        UPDATE laps
+        SET
+            distance=COALESCE(stats.distance, laps.distance),
+            average_heartrate=COALESCE(stats.average_heartrate::smallint, laps.average_heartrate),
+            min_heartrate=COALESCE(stats.min_heartrate, laps.min_heartrate),
+            max_heartrate=COALESCE(stats.max_heartrate, laps.max_heartrate),
+            average_speed=COALESCE(stats.average_speed, laps.average_speed),
+            min_speed=COALESCE(stats.min_speed, laps.min_speed),
+            max_speed=COALESCE(stats.max_speed, laps.max_speed)
+        FROM stats
+        WHERE laps.id=stats.id
+        "#,
+        activity_id as i32
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't update laps: {}", e)))?;
+    Ok(())
+}
+
+/// Cuts an activity to the time between `start` and `end` and recalculates everything derived
+/// from its records.
+///
+/// Sessions and laps are clamped to the new time range and their statistics are recalculated
+/// from the remaining records, calories are scaled with the time that is left. If `cut` is set,
+/// records were removed or added, so the session and lap statistics and the duration are
+/// recalculated from the records and activities without heart rate records keep their load,
+/// scaled to the new duration. Otherwise the recorded statistics and duration are kept.
+#[cfg(feature = "ssr")]
+pub async fn rebuild_activity(
+    activity_id: i64,
+    user_id: i64,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+    cut: bool,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    sqlx::query!(
+        r#"
+        DELETE FROM records
+        WHERE activity_id=$1 and (timestamp < $2 or timestamp > $3)
+        "#,
+        activity_id as i32,
+        start,
+        end
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't delete records: {}", e)))?;
+    sqlx::query!(
+        r#"
+        DELETE FROM strength_sets
+        WHERE activity_id=$1 and (start_time < $2 or start_time > $3)
+        "#,
+        activity_id as i32,
+        start,
+        end
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't delete strength sets: {}", e)))?;
+    sqlx::query!(
+        r#"
+        DELETE FROM swim_lengths
+        WHERE activity_id=$1 and (start_time < $2 or start_time > $3)
+        "#,
+        activity_id as i32,
+        start,
+        end
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't delete swim lengths: {}", e)))?;
+    sqlx::query!(
+        r#"
+        DELETE FROM sessions
+        WHERE activity_id=$1 and (end_time <= $2 or start_time >= $3)
+        "#,
+        activity_id as i32,
+        start,
+        end
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't delete sessions: {}", e)))?;
+    sqlx::query!(
+        r#"
+        DELETE FROM laps
+        WHERE activity_id=$1 and (end_time <= $2 or start_time >= $3)
+        "#,
+        activity_id as i32,
+        start,
+        end
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't delete laps: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET
+            start_time=GREATEST(start_time, $2),
+            end_time=LEAST(end_time, $3),
+            calories=(calories * extract(epoch from LEAST(end_time, $3) - GREATEST(start_time, $2))
+                / NULLIF(extract(epoch from end_time - start_time), 0))::smallint
+        WHERE activity_id=$1
+        "#,
+        activity_id as i32,
+        start,
+        end
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't trim sessions: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE laps
+        SET
+            start_time=GREATEST(start_time, $2),
+            end_time=LEAST(end_time, $3),
+            calories=(calories * extract(epoch from LEAST(end_time, $3) - GREATEST(start_time, $2))
+                / NULLIF(extract(epoch from end_time - start_time), 0))::smallint
+        WHERE activity_id=$1
+        "#,
+        activity_id as i32,
+        start,
+        end
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't trim laps: {}", e)))?;
+    if cut {
+        // the device's own statistics are kept as long as all of their records are there
+        recalculate_session_stats(activity_id, executor).await?;
+    }
+    update_activity_climb(activity_id, executor).await?;
+
+    let records: Vec<DatabaseEntry<New, Record>> = sqlx::query!(
+        r#"
+        SELECT
+            timestamp,
+            heartrate,
+            latitude,
+            longitude,
+            distance::float8,
+            speed::float8,
+            altitude::float8,
+            power
+        FROM records
+        WHERE activity_id=$1
+        ORDER BY timestamp
+        "#,
+        activity_id as i32
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load records: {}", e)))?
+    .into_iter()
+    .map(|r| DatabaseEntry {
+        state: Box::new(Record {
+            timestamp: r.timestamp.into(),
+            heartrate: r.heartrate,
+            latitude: r.latitude,
+            longitude: r.longitude,
+            raw_latitude: None,
+            raw_longitude: None,
+            distance: r.distance,
+            speed: r.speed,
+            altitude: r.altitude,
+            raw_altitude: None,
+            power: r.power,
+        }),
+        extra: New,
+    })
+    .collect();
+    let sessions: Vec<DatabaseEntry<New, Session>> = sqlx::query!(
+        r#"
+        SELECT start_time, end_time, sport
+        FROM sessions
+        WHERE activity_id=$1
+        ORDER BY start_time
+        "#,
+        activity_id as i32
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load sessions: {}", e)))?
+    .into_iter()
+    .map(|s| DatabaseEntry {
+        state: Box::new(Session {
+            start_time: s.start_time.into(),
+            end_time: s.end_time.into(),
+            sport: s.sport,
+            sub_sport: None,
+            distance: None,
+            calories: None,
+            average_heartrate: None,
+            min_heartrate: None,
+            max_heartrate: None,
+            average_power: None,
+            ascent: None,
+            descent: None,
+            average_speed: None,
+            max_speed: None,
+            pool_length: None,
+        }),
+        extra: New,
+    })
+    .collect();
+    if sessions.is_empty() {
+        return Err(ModelError::ParseError(
+            "No session of the activity is left".to_string(),
+        ));
+    }
+    let sets: Vec<DatabaseEntry<New, StrengthSet>> = sqlx::query!(
+        r#"
+        SELECT duration, repetitions, weight, rest
+        FROM strength_sets
+        WHERE activity_id=$1
+        "#,
+        activity_id as i32
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load strength sets: {}", e)))?
+    .into_iter()
+    .map(|s| DatabaseEntry {
+        state: Box::new(StrengthSet {
+            duration: s.duration,
+            repetitions: s.repetitions,
+            weight: s.weight,
+            rest: s.rest,
+            ..StrengthSet::new(String::new(), 0, 0.0, 0.0)
+        }),
+        extra: New,
+    })
+    .collect();
+
+    let activity = sqlx::query!(
+        r#"
+        SELECT duration::float8 as "duration!", measured_load, indoor
+        FROM activities
+        WHERE id=$1
+        "#,
+        activity_id
+    )
+    .fetch_one(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load activity: {}", e)))?;
+    let preferences = get_user_preferences(user_id, start, &mut *executor).await;
+    let timestamps: Vec<_> = records.iter().map(|r| r.state.timestamp).collect();
+    let span = (end - start).num_milliseconds() as f64 / 1000.0;
+    let duration = if !cut {
+        activity.duration
+    } else if timestamps.len() > 1 {
+        moving_duration(&timestamps)
+    } else {
+        activity.duration.min(span)
+    };
+    let heartrates: Vec<u32> = records
+        .iter()
+        .filter_map(|r| r.state.heartrate.map(|hr| hr as u32))
+        .collect();
+    let swim_load =
+        activity_swim_load(activity_id, preferences.critical_swim_speed, executor).await?;
+    let (avg_heartrate, measured_load) = if heartrates.is_empty() {
+        let scale = if activity.duration > 0.0 {
+            duration / activity.duration
+        } else {
+            1.0
+        };
+        (
+            None,
+            swim_load.or(activity
+                .measured_load
+                .map(|l| (l as f64 * scale).round() as u32)),
+        )
+    } else {
+        (
+            Some((heartrates.iter().sum::<u32>() / heartrates.len() as u32) as i16),
+            Some(preferences.calculate_load(heartrates)),
+        )
+    };
+    let measured_load = if sets.is_empty() {
+        measured_load
+    } else {
+        // heart rate underestimates strength sessions, so we take the higher load
+        let load = strength_load(&sets, &preferences);
+        Some(measured_load.map_or(load, |l| l.max(load)))
+    };
+    // heart rate is unreliable in the water, so pool swims take the higher pace based load
+    let measured_load = match (measured_load, swim_load) {
+        (Some(load), Some(swim_load)) => Some(load.max(swim_load)),
+        (load, swim_load) => load.or(swim_load),
+    };
+    sqlx::query!(
+        r#"
+        UPDATE activities
+        SET
+            start_time=$2,
+            end_time=$3,
+            duration=$4::float8,
+            avg_heartrate=COALESCE($5, avg_heartrate),
+            measured_load=$6
+        WHERE id=$1
+        "#,
+        activity_id,
+        start,
+        end,
+        (duration * 10.0).round() / 10.0,
+        avg_heartrate,
+        measured_load.map(|l| l as i32)
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't update activity: {}", e)))?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM slope_speed
+        WHERE activity_id=$1
+        "#,
+        activity_id as i32
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't delete slope speed: {}", e)))?;
+    let slopes = if activity.indoor {
+        Vec::new()
+    } else {
+        slopes_from_records(&records, &sessions, start, user_id, &preferences)
+    };
+    if !slopes.is_empty() {
+        insert_slopes(slopes, activity_id, &mut *executor).await?;
+    }
+    update_heatmap_cells(activity_id, executor).await?;
+    match_activity_segments(activity_id, user_id, executor).await?;
+    update_heartrate_recovery(activity_id, user_id, executor).await?;
+    assign_sports(user_id, Some(activity_id), executor).await?;
+    update_session_loads(activity_id, user_id, executor).await?;
+    update_activity_load(activity_id, user_id, executor).await?;
+    update_completion_actuals(user_id, Some(activity_id), executor).await
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::moving_duration;
+    #[cfg(feature = "ssr")]
+    use chrono::{Local, TimeDelta};
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_moving_duration() {
+        let start = Local::now();
+        let timestamps: Vec<_> = [0, 1, 2, 5, 65, 66]
+            .into_iter()
+            .map(|s| start + TimeDelta::seconds(s))
+            .collect();
+        // the minute between 5 and 65 is a pause
+        assert_eq!(moving_duration(&timestamps), 6.0);
+        assert_eq!(moving_duration(&timestamps[..1]), 0.0);
+    }
+}
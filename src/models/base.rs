@@ -6,6 +6,8 @@ pub enum ModelError {
     ParseError(String),
     #[error("couldn't insert entry into database: {0}")]
     InsertError(String),
+    #[error("couldn't query database: {0}")]
+    QueryError(String),
 }
 
 #[derive(Debug, Clone)]
@@ -26,6 +28,22 @@ pub trait DatabaseState {}
 impl DatabaseState for New {}
 impl DatabaseState for Stored {}
 
+/// Turns a FIT enum value like `bench_press` into a name like `Bench Press`.
+pub fn display_name(value: &str) -> String {
+    value
+        .split('_')
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut chars = w.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 #[derive(Debug, Clone)]
 pub struct Coordinates {
     pub latitude: i32,
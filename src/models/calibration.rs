@@ -0,0 +1,255 @@
+//! Indoor activities and the calibration of their sensor distance.
+//!
+//! Indoor runs take their distance from a footpod, which is often off by a few percent. Outdoor
+//! runs with both GPS and the same footpod tell how far off it is.
+use fitparser::{profile::MesgNum, FitDataRecord, Value};
+#[cfg(feature = "ssr")]
+use sqlx::PgConnection;
+
+#[cfg(feature = "ssr")]
+use super::base::ModelError;
+use super::base::{display_name, DatabaseEntry, New};
+use super::record::Record;
+
+/// FIT sub sports that are done indoors.
+const INDOOR_SUB_SPORTS: [&str; 8] = [
+    "treadmill",
+    "indoor_running",
+    "indoor_walking",
+    "indoor_cycling",
+    "indoor_rowing",
+    "indoor_skiing",
+    "indoor_climbing",
+    "virtual_activity",
+];
+
+/// FIT sports whose footpod distance is calibrated.
+const CALIBRATED_SPORTS: [&str; 1] = ["running"];
+
+/// Calibrations outside of this range are measurement errors rather than sensor drift.
+const MAX_CALIBRATION_ERROR: f64 = 0.25;
+
+/// Minimal distance in meters for a run to tell the calibration of its sensor.
+const MIN_CALIBRATION_DISTANCE: f64 = 1000.0;
+
+pub fn is_indoor_sub_sport(sub_sport: &str) -> bool {
+    INDOOR_SUB_SPORTS.contains(&sub_sport)
+}
+
+pub fn is_calibrated_sport(sport: &str) -> bool {
+    CALIBRATED_SPORTS.contains(&sport)
+}
+
+/// Name of the footpod a FIT `device_info` message is about, e.g. `Stryd 123456`, or `None` if
+/// it is about another device.
+pub fn footpod_name(value: &FitDataRecord) -> Option<String> {
+    if !matches!(value.kind(), MesgNum::DeviceInfo) {
+        return None;
+    }
+    let fields = value.fields();
+    let footpod = fields.iter().any(|f| match (f.name(), f.value()) {
+        ("antplus_device_type", Value::String(val)) => val == "stride_speed_distance",
+        ("ble_device_type", Value::String(val)) => val == "footpod",
+        _ => false,
+    });
+    if !footpod {
+        return None;
+    }
+    let text = |name: &str| {
+        fields
+            .iter()
+            .find(|&f| f.name() == name)
+            .map(|f| f.value().to_string().trim().to_string())
+            .filter(|val| !val.is_empty())
+    };
+    let name = [
+        text("manufacturer").map(|m| display_name(&m)),
+        text("product_name"),
+        text("serial_number").or(text("ant_device_number")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(" ");
+    Some(if name.is_empty() {
+        "Footpod".to_string()
+    } else {
+        name
+    })
+}
+
+/// Great circle distance in meters between two latitude/longitude points.
+pub fn haversine(from: (f64, f64), to: (f64, f64)) -> f64 {
+    const EARTH_RADIUS: f64 = 6_371_000.0;
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+/// Great circle distance in meters along a track of latitude/longitude points.
+pub fn gps_distance(points: &[(f64, f64)]) -> f64 {
+    points
+        .windows(2)
+        .map(|pair| haversine(pair[0], pair[1]))
+        .sum()
+}
+
+/// Ratio of the GPS distance to the recorded sensor distance of an activity, the factor its
+/// sensor distance has to be calibrated with.
+pub fn gps_distance_ratio(records: &[DatabaseEntry<New, Record>]) -> Option<f64> {
+    let track: Vec<_> = records
+        .iter()
+        .filter_map(
+            |r| match (r.state.latitude, r.state.longitude, r.state.distance) {
+                (Some(lat), Some(long), Some(distance)) => Some(((lat, long), distance)),
+                _ => None,
+            },
+        )
+        .collect();
+    let sensor_distance = track.last()?.1 - track.first()?.1;
+    if sensor_distance < MIN_CALIBRATION_DISTANCE {
+        return None;
+    }
+    let points: Vec<_> = track.into_iter().map(|(point, _)| point).collect();
+    let ratio = gps_distance(&points) / sensor_distance;
+    ((ratio - 1.0).abs() <= MAX_CALIBRATION_ERROR).then_some(ratio)
+}
+
+/// Calibration learned for a footpod in a sport, the median ratio of GPS to footpod distance of
+/// the user's activities of that sport recorded with it.
+#[cfg(feature = "ssr")]
+pub async fn footpod_calibration(
+    user_id: i64,
+    footpod: &str,
+    sport: &str,
+    executor: &mut PgConnection,
+) -> Result<Option<f64>, ModelError> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT percentile_cont(0.5) WITHIN GROUP (ORDER BY gps_distance_ratio)
+        FROM activities
+        WHERE user_id=$1::bigint
+            and footpod=$2
+            and gps_distance_ratio IS NOT NULL
+            and EXISTS (
+                SELECT 1
+                FROM sessions
+                WHERE sessions.activity_id = activities.id and sessions.sport=$3
+            )
+        "#,
+        user_id,
+        footpod,
+        sport
+    )
+    .fetch_one(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load footpod calibration: {}", e)))
+}
+
+/// Rescales the recorded distances and speeds of an activity to the calibration `factor`.
+///
+/// The factor is relative to the uncalibrated recording, so calibrating again replaces the
+/// previous calibration.
+#[cfg(feature = "ssr")]
+pub async fn calibrate_distance(
+    activity_id: i64,
+    user_id: i64,
+    factor: f64,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    if !(1.0 - MAX_CALIBRATION_ERROR..=1.0 + MAX_CALIBRATION_ERROR).contains(&factor) {
+        return Err(ModelError::ParseError(format!(
+            "Calibration has to be between {} and {}",
+            1.0 - MAX_CALIBRATION_ERROR,
+            1.0 + MAX_CALIBRATION_ERROR
+        )));
+    }
+    let previous = sqlx::query_scalar!(
+        r#"
+        SELECT distance_calibration
+        FROM activities
+        WHERE id=$1 and user_id=$2::bigint
+        "#,
+        activity_id,
+        user_id
+    )
+    .fetch_optional(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load activity: {}", e)))?
+    .ok_or(ModelError::QueryError("Activity not found".to_string()))?;
+    let scale = factor / previous;
+    sqlx::query!(
+        r#"
+        UPDATE records
+        SET distance=distance * $2::float8, speed=speed * $2::float8
+        WHERE activity_id=$1
+        "#,
+        activity_id as i32,
+        scale
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't calibrate records: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET
+            distance=distance * $2::float8,
+            average_speed=average_speed * $2::float8,
+            min_speed=min_speed * $2::float8,
+            max_speed=max_speed * $2::float8
+        WHERE activity_id=$1
+        "#,
+        activity_id as i32,
+        scale
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't calibrate sessions: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE laps
+        SET
+            distance=distance * $2::float8,
+            average_speed=average_speed * $2::float8,
+            min_speed=min_speed * $2::float8,
+            max_speed=max_speed * $2::float8
+        WHERE activity_id=$1
+        "#,
+        activity_id as i32,
+        scale
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't calibrate laps: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE activities
+        SET distance_calibration=$2::float8
+        WHERE id=$1
+        "#,
+        activity_id,
+        factor
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't calibrate activity: {}", e)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::gps_distance;
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_gps_distance() {
+        // a degree of latitude is about 111km
+        let distance = gps_distance(&[(47.0, 8.0), (48.0, 8.0)]);
+        assert!((distance - 111_195.0).abs() < 1.0);
+        assert_eq!(gps_distance(&[(47.0, 8.0)]), 0.0);
+    }
+}
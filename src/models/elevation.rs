@@ -0,0 +1,388 @@
+//! Altitude from a digital elevation model and ascent totals.
+//!
+//! Barometric and GPS altitude drifts and jitters, so GPS activities can take their altitude from
+//! SRTM HGT tiles in the directory configured as `dem_path` instead. Tiles are named after their
+//! south west corner like `N47E008.hgt`, Copernicus GeoTIFFs can be converted with
+//! `gdal_translate -of SRTMHGT`.
+#[cfg(feature = "ssr")]
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+#[cfg(feature = "ssr")]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "ssr")]
+use itertools::Itertools;
+#[cfg(feature = "ssr")]
+use sqlx::PgConnection;
+
+#[cfg(feature = "ssr")]
+use super::base::ModelError;
+#[cfg(feature = "ssr")]
+use crate::config::Config;
+
+/// Meters the altitude has to change before it counts towards ascent or descent.
+pub const ELEVATION_HYSTERESIS: f64 = 5.0;
+
+/// Marks missing heights in HGT tiles.
+const HGT_VOID: i16 = -32768;
+
+/// Tiles kept in memory, an SRTM1 tile takes about 25MB.
+#[cfg(feature = "ssr")]
+const MAX_CACHED_TILES: usize = 16;
+
+/// One degree by one degree of heights, rows running from north to south.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HgtTile {
+    south: i32,
+    west: i32,
+    size: usize,
+    heights: Vec<i16>,
+}
+
+impl HgtTile {
+    /// Reads a tile from the big endian heights of an HGT file, both 1 and 3 arc second tiles work.
+    pub fn parse(south: i32, west: i32, bytes: &[u8]) -> Option<Self> {
+        let count = bytes.len() / 2;
+        let size = (count as f64).sqrt().round() as usize;
+        if size < 2 || size * size != count || bytes.len() % 2 != 0 {
+            return None;
+        }
+        let heights = bytes
+            .chunks_exact(2)
+            .map(|b| i16::from_be_bytes([b[0], b[1]]))
+            .collect();
+        Some(Self {
+            south,
+            west,
+            size,
+            heights,
+        })
+    }
+
+    /// Height at a position, interpolated between the four surrounding samples.
+    pub fn elevation(&self, latitude: f64, longitude: f64) -> Option<f64> {
+        let last = (self.size - 1) as f64;
+        let row = (self.south as f64 + 1.0 - latitude) * last;
+        let col = (longitude - self.west as f64) * last;
+        if !(0.0..=last).contains(&row) || !(0.0..=last).contains(&col) {
+            return None;
+        }
+        let (top, left) = (row.floor().min(last - 1.0), col.floor().min(last - 1.0));
+        let (dy, dx) = (row - top, col - left);
+        let height = |r: f64, c: f64| -> Option<f64> {
+            let h = self.heights[r as usize * self.size + c as usize];
+            (h != HGT_VOID).then_some(h as f64)
+        };
+        let north = height(top, left)? * (1.0 - dx) + height(top, left + 1.0)? * dx;
+        let south = height(top + 1.0, left)? * (1.0 - dx) + height(top + 1.0, left + 1.0)? * dx;
+        Some(north * (1.0 - dy) + south * dy)
+    }
+}
+
+/// File name of the HGT tile covering a position.
+pub fn hgt_tile_name(latitude: f64, longitude: f64) -> String {
+    let (south, west) = (latitude.floor() as i32, longitude.floor() as i32);
+    format!(
+        "{}{:02}{}{:03}.hgt",
+        if south < 0 { 'S' } else { 'N' },
+        south.abs(),
+        if west < 0 { 'W' } else { 'E' },
+        west.abs()
+    )
+}
+
+/// Tiles covering a recording, by the latitude and longitude of their south west corner.
+#[cfg(feature = "ssr")]
+pub type DemTiles = HashMap<(i32, i32), Arc<HgtTile>>;
+
+#[cfg(feature = "ssr")]
+type TileCache = Mutex<HashMap<(i32, i32), Arc<HgtTile>>>;
+
+#[cfg(feature = "ssr")]
+fn tile_key(latitude: f64, longitude: f64) -> (i32, i32) {
+    (latitude.floor() as i32, longitude.floor() as i32)
+}
+
+/// Loads a tile from the configured directory, cached tiles are shared.
+///
+/// Missing tiles aren't cached, so tiles added to the directory later are picked up.
+#[cfg(feature = "ssr")]
+async fn load_tile(key: (i32, i32)) -> Option<Arc<HgtTile>> {
+    static TILES: OnceLock<TileCache> = OnceLock::new();
+    let tiles = TILES.get_or_init(Default::default);
+    let directory = Config::global().dem_path.clone()?;
+    if let Some(tile) = tiles.lock().ok()?.get(&key) {
+        return Some(tile.clone());
+    }
+    let name = hgt_tile_name(key.0 as f64, key.1 as f64);
+    let tile = tokio::task::spawn_blocking(move || {
+        let bytes = std::fs::read(directory.join(&name))
+            .or_else(|_| std::fs::read(directory.join(name.to_lowercase())))
+            .ok()?;
+        HgtTile::parse(key.0, key.1, &bytes).map(Arc::new)
+    })
+    .await
+    .ok()??;
+    let mut tiles = tiles.lock().ok()?;
+    if tiles.len() >= MAX_CACHED_TILES {
+        tiles.clear();
+    }
+    tiles.insert(key, tile.clone());
+    Some(tile)
+}
+
+/// Loads the tiles of the configured elevation model that cover `positions`.
+#[cfg(feature = "ssr")]
+pub async fn load_dem_tiles(positions: &[Option<(f64, f64)>]) -> DemTiles {
+    let keys: Vec<_> = positions
+        .iter()
+        .flatten()
+        .map(|(latitude, longitude)| tile_key(*latitude, *longitude))
+        .unique()
+        .collect();
+    let mut tiles = DemTiles::new();
+    for key in keys {
+        if let Some(tile) = load_tile(key).await {
+            tiles.insert(key, tile);
+        }
+    }
+    tiles
+}
+
+/// Height of a position in the loaded tiles of the elevation model.
+#[cfg(feature = "ssr")]
+pub fn dem_elevation(tiles: &DemTiles, latitude: f64, longitude: f64) -> Option<f64> {
+    tiles
+        .get(&tile_key(latitude, longitude))?
+        .elevation(latitude, longitude)
+}
+
+/// Altitudes for a recording from its positions, records without a position take the altitude of
+/// the closest one before, or after at the start.
+///
+/// Returns nothing if there are no positions or the model doesn't cover all of them, mixing model
+/// and recorded altitudes would show up as steps.
+pub fn corrected_altitudes(
+    positions: &[Option<(f64, f64)>],
+    mut elevation: impl FnMut(f64, f64) -> Option<f64>,
+) -> Option<Vec<f64>> {
+    let mut altitudes = Vec::with_capacity(positions.len());
+    let mut last = None;
+    for position in positions {
+        if let Some((latitude, longitude)) = position {
+            last = Some(elevation(*latitude, *longitude)?);
+        }
+        altitudes.push(last);
+    }
+    let first = altitudes.iter().find_map(|a| *a)?;
+    Some(altitudes.into_iter().map(|a| a.unwrap_or(first)).collect())
+}
+
+/// Ascent and descent of a series of altitudes, ignoring changes smaller than `threshold`.
+pub fn hysteresis_climb(altitudes: impl IntoIterator<Item = f64>, threshold: f64) -> (f64, f64) {
+    let mut reference: Option<f64> = None;
+    let (mut ascent, mut descent) = (0.0, 0.0);
+    for altitude in altitudes {
+        match reference {
+            None => reference = Some(altitude),
+            Some(r) if altitude - r >= threshold => {
+                ascent += altitude - r;
+                reference = Some(altitude);
+            }
+            Some(r) if r - altitude >= threshold => {
+                descent += r - altitude;
+                reference = Some(altitude);
+            }
+            Some(_) => {}
+        }
+    }
+    (ascent, descent)
+}
+
+/// Replaces the altitude of an activity's records with the elevation model's, keeping the recorded
+/// one as `raw_altitude`. Returns whether the model covered the activity.
+#[cfg(feature = "ssr")]
+pub async fn correct_activity_altitude(
+    activity_id: i64,
+    executor: &mut PgConnection,
+) -> Result<bool, ModelError> {
+    let records = sqlx::query!(
+        r#"
+        SELECT timestamp, latitude, longitude
+        FROM records
+        WHERE activity_id=$1
+        ORDER BY timestamp
+        "#,
+        activity_id as i32
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load records: {}", e)))?;
+    let positions: Vec<_> = records
+        .iter()
+        .map(|r| r.latitude.zip(r.longitude))
+        .collect();
+    let tiles = load_dem_tiles(&positions).await;
+    let Some(altitudes) =
+        corrected_altitudes(&positions, |lat, long| dem_elevation(&tiles, lat, long))
+    else {
+        return Ok(false);
+    };
+    let timestamps: Vec<_> = records.iter().map(|r| r.timestamp).collect();
+    sqlx::query!(
+        r#"
+        UPDATE records
+        SET
+            raw_altitude=COALESCE(records.raw_altitude, records.altitude),
+            altitude=corrected.altitude
+        FROM UNNEST($2::timestamptz[], $3::float8[]) AS corrected(timestamp, altitude)
+        WHERE records.activity_id=$1 AND records.timestamp=corrected.timestamp
+        "#,
+        activity_id as i32,
+        &timestamps[..],
+        &altitudes[..]
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't correct altitude: {}", e)))?;
+    Ok(true)
+}
+
+/// Recomputes ascent and descent of an activity's sessions and laps from its record altitudes.
+///
+/// Sessions and laps without any altitude keep theirs.
+#[cfg(feature = "ssr")]
+pub async fn update_activity_climb(
+    activity_id: i64,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    let altitudes = sqlx::query!(
+        r#"
+        SELECT timestamp, altitude::float8 as "altitude!"
+        FROM records
+        WHERE activity_id=$1 AND altitude IS NOT NULL
+        ORDER BY timestamp
+        "#,
+        activity_id as i32
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load altitudes: {}", e)))?;
+    if altitudes.is_empty() {
+        return Ok(());
+    }
+    let climb = |start: DateTime<Utc>, end: DateTime<Utc>| -> Option<(i32, i32)> {
+        let window: Vec<f64> = altitudes
+            .iter()
+            .filter(|a| a.timestamp >= start && a.timestamp <= end)
+            .map(|a| a.altitude)
+            .collect();
+        if window.is_empty() {
+            return None;
+        }
+        let (ascent, descent) = hysteresis_climb(window, ELEVATION_HYSTERESIS);
+        Some((ascent.round() as i32, descent.round() as i32))
+    };
+
+    let sessions = sqlx::query!(
+        r#"
+        SELECT id, start_time, end_time
+        FROM sessions
+        WHERE activity_id=$1
+        "#,
+        activity_id as i32
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load sessions: {}", e)))?;
+    let (ids, ascents, descents): (Vec<_>, Vec<_>, Vec<_>) = sessions
+        .into_iter()
+        .filter_map(|s| climb(s.start_time, s.end_time).map(|(a, d)| (s.id, a, d)))
+        .multiunzip();
+    sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET ascent=climb.ascent, descent=climb.descent
+        FROM UNNEST($1::bigint[], $2::int[], $3::int[]) AS climb(id, ascent, descent)
+        WHERE sessions.id=climb.id
+        "#,
+        &ids[..],
+        &ascents[..],
+        &descents[..]
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't update session ascent: {}", e)))?;
+
+    let laps = sqlx::query!(
+        r#"
+        SELECT id, start_time, end_time
+        FROM laps
+        WHERE activity_id=$1
+        "#,
+        activity_id as i32
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load laps: {}", e)))?;
+    let (ids, ascents, descents): (Vec<_>, Vec<_>, Vec<_>) = laps
+        .into_iter()
+        .filter_map(|l| climb(l.start_time, l.end_time).map(|(a, d)| (l.id, a, d)))
+        .multiunzip();
+    sqlx::query!(
+        r#"
+        UPDATE laps
+        SET ascent=climb.ascent, descent=climb.descent
+        FROM UNNEST($1::bigint[], $2::int[], $3::int[]) AS climb(id, ascent, descent)
+        WHERE laps.id=climb.id
+        "#,
+        &ids[..],
+        &ascents[..],
+        &descents[..]
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't update lap ascent: {}", e)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{corrected_altitudes, hgt_tile_name, hysteresis_climb, HgtTile};
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_elevation() {
+        assert_eq!(hgt_tile_name(47.3, 8.5), "N47E008.hgt");
+        assert_eq!(hgt_tile_name(-0.5, -70.1), "S01W071.hgt");
+
+        // 3x3 samples, north row 100m, south row 200m, void in the south east corner
+        let heights: [i16; 9] = [100, 100, 100, 150, 150, 150, 200, 200, -32768];
+        let bytes: Vec<u8> = heights.iter().flat_map(|h| h.to_be_bytes()).collect();
+        let tile = HgtTile::parse(47, 8, &bytes).unwrap();
+        assert_eq!(tile.elevation(48.0, 8.0), Some(100.0));
+        assert_eq!(tile.elevation(47.75, 8.25), Some(125.0));
+        assert_eq!(tile.elevation(47.0, 8.0), Some(200.0));
+        assert_eq!(tile.elevation(47.25, 8.75), None);
+        assert_eq!(tile.elevation(46.9, 8.0), None);
+        assert_eq!(HgtTile::parse(47, 8, &bytes[..6]), None);
+
+        let positions = [None, Some((47.0, 8.0)), None, Some((48.0, 8.0))];
+        assert_eq!(
+            corrected_altitudes(&positions, |lat, lon| tile.elevation(lat, lon)),
+            Some(vec![200.0, 200.0, 200.0, 100.0])
+        );
+        assert_eq!(corrected_altitudes(&[None], |_, _| Some(1.0)), None);
+        assert_eq!(corrected_altitudes(&positions, |_, _| None), None);
+
+        let (ascent, descent) = hysteresis_climb([100.0, 102.0, 99.0, 104.0, 110.0, 106.0], 5.0);
+        assert_eq!(ascent, 10.0);
+        assert_eq!(descent, 0.0);
+        let (ascent, descent) = hysteresis_climb([100.0, 110.0, 95.0, 97.0], 5.0);
+        assert_eq!(ascent, 10.0);
+        assert_eq!(descent, 15.0);
+    }
+}
@@ -0,0 +1,904 @@
+//! Export of stored activities as FIT, GPX, TCX and CSV files.
+//!
+//! The files are regenerated from the stored sessions, laps and records, so they contain the
+//! cleaned up track and corrected altitudes rather than what the device recorded.
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use fitparser::profile::field_types::{Sport, SubSport};
+#[cfg(feature = "ssr")]
+use sqlx::PgConnection;
+
+#[cfg(feature = "ssr")]
+use super::base::ModelError;
+use super::{lap::Lap, record::Record, session::Session};
+
+/// Seconds between the unix epoch and the FIT epoch, 1989-12-31T00:00:00Z.
+const FIT_EPOCH: i64 = 631065600;
+
+const FIT_CRC_TABLE: [u16; 16] = [
+    0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401, 0xA001, 0x6C00, 0x7800, 0xB401,
+    0x5000, 0x9C01, 0x8801, 0x4400,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Fit,
+    Gpx,
+    Tcx,
+    Csv,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 4] = [
+        ExportFormat::Fit,
+        ExportFormat::Gpx,
+        ExportFormat::Tcx,
+        ExportFormat::Csv,
+    ];
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|f| f.extension().eq_ignore_ascii_case(extension))
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Fit => "fit",
+            ExportFormat::Gpx => "gpx",
+            ExportFormat::Tcx => "tcx",
+            ExportFormat::Csv => "csv",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Fit => "application/vnd.ant.fit",
+            ExportFormat::Gpx => "application/gpx+xml",
+            ExportFormat::Tcx => "application/vnd.garmin.tcx+xml",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+        }
+    }
+}
+
+/// Totals of a session or lap, the parts both are exported with.
+struct Summary {
+    start_time: DateTime<Local>,
+    end_time: DateTime<Local>,
+    sport: Option<String>,
+    sub_sport: Option<String>,
+    distance: Option<f64>,
+    calories: Option<i32>,
+    average_heartrate: Option<i16>,
+    min_heartrate: Option<i16>,
+    max_heartrate: Option<i16>,
+    average_power: Option<i32>,
+    ascent: Option<i32>,
+    descent: Option<i32>,
+    average_speed: Option<f64>,
+    max_speed: Option<f64>,
+}
+
+impl Summary {
+    fn duration(&self) -> f64 {
+        (self.end_time - self.start_time).num_milliseconds() as f64 / 1000.0
+    }
+}
+
+impl From<&Session> for Summary {
+    fn from(s: &Session) -> Self {
+        Summary {
+            start_time: s.start_time,
+            end_time: s.end_time,
+            sport: s.sport.clone(),
+            sub_sport: s.sub_sport.clone(),
+            distance: s.distance,
+            calories: s.calories,
+            average_heartrate: s.average_heartrate,
+            min_heartrate: s.min_heartrate,
+            max_heartrate: s.max_heartrate,
+            average_power: s.average_power,
+            ascent: s.ascent,
+            descent: s.descent,
+            average_speed: s.average_speed,
+            max_speed: s.max_speed,
+        }
+    }
+}
+
+impl From<&Lap> for Summary {
+    fn from(l: &Lap) -> Self {
+        Summary {
+            start_time: l.start_time,
+            end_time: l.end_time,
+            sport: l.sport.clone(),
+            sub_sport: l.sub_sport.clone(),
+            distance: l.distance,
+            calories: l.calories,
+            average_heartrate: l.average_heartrate,
+            min_heartrate: l.min_heartrate,
+            max_heartrate: l.max_heartrate,
+            average_power: l.average_power,
+            ascent: l.ascent,
+            descent: l.descent,
+            average_speed: l.average_speed,
+            max_speed: l.max_speed,
+        }
+    }
+}
+
+/// Everything stored about an activity that goes into an exported file.
+#[derive(Debug, Clone)]
+pub struct ActivityExport {
+    pub sessions: Vec<Session>,
+    pub laps: Vec<Lap>,
+    pub records: Vec<Record>,
+}
+
+impl ActivityExport {
+    pub fn export(&self, format: ExportFormat) -> Vec<u8> {
+        match format {
+            ExportFormat::Fit => self.fit(),
+            ExportFormat::Gpx => self.gpx().into_bytes(),
+            ExportFormat::Tcx => self.tcx().into_bytes(),
+            ExportFormat::Csv => self.csv().into_bytes(),
+        }
+    }
+
+    fn sessions(&self) -> Vec<Summary> {
+        self.sessions.iter().map(Summary::from).collect()
+    }
+
+    /// Laps of the activity, activities without any have one lap per session.
+    fn laps(&self) -> Vec<Summary> {
+        if self.laps.is_empty() {
+            self.sessions()
+        } else {
+            self.laps.iter().map(Summary::from).collect()
+        }
+    }
+
+    fn start_time(&self) -> Option<DateTime<Local>> {
+        self.sessions
+            .iter()
+            .map(|s| s.start_time)
+            .chain(self.records.first().map(|r| r.timestamp))
+            .min()
+    }
+
+    fn sport(&self) -> Option<&str> {
+        self.sessions.first().and_then(|s| s.sport.as_deref())
+    }
+
+    /// FIT activity file with a file id, all records, laps and sessions and the activity.
+    pub fn fit(&self) -> Vec<u8> {
+        use FitBaseType::*;
+        const FILE_ID: u8 = 0;
+        const RECORD: u8 = 1;
+        const LAP: u8 = 2;
+        const SESSION: u8 = 3;
+        const ACTIVITY: u8 = 4;
+        let file_id_fields = [(0, Enum), (1, UInt16), (4, UInt32)];
+        let record_fields = [
+            (253, UInt32),
+            (0, SInt32),
+            (1, SInt32),
+            (2, UInt16),
+            (3, UInt8),
+            (5, UInt32),
+            (6, UInt16),
+            (7, UInt16),
+        ];
+        let lap_fields = [
+            (253, UInt32),
+            (0, Enum),
+            (1, Enum),
+            (2, UInt32),
+            (7, UInt32),
+            (8, UInt32),
+            (9, UInt32),
+            (11, UInt16),
+            (13, UInt16),
+            (14, UInt16),
+            (15, UInt8),
+            (16, UInt8),
+            (19, UInt16),
+            (21, UInt16),
+            (22, UInt16),
+            (25, Enum),
+            (39, Enum),
+            (63, UInt8),
+        ];
+        let session_fields = [
+            (253, UInt32),
+            (0, Enum),
+            (1, Enum),
+            (2, UInt32),
+            (5, Enum),
+            (6, Enum),
+            (7, UInt32),
+            (8, UInt32),
+            (9, UInt32),
+            (11, UInt16),
+            (14, UInt16),
+            (15, UInt16),
+            (16, UInt8),
+            (17, UInt8),
+            (20, UInt16),
+            (22, UInt16),
+            (23, UInt16),
+            (26, UInt16),
+            (64, UInt8),
+        ];
+        let activity_fields = [
+            (253, UInt32),
+            (0, UInt32),
+            (1, UInt16),
+            (2, Enum),
+            (3, Enum),
+            (4, Enum),
+        ];
+
+        let fit_time = |time: DateTime<Local>| Some(time.timestamp() - FIT_EPOCH);
+        let scaled = |value: Option<f64>, scale: f64| value.map(|v| (v * scale).round() as i64);
+        let semicircles = |degrees: Option<f64>| scaled(degrees, 2f64.powi(31) / 180.0);
+        let start = self.start_time().unwrap_or_else(Local::now);
+        let laps = self.laps();
+        let sessions = self.sessions();
+
+        let mut fit = FitWriter::default();
+        fit.definition(FILE_ID, 0, &file_id_fields);
+        // activity file of a development manufacturer
+        fit.data(
+            FILE_ID,
+            &file_id_fields,
+            &[Some(4), Some(255), fit_time(start)],
+        );
+
+        fit.definition(RECORD, 20, &record_fields);
+        for r in self.records.iter() {
+            fit.data(
+                RECORD,
+                &record_fields,
+                &[
+                    fit_time(r.timestamp),
+                    semicircles(r.latitude),
+                    semicircles(r.longitude),
+                    scaled(r.altitude.map(|a| a + 500.0), 5.0),
+                    r.heartrate.map(i64::from),
+                    scaled(r.distance, 100.0),
+                    scaled(r.speed, 1000.0),
+                    r.power.map(i64::from),
+                ],
+            );
+        }
+
+        fit.definition(LAP, 19, &lap_fields);
+        for lap in laps.iter() {
+            fit.data(
+                LAP,
+                &lap_fields,
+                &[
+                    fit_time(lap.end_time),
+                    // lap, stop
+                    Some(9),
+                    Some(1),
+                    fit_time(lap.start_time),
+                    scaled(Some(lap.duration()), 1000.0),
+                    scaled(Some(lap.duration()), 1000.0),
+                    scaled(lap.distance, 100.0),
+                    lap.calories.map(i64::from),
+                    scaled(lap.average_speed, 1000.0),
+                    scaled(lap.max_speed, 1000.0),
+                    lap.average_heartrate.map(i64::from),
+                    lap.max_heartrate.map(i64::from),
+                    lap.average_power.map(i64::from),
+                    lap.ascent.map(i64::from),
+                    lap.descent.map(i64::from),
+                    fit_sport(lap.sport.as_deref()),
+                    fit_sub_sport(lap.sub_sport.as_deref()),
+                    lap.min_heartrate.map(i64::from),
+                ],
+            );
+        }
+
+        fit.definition(SESSION, 18, &session_fields);
+        for session in sessions.iter() {
+            let num_laps = laps
+                .iter()
+                .filter(|l| l.start_time >= session.start_time && l.start_time < session.end_time)
+                .count();
+            fit.data(
+                SESSION,
+                &session_fields,
+                &[
+                    fit_time(session.end_time),
+                    // session, stop
+                    Some(8),
+                    Some(1),
+                    fit_time(session.start_time),
+                    fit_sport(session.sport.as_deref()),
+                    fit_sub_sport(session.sub_sport.as_deref()),
+                    scaled(Some(session.duration()), 1000.0),
+                    scaled(Some(session.duration()), 1000.0),
+                    scaled(session.distance, 100.0),
+                    session.calories.map(i64::from),
+                    scaled(session.average_speed, 1000.0),
+                    scaled(session.max_speed, 1000.0),
+                    session.average_heartrate.map(i64::from),
+                    session.max_heartrate.map(i64::from),
+                    session.average_power.map(i64::from),
+                    session.ascent.map(i64::from),
+                    session.descent.map(i64::from),
+                    Some(num_laps as i64),
+                    session.min_heartrate.map(i64::from),
+                ],
+            );
+        }
+
+        let end = sessions
+            .iter()
+            .map(|s| s.end_time)
+            .chain(self.records.last().map(|r| r.timestamp))
+            .max()
+            .unwrap_or(start);
+        let timer_time: f64 = sessions.iter().map(|s| s.duration()).sum();
+        fit.definition(ACTIVITY, 34, &activity_fields);
+        fit.data(
+            ACTIVITY,
+            &activity_fields,
+            &[
+                fit_time(end),
+                scaled(Some(timer_time), 1000.0),
+                Some(sessions.len() as i64),
+                // manual, activity, stop
+                Some(0),
+                Some(26),
+                Some(1),
+            ],
+        );
+        fit.finish()
+    }
+
+    /// GPX track of the records with a position, with the heart rate as Garmin extension.
+    pub fn gpx(&self) -> String {
+        let mut gpx = String::from(concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            "\n",
+            r#"<gpx version="1.1" creator="toedirs" xmlns="http://www.topografix.com/GPX/1/1" "#,
+            r#"xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1">"#,
+            "\n"
+        ));
+        if let Some(start) = self.start_time() {
+            gpx.push_str(&format!(
+                "  <metadata><time>{}</time></metadata>\n",
+                xml_time(start)
+            ));
+        }
+        gpx.push_str("  <trk>\n");
+        if let Some(sport) = self.sport() {
+            gpx.push_str(&format!("    <type>{}</type>\n", sport));
+        }
+        gpx.push_str("    <trkseg>\n");
+        for r in self.records.iter() {
+            let (Some(latitude), Some(longitude)) = (r.latitude, r.longitude) else {
+                continue;
+            };
+            gpx.push_str(&format!(
+                r#"      <trkpt lat="{:.7}" lon="{:.7}">"#,
+                latitude, longitude
+            ));
+            if let Some(altitude) = r.altitude {
+                gpx.push_str(&format!("<ele>{:.1}</ele>", altitude));
+            }
+            gpx.push_str(&format!("<time>{}</time>", xml_time(r.timestamp)));
+            if let Some(heartrate) = r.heartrate {
+                gpx.push_str(&format!(
+                    "<extensions><gpxtpx:TrackPointExtension><gpxtpx:hr>{}</gpxtpx:hr></gpxtpx:TrackPointExtension></extensions>",
+                    heartrate
+                ));
+            }
+            gpx.push_str("</trkpt>\n");
+        }
+        gpx.push_str("    </trkseg>\n  </trk>\n</gpx>\n");
+        gpx
+    }
+
+    /// TCX activity with its laps and their track points.
+    pub fn tcx(&self) -> String {
+        let sport = match self.sport() {
+            Some("running") => "Running",
+            Some("cycling") => "Biking",
+            _ => "Other",
+        };
+        let mut tcx = String::from(concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            "\n",
+            r#"<TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2" "#,
+            r#"xmlns:ns3="http://www.garmin.com/xmlschemas/ActivityExtension/v2">"#,
+            "\n  <Activities>\n"
+        ));
+        tcx.push_str(&format!("    <Activity Sport=\"{}\">\n", sport));
+        if let Some(start) = self.start_time() {
+            tcx.push_str(&format!("      <Id>{}</Id>\n", xml_time(start)));
+        }
+        let laps = self.laps();
+        for (i, lap) in laps.iter().enumerate() {
+            // records after the last lap still belong to it
+            let last = i + 1 == laps.len();
+            let records = self
+                .records
+                .iter()
+                .filter(|r| r.timestamp >= lap.start_time && (r.timestamp < lap.end_time || last));
+            tcx.push_str(&format!(
+                "      <Lap StartTime=\"{}\">\n",
+                xml_time(lap.start_time)
+            ));
+            tcx.push_str(&format!(
+                "        <TotalTimeSeconds>{:.1}</TotalTimeSeconds>\n",
+                lap.duration()
+            ));
+            tcx.push_str(&format!(
+                "        <DistanceMeters>{:.1}</DistanceMeters>\n",
+                lap.distance.unwrap_or_default()
+            ));
+            if let Some(max_speed) = lap.max_speed {
+                tcx.push_str(&format!(
+                    "        <MaximumSpeed>{:.3}</MaximumSpeed>\n",
+                    max_speed
+                ));
+            }
+            tcx.push_str(&format!(
+                "        <Calories>{}</Calories>\n",
+                lap.calories.unwrap_or_default().max(0)
+            ));
+            if let Some(heartrate) = lap.average_heartrate {
+                tcx.push_str(&format!(
+                    "        <AverageHeartRateBpm><Value>{}</Value></AverageHeartRateBpm>\n",
+                    heartrate
+                ));
+            }
+            if let Some(heartrate) = lap.max_heartrate {
+                tcx.push_str(&format!(
+                    "        <MaximumHeartRateBpm><Value>{}</Value></MaximumHeartRateBpm>\n",
+                    heartrate
+                ));
+            }
+            tcx.push_str("        <Intensity>Active</Intensity>\n");
+            tcx.push_str("        <TriggerMethod>Manual</TriggerMethod>\n");
+            tcx.push_str("        <Track>\n");
+            for r in records {
+                tcx.push_str("          <Trackpoint>");
+                tcx.push_str(&format!("<Time>{}</Time>", xml_time(r.timestamp)));
+                if let (Some(latitude), Some(longitude)) = (r.latitude, r.longitude) {
+                    tcx.push_str(&format!(
+                        "<Position><LatitudeDegrees>{:.7}</LatitudeDegrees><LongitudeDegrees>{:.7}</LongitudeDegrees></Position>",
+                        latitude, longitude
+                    ));
+                }
+                if let Some(altitude) = r.altitude {
+                    tcx.push_str(&format!("<AltitudeMeters>{:.1}</AltitudeMeters>", altitude));
+                }
+                if let Some(distance) = r.distance {
+                    tcx.push_str(&format!("<DistanceMeters>{:.2}</DistanceMeters>", distance));
+                }
+                if let Some(heartrate) = r.heartrate {
+                    tcx.push_str(&format!(
+                        "<HeartRateBpm><Value>{}</Value></HeartRateBpm>",
+                        heartrate
+                    ));
+                }
+                if r.speed.is_some() || r.power.is_some() {
+                    tcx.push_str("<Extensions><ns3:TPX>");
+                    if let Some(speed) = r.speed {
+                        tcx.push_str(&format!("<ns3:Speed>{:.3}</ns3:Speed>", speed));
+                    }
+                    if let Some(power) = r.power {
+                        tcx.push_str(&format!("<ns3:Watts>{}</ns3:Watts>", power));
+                    }
+                    tcx.push_str("</ns3:TPX></Extensions>");
+                }
+                tcx.push_str("</Trackpoint>\n");
+            }
+            tcx.push_str("        </Track>\n      </Lap>\n");
+        }
+        tcx.push_str("    </Activity>\n  </Activities>\n</TrainingCenterDatabase>\n");
+        tcx
+    }
+
+    /// All records as CSV, one row per record.
+    pub fn csv(&self) -> String {
+        let optional = |value: Option<f64>, precision: usize| {
+            value
+                .map(|v| format!("{:.*}", precision, v))
+                .unwrap_or_default()
+        };
+        let start = self.records.first().map(|r| r.timestamp);
+        let mut csv = String::from(
+            "timestamp,elapsed_s,latitude,longitude,altitude_m,distance_m,speed_mps,heartrate,power\n",
+        );
+        for r in self.records.iter() {
+            let elapsed = start
+                .map(|start| (r.timestamp - start).num_milliseconds() as f64 / 1000.0)
+                .unwrap_or_default();
+            csv.push_str(&format!(
+                "{},{:.1},{},{},{},{},{},{},{}\n",
+                xml_time(r.timestamp),
+                elapsed,
+                optional(r.latitude, 7),
+                optional(r.longitude, 7),
+                optional(r.altitude, 1),
+                optional(r.distance, 2),
+                optional(r.speed, 3),
+                r.heartrate.map(|h| h.to_string()).unwrap_or_default(),
+                r.power.map(|p| p.to_string()).unwrap_or_default(),
+            ));
+        }
+        csv
+    }
+}
+
+fn xml_time(time: DateTime<Local>) -> String {
+    time.with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// FIT enum value of a sport as it's stored, e.g. `running`.
+fn fit_sport(sport: Option<&str>) -> Option<i64> {
+    let sport = sport?;
+    (0..u8::MAX)
+        .find(|&v| Sport::is_named_variant(v as i64) && Sport::from(v).to_string() == sport)
+        .map(i64::from)
+}
+
+/// FIT enum value of a sub sport as it's stored, e.g. `trail`.
+fn fit_sub_sport(sub_sport: Option<&str>) -> Option<i64> {
+    let sub_sport = sub_sport?;
+    (0..u8::MAX)
+        .find(|&v| {
+            SubSport::is_named_variant(v as i64) && SubSport::from(v).to_string() == sub_sport
+        })
+        .map(i64::from)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FitBaseType {
+    Enum,
+    UInt8,
+    UInt16,
+    SInt32,
+    UInt32,
+}
+
+impl FitBaseType {
+    fn id(&self) -> u8 {
+        match self {
+            FitBaseType::Enum => 0x00,
+            FitBaseType::UInt8 => 0x02,
+            FitBaseType::UInt16 => 0x84,
+            FitBaseType::SInt32 => 0x85,
+            FitBaseType::UInt32 => 0x86,
+        }
+    }
+
+    fn size(&self) -> u8 {
+        match self {
+            FitBaseType::Enum | FitBaseType::UInt8 => 1,
+            FitBaseType::UInt16 => 2,
+            FitBaseType::SInt32 | FitBaseType::UInt32 => 4,
+        }
+    }
+
+    /// Range of valid values, the value just past it marks a missing one.
+    fn range(&self) -> (i64, i64) {
+        match self {
+            FitBaseType::Enum | FitBaseType::UInt8 => (0, 0xFF),
+            FitBaseType::UInt16 => (0, 0xFFFF),
+            FitBaseType::SInt32 => (-0x7FFFFFFF, 0x7FFFFFFF),
+            FitBaseType::UInt32 => (0, 0xFFFFFFFF),
+        }
+    }
+}
+
+/// Writes FIT messages in little endian and wraps them in a header and checksum.
+#[derive(Default)]
+struct FitWriter {
+    data: Vec<u8>,
+}
+
+impl FitWriter {
+    fn definition(&mut self, local: u8, global: u16, fields: &[(u8, FitBaseType)]) {
+        self.data.push(0x40 | local);
+        // reserved, little endian
+        self.data.extend([0, 0]);
+        self.data.extend(global.to_le_bytes());
+        self.data.push(fields.len() as u8);
+        for (number, base_type) in fields {
+            self.data
+                .extend([*number, base_type.size(), base_type.id()]);
+        }
+    }
+
+    /// Data message of a local type defined with `fields`, values out of range are left out.
+    fn data(&mut self, local: u8, fields: &[(u8, FitBaseType)], values: &[Option<i64>]) {
+        self.data.push(local);
+        for ((_, base_type), value) in fields.iter().zip(values) {
+            let (min, invalid) = base_type.range();
+            let value = value
+                .filter(|v| (min..invalid).contains(v))
+                .unwrap_or(invalid);
+            self.data
+                .extend(&value.to_le_bytes()[..base_type.size() as usize]);
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let mut file = vec![14, 0x20];
+        // profile version 21.32
+        file.extend(2132u16.to_le_bytes());
+        file.extend((self.data.len() as u32).to_le_bytes());
+        file.extend(b".FIT");
+        file.extend(fit_crc(&file).to_le_bytes());
+        file.extend(self.data);
+        file.extend(fit_crc(&file).to_le_bytes());
+        file
+    }
+}
+
+fn fit_crc(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0, |crc, &byte| {
+        let crc =
+            (crc >> 4) ^ FIT_CRC_TABLE[(crc & 0xF) as usize] ^ FIT_CRC_TABLE[(byte & 0xF) as usize];
+        (crc >> 4) ^ FIT_CRC_TABLE[(crc & 0xF) as usize] ^ FIT_CRC_TABLE[(byte >> 4) as usize]
+    })
+}
+
+/// Sessions, laps and records of an activity of the user.
+#[cfg(feature = "ssr")]
+pub async fn load_activity_export(
+    activity_id: i64,
+    user_id: i64,
+    executor: &mut PgConnection,
+) -> Result<ActivityExport, ModelError> {
+    sqlx::query!(
+        r#"
+        SELECT id
+        FROM activities
+        WHERE id=$1 and user_id=$2::bigint
+        "#,
+        activity_id,
+        user_id
+    )
+    .fetch_optional(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load activity: {}", e)))?
+    .ok_or(ModelError::QueryError("Activity not found".to_string()))?;
+    let sessions = sqlx::query!(
+        r#"
+        SELECT
+            start_time,
+            end_time,
+            sport,
+            sub_sport,
+            distance::float8,
+            calories,
+            average_heartrate,
+            min_heartrate,
+            max_heartrate,
+            average_power,
+            ascent,
+            descent,
+            average_speed::float8,
+            max_speed::float8,
+            pool_length::float8
+        FROM sessions
+        WHERE activity_id=$1
+        ORDER BY start_time
+        "#,
+        activity_id as i32
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load sessions: {}", e)))?
+    .into_iter()
+    .map(|s| Session {
+        start_time: s.start_time.into(),
+        end_time: s.end_time.into(),
+        sport: s.sport,
+        sub_sport: s.sub_sport,
+        distance: s.distance,
+        calories: s.calories.map(i32::from),
+        average_heartrate: s.average_heartrate,
+        min_heartrate: s.min_heartrate,
+        max_heartrate: s.max_heartrate,
+        average_power: s.average_power.map(i32::from),
+        ascent: s.ascent.map(i32::from),
+        descent: s.descent.map(i32::from),
+        average_speed: s.average_speed,
+        max_speed: s.max_speed,
+        pool_length: s.pool_length,
+    })
+    .collect();
+    let laps = sqlx::query!(
+        r#"
+        SELECT
+            start_time,
+            end_time,
+            sport,
+            sub_sport,
+            distance::float8,
+            calories,
+            average_heartrate,
+            min_heartrate,
+            max_heartrate,
+            average_power,
+            ascent,
+            descent,
+            average_speed::float8,
+            max_speed::float8
+        FROM laps
+        WHERE activity_id=$1
+        ORDER BY start_time
+        "#,
+        activity_id as i32
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load laps: {}", e)))?
+    .into_iter()
+    .map(|l| Lap {
+        start_time: l.start_time.into(),
+        end_time: l.end_time.into(),
+        sport: l.sport,
+        sub_sport: l.sub_sport,
+        distance: l.distance,
+        calories: l.calories.map(i32::from),
+        average_heartrate: l.average_heartrate,
+        min_heartrate: l.min_heartrate,
+        max_heartrate: l.max_heartrate,
+        average_power: l.average_power.map(i32::from),
+        ascent: l.ascent.map(i32::from),
+        descent: l.descent.map(i32::from),
+        average_speed: l.average_speed,
+        max_speed: l.max_speed,
+    })
+    .collect();
+    let records = sqlx::query!(
+        r#"
+        SELECT
+            timestamp,
+            heartrate,
+            latitude,
+            longitude,
+            distance::float8,
+            speed::float8,
+            altitude::float8,
+            power
+        FROM records
+        WHERE activity_id=$1
+        ORDER BY timestamp
+        "#,
+        activity_id as i32
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load records: {}", e)))?
+    .into_iter()
+    .map(|r| Record {
+        timestamp: r.timestamp.into(),
+        heartrate: r.heartrate,
+        latitude: r.latitude,
+        longitude: r.longitude,
+        raw_latitude: None,
+        raw_longitude: None,
+        distance: r.distance,
+        speed: r.speed,
+        altitude: r.altitude,
+        raw_altitude: None,
+        power: r.power,
+    })
+    .collect();
+    Ok(ActivityExport {
+        sessions,
+        laps,
+        records,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{ActivityExport, ExportFormat};
+    #[cfg(feature = "ssr")]
+    use crate::models::{
+        base::{DatabaseEntry, New},
+        record::Record,
+        session::Session,
+    };
+    #[cfg(feature = "ssr")]
+    use chrono::{Duration, Local, TimeZone};
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_export() {
+        let start = Local.with_ymd_and_hms(2024, 5, 1, 8, 0, 0).unwrap();
+        let records: Vec<Record> = (0..60)
+            .map(|t| Record {
+                timestamp: start + Duration::try_seconds(t).unwrap(),
+                heartrate: Some(120 + t as i16),
+                latitude: Some(47.0 + t as f64 * 0.0001),
+                longitude: Some(8.0),
+                raw_latitude: None,
+                raw_longitude: None,
+                distance: Some(t as f64 * 3.0),
+                speed: Some(3.0),
+                altitude: Some(400.0),
+                raw_altitude: None,
+                power: None,
+            })
+            .collect();
+        let export = ActivityExport {
+            sessions: vec![Session {
+                start_time: start,
+                end_time: start + Duration::try_seconds(59).unwrap(),
+                sport: Some("running".to_string()),
+                sub_sport: Some("trail".to_string()),
+                distance: Some(177.0),
+                calories: Some(12),
+                average_heartrate: Some(150),
+                min_heartrate: Some(120),
+                max_heartrate: Some(179),
+                average_power: None,
+                ascent: Some(0),
+                descent: Some(0),
+                average_speed: Some(3.0),
+                max_speed: Some(3.0),
+                pool_length: None,
+            }],
+            laps: vec![],
+            records,
+        };
+
+        let fit = export.fit();
+        let messages = fitparser::from_bytes(&fit).unwrap();
+        let mut records = Vec::new();
+        let mut sessions = Vec::new();
+        for message in messages {
+            match message.kind() {
+                fitparser::profile::MesgNum::Record => {
+                    records.push(DatabaseEntry::<New, Record>::try_from(message).unwrap())
+                }
+                fitparser::profile::MesgNum::Session => {
+                    sessions.push(DatabaseEntry::<New, Session>::try_from(message).unwrap())
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(records.len(), 60);
+        assert_eq!(records[10].state.timestamp, export.records[10].timestamp);
+        assert_eq!(records[10].state.heartrate, Some(130));
+        assert!((records[10].state.latitude.unwrap() - 47.001).abs() < 1e-6);
+        assert_eq!(records[10].state.distance, Some(30.0));
+        assert_eq!(records[10].state.altitude, Some(400.0));
+        assert_eq!(records[10].state.power, None);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].state.sport.as_deref(), Some("running"));
+        assert_eq!(sessions[0].state.sub_sport.as_deref(), Some("trail"));
+        assert_eq!(sessions[0].state.distance, Some(177.0));
+
+        let gpx = export.gpx();
+        assert_eq!(gpx.matches("<trkpt").count(), 60);
+        assert!(gpx.contains(r#"<trkpt lat="47.0000000" lon="8.0000000"><ele>400.0</ele>"#));
+        let tcx = export.tcx();
+        assert!(tcx.contains(r#"<Activity Sport="Running">"#));
+        assert_eq!(tcx.matches("<Trackpoint>").count(), 60);
+        let csv = export.csv();
+        assert_eq!(csv.lines().count(), 61);
+        assert!(csv
+            .lines()
+            .nth(2)
+            .unwrap()
+            .ends_with(",1.0,47.0001000,8.0000000,400.0,3.00,3.000,121,"));
+        assert_eq!(ExportFormat::from_extension("GPX"), Some(ExportFormat::Gpx));
+        assert_eq!(ExportFormat::from_extension("kml"), None);
+    }
+}
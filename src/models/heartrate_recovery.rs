@@ -0,0 +1,285 @@
+//! Heart rate recovery after hard efforts and cardiac drift over an activity.
+//!
+//! Recovery is the drop of the heart rate one and two minutes after a peak, the faster it drops
+//! the fitter. Cardiac drift is how much the heart rate rises for the same speed or power from
+//! the first to the second half, it's only meaningful for steady efforts.
+#[cfg(feature = "ssr")]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "ssr")]
+use sqlx::{PgConnection, PgPool};
+
+#[cfg(feature = "ssr")]
+use super::{base::ModelError, user_preferences::get_user_preferences};
+
+/// Seconds around a peak it has to be the highest heart rate in.
+const PEAK_WINDOW: f64 = 30.0;
+/// Drop in bpm one minute after a peak, below which the effort didn't end.
+const MIN_DROP: f64 = 10.0;
+/// Rise in bpm in the second minute after a peak that means the next effort started.
+const MAX_RISE: f64 = 5.0;
+/// Seconds a heart rate sample may be away from the time it's taken for.
+const MAX_GAP: f64 = 5.0;
+/// Seconds an activity has to last for its cardiac drift to be calculated.
+const MIN_DRIFT_DURATION: f64 = 1200.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeartrateRecovery {
+    /// Seconds since the start of the activity.
+    pub time: f64,
+    pub peak: f64,
+    /// Drop in bpm one minute after the peak.
+    pub recovery_60: f64,
+    /// Drop in bpm two minutes after the peak, unless the next effort started before.
+    pub recovery_120: Option<f64>,
+}
+
+/// Heart rate of the first sample at or after `time`, if it's close enough.
+fn heartrate_at(samples: &[(f64, f64)], time: f64) -> Option<f64> {
+    let index = samples.partition_point(|s| s.0 < time);
+    samples
+        .get(index)
+        .filter(|s| s.0 - time <= MAX_GAP)
+        .map(|s| s.1)
+}
+
+fn max_heartrate(samples: &[(f64, f64)], from: f64, to: f64) -> Option<f64> {
+    let start = samples.partition_point(|s| s.0 <= from);
+    samples[start..]
+        .iter()
+        .take_while(|s| s.0 <= to)
+        .map(|s| s.1)
+        .reduce(f64::max)
+}
+
+/// Recoveries after all peaks of at least `min_peak` bpm that are followed by an easy minute.
+///
+/// `samples` are pairs of seconds since the start and heart rate, sorted by time.
+pub fn heartrate_recoveries(samples: &[(f64, f64)], min_peak: f64) -> Vec<HeartrateRecovery> {
+    let mut recoveries: Vec<HeartrateRecovery> = Vec::new();
+    for (i, &(time, peak)) in samples.iter().enumerate() {
+        if peak < min_peak || recoveries.last().is_some_and(|r| time < r.time + 60.0) {
+            continue;
+        }
+        // the first sample of a plateau is the peak
+        let is_peak = samples[..i]
+            .iter()
+            .rev()
+            .take_while(|s| s.0 >= time - PEAK_WINDOW)
+            .all(|s| s.1 < peak)
+            && samples[i + 1..]
+                .iter()
+                .take_while(|s| s.0 <= time + PEAK_WINDOW)
+                .all(|s| s.1 <= peak);
+        if !is_peak || max_heartrate(samples, time, time + 60.0).is_some_and(|h| h > peak) {
+            continue;
+        }
+        let Some(after_60) = heartrate_at(samples, time + 60.0) else {
+            continue;
+        };
+        if peak - after_60 < MIN_DROP {
+            continue;
+        }
+        let recovery_120 = heartrate_at(samples, time + 120.0)
+            .filter(|_| {
+                max_heartrate(samples, time + 60.0, time + 120.0)
+                    .is_some_and(|h| h <= after_60 + MAX_RISE)
+            })
+            .map(|after_120| peak - after_120);
+        recoveries.push(HeartrateRecovery {
+            time,
+            peak,
+            recovery_60: peak - after_60,
+            recovery_120,
+        });
+    }
+    recoveries
+}
+
+/// Rise in percent of the heart rate relative to the output, speed or power, from the first to
+/// the second half of the activity.
+///
+/// `samples` are tuples of seconds since the start, heart rate and output, sorted by time.
+/// Returns nothing for short activities.
+pub fn cardiac_drift(samples: &[(f64, f64, f64)]) -> Option<f64> {
+    let samples: Vec<_> = samples.iter().filter(|s| s.1 > 0.0 && s.2 > 0.0).collect();
+    let start = samples.first()?.0;
+    let end = samples.last()?.0;
+    if end - start < MIN_DRIFT_DURATION {
+        return None;
+    }
+    let middle = start + (end - start) / 2.0;
+    let efficiency = |half: &[&(f64, f64, f64)]| -> Option<f64> {
+        let heartrate: f64 = half.iter().map(|s| s.1).sum();
+        let output: f64 = half.iter().map(|s| s.2).sum();
+        (heartrate > 0.0).then(|| output / heartrate)
+    };
+    let split = samples.partition_point(|s| s.0 < middle);
+    let first = efficiency(&samples[..split])?;
+    let second = efficiency(&samples[split..])?;
+    Some((first - second) / first * 100.0)
+}
+
+/// Recalculates the heart rate recovery and cardiac drift of an activity from its records.
+///
+/// Peaks have to be above the user's aerobic threshold to count as a hard effort.
+#[cfg(feature = "ssr")]
+pub async fn update_heartrate_recovery(
+    activity_id: i64,
+    user_id: i64,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    let records = sqlx::query!(
+        r#"
+        SELECT
+            timestamp as "timestamp!:DateTime<Utc>",
+            heartrate,
+            speed::float8 as speed,
+            power
+        FROM records
+        WHERE activity_id=$1
+        ORDER BY timestamp
+        "#,
+        activity_id as i32
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load records: {}", e)))?;
+    let (recoveries, drift) = match records.first() {
+        Some(first) => {
+            let start = first.timestamp;
+            let preferences = get_user_preferences(user_id, start.into(), &mut *executor).await;
+            let elapsed =
+                |timestamp: DateTime<Utc>| (timestamp - start).num_milliseconds() as f64 / 1000.0;
+            let heartrates: Vec<(f64, f64)> = records
+                .iter()
+                .filter_map(|r| Some((elapsed(r.timestamp), r.heartrate? as f64)))
+                .collect();
+            // power tracks the effort better than speed, if there is any
+            let has_power = records.iter().any(|r| r.power.is_some_and(|p| p > 0));
+            let outputs: Vec<(f64, f64, f64)> = records
+                .iter()
+                .filter_map(|r| {
+                    let output = if has_power { r.power? as f64 } else { r.speed? };
+                    Some((elapsed(r.timestamp), r.heartrate? as f64, output))
+                })
+                .collect();
+            (
+                heartrate_recoveries(&heartrates, preferences.aerobic_threshold as f64),
+                cardiac_drift(&outputs),
+            )
+        }
+        None => (Vec::new(), None),
+    };
+    let average = |values: Vec<f64>| {
+        (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+    };
+    sqlx::query!(
+        r#"
+        INSERT INTO heartrate_recoveries(activity_id, efforts, recovery_60, recovery_120, cardiac_drift)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (activity_id) DO UPDATE
+        SET efforts=EXCLUDED.efforts,
+            recovery_60=EXCLUDED.recovery_60,
+            recovery_120=EXCLUDED.recovery_120,
+            cardiac_drift=EXCLUDED.cardiac_drift
+        "#,
+        activity_id as i32,
+        recoveries.len() as i32,
+        average(recoveries.iter().map(|r| r.recovery_60).collect()),
+        average(recoveries.iter().filter_map(|r| r.recovery_120).collect()),
+        drift
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| {
+        ModelError::InsertError(format!("Couldn't update heart rate recovery: {}", e))
+    })?;
+    Ok(())
+}
+
+/// Calculates the heart rate recovery of all activities with heart rate records that were
+/// uploaded before it was tracked.
+///
+/// Every calculated activity gets a row, so this only has work to do the first time it runs.
+/// Activities that fail are logged and skipped, they are retried on the next start.
+#[cfg(feature = "ssr")]
+pub async fn backfill_heartrate_recovery(pool: &PgPool) -> Result<(), ModelError> {
+    let missing = sqlx::query!(
+        r#"
+        SELECT activities.id, activities.user_id
+        FROM activities
+        WHERE NOT EXISTS (
+                SELECT 1 FROM heartrate_recoveries
+                WHERE heartrate_recoveries.activity_id = activities.id
+            )
+            AND EXISTS (
+                SELECT 1 FROM records
+                WHERE records.activity_id = activities.id AND records.heartrate IS NOT NULL
+            )
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load activities: {}", e)))?;
+    for activity in missing {
+        let result = match pool.acquire().await {
+            Ok(mut connection) => {
+                update_heartrate_recovery(activity.id, activity.user_id as i64, &mut connection)
+                    .await
+            }
+            Err(e) => Err(ModelError::QueryError(format!(
+                "Couldn't acquire connection: {}",
+                e
+            ))),
+        };
+        if let Err(e) = result {
+            log::error!(
+                "couldn't backfill heart rate recovery of activity {}: {}",
+                activity.id,
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{cardiac_drift, heartrate_recoveries};
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_heartrate_recovery() {
+        // 3 x (3 min hard, 3 min easy), recovering 30 bpm in the first minute and 10 more after
+        let samples: Vec<(f64, f64)> = (0..1080)
+            .map(|t| {
+                let phase = t % 360;
+                let heartrate = if phase < 180 {
+                    140.0 + phase as f64 / 6.0
+                } else if phase < 240 {
+                    170.0 - (phase - 180) as f64 / 2.0
+                } else if phase < 300 {
+                    140.0 - (phase - 240) as f64 / 6.0
+                } else {
+                    130.0
+                };
+                (t as f64, heartrate)
+            })
+            .collect();
+        let recoveries = heartrate_recoveries(&samples, 160.0);
+        assert_eq!(recoveries.len(), 3);
+        assert_eq!(recoveries[0].time, 180.0);
+        assert!((recoveries[0].recovery_60 - 30.0).abs() < 1.0);
+        assert!((recoveries[0].recovery_120.unwrap() - 40.0).abs() < 1.0);
+        assert!(heartrate_recoveries(&samples, 175.0).is_empty());
+
+        // heart rate rising 10% over an hour at the same speed
+        let steady: Vec<(f64, f64, f64)> = (0..3600)
+            .map(|t| (t as f64, 140.0 + 14.0 * t as f64 / 3600.0, 3.0))
+            .collect();
+        let drift = cardiac_drift(&steady).unwrap();
+        assert!(drift > 4.0 && drift < 6.0);
+        assert_eq!(cardiac_drift(&steady[..600]), None);
+    }
+}
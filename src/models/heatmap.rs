@@ -0,0 +1,180 @@
+//! Recorded positions aggregated into geohash cells, so the heatmap doesn't need every record.
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "ssr")]
+use sqlx::PgConnection;
+
+#[cfg(feature = "ssr")]
+use super::base::ModelError;
+
+/// Geohash length of the stored cells, about 150m by 150m.
+pub const HEATMAP_PRECISION: usize = 7;
+
+const GEOHASH_BASE32: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Most geohash prefixes a map view is covered with.
+const MAX_COVER_CELLS: usize = 64;
+
+/// Visible area of a map as latitude/longitude.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MapBounds {
+    pub south: f64,
+    pub west: f64,
+    pub north: f64,
+    pub east: f64,
+}
+
+impl MapBounds {
+    /// Geohash prefixes of equal length whose cells cover the bounds, as long as at most
+    /// `MAX_COVER_CELLS` are needed but no longer than `max_chars`.
+    pub fn geohash_cover(&self, max_chars: usize) -> Vec<String> {
+        let south = self.south.clamp(-90.0, 90.0 - 1e-9);
+        let north = self.north.clamp(south, 90.0 - 1e-9);
+        let west = self.west.clamp(-180.0, 180.0 - 1e-9);
+        let east = self.east.clamp(west, 180.0 - 1e-9);
+        for chars in (1..=max_chars).rev() {
+            // longitude takes the first of the 5 bits of a character
+            let width = 360.0 / 2f64.powi(((5 * chars + 1) / 2) as i32);
+            let height = 180.0 / 2f64.powi((5 * chars / 2) as i32);
+            let cols =
+                ((west + 180.0) / width).floor() as i64..=((east + 180.0) / width).floor() as i64;
+            let rows =
+                ((south + 90.0) / height).floor() as i64..=((north + 90.0) / height).floor() as i64;
+            if cols.clone().count() * rows.clone().count() > MAX_COVER_CELLS {
+                continue;
+            }
+            return rows
+                .flat_map(|row| {
+                    cols.clone().map(move |col| {
+                        let latitude = (row as f64 + 0.5) * height - 90.0;
+                        let longitude = (col as f64 + 0.5) * width - 180.0;
+                        geohash_encode(latitude, longitude, chars)
+                    })
+                })
+                .collect();
+        }
+        Vec::new()
+    }
+}
+
+/// Geohash of a position with `chars` characters.
+pub fn geohash_encode(latitude: f64, longitude: f64, chars: usize) -> String {
+    let (mut lat_min, mut lat_max) = (-90.0, 90.0);
+    let (mut lon_min, mut lon_max) = (-180.0, 180.0);
+    let mut even = true;
+    (0..chars)
+        .map(|_| {
+            let mut value = 0;
+            for _ in 0..5 {
+                let (min, max, position) = if even {
+                    (&mut lon_min, &mut lon_max, longitude)
+                } else {
+                    (&mut lat_min, &mut lat_max, latitude)
+                };
+                let mid = (*min + *max) / 2.0;
+                value <<= 1;
+                if position >= mid {
+                    value |= 1;
+                    *min = mid;
+                } else {
+                    *max = mid;
+                }
+                even = !even;
+            }
+            GEOHASH_BASE32[value] as char
+        })
+        .collect()
+}
+
+/// South west and north east corner of a geohash cell as latitude/longitude.
+pub fn geohash_bounds(geohash: &str) -> Option<((f64, f64), (f64, f64))> {
+    let (mut lat_min, mut lat_max) = (-90.0, 90.0);
+    let (mut lon_min, mut lon_max) = (-180.0, 180.0);
+    let mut even = true;
+    for c in geohash.bytes() {
+        let value = GEOHASH_BASE32.iter().position(|&b| b == c)?;
+        for bit in (0..5).rev() {
+            let set = value & (1 << bit) != 0;
+            let (min, max) = if even {
+                (&mut lon_min, &mut lon_max)
+            } else {
+                (&mut lat_min, &mut lat_max)
+            };
+            let mid = (*min + *max) / 2.0;
+            if set {
+                *min = mid;
+            } else {
+                *max = mid;
+            }
+            even = !even;
+        }
+    }
+    Some(((lat_min, lon_min), (lat_max, lon_max)))
+}
+
+/// Recounts the heatmap cells of an activity from its records.
+#[cfg(feature = "ssr")]
+pub async fn update_heatmap_cells(
+    activity_id: i64,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    sqlx::query!(
+        r#"
+        DELETE FROM heatmap_cells
+        WHERE activity_id=$1
+        "#,
+        activity_id as i32
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't delete heatmap cells: {}", e)))?;
+    sqlx::query!(
+        r#"
+        INSERT INTO heatmap_cells (activity_id, geohash, points)
+        SELECT activity_id, geohash_encode(latitude, longitude, $2), COUNT(*)
+        FROM records
+        WHERE activity_id=$1 and latitude IS NOT NULL and longitude IS NOT NULL
+        GROUP BY activity_id, geohash_encode(latitude, longitude, $2)
+        "#,
+        activity_id as i32,
+        HEATMAP_PRECISION as i32
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't insert heatmap cells: {}", e)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{geohash_bounds, geohash_encode, MapBounds};
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_geohash_bounds() {
+        let ((south, west), (north, east)) = geohash_bounds("u4pruyd").unwrap();
+        assert!(south <= 57.64911 && 57.64911 <= north);
+        assert!(west <= 10.40744 && 10.40744 <= east);
+        assert!(north - south < 0.002 && east - west < 0.002);
+        assert_eq!(geohash_bounds("u4a"), None);
+        assert_eq!(geohash_encode(57.64911, 10.40744, 7), "u4pruyd");
+    }
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_geohash_cover() {
+        let bounds = MapBounds {
+            south: 57.6,
+            west: 10.3,
+            north: 57.7,
+            east: 10.5,
+        };
+        let cover = bounds.geohash_cover(7);
+        assert!(!cover.is_empty() && cover.len() <= 64);
+        let chars = cover[0].len();
+        assert!(cover.iter().all(|prefix| prefix.len() == chars));
+        assert!(cover.contains(&geohash_encode(57.64911, 10.40744, chars)));
+        assert!(cover.contains(&geohash_encode(57.6, 10.3, chars)));
+        assert!(cover.contains(&geohash_encode(57.7, 10.5, chars)));
+    }
+}
@@ -0,0 +1,206 @@
+//! Work and rest intervals of activities recorded without pressing the lap button.
+//!
+//! The recording is smoothed and split into work and rest with the threshold between the two
+//! levels the effort settles on. Power is used if the activity has it, speed otherwise and heart
+//! rate as a last resort, since it lags behind the effort.
+
+/// Seconds on either side of a sample the effort is averaged over.
+const SMOOTHING_WINDOW: f64 = 10.0;
+/// Seconds an interval lasts at least, shorter ones are merged into their neighbours.
+const MIN_INTERVAL: f64 = 20.0;
+/// Difference between the work and rest level, relative to the work level, below which the
+/// activity is taken as a steady effort.
+const MIN_CONTRAST: f64 = 0.15;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntervalSample {
+    /// Seconds since the start of the activity.
+    pub time: f64,
+    pub heartrate: Option<f64>,
+    pub speed: Option<f64>,
+    pub power: Option<f64>,
+    pub distance: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interval {
+    pub work: bool,
+    /// Seconds since the start of the activity.
+    pub start: f64,
+    pub end: f64,
+    /// Meters.
+    pub distance: Option<f64>,
+    pub average_heartrate: Option<f64>,
+    pub average_speed: Option<f64>,
+    pub average_power: Option<f64>,
+}
+
+impl Interval {
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+fn average(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+/// Centered moving average over time, samples without a value count as zero.
+fn smooth(times: &[f64], values: &[f64]) -> Vec<f64> {
+    let mut smoothed = Vec::with_capacity(values.len());
+    let (mut from, mut to, mut sum) = (0, 0, 0.0);
+    for &time in times {
+        while to < times.len() && times[to] <= time + SMOOTHING_WINDOW {
+            sum += values[to];
+            to += 1;
+        }
+        while times[from] < time - SMOOTHING_WINDOW {
+            sum -= values[from];
+            from += 1;
+        }
+        smoothed.push(sum / (to - from) as f64);
+    }
+    smoothed
+}
+
+/// Rest and work level of the effort, as the two means of a one dimensional k-means.
+fn levels(values: &[f64]) -> (f64, f64) {
+    let mut low = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let mut high = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    for _ in 0..20 {
+        let threshold = (low + high) / 2.0;
+        let (rest, work): (Vec<f64>, Vec<f64>) = values.iter().partition(|&&v| v < threshold);
+        low = average(rest.into_iter()).unwrap_or(low);
+        high = average(work.into_iter()).unwrap_or(high);
+    }
+    (low, high)
+}
+
+/// Detects alternating work and rest intervals in the samples of an activity.
+///
+/// Returns nothing for steady efforts, or if there's no power, speed or heart rate to go by.
+pub fn detect_intervals(samples: &[IntervalSample]) -> Vec<Interval> {
+    let metrics: [fn(&IntervalSample) -> Option<f64>; 3] =
+        [|s| s.power, |s| s.speed, |s| s.heartrate];
+    let Some(metric) = metrics
+        .into_iter()
+        .find(|metric| samples.iter().any(|s| metric(s).is_some_and(|v| v > 0.0)))
+    else {
+        return Vec::new();
+    };
+    let times: Vec<f64> = samples.iter().map(|s| s.time).collect();
+    let values: Vec<f64> = samples.iter().map(|s| metric(s).unwrap_or(0.0)).collect();
+    let smoothed = smooth(&times, &values);
+    let (low, high) = levels(&smoothed);
+    if high <= 0.0 || (high - low) / high < MIN_CONTRAST {
+        return Vec::new();
+    }
+    let threshold = (low + high) / 2.0;
+
+    // runs of (work, first sample, last sample)
+    let mut runs: Vec<(bool, usize, usize)> = Vec::new();
+    for (i, &value) in smoothed.iter().enumerate() {
+        let work = value >= threshold;
+        match runs.last_mut() {
+            Some(run) if run.0 == work => run.2 = i,
+            _ => runs.push((work, i, i)),
+        }
+    }
+    // runs end where the next one starts, so the intervals cover the whole activity
+    let run_duration = |runs: &[(bool, usize, usize)], i: usize| {
+        let end = runs
+            .get(i + 1)
+            .map_or(times[runs[i].2], |next| times[next.1]);
+        end - times[runs[i].1]
+    };
+    // flip the shortest run and merge it with its neighbours until all are long enough
+    while runs.len() > 1 {
+        let (shortest, duration) = (0..runs.len())
+            .map(|i| (i, run_duration(&runs, i)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        if duration >= MIN_INTERVAL {
+            break;
+        }
+        // the neighbours are of the other kind, so all three become one run
+        let start = shortest.saturating_sub(1);
+        let end = (shortest + 1).min(runs.len() - 1);
+        let merged = (!runs[shortest].0, runs[start].1, runs[end].2);
+        runs.splice(start..=end, [merged]);
+    }
+    if !runs.iter().any(|r| r.0) || !runs.iter().any(|r| !r.0) {
+        return Vec::new();
+    }
+
+    let distance_at = |index: usize| samples[index..].iter().find_map(|s| s.distance);
+    (0..runs.len())
+        .map(|i| {
+            let (work, first, last) = runs[i];
+            let end = runs.get(i + 1).map_or(last, |next| next.1);
+            let start = times[first];
+            let samples = &samples[first..=last];
+            Interval {
+                work,
+                start,
+                end: times[end],
+                distance: distance_at(first)
+                    .zip(distance_at(end))
+                    .map(|(start, end)| end - start),
+                average_heartrate: average(samples.iter().filter_map(|s| s.heartrate)),
+                average_speed: average(samples.iter().filter_map(|s| s.speed)),
+                average_power: average(samples.iter().filter_map(|s| s.power)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{detect_intervals, IntervalSample};
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_detect_intervals() {
+        // 5 x (2 min at 4 m/s, 1 min at 2 m/s) with a short stop in the first rep
+        let mut distance = 0.0;
+        let samples: Vec<IntervalSample> = (0..900)
+            .map(|t| {
+                let work = t % 180 < 120;
+                let speed = match (work, t) {
+                    (_, 50..=54) => 0.0,
+                    (true, _) => 4.0,
+                    (false, _) => 2.0,
+                };
+                distance += speed;
+                IntervalSample {
+                    time: t as f64,
+                    heartrate: Some(if work { 170.0 } else { 140.0 }),
+                    speed: Some(speed),
+                    distance: Some(distance),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        let intervals = detect_intervals(&samples);
+        assert_eq!(intervals.len(), 10);
+        assert!(intervals[0].work);
+        assert!(!intervals[1].work);
+        assert!((intervals[0].duration() - 120.0).abs() <= 2.0);
+        assert!((intervals[3].duration() - 60.0).abs() <= 2.0);
+        assert!((intervals[2].distance.unwrap() - 480.0).abs() <= 8.0);
+        assert!((intervals[2].average_speed.unwrap() - 4.0).abs() < 0.1);
+        assert_eq!(intervals.last().unwrap().end, 899.0);
+
+        let steady: Vec<IntervalSample> = (0..600)
+            .map(|t| IntervalSample {
+                time: t as f64,
+                speed: Some(3.0 + (t % 7) as f64 * 0.05),
+                ..Default::default()
+            })
+            .collect();
+        assert!(detect_intervals(&steady).is_empty());
+        assert!(detect_intervals(&[]).is_empty());
+    }
+}
@@ -15,6 +15,7 @@ pub struct Lap {
     pub start_time: DateTime<Local>,
     pub end_time: DateTime<Local>,
     pub sport: Option<String>,
+    pub sub_sport: Option<String>,
     pub distance: Option<f64>,
     pub calories: Option<i32>,
     pub average_heartrate: Option<i16>,
@@ -72,6 +73,14 @@ impl TryFrom<FitDataRecord> for DatabaseEntry<New, Lap> {
                 _ => None,
             });
 
+        let sub_sport = fields.iter().find(|&f| f.name() == "sub_sport");
+        let sub_sport = sub_sport
+            .map(|val| val.clone().into_value())
+            .and_then(|val| match val {
+                Value::String(val) => Some(val),
+                _ => None,
+            });
+
         let average_heartrate = fields.iter().find(|&f| f.name() == "avg_heart_rate");
         let average_heartrate = average_heartrate
             .map(|val| val.clone().into_value())
@@ -169,6 +178,7 @@ impl TryFrom<FitDataRecord> for DatabaseEntry<New, Lap> {
                 start_time,
                 end_time,
                 sport,
+                sub_sport,
                 distance,
                 calories,
                 average_heartrate,
@@ -238,13 +248,14 @@ pub async fn insert_laps(
             )
         })
         .multiunzip();
-    // itertools only supports up to 12 iterators, so we do this one separately
+    // itertools only supports up to 12 iterators, so we do these separately
+    let sub_sport: Vec<_> = laps.iter().map(|r|r.state.sub_sport.clone()).collect();
     let max_speed: Vec<_> = laps.into_iter().map(|r|r.state.max_speed).collect();
     sqlx::query!(
         r#"
-        INSERT INTO laps(activity_id,start_time,end_time,sport,distance,calories,average_heartrate,min_heartrate,max_heartrate,average_power,ascent,descent,average_speed,max_speed)
+        INSERT INTO laps(activity_id,start_time,end_time,sport,distance,calories,average_heartrate,min_heartrate,max_heartrate,average_power,ascent,descent,average_speed,max_speed,sub_sport)
         SELECT *
-        FROM UNNEST($1::bigint[], $2::timestamptz[],$3::timestamptz[], $4::varchar[], $5::float8[], $6::int[], $7::smallint[], $8::smallint[], $9::smallint[], $10::int[], $11::int[], $12::int[], $13::float8[], $14::float8[])
+        FROM UNNEST($1::bigint[], $2::timestamptz[],$3::timestamptz[], $4::varchar[], $5::float8[], $6::int[], $7::smallint[], $8::smallint[], $9::smallint[], $10::int[], $11::int[], $12::int[], $13::float8[], $14::float8[], $15::varchar[])
         "#,
         &activity_ids[..],
         &start_time[..] as _,
@@ -260,6 +271,7 @@ pub async fn insert_laps(
         &descent[..] as _,
         &average_speed[..] as _,
         &max_speed[..] as _,
+        &sub_sport[..] as _,
         
     ).execute(executor).await
         .map_err(|e| ModelError::InsertError(format!("Couldn't insert lap: {}", e)))?;
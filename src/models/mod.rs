@@ -1,7 +1,22 @@
 pub mod activity;
+pub mod activity_edit;
 pub mod base;
+pub mod calibration;
+pub mod elevation;
+pub mod export;
+pub mod heartrate_recovery;
+pub mod heatmap;
+pub mod interval;
 pub mod lap;
 pub mod record;
+pub mod route_plan;
+pub mod segment;
 pub mod session;
 pub mod slope_speed;
+pub mod split;
+pub mod sport;
+pub mod strength_set;
+pub mod swim_length;
+pub mod track_cleanup;
 pub mod user_preferences;
+pub mod workout_completion;
@@ -12,9 +12,15 @@ pub struct Record {
     pub heartrate: Option<i16>,
     pub latitude: Option<f64>,
     pub longitude: Option<f64>,
+    /// Recorded position, if the track cleanup moved it.
+    pub raw_latitude: Option<f64>,
+    pub raw_longitude: Option<f64>,
     pub distance: Option<f64>,
     pub speed: Option<f64>,
     pub altitude: Option<f64>,
+    /// Recorded altitude, if `altitude` was corrected from an elevation model.
+    pub raw_altitude: Option<f64>,
+    pub power: Option<i16>,
 }
 
 fn int_to_coord(value: i32) -> f64 {
@@ -93,15 +99,25 @@ impl TryFrom<FitDataRecord> for DatabaseEntry<New, Record> {
             _ => None,
         });
 
+        let power = fields.iter().find(|&f| f.name() == "power");
+        let power = power.map(|p| p.clone().into_value()).and_then(|p| match p {
+            Value::UInt16(p) => i16::try_from(p).ok(),
+            _ => None,
+        });
+
         Ok(DatabaseEntry {
             state: Box::new(Record {
                 timestamp,
                 heartrate,
                 latitude,
                 longitude,
+                raw_latitude: None,
+                raw_longitude: None,
                 altitude,
+                raw_altitude: None,
                 distance,
                 speed,
+                power,
             }),
             extra: New,
         })
@@ -115,7 +131,23 @@ pub async fn insert_records(
 ) -> Result<(), ModelError> {
     let num_records = records.len();
     let activity_ids: Vec<i64> = std::iter::repeat(activity_id).take(num_records).collect();
-    let (timestamp, heartrate, distance, speed, altitude, latitude, longitude): (
+    let (
+        timestamp,
+        heartrate,
+        distance,
+        speed,
+        altitude,
+        raw_altitude,
+        latitude,
+        longitude,
+        raw_latitude,
+        raw_longitude,
+        power,
+    ): (
+        Vec<_>,
+        Vec<_>,
+        Vec<_>,
+        Vec<_>,
         Vec<_>,
         Vec<_>,
         Vec<_>,
@@ -132,18 +164,22 @@ pub async fn insert_records(
                 r.state.distance,
                 r.state.speed,
                 r.state.altitude,
+                r.state.raw_altitude,
                 r.state.latitude,
                 r.state.longitude,
+                r.state.raw_latitude,
+                r.state.raw_longitude,
+                r.state.power,
             )
         })
         .multiunzip();
     sqlx::query!(
         r#"
-        INSERT INTO records(activity_id, timestamp, heartrate, distance, speed, altitude, latitude, longitude)
+        INSERT INTO records(activity_id, timestamp, heartrate, distance, speed, altitude, raw_altitude, latitude, longitude, raw_latitude, raw_longitude, power)
         SELECT *
-        FROM UNNEST($1::bigint[], $2::timestamptz[], $3::smallint[], $4::float8[], $5::float8[], $6::float8[], $7::float8[], $8::float8[])
+        FROM UNNEST($1::bigint[], $2::timestamptz[], $3::smallint[], $4::float8[], $5::float8[], $6::float8[], $7::float8[], $8::float8[], $9::float8[], $10::float8[], $11::float8[], $12::smallint[])
         "#,
-        &activity_ids[..],&timestamp[..], &heartrate[..] as _, &distance[..] as _, &speed[..] as _, &altitude[..] as _, &latitude[..] as _, &longitude[..] as _).execute(executor).await
+        &activity_ids[..],&timestamp[..], &heartrate[..] as _, &distance[..] as _, &speed[..] as _, &altitude[..] as _, &raw_altitude[..] as _, &latitude[..] as _, &longitude[..] as _, &raw_latitude[..] as _, &raw_longitude[..] as _, &power[..] as _).execute(executor).await
         .map_err(|e| ModelError::InsertError(format!("Couldn't insert records: {}", e)))?;
 
     Ok(())
@@ -0,0 +1,210 @@
+//! Time estimates for planned routes from the personal slope speed curve.
+//!
+//! The route is cut into the same 100m sections slope speeds are recorded in, each section is
+//! ridden or run at the speed the user managed on its grade.
+use super::calibration::haversine;
+
+/// Length in meters of the sections the grade is computed over.
+const SECTION_LENGTH: f64 = 100.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutePoint {
+    pub position: (f64, f64),
+    /// Meters above sea level, drawn routes have none.
+    pub elevation: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteSplit {
+    /// Meters from the start to the end of the split.
+    pub distance: f64,
+    /// Seconds for this split.
+    pub duration: f64,
+    /// Seconds from the start to the end of the split.
+    pub elapsed: f64,
+    pub ascent: f64,
+    pub descent: f64,
+}
+
+fn attribute(tag: &str, name: &str) -> Option<f64> {
+    let start = tag.find(&format!(" {}=", name))? + name.len() + 2;
+    let quote = tag[start..].chars().next()?;
+    let value = &tag[start + 1..];
+    value[..value.find(quote)?].trim().parse().ok()
+}
+
+/// Track points of a GPX file, or its route points if it has no track.
+///
+/// Only positions and elevations are read, everything else in the file is ignored.
+pub fn parse_gpx(gpx: &str) -> Vec<RoutePoint> {
+    let points = |element: &str| -> Vec<RoutePoint> {
+        gpx.split(&format!("<{}", element))
+            .skip(1)
+            .filter_map(|point| {
+                let tag = &point[..point.find('>')?];
+                let content = point
+                    .find(&format!("</{}>", element))
+                    .map_or("", |end| &point[..end]);
+                let elevation = content.find("<ele>").and_then(|start| {
+                    let value = &content[start + 5..];
+                    value[..value.find("</ele>")?].trim().parse().ok()
+                });
+                Some(RoutePoint {
+                    position: (attribute(tag, "lat")?, attribute(tag, "lon")?),
+                    elevation,
+                })
+            })
+            .collect()
+    };
+    let track = points("trkpt");
+    if track.is_empty() {
+        points("rtept")
+    } else {
+        track
+    }
+}
+
+/// Speed in m/s on a grade, interpolated between the grades of the curve.
+///
+/// Grades beyond either end of the curve get the speed at that end.
+pub fn speed_for_slope(curve: &[(f64, f64)], slope: f64) -> Option<f64> {
+    let above = curve.iter().position(|&(s, _)| s >= slope);
+    match above {
+        Some(0) => curve.first().map(|&(_, speed)| speed),
+        Some(i) => {
+            let (low_slope, low_speed) = curve[i - 1];
+            let (high_slope, high_speed) = curve[i];
+            let ratio = (slope - low_slope) / (high_slope - low_slope);
+            Some(low_speed + (high_speed - low_speed) * ratio)
+        }
+        None => curve.last().map(|&(_, speed)| speed),
+    }
+}
+
+/// Estimated splits of `split_length` meters along a route.
+///
+/// `curve` holds the average speed per grade, sorted by grade. Points without elevation count as
+/// flat. Returns nothing if there's no route or no speeds to estimate with.
+pub fn plan_route(
+    points: &[RoutePoint],
+    curve: &[(f64, f64)],
+    split_length: f64,
+) -> Option<Vec<RouteSplit>> {
+    let curve: Vec<(f64, f64)> = curve.iter().copied().filter(|&(_, s)| s > 0.0).collect();
+    if points.len() < 2 || curve.is_empty() {
+        return None;
+    }
+    // cumulative distance and elevation, carrying the last known elevation over gaps
+    let mut profile = Vec::with_capacity(points.len());
+    let mut distance = 0.0;
+    let mut elevation = points.iter().find_map(|p| p.elevation).unwrap_or_default();
+    for (i, point) in points.iter().enumerate() {
+        if i > 0 {
+            distance += haversine(points[i - 1].position, point.position);
+        }
+        elevation = point.elevation.unwrap_or(elevation);
+        profile.push((distance, elevation));
+    }
+    let total = distance;
+    if total <= 0.0 {
+        return None;
+    }
+    let mut index = 0;
+    let mut elevation_at = |at: f64| -> f64 {
+        while index + 1 < profile.len() - 1 && profile[index + 1].0 < at {
+            index += 1;
+        }
+        let (d0, e0) = profile[index];
+        let (d1, e1) = profile[index + 1];
+        if d1 > d0 {
+            e0 + (e1 - e0) * ((at - d0) / (d1 - d0)).clamp(0.0, 1.0)
+        } else {
+            e1
+        }
+    };
+
+    let mut splits = Vec::new();
+    let mut current = RouteSplit {
+        distance: 0.0,
+        duration: 0.0,
+        elapsed: 0.0,
+        ascent: 0.0,
+        descent: 0.0,
+    };
+    let mut start = 0.0;
+    let mut start_elevation = elevation_at(0.0);
+    while start < total {
+        let next_split = (splits.len() + 1) as f64 * split_length;
+        let end = (start + SECTION_LENGTH).min(next_split).min(total);
+        let end_elevation = elevation_at(end);
+        let climb = end_elevation - start_elevation;
+        let slope = (climb / (end - start)).clamp(-1.0, 1.0);
+        let duration = (end - start) / speed_for_slope(&curve, slope)?;
+        current.distance = end;
+        current.duration += duration;
+        current.elapsed += duration;
+        if climb > 0.0 {
+            current.ascent += climb;
+        } else {
+            current.descent -= climb;
+        }
+        if end >= next_split || end >= total {
+            splits.push(current.clone());
+            current.duration = 0.0;
+            current.ascent = 0.0;
+            current.descent = 0.0;
+        }
+        start = end;
+        start_elevation = end_elevation;
+    }
+    Some(splits)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{parse_gpx, plan_route, speed_for_slope, RoutePoint};
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_plan_route() {
+        let gpx = r#"<gpx><trk><trkseg>
+            <trkpt lat="47.0" lon="8.0"><ele>400</ele></trkpt>
+            <trkpt lon='8.0' lat='47.01'><ele>500.0</ele><time>2024-01-01T00:00:00Z</time></trkpt>
+            <trkpt lat="47.015" lon="8.0"></trkpt>
+        </trkseg></trk></gpx>"#;
+        let points = parse_gpx(gpx);
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[1].position, (47.01, 8.0));
+        assert_eq!(points[1].elevation, Some(500.0));
+        assert_eq!(points[2].elevation, None);
+
+        let curve = [(-0.1, 4.0), (0.0, 3.0), (0.1, 2.0)];
+        assert_eq!(speed_for_slope(&curve, 0.05), Some(2.5));
+        assert_eq!(speed_for_slope(&curve, 0.3), Some(2.0));
+        assert_eq!(speed_for_slope(&curve, -0.3), Some(4.0));
+        assert_eq!(speed_for_slope(&[], 0.0), None);
+
+        // about 1.1km climbing 100m, then 0.55km flat
+        let splits = plan_route(&points, &curve, 1000.0).unwrap();
+        assert_eq!(splits.len(), 2);
+        assert_eq!(splits[0].distance, 1000.0);
+        assert!((splits[0].ascent - 90.0).abs() < 1.0);
+        // 9% climb at 2.1 m/s
+        assert!((splits[0].duration - 1000.0 / 2.1).abs() < 5.0);
+        assert!((splits[1].distance - 1668.0).abs() < 1.0);
+        assert!((splits[1].elapsed - splits[0].duration - splits[1].duration).abs() < 1e-6);
+
+        let flat = [
+            RoutePoint {
+                position: (47.0, 8.0),
+                elevation: None,
+            },
+            RoutePoint {
+                position: (47.0, 8.0),
+                elevation: None,
+            },
+        ];
+        assert_eq!(plan_route(&flat, &curve, 1000.0), None);
+    }
+}
@@ -0,0 +1,363 @@
+//! Segments, stretches of a track the user repeats, and the efforts on them.
+//!
+//! An activity has an effort on a segment if it passes close to the segment's start, reaches its
+//! end after about the segment's distance and follows its path in between.
+use chrono::{DateTime, Local};
+#[cfg(feature = "ssr")]
+use sqlx::PgConnection;
+
+#[cfg(feature = "ssr")]
+use super::base::ModelError;
+use super::calibration::{gps_distance, haversine};
+
+/// Meters between the stored points of a segment.
+pub const SEGMENT_POINT_SPACING: f64 = 20.0;
+
+/// How close in meters an activity has to pass the start and end of a segment.
+const ENDPOINT_RADIUS: f64 = 30.0;
+
+/// How close in meters an activity has to pass the points of a segment.
+const PATH_RADIUS: f64 = 40.0;
+
+/// Share of the segment's points an effort has to pass.
+const MIN_PATH_MATCH: f64 = 0.9;
+
+/// How much longer or shorter than the segment an effort may be.
+const MAX_DISTANCE_DEVIATION: f64 = 0.2;
+
+/// Recorded position of an activity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackPoint {
+    pub timestamp: DateTime<Local>,
+    pub position: (f64, f64),
+    pub heartrate: Option<i16>,
+    pub power: Option<i16>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentEffort {
+    pub start_time: DateTime<Local>,
+    pub end_time: DateTime<Local>,
+    /// Duration in seconds.
+    pub duration: f64,
+    pub average_heartrate: Option<i16>,
+    pub average_power: Option<i16>,
+}
+
+/// Thins out a track to points at least `spacing` meters apart, keeping its start and end.
+pub fn simplify_track(points: &[(f64, f64)], spacing: f64) -> Vec<(f64, f64)> {
+    let mut simplified: Vec<(f64, f64)> = Vec::new();
+    for &point in points {
+        match simplified.last() {
+            Some(&last) if haversine(last, point) < spacing => {}
+            _ => simplified.push(point),
+        }
+    }
+    if let (Some(&last), Some(&kept)) = (points.last(), simplified.last()) {
+        if last != kept {
+            simplified.push(last);
+        }
+    }
+    simplified
+}
+
+fn average(values: impl Iterator<Item = i16>) -> Option<i16> {
+    let (sum, count) = values.fold((0_i64, 0_i64), |(sum, count), v| {
+        (sum + v as i64, count + 1)
+    });
+    (count > 0).then(|| (sum / count) as i16)
+}
+
+/// Index of the point closest to `target` within the run of points in reach of it from `from`.
+fn closest_in_reach(track: &[TrackPoint], from: usize, target: (f64, f64)) -> usize {
+    track[from..]
+        .iter()
+        .take_while(|p| haversine(p.position, target) <= ENDPOINT_RADIUS)
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            haversine(a.position, target).total_cmp(&haversine(b.position, target))
+        })
+        .map_or(from, |(i, _)| from + i)
+}
+
+/// Whether a stretch of track passes close to most points of the segment.
+fn follows_path(segment: &[(f64, f64)], track: &[TrackPoint]) -> bool {
+    let passed = segment
+        .iter()
+        .filter(|&&point| {
+            track
+                .iter()
+                .any(|p| haversine(p.position, point) <= PATH_RADIUS)
+        })
+        .count();
+    passed as f64 >= segment.len() as f64 * MIN_PATH_MATCH
+}
+
+/// All efforts on the segment within a track ordered by time.
+pub fn find_efforts(segment: &[(f64, f64)], track: &[TrackPoint]) -> Vec<SegmentEffort> {
+    let (Some(&start), Some(&end)) = (segment.first(), segment.last()) else {
+        return Vec::new();
+    };
+    let segment_distance = gps_distance(segment);
+    let mut efforts = Vec::new();
+    let mut i = 0;
+    while i < track.len() {
+        if haversine(track[i].position, start) > ENDPOINT_RADIUS {
+            i += 1;
+            continue;
+        }
+        let first = closest_in_reach(track, i, start);
+        let mut travelled = 0.0;
+        let mut last = None;
+        for j in first + 1..track.len() {
+            travelled += haversine(track[j - 1].position, track[j].position);
+            if travelled > segment_distance * (1.0 + MAX_DISTANCE_DEVIATION) + ENDPOINT_RADIUS {
+                break;
+            }
+            if travelled >= segment_distance * (1.0 - MAX_DISTANCE_DEVIATION)
+                && haversine(track[j].position, end) <= ENDPOINT_RADIUS
+            {
+                last = Some(closest_in_reach(track, j, end));
+                break;
+            }
+        }
+        match last {
+            Some(last) if follows_path(segment, &track[first..=last]) => {
+                let stretch = &track[first..=last];
+                let start_time = track[first].timestamp;
+                let end_time = track[last].timestamp;
+                efforts.push(SegmentEffort {
+                    start_time,
+                    end_time,
+                    duration: (end_time - start_time).num_milliseconds() as f64 / 1000.0,
+                    average_heartrate: average(stretch.iter().filter_map(|p| p.heartrate)),
+                    average_power: average(stretch.iter().filter_map(|p| p.power)),
+                });
+                i = last + 1;
+            }
+            _ => i = first + 1,
+        }
+    }
+    efforts
+}
+
+/// Saves a segment and returns its id.
+#[cfg(feature = "ssr")]
+pub async fn insert_segment(
+    user_id: i64,
+    name: &str,
+    points: &[(f64, f64)],
+    executor: &mut PgConnection,
+) -> Result<i64, ModelError> {
+    let (latitudes, longitudes): (Vec<f64>, Vec<f64>) = points.iter().copied().unzip();
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO segments (user_id, name, latitudes, longitudes, distance)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+        user_id as i32,
+        name,
+        &latitudes[..],
+        &longitudes[..],
+        gps_distance(points)
+    )
+    .fetch_one(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't insert segment: {}", e)))
+}
+
+#[cfg(feature = "ssr")]
+async fn load_track(
+    activity_id: i64,
+    executor: &mut PgConnection,
+) -> Result<Vec<TrackPoint>, ModelError> {
+    Ok(sqlx::query!(
+        r#"
+        SELECT timestamp, latitude as "latitude!", longitude as "longitude!", heartrate, power
+        FROM records
+        WHERE activity_id=$1 and latitude IS NOT NULL and longitude IS NOT NULL
+        ORDER BY timestamp
+        "#,
+        activity_id as i32
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load track: {}", e)))?
+    .into_iter()
+    .map(|r| TrackPoint {
+        timestamp: r.timestamp.into(),
+        position: (r.latitude, r.longitude),
+        heartrate: r.heartrate,
+        power: r.power,
+    })
+    .collect())
+}
+
+#[cfg(feature = "ssr")]
+async fn insert_efforts(
+    segment_id: i64,
+    activity_id: i64,
+    efforts: Vec<SegmentEffort>,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    for effort in efforts {
+        sqlx::query!(
+            r#"
+            INSERT INTO segment_efforts (segment_id, activity_id, start_time, end_time, duration,
+                average_heartrate, average_power)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            segment_id,
+            activity_id as i32,
+            effort.start_time,
+            effort.end_time,
+            effort.duration,
+            effort.average_heartrate,
+            effort.average_power
+        )
+        .execute(&mut *executor)
+        .await
+        .map_err(|e| ModelError::InsertError(format!("Couldn't insert segment effort: {}", e)))?;
+    }
+    Ok(())
+}
+
+/// Finds the efforts of an activity on all of the user's segments, replacing earlier ones.
+#[cfg(feature = "ssr")]
+pub async fn match_activity_segments(
+    activity_id: i64,
+    user_id: i64,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    sqlx::query!(
+        r#"
+        DELETE FROM segment_efforts
+        WHERE activity_id=$1
+        "#,
+        activity_id as i32
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't delete segment efforts: {}", e)))?;
+    // only segments starting near the track are worth matching, indoor positions are bogus
+    let segments = sqlx::query!(
+        r#"
+        SELECT segments.id, segments.latitudes, segments.longitudes
+        FROM segments
+        INNER JOIN activities ON activities.id=$1
+        WHERE segments.user_id=$2::bigint
+            AND NOT activities.indoor
+            AND EXISTS (
+                SELECT 1
+                FROM records
+                WHERE records.activity_id=$1
+                    AND records.latitude BETWEEN segments.latitudes[1] - 0.001 AND segments.latitudes[1] + 0.001
+                    AND records.longitude BETWEEN segments.longitudes[1] - 0.001 AND segments.longitudes[1] + 0.001
+            )
+        "#,
+        activity_id as i32,
+        user_id
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load segments: {}", e)))?;
+    if segments.is_empty() {
+        return Ok(());
+    }
+    let track = load_track(activity_id, executor).await?;
+    for segment in segments {
+        let points: Vec<_> = segment
+            .latitudes
+            .into_iter()
+            .zip(segment.longitudes)
+            .collect();
+        let efforts = find_efforts(&points, &track);
+        insert_efforts(segment.id, activity_id, efforts, executor).await?;
+    }
+    Ok(())
+}
+
+/// Finds the efforts on a segment in all of the user's activities.
+///
+/// Only activities passing the start of the segment have their track loaded, but it still runs
+/// over the user's whole history.
+#[cfg(feature = "ssr")]
+pub async fn match_segment_activities(
+    segment_id: i64,
+    user_id: i64,
+    points: &[(f64, f64)],
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    let Some(&(latitude, longitude)) = points.first() else {
+        return Ok(());
+    };
+    let activities = sqlx::query_scalar!(
+        r#"
+        SELECT activities.id
+        FROM activities
+        WHERE activities.user_id=$1::bigint
+            AND NOT activities.indoor
+            AND EXISTS (
+                SELECT 1
+                FROM records
+                WHERE records.activity_id=activities.id
+                    AND records.latitude BETWEEN $2::float8 - 0.001 AND $2::float8 + 0.001
+                    AND records.longitude BETWEEN $3::float8 - 0.001 AND $3::float8 + 0.001
+            )
+        "#,
+        user_id,
+        latitude,
+        longitude
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load activities: {}", e)))?;
+    for activity_id in activities {
+        let track = load_track(activity_id, executor).await?;
+        let efforts = find_efforts(points, &track);
+        insert_efforts(segment_id, activity_id, efforts, executor).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{find_efforts, simplify_track, TrackPoint, SEGMENT_POINT_SPACING};
+    #[cfg(feature = "ssr")]
+    use chrono::{Local, TimeDelta};
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_find_efforts() {
+        let start = Local::now();
+        // about 11m per second heading north
+        let track = |offset: fn(usize) -> f64| -> Vec<TrackPoint> {
+            (0..100)
+                .map(|i| TrackPoint {
+                    timestamp: start + TimeDelta::seconds(i as i64),
+                    position: (47.0 + i as f64 * 0.0001, 8.0 + offset(i)),
+                    heartrate: Some(150),
+                    power: None,
+                })
+                .collect()
+        };
+        let straight = track(|_| 0.0);
+        let positions: Vec<_> = straight[20..60].iter().map(|p| p.position).collect();
+        let segment = simplify_track(&positions, SEGMENT_POINT_SPACING);
+        assert_eq!(segment.first(), positions.first());
+        assert_eq!(segment.last(), positions.last());
+
+        let efforts = find_efforts(&segment, &straight);
+        assert_eq!(efforts.len(), 1);
+        assert_eq!(efforts[0].start_time, straight[20].timestamp);
+        assert_eq!(efforts[0].duration, 39.0);
+        assert_eq!(efforts[0].average_heartrate, Some(150));
+        assert_eq!(efforts[0].average_power, None);
+
+        // a detour of about 150m in the middle doesn't follow the segment
+        let detour = track(|i| if (30..50).contains(&i) { 0.002 } else { 0.0 });
+        assert!(find_efforts(&segment, &detour).is_empty());
+    }
+}
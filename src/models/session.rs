@@ -6,6 +6,10 @@ use fitparser::{profile::MesgNum, FitDataRecord, Value};
 use itertools::Itertools;
 
 use super::base::{DatabaseEntry, ModelError, New};
+#[cfg(feature = "ssr")]
+use super::user_preferences::get_user_preferences;
+#[cfg(feature = "ssr")]
+use sqlx::PgConnection;
 
 
 
@@ -15,6 +19,7 @@ pub struct Session {
     pub start_time: DateTime<Local>,
     pub end_time: DateTime<Local>,
     pub sport: Option<String>,
+    pub sub_sport: Option<String>,
     pub distance: Option<f64>,
     pub calories: Option<i32>,
     pub average_heartrate: Option<i16>,
@@ -25,6 +30,8 @@ pub struct Session {
     pub descent: Option<i32>,
     pub average_speed: Option<f64>,
     pub max_speed: Option<f64>,
+    /// Pool length in meters for pool swims.
+    pub pool_length: Option<f64>,
 }
 
 impl TryFrom<FitDataRecord> for DatabaseEntry<New, Session> {
@@ -73,6 +80,14 @@ impl TryFrom<FitDataRecord> for DatabaseEntry<New, Session> {
                 _ => None,
             });
 
+        let sub_sport = fields.iter().find(|&f| f.name() == "sub_sport");
+        let sub_sport = sub_sport
+            .map(|val| val.clone().into_value())
+            .and_then(|val| match val {
+                Value::String(val) => Some(val),
+                _ => None,
+            });
+
         let average_heartrate = fields.iter().find(|&f| f.name() == "avg_heart_rate");
         let average_heartrate = average_heartrate
             .map(|val| val.clone().into_value())
@@ -165,11 +180,20 @@ impl TryFrom<FitDataRecord> for DatabaseEntry<New, Session> {
                 _ => None,
             });
 
+        let pool_length = fields.iter().find(|&f| f.name() == "pool_length");
+        let pool_length = pool_length
+            .map(|val| val.clone().into_value())
+            .and_then(|val| match val {
+                Value::Float64(val) => Some(val),
+                _ => None,
+            });
+
         Ok(DatabaseEntry {
             state: Box::new(Session {
                 start_time,
                 end_time,
                 sport,
+                sub_sport,
                 distance,
                 calories,
                 average_heartrate,
@@ -180,6 +204,7 @@ impl TryFrom<FitDataRecord> for DatabaseEntry<New, Session> {
                 average_power,
                 average_speed,
                 max_speed,
+                pool_length,
             }),
             extra: New,
         })
@@ -239,13 +264,15 @@ pub async fn insert_sessions(
             )
         })
         .multiunzip();
-    // itertools only supports up to 12 iterators, so we do this one separately
-    let max_speed: Vec<_> = sessions.into_iter().map(|r|r.state.max_speed).collect();
+    // itertools only supports up to 12 iterators, so we do these separately
+    let sub_sport: Vec<_> = sessions.iter().map(|r|r.state.sub_sport.clone()).collect();
+    let max_speed: Vec<_> = sessions.iter().map(|r|r.state.max_speed).collect();
+    let pool_length: Vec<_> = sessions.into_iter().map(|r|r.state.pool_length).collect();
     sqlx::query!(
         r#"
-        INSERT INTO sessions(activity_id,start_time,end_time,sport,distance,calories,average_heartrate,min_heartrate,max_heartrate,average_power,ascent,descent,average_speed,max_speed)
+        INSERT INTO sessions(activity_id,start_time,end_time,sport,distance,calories,average_heartrate,min_heartrate,max_heartrate,average_power,ascent,descent,average_speed,max_speed,sub_sport,pool_length)
         SELECT *
-        FROM UNNEST($1::bigint[], $2::timestamptz[],$3::timestamptz[], $4::varchar[], $5::float8[], $6::int[], $7::smallint[], $8::smallint[], $9::smallint[], $10::int[], $11::int[], $12::int[], $13::float8[], $14::float8[])
+        FROM UNNEST($1::bigint[], $2::timestamptz[],$3::timestamptz[], $4::varchar[], $5::float8[], $6::int[], $7::smallint[], $8::smallint[], $9::smallint[], $10::int[], $11::int[], $12::int[], $13::float8[], $14::float8[], $15::varchar[], $16::float8[])
         "#,
         &activity_ids[..],
         &start_time[..] as _,
@@ -261,9 +288,78 @@ pub async fn insert_sessions(
         &descent[..] as _,
         &average_speed[..] as _,
         &max_speed[..] as _,
-        
+        &sub_sport[..] as _,
+        &pool_length[..] as _,
     ).execute(executor).await
         .map_err(|e| ModelError::InsertError(format!("Couldn't insert session: {}", e)))?;
 
     Ok(())
 }
+
+/// Stores the heart rate load of each session of an activity, so the activity's load can be
+/// attributed to the sports of a multisport activity.
+#[cfg(feature = "ssr")]
+pub async fn update_session_loads(
+    activity_id: i64,
+    user_id: i64,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    let sessions = sqlx::query!(
+        r#"
+        SELECT sessions.id, sessions.start_time, sessions.end_time, activities.start_time as activity_start
+        FROM sessions
+        INNER JOIN activities ON activities.id = sessions.activity_id
+        WHERE sessions.activity_id=$1 and activities.user_id=$2::bigint
+        "#,
+        activity_id as i32,
+        user_id
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load sessions: {}", e)))?;
+    let Some(activity_start) = sessions.first().map(|s| s.activity_start) else {
+        return Ok(());
+    };
+    let records = sqlx::query!(
+        r#"
+        SELECT timestamp, heartrate as "heartrate!"
+        FROM records
+        WHERE activity_id=$1 and heartrate IS NOT NULL
+        "#,
+        activity_id as i32
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load records: {}", e)))?;
+    let preferences = get_user_preferences(user_id, activity_start.into(), &mut *executor).await;
+    let (ids, loads): (Vec<_>, Vec<_>) = sessions
+        .iter()
+        .map(|s| {
+            let heartrates: Vec<u32> = records
+                .iter()
+                .filter(|r| r.timestamp >= s.start_time && r.timestamp <= s.end_time)
+                .map(|r| r.heartrate as u32)
+                .collect();
+            let load = if heartrates.is_empty() {
+                None
+            } else {
+                Some(preferences.calculate_load(heartrates) as i32)
+            };
+            (s.id, load)
+        })
+        .unzip();
+    sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET load=l.load
+        FROM UNNEST($1::bigint[], $2::int[]) AS l(id, load)
+        WHERE sessions.id=l.id
+        "#,
+        &ids[..],
+        &loads[..] as _
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't update session loads: {}", e)))?;
+    Ok(())
+}
@@ -5,7 +5,11 @@ use super::{
     session::Session,
     user_preferences::UserPreferences,
 };
+#[cfg(feature = "ssr")]
+use chrono::TimeDelta;
 use chrono::{DateTime, Local};
+#[cfg(feature = "ssr")]
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use strum::Display;
 
@@ -117,14 +121,51 @@ pub fn slope_speed_from_records(
     })
 }
 
+/// Slope speeds of an activity, one for every 100m of its records.
+///
+/// The first 10 minutes are skipped since the heart rate hasn't settled yet.
+#[cfg(feature = "ssr")]
+pub fn slopes_from_records(
+    records: &[DatabaseEntry<New, Record>],
+    sessions: &Vec<DatabaseEntry<New, Session>>,
+    activity_start: DateTime<Local>,
+    user_id: i64,
+    user_preferences: &UserPreferences,
+) -> Vec<DatabaseEntry<New, SlopeSpeed>> {
+    let warmup_end = activity_start + TimeDelta::minutes(10);
+    records
+        .iter()
+        .filter(|r| {
+            r.state.timestamp > warmup_end
+                && r.state.distance.is_some()
+                && r.state.altitude.is_some()
+                && r.state.speed.is_some()
+                && r.state.heartrate.is_some()
+        })
+        .group_by(|r| (r.state.distance.unwrap() / 100.0).floor())
+        .into_iter()
+        .map(|(_, group)| {
+            slope_speed_from_records(
+                group.cloned().collect::<Vec<_>>(),
+                sessions,
+                user_id,
+                user_preferences,
+            )
+        })
+        .filter_map(|s| s.ok())
+        .map(|s| DatabaseEntry {
+            state: Box::new(s),
+            extra: New,
+        })
+        .collect()
+}
+
 #[cfg(feature = "ssr")]
 pub async fn insert_slopes(
     slopes: Vec<DatabaseEntry<New, SlopeSpeed>>,
     activity_id: i64,
     executor: impl sqlx::PgExecutor<'_>,
 ) -> Result<(), ModelError> {
-    use itertools::Itertools;
-
     let num_slopes = slopes.len();
     let activity_ids: Vec<i64> = std::iter::repeat(activity_id).take(num_slopes).collect();
     let (user_id, start_time, sport, slope, average_speed, heartrate_zone): (
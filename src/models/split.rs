@@ -0,0 +1,189 @@
+//! Splits of an activity over a fixed distance, e.g. per kilometre.
+
+/// Steepest grade the grade adjustment is defined for, steeper ones count as this steep.
+const MAX_GRADE: f64 = 0.45;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SplitSample {
+    /// Seconds since the start of the activity.
+    pub time: f64,
+    /// Meters since the start of the activity.
+    pub distance: Option<f64>,
+    pub heartrate: Option<f64>,
+    pub altitude: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistanceSplit {
+    /// Meters, the last split is usually shorter than the others.
+    pub distance: f64,
+    /// Seconds.
+    pub duration: f64,
+    pub average_heartrate: Option<f64>,
+    /// Altitude at the end minus the altitude at the start in meters.
+    pub elevation_change: Option<f64>,
+    /// Seconds the split would have taken on flat ground at the same effort.
+    pub grade_adjusted_duration: Option<f64>,
+}
+
+impl DistanceSplit {
+    /// Seconds per `unit` meters.
+    pub fn pace(&self, unit: f64) -> Option<f64> {
+        (self.distance > 0.0).then(|| self.duration / self.distance * unit)
+    }
+
+    /// Seconds per `unit` meters on flat ground at the same effort.
+    pub fn grade_adjusted_pace(&self, unit: f64) -> Option<f64> {
+        let duration = self.grade_adjusted_duration?;
+        (self.distance > 0.0).then(|| duration / self.distance * unit)
+    }
+}
+
+/// Energy cost of running on a grade relative to flat ground, after Minetti et al. (2002).
+pub fn grade_cost_factor(grade: f64) -> f64 {
+    let g = grade.clamp(-MAX_GRADE, MAX_GRADE);
+    let cost =
+        155.4 * g.powi(5) - 30.4 * g.powi(4) - 43.3 * g.powi(3) + 46.3 * g.powi(2) + 19.5 * g + 3.6;
+    cost / 3.6
+}
+
+/// Elapsed seconds at which every full `split_length` meters are passed.
+///
+/// `samples` are pairs of elapsed seconds and distance in meters in recording order, the time
+/// is interpolated between the samples around each split boundary.
+pub fn split_times(samples: &[(f64, f64)], split_length: f64) -> Vec<f64> {
+    let mut times = Vec::new();
+    if split_length <= 0.0 {
+        return times;
+    }
+    let mut next = split_length;
+    for pair in samples.windows(2) {
+        let ((t0, d0), (t1, d1)) = (pair[0], pair[1]);
+        while d1 >= next {
+            let ratio = if d1 > d0 {
+                ((next - d0) / (d1 - d0)).clamp(0.0, 1.0)
+            } else {
+                1.0
+            };
+            times.push(t0 + (t1 - t0) * ratio);
+            next += split_length;
+        }
+    }
+    times
+}
+
+/// Linear interpolation of a sample field at `time`, samples must be sorted by time.
+fn interpolate(
+    samples: &[SplitSample],
+    time: f64,
+    field: impl Fn(&SplitSample) -> Option<f64>,
+) -> Option<f64> {
+    let after = samples.partition_point(|s| s.time < time);
+    let next = samples[after.min(samples.len() - 1)..]
+        .iter()
+        .find_map(|s| Some((s.time, field(s)?)));
+    let previous = samples[..after]
+        .iter()
+        .rev()
+        .find_map(|s| Some((s.time, field(s)?)));
+    match (previous, next) {
+        (Some((t0, v0)), Some((t1, v1))) if t1 > t0 => {
+            Some(v0 + (v1 - v0) * ((time - t0) / (t1 - t0)).clamp(0.0, 1.0))
+        }
+        (Some((_, v)), _) | (None, Some((_, v))) => Some(v),
+        (None, None) => None,
+    }
+}
+
+/// Splits of `split_length` meters with their averages, plus the rest of the distance if any.
+pub fn distance_splits(samples: &[SplitSample], split_length: f64) -> Vec<DistanceSplit> {
+    let samples: Vec<SplitSample> = samples
+        .iter()
+        .filter(|s| s.distance.is_some())
+        .cloned()
+        .collect();
+    let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+        return Vec::new();
+    };
+    let distances: Vec<(f64, f64)> = samples
+        .iter()
+        .filter_map(|s| Some((s.time, s.distance?)))
+        .collect();
+    let start = (first.time, first.distance.unwrap_or_default());
+    let end = (last.time, last.distance.unwrap_or_default());
+    let mut boundaries: Vec<(f64, f64)> = vec![start];
+    boundaries.extend(
+        split_times(&distances, split_length)
+            .into_iter()
+            .enumerate()
+            .map(|(i, time)| (time, (i + 1) as f64 * split_length))
+            .filter(|&(_, distance)| distance > start.1),
+    );
+    // a few meters after the last full split aren't worth a split of their own
+    if end.1 - boundaries.last().map_or(0.0, |b| b.1) > split_length * 0.01 {
+        boundaries.push(end);
+    }
+    boundaries
+        .windows(2)
+        .map(|pair| {
+            let ((t0, d0), (t1, d1)) = (pair[0], pair[1]);
+            let heartrates: Vec<f64> = samples
+                .iter()
+                .filter(|s| s.time >= t0 && s.time < t1)
+                .filter_map(|s| s.heartrate)
+                .collect();
+            let elevation_change = interpolate(&samples, t1, |s| s.altitude)
+                .zip(interpolate(&samples, t0, |s| s.altitude))
+                .map(|(end, start)| end - start);
+            let distance = d1 - d0;
+            DistanceSplit {
+                distance,
+                duration: t1 - t0,
+                average_heartrate: (!heartrates.is_empty())
+                    .then(|| heartrates.iter().sum::<f64>() / heartrates.len() as f64),
+                elevation_change,
+                grade_adjusted_duration: elevation_change
+                    .filter(|_| distance > 0.0)
+                    .map(|change| (t1 - t0) / grade_cost_factor(change / distance)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{distance_splits, grade_cost_factor, split_times, SplitSample};
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_splits() {
+        let samples = [(0.0, 0.0), (200.0, 900.0), (300.0, 1300.0), (600.0, 2500.0)];
+        assert_eq!(split_times(&samples, 1000.0), vec![225.0, 475.0]);
+        assert!(split_times(&samples[..2], 1000.0).is_empty());
+        assert!(split_times(&samples, 0.0).is_empty());
+
+        // 2.5km at 4 m/s, climbing 1% on the first kilometre
+        let samples: Vec<SplitSample> = (0..=625)
+            .map(|t| SplitSample {
+                time: t as f64,
+                distance: Some(t as f64 * 4.0),
+                heartrate: Some(if t < 250 { 140.0 } else { 160.0 }),
+                altitude: Some((t.min(250) as f64 * 4.0) / 100.0),
+            })
+            .collect();
+        let splits = distance_splits(&samples, 1000.0);
+        assert_eq!(splits.len(), 3);
+        assert_eq!(splits[0].duration, 250.0);
+        assert_eq!(splits[0].pace(1000.0), Some(250.0));
+        assert_eq!(splits[0].average_heartrate, Some(140.0));
+        assert_eq!(splits[0].elevation_change, Some(10.0));
+        assert!(splits[0].grade_adjusted_pace(1000.0).unwrap() < 250.0);
+        assert_eq!(splits[1].grade_adjusted_duration, Some(250.0));
+        assert_eq!(splits[2].distance, 500.0);
+        assert_eq!(splits[2].pace(1000.0), Some(250.0));
+        assert_eq!(grade_cost_factor(0.0), 1.0);
+        assert!(grade_cost_factor(0.1) > 1.5);
+        assert!(grade_cost_factor(-0.1) < 1.0);
+    }
+}
@@ -0,0 +1,284 @@
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "ssr")]
+use sqlx::PgConnection;
+#[cfg(feature = "ssr")]
+use std::str::FromStr;
+
+#[cfg(feature = "ssr")]
+use super::base::ModelError;
+use crate::pages::workout_schedule::WorkoutType;
+
+/// A sport of the shared sport taxonomy.
+///
+/// Sports form a tree, e.g. trail running is a sub sport of running. Built-in sports are shared
+/// by all users, users can add their own on top of them.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Sport {
+    pub id: i64,
+    /// The sport this is a sub sport of.
+    pub parent_id: Option<i64>,
+    pub name: String,
+    /// The type of workout this sport counts as.
+    pub workout_type: WorkoutType,
+    /// Whether the sport was added by the user.
+    pub user_defined: bool,
+    /// How deeply the sport is nested, top level sports have a depth of 0.
+    pub depth: i32,
+    /// FIT `sport` or `sport/sub_sport` codes that are recorded as this sport.
+    pub fit_codes: Vec<String>,
+}
+
+/// The sports of an activity's sessions, including all sports they are a sub sport of.
+#[derive(Debug, Clone, Default)]
+pub struct ActivitySports {
+    pub sport_ids: Vec<i64>,
+    pub workout_types: Vec<WorkoutType>,
+}
+
+impl ActivitySports {
+    /// Whether the activity fulfills a workout of the given sport.
+    ///
+    /// Workouts without a sport are fulfilled by any activity of the same workout type, general
+    /// endurance workouts by any activity at all.
+    pub fn fulfills(&self, sport_id: Option<i64>, workout_type: &WorkoutType) -> bool {
+        match sport_id {
+            Some(sport_id) => self.sport_ids.contains(&sport_id),
+            None => {
+                *workout_type == WorkoutType::Endurance || self.workout_types.contains(workout_type)
+            }
+        }
+    }
+}
+
+/// All sports visible to a user, ordered as a tree with sub sports after their parent.
+#[cfg(feature = "ssr")]
+pub async fn get_sports(
+    user_id: i64,
+    executor: impl sqlx::PgExecutor<'_>,
+) -> Result<Vec<Sport>, ModelError> {
+    let sports = sqlx::query!(
+        r#"
+        WITH RECURSIVE tree AS (
+            SELECT id, parent_id, name, workout_type, user_id, 0 as depth, ARRAY[name] as path
+            FROM sports
+            WHERE parent_id IS NULL and (user_id IS NULL or user_id=$1::bigint)
+            UNION ALL
+            SELECT s.id, s.parent_id, s.name, s.workout_type, s.user_id, t.depth + 1, t.path || s.name
+            FROM sports s
+            INNER JOIN tree t ON s.parent_id=t.id
+            WHERE s.user_id IS NULL or s.user_id=$1::bigint
+        )
+        SELECT
+            t.id as "id!",
+            t.parent_id,
+            t.name as "name!",
+            t.workout_type::text as "workout_type!",
+            t.user_id IS NOT NULL as "user_defined!",
+            t.depth as "depth!",
+            ARRAY_REMOVE(ARRAY_AGG(m.fit_sport || COALESCE('/' || m.fit_sub_sport, '') ORDER BY m.id), NULL) as "fit_codes!"
+        FROM tree t
+        LEFT JOIN sport_fit_mappings m ON m.sport_id=t.id
+        GROUP BY t.id, t.parent_id, t.name, t.workout_type, t.user_id, t.depth, t.path
+        ORDER BY t.path
+        "#,
+        user_id
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load sports: {}", e)))?;
+    sports
+        .into_iter()
+        .map(|s| {
+            Ok(Sport {
+                id: s.id,
+                parent_id: s.parent_id,
+                name: s.name,
+                workout_type: WorkoutType::from_str(&s.workout_type)
+                    .map_err(|e| ModelError::ParseError(format!("Invalid workout type: {}", e)))?,
+                user_defined: s.user_defined,
+                depth: s.depth,
+                fit_codes: s.fit_codes,
+            })
+        })
+        .collect()
+}
+
+/// Checks that a sport exists and is visible to the user, returning its id.
+#[cfg(feature = "ssr")]
+pub async fn find_sport(
+    user_id: i64,
+    sport_id: i64,
+    executor: impl sqlx::PgExecutor<'_>,
+) -> Result<Option<i64>, ModelError> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT id
+        FROM sports
+        WHERE id=$1 and (user_id IS NULL or user_id=$2::bigint)
+        "#,
+        sport_id,
+        user_id
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load sport: {}", e)))
+}
+
+/// Looks up a sport visible to the user by name, ignoring case. User defined sports win.
+#[cfg(feature = "ssr")]
+pub async fn find_sport_by_name(
+    user_id: i64,
+    name: &str,
+    executor: impl sqlx::PgExecutor<'_>,
+) -> Result<Option<i64>, ModelError> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT id
+        FROM sports
+        WHERE lower(name)=lower($1) and (user_id IS NULL or user_id=$2::bigint)
+        ORDER BY user_id IS NULL
+        LIMIT 1
+        "#,
+        name.trim(),
+        user_id
+    )
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load sport: {}", e)))
+}
+
+/// Sets the sport of sessions, laps and slope speeds from their FIT sport and sub sport.
+///
+/// Mappings of a sub sport win over mappings of the whole sport and mappings of user defined
+/// sports win over built-in ones. Sessions without a FIT sport were entered by hand and keep their
+/// sport. Updates all activities of the user if `activity_id` is `None`.
+#[cfg(feature = "ssr")]
+pub async fn assign_sports(
+    user_id: i64,
+    activity_id: Option<i64>,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    sqlx::query!(
+        r#"
+        UPDATE sessions s
+        SET sport_id=(
+            SELECT m.sport_id
+            FROM sport_fit_mappings m
+            INNER JOIN sports sp ON sp.id=m.sport_id
+            WHERE m.fit_sport=s.sport
+                and (m.fit_sub_sport IS NULL or m.fit_sub_sport=s.sub_sport)
+                and (sp.user_id IS NULL or sp.user_id=$1::bigint)
+            ORDER BY m.fit_sub_sport IS NULL, sp.user_id IS NULL, m.id
+            LIMIT 1
+        )
+        FROM activities a
+        WHERE a.id=s.activity_id and a.user_id=$1::bigint and ($2::bigint IS NULL or a.id=$2)
+            and s.sport IS NOT NULL
+        "#,
+        user_id,
+        activity_id
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't assign session sports: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE laps l
+        SET sport_id=(
+            SELECT m.sport_id
+            FROM sport_fit_mappings m
+            INNER JOIN sports sp ON sp.id=m.sport_id
+            WHERE m.fit_sport=l.sport
+                and (m.fit_sub_sport IS NULL or m.fit_sub_sport=l.sub_sport)
+                and (sp.user_id IS NULL or sp.user_id=$1::bigint)
+            ORDER BY m.fit_sub_sport IS NULL, sp.user_id IS NULL, m.id
+            LIMIT 1
+        )
+        FROM activities a
+        WHERE a.id=l.activity_id and a.user_id=$1::bigint and ($2::bigint IS NULL or a.id=$2)
+        "#,
+        user_id,
+        activity_id
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't assign lap sports: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE slope_speed sl
+        SET sport_id=s.sport_id
+        FROM sessions s
+        WHERE s.activity_id=sl.activity_id
+            and s.start_time <= sl.start_time and s.end_time > sl.start_time
+            and sl.user_id=$1::bigint and ($2::bigint IS NULL or sl.activity_id=$2)
+        "#,
+        user_id,
+        activity_id
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't assign slope sports: {}", e)))?;
+    Ok(())
+}
+
+/// The sports of an activity, see [`ActivitySports`].
+#[cfg(feature = "ssr")]
+pub async fn get_activity_sports(
+    activity_id: i64,
+    executor: &mut PgConnection,
+) -> Result<ActivitySports, ModelError> {
+    let sports = sqlx::query!(
+        r#"
+        WITH RECURSIVE ancestors AS (
+            SELECT sp.id, sp.parent_id, sp.workout_type
+            FROM sessions s
+            INNER JOIN sports sp ON sp.id=s.sport_id
+            WHERE s.activity_id=$1
+            UNION
+            SELECT sp.id, sp.parent_id, sp.workout_type
+            FROM sports sp
+            INNER JOIN ancestors a ON a.parent_id=sp.id
+        )
+        SELECT
+            COALESCE(ARRAY_AGG(DISTINCT id), '{}') as "sport_ids!",
+            COALESCE(ARRAY_AGG(DISTINCT workout_type::text), '{}') as "workout_types!"
+        FROM ancestors
+        "#,
+        activity_id as i32
+    )
+    .fetch_one(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load activity sports: {}", e)))?;
+    Ok(ActivitySports {
+        sport_ids: sports.sport_ids,
+        workout_types: sports
+            .workout_types
+            .iter()
+            .filter_map(|t| WorkoutType::from_str(t).ok())
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{ActivitySports, WorkoutType};
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_activity_fulfills() {
+        // a trail run, which is a sub sport of running
+        let sports = ActivitySports {
+            sport_ids: vec![2, 1],
+            workout_types: vec![WorkoutType::Run],
+        };
+        assert!(sports.fulfills(Some(1), &WorkoutType::Run));
+        assert!(sports.fulfills(Some(2), &WorkoutType::Run));
+        assert!(!sports.fulfills(Some(3), &WorkoutType::Run));
+        assert!(sports.fulfills(None, &WorkoutType::Run));
+        assert!(sports.fulfills(None, &WorkoutType::Endurance));
+        assert!(!sports.fulfills(None, &WorkoutType::Cycling));
+        assert!(ActivitySports::default().fulfills(None, &WorkoutType::Endurance));
+        assert!(!ActivitySports::default().fulfills(None, &WorkoutType::Strength));
+    }
+}
@@ -0,0 +1,322 @@
+use chrono::{DateTime, Local};
+use fitparser::{profile::MesgNum, FitDataRecord, Value};
+#[cfg(feature = "ssr")]
+use itertools::Itertools;
+#[cfg(feature = "ssr")]
+use sqlx::PgConnection;
+#[cfg(feature = "ssr")]
+use std::collections::HashMap;
+
+use super::base::{display_name, DatabaseEntry, ModelError, New};
+use super::user_preferences::UserPreferences;
+
+/// Seconds a repetition takes if the set has no duration.
+const SECONDS_PER_REPETITION: f64 = 3.0;
+/// Most repetitions of a single set.
+pub const MAX_REPETITIONS: i32 = 1000;
+/// Heaviest weight of a set in kilograms.
+pub const MAX_WEIGHT: f64 = 1000.0;
+/// Longest rest after a set in seconds.
+pub const MAX_REST: f64 = 3600.0;
+
+/// A set of a strength exercise, e.g. 10 squats with 60kg.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrengthSet {
+    /// Name of the exercise, e.g. `Bench Press`.
+    pub exercise: Option<String>,
+    /// Exercise category as named in the FIT profile, e.g. `bench_press`.
+    pub fit_category: Option<String>,
+    pub start_time: Option<DateTime<Local>>,
+    /// Duration of the set in seconds.
+    pub duration: Option<f64>,
+    pub repetitions: Option<i32>,
+    /// Weight in kilograms.
+    pub weight: Option<f64>,
+    /// Rest after the set in seconds.
+    pub rest: Option<f64>,
+}
+
+impl StrengthSet {
+    pub fn new(exercise: String, repetitions: i32, weight: f64, rest: f64) -> Self {
+        Self {
+            exercise: Some(exercise),
+            fit_category: None,
+            start_time: None,
+            duration: None,
+            repetitions: Some(repetitions),
+            weight: Some(weight),
+            rest: Some(rest),
+        }
+    }
+
+    /// Volume load of the set, repetitions times weight.
+    pub fn volume(&self) -> f64 {
+        self.repetitions.unwrap_or(0) as f64 * self.weight.unwrap_or(0.0)
+    }
+
+    /// Checks that repetitions, weight and rest are within what a person can do.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self
+            .repetitions
+            .map_or(true, |r| (0..=MAX_REPETITIONS).contains(&r))
+        {
+            return Err(format!(
+                "Repetitions have to be between 0 and {}",
+                MAX_REPETITIONS
+            ));
+        }
+        if !self
+            .weight
+            .map_or(true, |w| (0.0..=MAX_WEIGHT).contains(&w))
+        {
+            return Err(format!("Weight has to be between 0 and {}kg", MAX_WEIGHT));
+        }
+        if !self.rest.map_or(true, |r| (0.0..=MAX_REST).contains(&r)) {
+            return Err(format!("Rest has to be between 0 and {}s", MAX_REST));
+        }
+        Ok(())
+    }
+
+    /// Time under load in seconds, estimated from the repetitions if the set has no duration.
+    pub fn active_time(&self) -> f64 {
+        self.duration
+            .unwrap_or(self.repetitions.unwrap_or(0) as f64 * SECONDS_PER_REPETITION)
+    }
+}
+
+/// A FIT `set` message, which is either an exercise set or the rest between two sets.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FitSet {
+    Active(StrengthSet),
+    Rest(Option<f64>),
+}
+
+impl TryFrom<FitDataRecord> for FitSet {
+    type Error = ModelError;
+
+    fn try_from(value: FitDataRecord) -> Result<Self, Self::Error> {
+        match value.kind() {
+            MesgNum::Set => {}
+            _ => return Err(ModelError::ParseError("Not a Set".to_string())),
+        };
+        let fields = value.fields();
+        let duration = fields.iter().find(|&f| f.name() == "duration");
+        let duration = duration
+            .map(|val| val.clone().into_value())
+            .and_then(|val| match val {
+                Value::Float64(val) => Some(val),
+                Value::UInt32(val) => Some(val as f64 / 1000.0),
+                _ => None,
+            });
+
+        let set_type = fields.iter().find(|&f| f.name() == "set_type");
+        let set_type = set_type
+            .map(|val| val.clone().into_value())
+            .and_then(|val| match val {
+                Value::String(val) => Some(val),
+                _ => None,
+            });
+        if set_type.as_deref() == Some("rest") {
+            return Ok(FitSet::Rest(duration));
+        }
+
+        let start_time = fields.iter().find(|&f| f.name() == "start_time");
+        let start_time = start_time
+            .map(|val| val.clone().into_value())
+            .and_then(|val| match val {
+                Value::Timestamp(val) => Some(val),
+                _ => None,
+            });
+
+        let repetitions = fields.iter().find(|&f| f.name() == "repetitions");
+        let repetitions =
+            repetitions
+                .map(|val| val.clone().into_value())
+                .and_then(|val| match val {
+                    Value::UInt16(val) => Some(i32::from(val)),
+                    _ => None,
+                });
+
+        let weight = fields.iter().find(|&f| f.name() == "weight");
+        let weight = weight
+            .map(|val| val.clone().into_value())
+            .and_then(|val| match val {
+                Value::Float64(val) => Some(val),
+                Value::UInt16(val) => Some(val as f64 / 16.0),
+                _ => None,
+            });
+
+        // the category is an array, watches only fill in the first entry
+        let category = fields.iter().find(|&f| f.name() == "category");
+        let fit_category = category
+            .map(|val| val.clone().into_value())
+            .and_then(|val| match val {
+                Value::String(val) => Some(val),
+                Value::Array(vals) => vals.into_iter().find_map(|val| match val {
+                    Value::String(val) => Some(val),
+                    _ => None,
+                }),
+                _ => None,
+            });
+
+        Ok(FitSet::Active(StrengthSet {
+            exercise: fit_category.as_deref().map(display_name),
+            fit_category,
+            start_time,
+            duration,
+            repetitions,
+            weight,
+            rest: None,
+        }))
+    }
+}
+
+/// Attaches the rest between sets to the set before it.
+pub fn merge_rest(sets: Vec<FitSet>) -> Vec<DatabaseEntry<New, StrengthSet>> {
+    let mut merged: Vec<DatabaseEntry<New, StrengthSet>> = Vec::new();
+    for set in sets {
+        match set {
+            FitSet::Active(set) => merged.push(DatabaseEntry {
+                state: Box::new(set),
+                extra: New,
+            }),
+            FitSet::Rest(rest) => {
+                if let Some(last) = merged.last_mut() {
+                    last.state.rest = match (last.state.rest, rest) {
+                        (Some(a), Some(b)) => Some(a + b),
+                        (a, b) => a.or(b),
+                    };
+                }
+            }
+        }
+    }
+    merged
+}
+
+/// Training load of a strength session.
+///
+/// Sets barely show up in the average heart rate, so the time under load counts as time at the
+/// anaerobic threshold and the rest as time at the aerobic threshold.
+pub fn strength_load(
+    sets: &[DatabaseEntry<New, StrengthSet>],
+    preferences: &UserPreferences,
+) -> u32 {
+    let active = sets.iter().map(|s| s.state.active_time()).sum::<f64>();
+    let rest = sets
+        .iter()
+        .map(|s| s.state.rest.unwrap_or(0.0))
+        .sum::<f64>();
+    preferences.constant_load(&[
+        (preferences.anaerobic_threshold as u32, active),
+        (preferences.aerobic_threshold as u32, rest),
+    ])
+}
+
+/// Stores the sets of an activity, creating exercises that don't exist yet.
+#[cfg(feature = "ssr")]
+pub async fn insert_strength_sets(
+    sets: Vec<DatabaseEntry<New, StrengthSet>>,
+    activity_id: i64,
+    user_id: i64,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    let mut exercise_ids = HashMap::new();
+    for set in sets.iter() {
+        let Some(exercise) = set.state.exercise.clone() else {
+            continue;
+        };
+        if exercise_ids.contains_key(&exercise) {
+            continue;
+        }
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO exercises (user_id, name, fit_category)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, name) DO UPDATE
+            SET fit_category=COALESCE(exercises.fit_category, EXCLUDED.fit_category)
+            RETURNING id
+            "#,
+            user_id as i32,
+            exercise,
+            set.state.fit_category
+        )
+        .fetch_one(&mut *executor)
+        .await
+        .map_err(|e| ModelError::InsertError(format!("Couldn't insert exercise: {}", e)))?;
+        exercise_ids.insert(exercise, id);
+    }
+    let activity_ids: Vec<i64> = std::iter::repeat(activity_id).take(sets.len()).collect();
+    let (exercise_id, position, start_time, duration, repetitions, weight, rest): (
+        Vec<_>,
+        Vec<_>,
+        Vec<_>,
+        Vec<_>,
+        Vec<_>,
+        Vec<_>,
+        Vec<_>,
+    ) = sets
+        .into_iter()
+        .enumerate()
+        .map(|(i, s)| {
+            (
+                s.state
+                    .exercise
+                    .as_ref()
+                    .and_then(|e| exercise_ids.get(e).copied()),
+                i as i32,
+                s.state.start_time,
+                s.state.duration,
+                s.state.repetitions,
+                s.state.weight,
+                s.state.rest,
+            )
+        })
+        .multiunzip();
+    sqlx::query!(
+        r#"
+        INSERT INTO strength_sets(activity_id,exercise_id,position,start_time,duration,repetitions,weight,rest)
+        SELECT *
+        FROM UNNEST($1::bigint[], $2::bigint[], $3::int[], $4::timestamptz[], $5::float8[], $6::int[], $7::float8[], $8::float8[])
+        "#,
+        &activity_ids[..],
+        &exercise_id[..] as _,
+        &position[..],
+        &start_time[..] as _,
+        &duration[..] as _,
+        &repetitions[..] as _,
+        &weight[..] as _,
+        &rest[..] as _,
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't insert strength sets: {}", e)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{display_name, merge_rest, FitSet, StrengthSet};
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_merge_rest() {
+        assert_eq!(display_name("bench_press"), "Bench Press");
+        let sets = merge_rest(vec![
+            FitSet::Rest(Some(30.0)),
+            FitSet::Active(StrengthSet::new("Squat".to_string(), 10, 60.0, 0.0)),
+            FitSet::Rest(Some(90.0)),
+            FitSet::Active(StrengthSet {
+                rest: None,
+                ..StrengthSet::new("Squat".to_string(), 8, 70.0, 0.0)
+            }),
+            FitSet::Rest(Some(60.0)),
+            FitSet::Rest(None),
+        ]);
+        assert_eq!(sets.len(), 2);
+        assert_eq!(sets[0].state.rest, Some(90.0));
+        assert_eq!(sets[1].state.rest, Some(60.0));
+        assert_eq!(sets[1].state.volume(), 560.0);
+        assert_eq!(sets[1].state.active_time(), 24.0);
+    }
+}
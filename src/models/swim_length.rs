@@ -0,0 +1,259 @@
+use chrono::{DateTime, Local};
+use fitparser::{profile::MesgNum, FitDataRecord, Value};
+#[cfg(feature = "ssr")]
+use itertools::Itertools;
+#[cfg(feature = "ssr")]
+use sqlx::PgConnection;
+
+use super::base::{DatabaseEntry, ModelError, New};
+
+/// A pool length of a swim, or a rest at the wall if it isn't `active`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwimLength {
+    pub start_time: DateTime<Local>,
+    /// Duration in seconds.
+    pub duration: f64,
+    pub active: bool,
+    /// Stroke as named in the FIT profile, e.g. `freestyle`.
+    pub stroke: Option<String>,
+    pub strokes: Option<i32>,
+}
+
+impl SwimLength {
+    pub fn new(start_time: DateTime<Local>, duration: f64, active: bool) -> Self {
+        Self {
+            start_time,
+            duration,
+            active,
+            stroke: None,
+            strokes: None,
+        }
+    }
+}
+
+impl TryFrom<FitDataRecord> for DatabaseEntry<New, SwimLength> {
+    type Error = ModelError;
+
+    fn try_from(value: FitDataRecord) -> Result<Self, Self::Error> {
+        match value.kind() {
+            MesgNum::Length => {}
+            _ => return Err(ModelError::ParseError("Not a Length".to_string())),
+        };
+        let fields = value.fields();
+        let start_time =
+            fields
+                .iter()
+                .find(|&f| f.name() == "start_time")
+                .ok_or(ModelError::ParseError(
+                    "no start_time in length".to_string(),
+                ))?;
+        let start_time = match start_time.clone().into_value() {
+            Value::Timestamp(date) => date,
+            _ => {
+                return Err(ModelError::ParseError(
+                    "start_time field is not a date".to_string(),
+                ))
+            }
+        };
+
+        let duration = fields
+            .iter()
+            .find(|&f| f.name() == "total_timer_time" || f.name() == "total_elapsed_time");
+        let duration = duration
+            .map(|val| val.clone().into_value())
+            .and_then(|val| match val {
+                Value::Float64(val) => Some(val),
+                _ => None,
+            })
+            .unwrap_or(0.0);
+
+        let length_type = fields.iter().find(|&f| f.name() == "length_type");
+        let active = length_type.map(|val| val.clone().into_value()).map_or(
+            true,
+            |val| !matches!(val, Value::String(val) if val == "idle"),
+        );
+
+        let stroke = fields.iter().find(|&f| f.name() == "swim_stroke");
+        let stroke = stroke
+            .map(|val| val.clone().into_value())
+            .and_then(|val| match val {
+                Value::String(val) => Some(val),
+                _ => None,
+            });
+
+        let strokes = fields.iter().find(|&f| f.name() == "total_strokes");
+        let strokes = strokes
+            .map(|val| val.clone().into_value())
+            .and_then(|val| match val {
+                Value::UInt16(val) => Some(i32::from(val)),
+                _ => None,
+            });
+
+        Ok(DatabaseEntry {
+            state: Box::new(SwimLength {
+                start_time,
+                duration,
+                active,
+                stroke,
+                strokes,
+            }),
+            extra: New,
+        })
+    }
+}
+
+/// Training load of a pool swim from its pace, since heart rate is barely recorded in the water.
+///
+/// Like swim TSS, every active length counts its duration in hours times 100 times the cube of
+/// its speed relative to the critical swim speed, rests don't count. `pool_length` is in meters,
+/// `critical_swim_speed` in meters per second.
+pub fn swim_load(lengths: &[SwimLength], pool_length: f64, critical_swim_speed: f64) -> u32 {
+    if critical_swim_speed <= 0.0 {
+        return 0;
+    }
+    lengths
+        .iter()
+        .filter(|l| l.active && l.duration > 0.0)
+        .map(|l| {
+            let intensity = pool_length / l.duration / critical_swim_speed;
+            l.duration / 3600.0 * 100.0 * intensity.powi(3)
+        })
+        .sum::<f64>()
+        .round() as u32
+}
+
+/// Formats a pace in seconds, e.g. per 100m, as `m:ss`.
+pub fn format_pace(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Parses a pace like `1:45` into seconds, plain numbers are taken as seconds.
+pub fn parse_pace(value: &str) -> Option<f64> {
+    match value.trim().split_once(':') {
+        Some((minutes, seconds)) => {
+            let minutes = minutes.trim().parse::<u32>().ok()?;
+            let seconds = seconds.trim().parse::<f64>().ok()?;
+            (seconds < 60.0).then_some(minutes as f64 * 60.0 + seconds)
+        }
+        None => value.trim().parse::<f64>().ok(),
+    }
+    .filter(|pace| *pace > 0.0)
+}
+
+#[cfg(feature = "ssr")]
+pub async fn insert_swim_lengths(
+    lengths: Vec<DatabaseEntry<New, SwimLength>>,
+    activity_id: i64,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    let activity_ids: Vec<i64> = std::iter::repeat(activity_id).take(lengths.len()).collect();
+    let (position, start_time, duration, active, stroke, strokes): (
+        Vec<_>,
+        Vec<_>,
+        Vec<_>,
+        Vec<_>,
+        Vec<_>,
+        Vec<_>,
+    ) = lengths
+        .into_iter()
+        .enumerate()
+        .map(|(i, l)| {
+            (
+                i as i32,
+                l.state.start_time,
+                l.state.duration,
+                l.state.active,
+                l.state.stroke,
+                l.state.strokes,
+            )
+        })
+        .multiunzip();
+    sqlx::query!(
+        r#"
+        INSERT INTO swim_lengths(activity_id,position,start_time,duration,active,stroke,strokes)
+        SELECT *
+        FROM UNNEST($1::bigint[], $2::int[], $3::timestamptz[], $4::float8[], $5::bool[], $6::varchar[], $7::int[])
+        "#,
+        &activity_ids[..],
+        &position[..],
+        &start_time[..],
+        &duration[..],
+        &active[..],
+        &stroke[..] as _,
+        &strokes[..] as _,
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't insert swim lengths: {}", e)))?;
+    Ok(())
+}
+
+/// Swim load of a stored activity, `None` if it has no pool lengths.
+#[cfg(feature = "ssr")]
+pub async fn activity_swim_load(
+    activity_id: i64,
+    critical_swim_speed: f64,
+    executor: &mut PgConnection,
+) -> Result<Option<u32>, ModelError> {
+    let pool_length = sqlx::query_scalar!(
+        r#"
+        SELECT MAX(pool_length)
+        FROM sessions
+        WHERE activity_id=$1
+        "#,
+        activity_id as i32
+    )
+    .fetch_one(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load pool length: {}", e)))?;
+    let Some(pool_length) = pool_length else {
+        return Ok(None);
+    };
+    let lengths: Vec<SwimLength> = sqlx::query!(
+        r#"
+        SELECT start_time, duration, active
+        FROM swim_lengths
+        WHERE activity_id=$1
+        ORDER BY position
+        "#,
+        activity_id as i32
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load swim lengths: {}", e)))?
+    .into_iter()
+    .map(|l| SwimLength::new(l.start_time.into(), l.duration, l.active))
+    .collect();
+    if lengths.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(swim_load(&lengths, pool_length, critical_swim_speed)))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{format_pace, parse_pace, swim_load, SwimLength};
+    #[cfg(feature = "ssr")]
+    use chrono::Local;
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_swim_load() {
+        let now = Local::now();
+        // an hour at critical swim speed is worth 100
+        let at_css: Vec<_> = (0..144).map(|_| SwimLength::new(now, 25.0, true)).collect();
+        assert_eq!(swim_load(&at_css, 25.0, 1.0), 100);
+        let with_rest = vec![
+            SwimLength::new(now, 20.0, true),
+            SwimLength::new(now, 60.0, false),
+        ];
+        assert_eq!(swim_load(&with_rest, 25.0, 1.0), 1);
+        assert_eq!(swim_load(&with_rest, 25.0, 0.0), 0);
+        assert_eq!(parse_pace("1:45"), Some(105.0));
+        assert_eq!(parse_pace("95"), Some(95.0));
+        assert_eq!(parse_pace("1:75"), None);
+        assert_eq!(format_pace(105.4), "1:45");
+    }
+}
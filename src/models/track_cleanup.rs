@@ -0,0 +1,359 @@
+//! Cleanup of GPS glitches before an activity is stored.
+//!
+//! Tunnels and street canyons make the position jump, which shows up as speed spikes, extra
+//! distance and bogus slopes. Positions that can only be reached faster than `max_speed` are
+//! interpolated from their neighbours and the distance they added is taken out again, the track
+//! can be smoothed on top. Moved positions keep their recording in `raw_latitude` and
+//! `raw_longitude`, the repaired distance and speed replace the recorded ones, so the cleanup is
+//! off unless it's enabled.
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+
+use super::{
+    base::{DatabaseEntry, New},
+    calibration::haversine,
+    record::Record,
+};
+
+/// Consecutive implausible positions after which the track is taken to have really moved there.
+const MAX_OUTLIER_RUN: usize = 30;
+
+/// Accuracy in meters the Kalman filter expects of a GPS position.
+const KALMAN_ACCURACY: f64 = 10.0;
+
+/// Speed in m/s the Kalman filter expects the position to change with.
+const KALMAN_PROCESS_SPEED: f64 = 3.0;
+
+/// Savitzky-Golay weights for a quadratic fit over 7 points.
+const SAVITZKY_GOLAY_WEIGHTS: [f64; 7] = [-2.0, 3.0, 6.0, 7.0, 6.0, 3.0, -2.0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Smoothing {
+    #[default]
+    None,
+    Kalman,
+    SavitzkyGolay,
+}
+
+/// Settings of the cleanup, configured as `[gps_cleanup]`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TrackCleanup {
+    /// Off by default, the recorded distance and speed aren't kept.
+    pub enabled: bool,
+    /// Fastest plausible speed in m/s, anything faster is a glitch.
+    pub max_speed: f64,
+    pub smoothing: Smoothing,
+}
+
+impl Default for TrackCleanup {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_speed: 40.0,
+            smoothing: Smoothing::None,
+        }
+    }
+}
+
+fn seconds(from: DateTime<Local>, to: DateTime<Local>) -> f64 {
+    (to - from).num_milliseconds() as f64 / 1000.0
+}
+
+fn position(record: &DatabaseEntry<New, Record>) -> Option<(f64, f64)> {
+    record.state.latitude.zip(record.state.longitude)
+}
+
+/// Moves a record, remembering where it was recorded the first time it's moved.
+fn move_record(record: &mut DatabaseEntry<New, Record>, to: Option<(f64, f64)>) {
+    if record.state.raw_latitude.is_none() && record.state.raw_longitude.is_none() {
+        record.state.raw_latitude = record.state.latitude;
+        record.state.raw_longitude = record.state.longitude;
+    }
+    record.state.latitude = to.map(|(latitude, _)| latitude);
+    record.state.longitude = to.map(|(_, longitude)| longitude);
+}
+
+fn interpolate(from: f64, to: f64, ratio: f64) -> f64 {
+    from + (to - from) * ratio
+}
+
+/// Positions that are too far from the last plausible one for the time in between.
+fn find_outliers(records: &[DatabaseEntry<New, Record>], max_speed: f64) -> Vec<bool> {
+    let mut outliers = vec![false; records.len()];
+    let mut last_good: Option<usize> = None;
+    let mut run = Vec::new();
+    for (i, record) in records.iter().enumerate() {
+        let Some(current) = position(record) else {
+            continue;
+        };
+        let Some(good) = last_good else {
+            last_good = Some(i);
+            continue;
+        };
+        let time = seconds(records[good].state.timestamp, record.state.timestamp).max(1.0);
+        if haversine(position(&records[good]).unwrap(), current) > max_speed * time {
+            run.push(i);
+            if run.len() > MAX_OUTLIER_RUN {
+                run.clear();
+                last_good = Some(i);
+            }
+        } else {
+            for outlier in run.drain(..) {
+                outliers[outlier] = true;
+            }
+            last_good = Some(i);
+        }
+    }
+    // nothing plausible follows, so there's nothing to interpolate towards
+    for outlier in run {
+        outliers[outlier] = true;
+    }
+    outliers
+}
+
+/// Interpolates runs of outliers between the plausible positions around them and takes the jump
+/// out of the recorded distance. Runs at the end lose their position.
+fn repair_outliers(records: &mut [DatabaseEntry<New, Record>], outliers: &[bool], max_speed: f64) {
+    let mut previous: Option<usize> = None;
+    let mut offset = 0.0;
+    let mut i = 0;
+    while i < records.len() {
+        if !outliers[i] {
+            records[i].state.distance = records[i].state.distance.map(|d| d - offset);
+            if position(&records[i]).is_some() {
+                previous = Some(i);
+            }
+            i += 1;
+            continue;
+        }
+        let end = (i..records.len())
+            .find(|&j| !outliers[j] && position(&records[j]).is_some())
+            .unwrap_or(records.len());
+        let (Some(from), true) = (previous, end < records.len()) else {
+            for record in &mut records[i..end] {
+                record.state.distance = record.state.distance.map(|d| d - offset);
+                if position(record).is_some() {
+                    move_record(record, None);
+                }
+            }
+            i = end;
+            continue;
+        };
+        let (start_time, end_time) = (records[from].state.timestamp, records[end].state.timestamp);
+        let span = seconds(start_time, end_time).max(1.0);
+        let (start_position, end_position) = (
+            position(&records[from]).unwrap(),
+            position(&records[end]).unwrap(),
+        );
+        // the jump there and back is in the recorded distance, the straight line is all that's left
+        let start_distance = records[from].state.distance;
+        if let (Some(start_distance), Some(end_distance)) =
+            (start_distance, records[end].state.distance)
+        {
+            let end_distance = end_distance - offset;
+            if end_distance - start_distance > max_speed * span {
+                offset += end_distance - start_distance - haversine(start_position, end_position);
+            }
+        }
+        let end_distance = records[end].state.distance.map(|d| d - offset);
+        for record in &mut records[i..end] {
+            let ratio = seconds(start_time, record.state.timestamp) / span;
+            if position(record).is_some() {
+                move_record(
+                    record,
+                    Some((
+                        interpolate(start_position.0, end_position.0, ratio),
+                        interpolate(start_position.1, end_position.1, ratio),
+                    )),
+                );
+            }
+            record.state.distance = start_distance
+                .zip(end_distance)
+                .map(|(from, to)| interpolate(from, to, ratio))
+                .or(record.state.distance.map(|d| d - offset));
+        }
+        i = end;
+    }
+}
+
+/// Replaces speeds faster than `max_speed` by interpolating between the plausible ones around.
+fn repair_speeds(records: &mut [DatabaseEntry<New, Record>], max_speed: f64) -> bool {
+    let plausible: Vec<Option<(DateTime<Local>, f64)>> = records
+        .iter()
+        .map(|r| {
+            r.state
+                .speed
+                .filter(|&s| s <= max_speed)
+                .map(|s| (r.state.timestamp, s))
+        })
+        .collect();
+    let mut changed = false;
+    for i in 0..records.len() {
+        if records[i].state.speed.map_or(true, |s| s <= max_speed) {
+            continue;
+        }
+        let before = plausible[..i].iter().rev().find_map(|s| *s);
+        let after = plausible[i + 1..].iter().find_map(|s| *s);
+        let timestamp = records[i].state.timestamp;
+        records[i].state.speed = match (before, after) {
+            (Some((start, from)), Some((end, to))) => Some(interpolate(
+                from,
+                to,
+                seconds(start, timestamp) / seconds(start, end).max(1.0),
+            )),
+            (Some((_, speed)), None) | (None, Some((_, speed))) => Some(speed),
+            (None, None) => None,
+        };
+        changed = true;
+    }
+    changed
+}
+
+/// Positions filtered with a random walk Kalman filter, expecting `KALMAN_ACCURACY` of each fix.
+fn kalman(fixes: &[(DateTime<Local>, (f64, f64))]) -> Vec<(f64, f64)> {
+    let mut filtered = Vec::with_capacity(fixes.len());
+    let mut state: Option<(DateTime<Local>, (f64, f64), f64)> = None;
+    for &(timestamp, measured) in fixes {
+        let next = match state {
+            None => (timestamp, measured, KALMAN_ACCURACY.powi(2)),
+            Some((last, (latitude, longitude), variance)) => {
+                let variance =
+                    variance + seconds(last, timestamp).max(0.0) * KALMAN_PROCESS_SPEED.powi(2);
+                let gain = variance / (variance + KALMAN_ACCURACY.powi(2));
+                (
+                    timestamp,
+                    (
+                        latitude + gain * (measured.0 - latitude),
+                        longitude + gain * (measured.1 - longitude),
+                    ),
+                    (1.0 - gain) * variance,
+                )
+            }
+        };
+        filtered.push(next.1);
+        state = Some(next);
+    }
+    filtered
+}
+
+/// Positions smoothed with a Savitzky-Golay filter, the first and last few stay as they are.
+fn savitzky_golay(fixes: &[(DateTime<Local>, (f64, f64))]) -> Vec<(f64, f64)> {
+    let half = SAVITZKY_GOLAY_WEIGHTS.len() / 2;
+    let total: f64 = SAVITZKY_GOLAY_WEIGHTS.iter().sum();
+    (0..fixes.len())
+        .map(|i| {
+            if i < half || i + half >= fixes.len() {
+                return fixes[i].1;
+            }
+            let window = &fixes[i - half..=i + half];
+            let (latitude, longitude) = window.iter().zip(SAVITZKY_GOLAY_WEIGHTS).fold(
+                (0.0, 0.0),
+                |(latitude, longitude), ((_, position), weight)| {
+                    (
+                        latitude + position.0 * weight,
+                        longitude + position.1 * weight,
+                    )
+                },
+            );
+            (latitude / total, longitude / total)
+        })
+        .collect()
+}
+
+/// Cleans up the GPS track of a recording, returns whether anything changed.
+pub fn clean_track(records: &mut [DatabaseEntry<New, Record>], settings: &TrackCleanup) -> bool {
+    if !settings.enabled || !records.iter().any(|r| position(r).is_some()) {
+        return false;
+    }
+    let outliers = find_outliers(records, settings.max_speed);
+    let mut changed = outliers.contains(&true);
+    if changed {
+        repair_outliers(records, &outliers, settings.max_speed);
+    }
+    changed |= repair_speeds(records, settings.max_speed);
+
+    let (indices, fixes): (Vec<usize>, Vec<_>) = records
+        .iter()
+        .enumerate()
+        .filter_map(|(i, r)| position(r).map(|p| (i, (r.state.timestamp, p))))
+        .unzip();
+    let smoothed = match settings.smoothing {
+        Smoothing::None => return changed,
+        Smoothing::Kalman => kalman(&fixes),
+        Smoothing::SavitzkyGolay => savitzky_golay(&fixes),
+    };
+    for (i, smoothed) in indices.into_iter().zip(smoothed) {
+        if position(&records[i]) != Some(smoothed) {
+            move_record(&mut records[i], Some(smoothed));
+            changed = true;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{clean_track, Smoothing, TrackCleanup};
+    #[cfg(feature = "ssr")]
+    use crate::models::{
+        base::{DatabaseEntry, New},
+        record::Record,
+    };
+    #[cfg(feature = "ssr")]
+    use chrono::{Local, TimeDelta};
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_clean_track() {
+        let start = Local::now();
+        // about 11m per second heading north, with a jump of about 1km at 5 seconds
+        let mut records: Vec<DatabaseEntry<New, Record>> = (0..10)
+            .map(|i| {
+                let jump = if i == 5 { 0.01 } else { 0.0 };
+                DatabaseEntry {
+                    state: Box::new(Record {
+                        timestamp: start + TimeDelta::seconds(i),
+                        heartrate: None,
+                        latitude: Some(47.0 + i as f64 * 0.0001 + jump),
+                        longitude: Some(8.0),
+                        raw_latitude: None,
+                        raw_longitude: None,
+                        distance: Some(i as f64 * 11.0 + if i >= 5 { 2200.0 } else { 0.0 }),
+                        speed: Some(if i == 5 { 1100.0 } else { 11.0 }),
+                        altitude: None,
+                        raw_altitude: None,
+                        power: None,
+                    }),
+                    extra: New,
+                }
+            })
+            .collect();
+        let settings = TrackCleanup {
+            enabled: true,
+            ..TrackCleanup::default()
+        };
+        assert!(clean_track(&mut records, &settings));
+        let jumped = &records[5].state;
+        assert!((jumped.latitude.unwrap() - 47.0005).abs() < 1e-9);
+        assert_eq!(jumped.raw_latitude, Some(47.0105));
+        assert_eq!(jumped.speed, Some(11.0));
+        assert_eq!(records[4].state.raw_latitude, None);
+        // the jump there and back is gone from the distance
+        assert!((records[9].state.distance.unwrap() - 99.0).abs() < 1.0);
+        assert!((jumped.distance.unwrap() - 55.0).abs() < 1.0);
+
+        // a clean track stays as it is
+        assert!(!clean_track(&mut records, &settings));
+
+        let smoothing = TrackCleanup {
+            smoothing: Smoothing::SavitzkyGolay,
+            ..settings
+        };
+        // a straight line at constant speed is already smooth
+        clean_track(&mut records, &smoothing);
+        assert!((records[5].state.latitude.unwrap() - 47.0005).abs() < 1e-9);
+    }
+}
@@ -3,6 +3,25 @@ use std::collections::HashMap;
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 
+use super::activity::session_rpe_load;
+
+/// How the training load of an activity is calculated.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[cfg_attr(feature = "ssr", derive(sqlx::Type))]
+#[cfg_attr(
+    feature = "ssr",
+    sqlx(type_name = "load_model", rename_all = "snake_case")
+)]
+#[derive(strum::EnumString, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum LoadModel {
+    /// Load from the recorded heart rate, session-RPE only for activities without one.
+    #[default]
+    Heartrate,
+    /// Session-RPE load after Foster for all activities with a perceived exertion.
+    SessionRpe,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct UserPreferences {
     pub user_id: i64,
@@ -13,6 +32,9 @@ pub struct UserPreferences {
     pub max_heartrate: i32,
     pub tau: f64,
     pub c: f64,
+    pub load_model: LoadModel,
+    /// Critical swim speed in meters per second, the pace swim load is relative to.
+    pub critical_swim_speed: f64,
 }
 
 impl UserPreferences {
@@ -31,12 +53,44 @@ impl UserPreferences {
             });
         hr_buckets
             .iter()
-            .map(|(&hr, time_s)| {
-                (self.c * (self.tau * *hr as f64).exp() + 1.0) * *time_s as f64 / 60.0
-            })
+            .map(|(&hr, &time_s)| self.heartrate_load(*hr, time_s as f64))
             .sum::<f64>()
             .round() as u32
     }
+
+    /// Training load of `seconds` at a constant heart rate, without rounding.
+    fn heartrate_load(&self, heartrate: u32, seconds: f64) -> f64 {
+        if heartrate as f64 > self.max_heartrate as f64 * 0.55 {
+            (self.c * (self.tau * heartrate as f64).exp() + 1.0) * seconds / 60.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Training load of the given time spent at each heart rate, in seconds.
+    pub fn constant_load(&self, times: &[(u32, f64)]) -> u32 {
+        times
+            .iter()
+            .map(|&(heartrate, seconds)| self.heartrate_load(heartrate, seconds))
+            .sum::<f64>()
+            .round() as u32
+    }
+
+    /// Training load of an activity according to the load model.
+    ///
+    /// `measured_load` is the load calculated from the recorded data, `duration` is in seconds.
+    pub fn activity_load(
+        &self,
+        measured_load: Option<u32>,
+        rpe: Option<i16>,
+        duration: f64,
+    ) -> Option<u32> {
+        let rpe_load = rpe.map(|rpe| session_rpe_load(rpe, duration));
+        match self.load_model {
+            LoadModel::Heartrate => measured_load.or(rpe_load),
+            LoadModel::SessionRpe => rpe_load.or(measured_load),
+        }
+    }
 }
 
 impl Default for UserPreferences {
@@ -50,6 +104,8 @@ impl Default for UserPreferences {
             max_heartrate: 183,
             tau: 0.0809749,
             c: 0.000002370473,
+            load_model: LoadModel::default(),
+            critical_swim_speed: 1.0,
         }
     }
 }
@@ -71,7 +127,9 @@ pub async fn get_user_preferences(
             anaerobic_threshold,
             max_heartrate,
             tau,
-            c
+            c,
+            load_model as "load_model: LoadModel",
+            critical_swim_speed
         FROM user_preferences
         WHERE user_id=$1 
             and (start_time IS NULL and end_time IS NULL) 
@@ -89,3 +147,27 @@ pub async fn get_user_preferences(
     .unwrap_or_default();
     return result;
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{LoadModel, UserPreferences};
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_activity_load() {
+        let heartrate = UserPreferences::default();
+        let session_rpe = UserPreferences {
+            load_model: LoadModel::SessionRpe,
+            ..Default::default()
+        };
+        assert_eq!(heartrate.activity_load(Some(80), Some(6), 3600.0), Some(80));
+        assert_eq!(heartrate.activity_load(None, Some(6), 3600.0), Some(360));
+        assert_eq!(
+            session_rpe.activity_load(Some(80), Some(6), 3600.0),
+            Some(360)
+        );
+        assert_eq!(session_rpe.activity_load(Some(80), None, 3600.0), Some(80));
+        assert_eq!(session_rpe.activity_load(None, None, 3600.0), None);
+    }
+}
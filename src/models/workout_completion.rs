@@ -0,0 +1,415 @@
+use chrono::{DateTime, Local};
+#[cfg(feature = "ssr")]
+use chrono::{Datelike, NaiveDate, NaiveTime, TimeZone};
+#[cfg(feature = "ssr")]
+use rrule::{RRule, RRuleSet, Tz, Unvalidated};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "ssr")]
+use sqlx::{Connection, PgConnection, PgPool};
+#[cfg(feature = "ssr")]
+use std::str::FromStr;
+
+#[cfg(feature = "ssr")]
+use super::{base::ModelError, sport::get_activity_sports};
+#[cfg(feature = "ssr")]
+use crate::pages::workout_schedule::WorkoutType;
+
+/// Links an activity to the scheduled workout occurence it fulfilled.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WorkoutCompletion {
+    pub activity_id: i64,
+    pub workout_instance_id: i64,
+    /// The date of the occurence that was completed.
+    pub occurence_date: DateTime<Local>,
+    /// Whether the link was set by the user instead of being matched on upload.
+    pub manual: bool,
+    /// Planned duration in seconds, with weekly scaling applied.
+    pub planned_duration: Option<i32>,
+    /// Planned distance in meters, with weekly scaling applied.
+    pub planned_distance: Option<i32>,
+    /// Planned training load, with weekly scaling applied.
+    pub planned_load: Option<i32>,
+    pub actual_duration: i32,
+    pub actual_distance: Option<i32>,
+    pub actual_load: Option<i32>,
+}
+
+impl WorkoutCompletion {
+    /// Ratio of actual to planned volume.
+    ///
+    /// Uses load if it was planned, otherwise duration and then distance.
+    pub fn compliance(&self) -> Option<f64> {
+        [
+            (self.planned_load, self.actual_load),
+            (self.planned_duration, Some(self.actual_duration)),
+            (self.planned_distance, self.actual_distance),
+        ]
+        .into_iter()
+        .find_map(|volume| match volume {
+            (Some(planned), Some(actual)) if planned > 0 => Some(actual as f64 / planned as f64),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone)]
+struct PlannedOccurence {
+    instance_id: i64,
+    workout_type: WorkoutType,
+    sport_id: Option<i64>,
+    /// Date of the first occurence of the instance, weekly scaling is counted from here.
+    first_date: NaiveDate,
+    date: DateTime<Local>,
+}
+
+#[cfg(feature = "ssr")]
+fn day_bounds(date: NaiveDate) -> Result<(DateTime<Local>, DateTime<Local>), ModelError> {
+    let local = |time: NaiveTime| {
+        Local
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+            .ok_or(ModelError::ParseError(format!(
+                "{} doesn't exist in local time",
+                date.and_time(time)
+            )))
+    };
+    Ok((
+        local(NaiveTime::MIN)?,
+        local(NaiveTime::from_hms_opt(23, 59, 59).unwrap())?,
+    ))
+}
+
+/// All occurences of active workout instances of a user on a day.
+#[cfg(feature = "ssr")]
+async fn planned_occurences(
+    user_id: i64,
+    date: NaiveDate,
+    executor: &mut PgConnection,
+) -> Result<Vec<PlannedOccurence>, ModelError> {
+    let (day_start, day_end) = day_bounds(date)?;
+    let instances = sqlx::query!(
+        r#"
+        SELECT
+            i.id,
+            i.start_date,
+            i.rrule,
+            i.valid_from,
+            i.valid_until,
+            t.workout_type::text as "workout_type!",
+            t.sport_id,
+            ARRAY_REMOVE(ARRAY_AGG(ex.exclusion_date), NULL) as "exclusion_dates!"
+        FROM workout_instances i
+        INNER JOIN workout_templates t ON i.workout_template_id=t.id
+        LEFT JOIN workout_exclusion_dates ex ON ex.workout_instance_id=i.id
+        WHERE i.user_id=$1::bigint and i.active and i.start_date <= $2
+        GROUP BY i.id, t.workout_type, t.sport_id
+        "#,
+        user_id,
+        day_end
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load workout instances: {}", e)))?;
+    let mut occurences = Vec::new();
+    for instance in instances {
+        let start = instance.start_date.with_timezone(&Tz::LOCAL);
+        let rrule = instance
+            .rrule
+            .parse::<RRule<Unvalidated>>()
+            .and_then(|r| r.validate(start))
+            .map_err(|e| ModelError::ParseError(format!("Invalid rrule: {}", e)))?;
+        let rrule = RRuleSet::new(start).rrule(rrule).set_exdates(
+            instance
+                .exclusion_dates
+                .iter()
+                .map(|d| d.with_timezone(&Tz::LOCAL))
+                .collect(),
+        );
+        let first_date = match rrule.clone().into_iter().next() {
+            Some(first) => first.date_naive(),
+            None => continue,
+        };
+        let workout_type = WorkoutType::from_str(&instance.workout_type)
+            .map_err(|e| ModelError::ParseError(format!("Invalid workout type: {}", e)))?;
+        occurences.extend(
+            rrule
+                .after(day_start.with_timezone(&Tz::LOCAL))
+                .before(day_end.with_timezone(&Tz::LOCAL))
+                .all_unchecked()
+                .into_iter()
+                .filter(|d| {
+                    instance.valid_from.map_or(true, |from| *d >= from)
+                        && instance.valid_until.map_or(true, |until| *d < until)
+                })
+                .map(|d| PlannedOccurence {
+                    instance_id: instance.id,
+                    workout_type: workout_type.clone(),
+                    sport_id: instance.sport_id,
+                    first_date,
+                    date: d.with_timezone(&Local),
+                }),
+        );
+    }
+    occurences.sort_by_key(|o| (o.date, o.instance_id));
+    Ok(occurences)
+}
+
+/// Planned duration, distance and load of an occurence, with weekly scaling applied.
+#[cfg(feature = "ssr")]
+async fn planned_volume(
+    occurence: &PlannedOccurence,
+    user_id: i64,
+    executor: &mut PgConnection,
+) -> Result<(Option<i32>, Option<i32>, Option<i32>), ModelError> {
+    let first_week = occurence.first_date.iso_week();
+    let week = occurence.date.iso_week();
+    let scaling = sqlx::query!(
+        r#"
+        SELECT COALESCE(SUM(scaling), 0)::int4 as "scaling!"
+        FROM weekly_scaling
+        WHERE user_id=$1::bigint and (year, week) > ($2, $3) and (year, week) <= ($4, $5)
+        "#,
+        user_id,
+        first_week.year(),
+        first_week.week() as i32,
+        week.year(),
+        week.week() as i32
+    )
+    .fetch_one(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load weekly scaling: {}", e)))?
+    .scaling;
+    let parameters = sqlx::query!(
+        r#"
+        SELECT
+            p.parameter_type::text as "parameter_type!",
+            COALESCE(l.value_override, p.value) as "value!",
+            p.scaling
+        FROM workout_instances i
+        INNER JOIN workout_parameters p ON p.workout_template_id=i.workout_template_id
+        LEFT JOIN parameter_links l ON l.parameter_id=p.id and l.instance_id=i.id
+        WHERE i.id=$1
+        "#,
+        occurence.instance_id
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load workout parameters: {}", e)))?;
+    let total = |parameter_type: &str| {
+        parameters
+            .iter()
+            .filter(|p| p.parameter_type == parameter_type)
+            .map(|p| {
+                if p.scaling {
+                    (p.value as f64 * (100 + scaling) as f64 / 100.0).round() as i32
+                } else {
+                    p.value
+                }
+            })
+            .reduce(|a, b| a + b)
+    };
+    Ok((total("time_s"), total("distance_m"), total("trainingload")))
+}
+
+/// Stores the link between an activity and a workout occurence, replacing existing links of either.
+///
+/// Existing links are only removed if the new one can be stored.
+#[cfg(feature = "ssr")]
+async fn insert_completion(
+    activity_id: i64,
+    user_id: i64,
+    occurence: &PlannedOccurence,
+    manual: bool,
+    executor: &mut PgConnection,
+) -> Result<WorkoutCompletion, ModelError> {
+    let (planned_duration, planned_distance, planned_load) =
+        planned_volume(occurence, user_id, executor).await?;
+    let mut transaction = executor
+        .begin()
+        .await
+        .map_err(|e| ModelError::InsertError(format!("Couldn't start transaction: {}", e)))?;
+    sqlx::query!(
+        r#"
+        DELETE FROM workout_completions
+        WHERE user_id=$1::bigint and (activity_id=$2 or (workout_instance_id=$3 and occurence_date=$4))
+        "#,
+        user_id,
+        activity_id as i32,
+        occurence.instance_id as i32,
+        occurence.date
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't replace workout completion: {}", e)))?;
+    let completion = sqlx::query_as!(
+        WorkoutCompletion,
+        r#"
+        INSERT INTO workout_completions (
+            user_id, activity_id, workout_instance_id, occurence_date, manual,
+            planned_duration, planned_distance, planned_load,
+            actual_duration, actual_distance, actual_load
+        )
+        SELECT
+            a.user_id, a.id, $3, $4, $5, $6, $7, $8,
+            ROUND(a.duration)::int4,
+            (SELECT ROUND(SUM(s.distance))::int4 FROM sessions s WHERE s.activity_id=a.id),
+            a.load
+        FROM activities a
+        WHERE a.id=$1 and a.user_id=$2::bigint
+        RETURNING
+            activity_id::int8 as "activity_id!",
+            workout_instance_id::int8 as "workout_instance_id!",
+            occurence_date as "occurence_date:DateTime<Local>",
+            manual,
+            planned_duration,
+            planned_distance,
+            planned_load,
+            actual_duration,
+            actual_distance,
+            actual_load
+        "#,
+        activity_id as i32,
+        user_id,
+        occurence.instance_id as i32,
+        occurence.date,
+        manual,
+        planned_duration,
+        planned_distance,
+        planned_load
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|e| ModelError::InsertError(format!("Couldn't insert workout completion: {}", e)))?;
+    transaction.commit().await.map_err(|e| {
+        ModelError::InsertError(format!("Couldn't insert workout completion: {}", e))
+    })?;
+    Ok(completion)
+}
+
+/// Matches an uploaded activity to a planned workout on the same day.
+///
+/// Only occurences whose sport, or workout type if the template has no sport, fits the sport of
+/// one of the activity's sessions and that aren't completed yet are considered. Activities that are already linked are left alone.
+#[cfg(feature = "ssr")]
+pub async fn match_activity_to_workout(
+    activity_id: i64,
+    user_id: i64,
+    executor: &mut PgConnection,
+) -> Result<Option<WorkoutCompletion>, ModelError> {
+    let activity = sqlx::query!(
+        r#"
+        SELECT
+            a.start_time,
+            EXISTS(SELECT 1 FROM workout_completions c WHERE c.activity_id=a.id) as "linked!"
+        FROM activities a
+        WHERE a.id=$1 and a.user_id=$2::bigint
+        "#,
+        activity_id,
+        user_id as i32
+    )
+    .fetch_one(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load activity: {}", e)))?;
+    if activity.linked {
+        return Ok(None);
+    }
+    let sports = get_activity_sports(activity_id, executor).await?;
+    let date = activity.start_time.with_timezone(&Local).date_naive();
+    let (day_start, day_end) = day_bounds(date)?;
+    let completed = sqlx::query!(
+        r#"
+        SELECT workout_instance_id::int8 as "workout_instance_id!", occurence_date
+        FROM workout_completions
+        WHERE user_id=$1::bigint and occurence_date >= $2 and occurence_date <= $3
+        "#,
+        user_id,
+        day_start,
+        day_end
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ModelError::QueryError(format!("Couldn't load workout completions: {}", e)))?;
+    let occurence = planned_occurences(user_id, date, executor)
+        .await?
+        .into_iter()
+        .filter(|o| {
+            !completed
+                .iter()
+                .any(|c| c.workout_instance_id == o.instance_id && c.occurence_date == o.date)
+        })
+        .find(|o| sports.fulfills(o.sport_id, &o.workout_type));
+    match occurence {
+        Some(occurence) => Ok(Some(
+            insert_completion(activity_id, user_id, &occurence, false, executor).await?,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Matches a newly saved activity to a planned workout once its transaction is committed.
+///
+/// An activity without a planned workout is fine, so failures are only logged.
+#[cfg(feature = "ssr")]
+pub async fn match_new_activity_to_workout(activity_id: i64, user_id: i64, pool: &PgPool) {
+    let result = match pool.acquire().await {
+        Ok(mut connection) => match_activity_to_workout(activity_id, user_id, &mut connection)
+            .await
+            .map(|_| ()),
+        Err(e) => Err(ModelError::QueryError(format!(
+            "Couldn't acquire connection: {}",
+            e
+        ))),
+    };
+    if let Err(e) = result {
+        leptos::logging::log!("couldn't match activity to workout: {}", e);
+    }
+}
+
+/// Manually links an activity to an occurence of a workout instance on the given day.
+#[cfg(feature = "ssr")]
+pub async fn link_activity_to_workout(
+    activity_id: i64,
+    instance_id: i64,
+    date: NaiveDate,
+    user_id: i64,
+    executor: &mut PgConnection,
+) -> Result<WorkoutCompletion, ModelError> {
+    let occurence = planned_occurences(user_id, date, executor)
+        .await?
+        .into_iter()
+        .find(|o| o.instance_id == instance_id)
+        .ok_or(ModelError::QueryError(
+            "Workout doesn't occur on this day".to_string(),
+        ))?;
+    insert_completion(activity_id, user_id, &occurence, true, executor).await
+}
+
+/// Refreshes the actual duration, distance and load stored with the workout completions of a
+/// user, or only those of one activity, after the activity changed.
+#[cfg(feature = "ssr")]
+pub async fn update_completion_actuals(
+    user_id: i64,
+    activity_id: Option<i64>,
+    executor: &mut PgConnection,
+) -> Result<(), ModelError> {
+    sqlx::query!(
+        r#"
+        UPDATE workout_completions c
+        SET
+            actual_duration=ROUND(a.duration)::int4,
+            actual_distance=(SELECT ROUND(SUM(s.distance))::int4 FROM sessions s WHERE s.activity_id=a.id),
+            actual_load=a.load
+        FROM activities a
+        WHERE a.id=c.activity_id and c.user_id=$1::bigint and ($2::bigint IS NULL or a.id=$2)
+        "#,
+        user_id,
+        activity_id
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| {
+        ModelError::InsertError(format!("Couldn't update workout completions: {}", e))
+    })?;
+    Ok(())
+}
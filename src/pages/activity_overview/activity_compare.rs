@@ -0,0 +1,433 @@
+use std::cmp;
+
+use super::activity_details::{activity_details, ActivityDetail, Record};
+#[cfg(feature = "ssr")]
+use crate::app::{auth, pool};
+use crate::models::{split::split_times, swim_length::format_pace};
+use charming::{
+    component::{Axis, Grid, Legend},
+    element::{AxisType, Tooltip, Trigger},
+    series::Line,
+    Chart, WasmRenderer,
+};
+use chrono::{DateTime, Local};
+use leptos::{
+    html::{Div, Select},
+    *,
+};
+use leptos_use::{use_element_size, UseElementSizeReturn};
+use serde::{Deserialize, Serialize};
+
+/// Another activity of the same sport the current one can be compared with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComparableActivity {
+    pub id: i64,
+    pub start_time: DateTime<Local>,
+    pub name: String,
+    /// Meters.
+    pub distance: Option<f64>,
+}
+
+/// The user's latest activities with a session of the same sport as the activity.
+#[server]
+pub async fn comparable_activities(
+    activity_id: i64,
+) -> Result<Vec<ComparableActivity>, ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let activities = sqlx::query_as!(
+        ComparableActivity,
+        r#"
+        SELECT
+            activities.id,
+            activities.start_time as "start_time:DateTime<Local>",
+            COALESCE(
+                activities.title,
+                string_agg(COALESCE(sports.name, sessions.sport), ', '),
+                'General'
+            ) as "name!",
+            SUM(sessions.distance)::float8 as distance
+        FROM activities
+        JOIN sessions ON sessions.activity_id = activities.id
+        LEFT JOIN sports ON sports.id = sessions.sport_id
+        WHERE activities.user_id = $1::bigint
+            AND activities.id <> $2
+            AND EXISTS (
+                SELECT 1
+                FROM sessions own
+                INNER JOIN activities own_activity ON own_activity.id = own.activity_id
+                WHERE own.activity_id = $2
+                    AND own_activity.user_id = $1::bigint
+                    AND own.sport IS NOT DISTINCT FROM sessions.sport
+                    AND own.sport_id IS NOT DISTINCT FROM sessions.sport_id
+            )
+        GROUP BY activities.id
+        ORDER BY activities.start_time DESC
+        LIMIT 50
+        "#,
+        user.id,
+        activity_id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load activities: {}", e)))?;
+    Ok(activities)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Alignment {
+    Time,
+    Distance,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Metric {
+    Heartrate,
+    Pace,
+    Power,
+    Altitude,
+}
+
+impl Metric {
+    fn label(&self) -> &'static str {
+        match self {
+            Metric::Heartrate => "Heartrate (bpm)",
+            Metric::Pace => "Pace (min/km)",
+            Metric::Power => "Power (W)",
+            Metric::Altitude => "Altitude (m)",
+        }
+    }
+
+    fn value(&self, record: &Record) -> Option<f64> {
+        match self {
+            Metric::Heartrate => record.heartrate.map(f64::from),
+            // standing still would stretch the axis to infinity
+            Metric::Pace => record.speed.filter(|s| *s > 0.5).map(|s| 1000.0 / s / 60.0),
+            Metric::Power => record.power.map(f64::from),
+            Metric::Altitude => record.altitude,
+        }
+    }
+}
+
+/// Label of an activity in the chart legend and table headers.
+fn activity_label(detail: &ActivityDetail) -> String {
+    format!(
+        "{} {}",
+        detail.start_time.format("%Y-%m-%d"),
+        detail.title.clone().unwrap_or(detail.sport.clone())
+    )
+}
+
+/// Points of a metric over elapsed minutes or kilometres.
+fn curve(detail: &ActivityDetail, alignment: Alignment, metric: Metric) -> Vec<Vec<f64>> {
+    let records = detail.records.as_deref().unwrap_or_default();
+    let Some(start) = records.first().map(|r| r.timestamp) else {
+        return Vec::new();
+    };
+    records
+        .iter()
+        .filter_map(|r| {
+            let x = match alignment {
+                Alignment::Time => (r.timestamp - start).num_seconds() as f64 / 60.0,
+                Alignment::Distance => r.distance? / 1000.0,
+            };
+            Some(vec![x, metric.value(r)?])
+        })
+        .collect()
+}
+
+/// Elapsed seconds of each full kilometre.
+fn kilometre_times(detail: &ActivityDetail) -> Vec<f64> {
+    let records = detail.records.as_deref().unwrap_or_default();
+    let Some(start) = records.first().map(|r| r.timestamp) else {
+        return Vec::new();
+    };
+    let samples: Vec<(f64, f64)> = records
+        .iter()
+        .filter_map(|r| {
+            r.distance
+                .map(|d| ((r.timestamp - start).num_milliseconds() as f64 / 1000.0, d))
+        })
+        .collect();
+    let times = split_times(&samples, 1000.0);
+    times
+        .iter()
+        .enumerate()
+        .map(|(i, t)| t - if i > 0 { times[i - 1] } else { 0.0 })
+        .collect()
+}
+
+/// Durations of the device laps in seconds, in order.
+fn lap_times(detail: &ActivityDetail) -> Vec<f64> {
+    let mut laps = detail.laps.clone().unwrap_or_default();
+    laps.sort_by_key(|l| l.start_time);
+    laps.iter()
+        .map(|l| (l.end_time - l.start_time).num_milliseconds() as f64 / 1000.0)
+        .collect()
+}
+
+/// Difference to the base activity, faster is negative.
+fn format_delta(seconds: f64) -> String {
+    let sign = if seconds < 0.0 { "-" } else { "+" };
+    format!("{}{}", sign, format_pace(seconds.abs()))
+}
+
+/// Overlays the curves of other activities on the current one and lists the time differences
+/// per kilometre or lap.
+#[component]
+pub fn ActivityComparison(detail: ActivityDetail) -> impl IntoView {
+    let activity_id = detail.id;
+    let compared = create_rw_signal(Vec::<i64>::new());
+    let alignment = create_rw_signal(Alignment::Time);
+    let metric = create_rw_signal(Metric::Heartrate);
+    let per_lap = create_rw_signal(false);
+    let candidates = create_resource(move || activity_id, comparable_activities);
+    let others = create_resource(
+        move || compared.get(),
+        |ids| async move {
+            let mut others = Vec::new();
+            for id in ids {
+                others.push(activity_details(id).await?);
+            }
+            Ok::<_, ServerFnError>(others)
+        },
+    );
+    let base = store_value(detail);
+    let chart_node = create_node_ref::<Div>();
+    let UseElementSizeReturn { width, height: _ } = use_element_size(chart_node);
+    let _chart = create_local_resource(
+        move || (others.get(), alignment.get(), metric.get(), width.get()),
+        move |(others, alignment, metric, width)| async move {
+            let Some(Ok(others)) = others else {
+                return;
+            };
+            if width == 0.0 || others.is_empty() {
+                return;
+            }
+            let mut chart = Chart::new()
+                .grid(Grid::new().top(40).bottom(30))
+                .tooltip(Tooltip::new().trigger(Trigger::Axis))
+                .legend(Legend::new())
+                .x_axis(Axis::new().type_(AxisType::Value).name(match alignment {
+                    Alignment::Time => "min",
+                    Alignment::Distance => "km",
+                }))
+                .y_axis(
+                    Axis::new()
+                        .type_(AxisType::Value)
+                        .name(metric.label())
+                        .scale(true)
+                        .inverse(metric == Metric::Pace),
+                );
+            let base = base.get_value();
+            for detail in std::iter::once(&base).chain(others.iter()) {
+                chart = chart.series(
+                    Line::new()
+                        .name(activity_label(detail))
+                        .show_symbol(false)
+                        .data(curve(detail, alignment, metric)),
+                );
+            }
+            let renderer = WasmRenderer::new(cmp::max(width as u32, 500), 400);
+            let _rendered = renderer.render("comparison_chart", &chart);
+        },
+    );
+    let select_node = create_node_ref::<Select>();
+    let add = move |ev| {
+        if let Ok(id) = event_target_value(&ev).parse::<i64>() {
+            compared.update(|c| {
+                if !c.contains(&id) {
+                    c.push(id)
+                }
+            });
+        }
+        // back to the placeholder, so the same activity can be added again after removing it
+        if let Some(select) = select_node.get() {
+            select.set_value("");
+        }
+    };
+    view! {
+        <details>
+            <summary class="is-size-7">Compare</summary>
+            <div class="field is-grouped is-grouped-multiline">
+                <div class="control">
+                    <div class="select is-small">
+                        <select node_ref=select_node on:change=add>
+                            <option value="">"Add activity..."</option>
+                            {move || {
+                                candidates
+                                    .get()
+                                    .and_then(|c| c.ok())
+                                    .unwrap_or_default()
+                                    .into_iter()
+                                    .map(|c| {
+                                        view! {
+                                            <option value=c
+                                                .id>
+                                                {c.start_time.format("%Y-%m-%d").to_string()} " " {c.name}
+                                                {c
+                                                    .distance
+                                                    .filter(|d| *d > 0.0)
+                                                    .map(|d| format!(" ({:.2} km)", d / 1000.0))}
+                                            </option>
+                                        }
+                                    })
+                                    .collect_view()
+                            }}
+
+                        </select>
+                    </div>
+                </div>
+                <div class="control">
+                    <div class="select is-small">
+                        <select on:change=move |ev| {
+                            alignment
+                                .set(
+                                    if event_target_value(&ev) == "distance" {
+                                        Alignment::Distance
+                                    } else {
+                                        Alignment::Time
+                                    },
+                                )
+                        }>
+                            <option value="time">By elapsed time</option>
+                            <option value="distance">By distance</option>
+                        </select>
+                    </div>
+                </div>
+                <div class="control">
+                    <div class="select is-small">
+                        <select on:change=move |ev| {
+                            metric
+                                .set(
+                                    match event_target_value(&ev).as_str() {
+                                        "pace" => Metric::Pace,
+                                        "power" => Metric::Power,
+                                        "altitude" => Metric::Altitude,
+                                        _ => Metric::Heartrate,
+                                    },
+                                )
+                        }>
+                            <option value="heartrate">Heartrate</option>
+                            <option value="pace">Pace</option>
+                            <option value="power">Power</option>
+                            <option value="altitude">Altitude</option>
+                        </select>
+                    </div>
+                </div>
+                <div class="control">
+                    <div class="select is-small">
+                        <select on:change=move |ev| per_lap.set(event_target_value(&ev) == "lap")>
+                            <option value="km">Per kilometre</option>
+                            <option value="lap">Per lap</option>
+                        </select>
+                    </div>
+                </div>
+            </div>
+            <Transition fallback=move || {
+                view! { <p>"Loading..."</p> }
+            }>
+                {move || {
+                    others
+                        .get()
+                        .map(|others| match others {
+                            Err(e) => {
+                                view! { <pre class="error">"Error: " {e.to_string()}</pre> }
+                                    .into_view()
+                            }
+                            Ok(others) if others.is_empty() => ().into_view(),
+                            Ok(others) => {
+                                let base = base.get_value();
+                                let splits = if per_lap.get() {
+                                    lap_times
+                                } else {
+                                    kilometre_times
+                                };
+                                let base_splits = splits(&base);
+                                let other_splits: Vec<Vec<f64>> = others
+                                    .iter()
+                                    .map(splits)
+                                    .collect();
+                                let rows = other_splits
+                                    .iter()
+                                    .map(Vec::len)
+                                    .fold(base_splits.len(), cmp::max);
+                                view! {
+                                    <div class="tags">
+                                        {others
+                                            .iter()
+                                            .map(|other| {
+                                                let id = other.id;
+                                                view! {
+                                                    <span class="tag is-info is-light">
+                                                        {activity_label(other)}
+                                                        <button
+                                                            class="delete is-small"
+                                                            on:click=move |_| {
+                                                                compared.update(|c| c.retain(|i| *i != id))
+                                                            }
+                                                        >
+                                                        </button>
+                                                    </span>
+                                                }
+                                            })
+                                            .collect_view()}
+                                    </div>
+                                    <table class="table is-striped is-hoverable is-fullwidth is-narrow">
+                                        <thead>
+                                            <tr>
+                                                <th>{if per_lap.get() { "Lap" } else { "Km" }}</th>
+                                                <th>{activity_label(&base)}</th>
+                                                {others
+                                                    .iter()
+                                                    .map(|other| view! { <th>{activity_label(other)}</th> })
+                                                    .collect_view()}
+                                            </tr>
+                                        </thead>
+                                        <tbody>
+                                            {(0..rows)
+                                                .map(|i| {
+                                                    let base_split = base_splits.get(i).copied();
+                                                    view! {
+                                                        <tr>
+                                                            <td>{i + 1}</td>
+                                                            <td>{base_split.map(format_pace)}</td>
+                                                            {other_splits
+                                                                .iter()
+                                                                .map(|splits| {
+                                                                    let split = splits.get(i).copied();
+                                                                    view! {
+                                                                        <td>
+                                                                            {split.map(format_pace)}
+                                                                            {split
+                                                                                .zip(base_split)
+                                                                                .map(|(split, base)| {
+                                                                                    format!(" ({})", format_delta(split - base))
+                                                                                })}
+
+                                                                        </td>
+                                                                    }
+                                                                })
+                                                                .collect_view()}
+                                                        </tr>
+                                                    }
+                                                })
+                                                .collect_view()}
+                                        </tbody>
+                                    </table>
+                                }
+                                    .into_view()
+                            }
+                        })
+                }}
+
+            </Transition>
+            <div node_ref=chart_node class:is-hidden=move || compared.with(Vec::is_empty)>
+                <div id="comparison_chart"></div>
+            </div>
+        </details>
+    }
+}
@@ -1,8 +1,24 @@
 use std::cmp;
 use std::time::Duration;
 
+use super::activity_compare::ActivityComparison;
+use super::activity_edit::ActivityEdit;
+use super::activity_export::ActivityDownloads;
+use super::activity_intervals::{record_intervals, IntervalTable};
+use super::activity_segments::{ActivitySegments, SegmentEffortSummary};
+use super::activity_splits::DistanceSplits;
+use super::feeling_label;
+use super::heartrate_recovery::HeartrateRecoveryTable;
+use super::swim_details::{SwimLength, SwimTables};
+#[cfg(feature = "ssr")]
+use super::{parse_optional, parse_tags};
+use crate::app::FitFileUploaded;
 #[cfg(feature = "ssr")]
 use crate::app::{auth, pool};
+#[cfg(feature = "ssr")]
+use crate::models::activity::update_activity_load;
+#[cfg(feature = "ssr")]
+use crate::models::workout_completion::update_completion_actuals;
 use crate::pages::user::get_preferences;
 use bigdecimal::{BigDecimal, ToPrimitive};
 use charming::{
@@ -15,6 +31,7 @@ use chrono::{DateTime, Local};
 use humantime::format_duration;
 use leptos::{html::Div, *};
 use leptos_leaflet::*;
+use leptos_router::ActionForm;
 use leptos_use::{use_element_size, UseElementSizeReturn};
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "ssr")]
@@ -46,16 +63,56 @@ pub struct Record {
     pub distance: Option<f64>,
     pub speed: Option<f64>,
     pub altitude: Option<f64>,
+    pub power: Option<i16>,
+}
+
+/// A session of a multisport activity, e.g. the bike leg of a triathlon.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::Type))]
+pub struct SessionSummary {
+    pub sport: String,
+    pub start_time: DateTime<Local>,
+    pub end_time: DateTime<Local>,
+    pub distance: Option<f64>,
+    pub average_heartrate: Option<i16>,
+    /// The session's share of the activity's load.
+    pub load: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::Type))]
+pub struct StrengthSet {
+    pub exercise: Option<String>,
+    pub repetitions: Option<i32>,
+    pub weight: Option<f64>,
+    pub rest: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActivityDetail {
     pub id: i64,
     pub start_time: DateTime<Local>,
+    pub end_time: DateTime<Local>,
     pub duration: BigDecimal,
     pub sport: String,
+    pub load: Option<i32>,
+    pub title: Option<String>,
+    pub notes: Option<String>,
+    pub rpe: Option<i16>,
+    pub feeling: Option<i16>,
+    pub tags: Vec<String>,
+    pub sessions: Option<Vec<SessionSummary>>,
     pub laps: Option<Vec<Lap>>,
-    pub records: Vec<Record>,
+    pub records: Option<Vec<Record>>,
+    pub sets: Option<Vec<StrengthSet>>,
+    /// Pool length in meters of pool swims.
+    pub pool_length: Option<f64>,
+    pub lengths: Option<Vec<SwimLength>>,
+    pub indoor: bool,
+    pub distance_calibration: f64,
+    /// Calibration learned for the footpod and sport of the activity.
+    pub footpod_calibration: Option<f64>,
+    pub efforts: Option<Vec<SegmentEffortSummary>>,
 }
 
 impl PartialEq for ActivityDetail {
@@ -78,8 +135,33 @@ pub async fn activity_details(id: i64) -> Result<ActivityDetail, ServerFnError>
         SELECT 
             activities.id, 
             activities.start_time, 
+            activities.end_time,
             activities.duration,
-            COALESCE(string_agg(sessions.sport,', '),'General') as "sport!",
+            COALESCE(string_agg(COALESCE(sports.name, sessions.sport),', '),'General') as "sport!",
+            activities.load,
+            activities.title,
+            activities.notes,
+            activities.rpe,
+            activities.feeling,
+            activities.tags,
+            (
+                SELECT
+                    ARRAY_AGG(
+                        (
+                            COALESCE(session_sports.name, s.sport, 'General'),
+                            s.start_time,
+                            s.end_time,
+                            s.distance::float8,
+                            s.average_heartrate,
+                            session_loads.load
+                        )
+                        ORDER BY s.start_time ASC
+                    )
+                FROM sessions s
+                INNER JOIN session_loads ON session_loads.session_id = s.id
+                LEFT JOIN sports session_sports ON session_sports.id = s.sport_id
+                WHERE s.activity_id = $2::bigint
+            ) as "sessions:Vec<SessionSummary>",
             (
                 SELECT
                     ARRAY_AGG(
@@ -92,12 +174,13 @@ pub async fn activity_details(id: i64) -> Result<ActivityDetail, ServerFnError>
                             laps.average_heartrate, 
                             laps.min_heartrate, 
                             laps.max_heartrate, 
-                            laps.sport,
+                            COALESCE(lap_sports.name, laps.sport),
                             laps.ascent, 
                             laps.descent
                         )
                     )
                 FROM laps
+                LEFT JOIN sports lap_sports ON lap_sports.id = laps.sport_id
                 WHERE laps.activity_id = $2::bigint
             ) as "laps:Vec<Lap>",
             (
@@ -110,15 +193,91 @@ pub async fn activity_details(id: i64) -> Result<ActivityDetail, ServerFnError>
                             records.longitude,
                             records.distance::float8,
                             records.speed::float8,
-                            records.altitude::float8
+                            records.altitude::float8,
+                            records.power
                         )
                         ORDER BY records.timestamp ASC
                     ) 
                 FROM records
                 WHERE records.activity_id = $2::bigint
-            ) as "records!:Vec<Record>"
+            ) as "records:Vec<Record>",
+            (
+                SELECT
+                    ARRAY_AGG(
+                        (
+                            exercises.name,
+                            strength_sets.repetitions,
+                            strength_sets.weight,
+                            strength_sets.rest
+                        )
+                        ORDER BY strength_sets.position ASC
+                    )
+                FROM strength_sets
+                LEFT JOIN exercises ON exercises.id = strength_sets.exercise_id
+                WHERE strength_sets.activity_id = $2::bigint
+            ) as "sets:Vec<StrengthSet>",
+            MAX(sessions.pool_length) as pool_length,
+            (
+                SELECT
+                    ARRAY_AGG(
+                        (
+                            swim_lengths.start_time,
+                            swim_lengths.duration,
+                            swim_lengths.active,
+                            swim_lengths.stroke,
+                            swim_lengths.strokes
+                        )
+                        ORDER BY swim_lengths.position ASC
+                    )
+                FROM swim_lengths
+                WHERE swim_lengths.activity_id = $2::bigint
+            ) as "lengths:Vec<SwimLength>",
+            activities.indoor,
+            activities.distance_calibration,
+            (
+                SELECT percentile_cont(0.5) WITHIN GROUP (ORDER BY calibrated.gps_distance_ratio)
+                FROM activities calibrated
+                WHERE calibrated.user_id = activities.user_id
+                    AND calibrated.footpod = activities.footpod
+                    AND calibrated.gps_distance_ratio IS NOT NULL
+                    AND EXISTS (
+                        SELECT 1
+                        FROM sessions calibrated_sessions
+                        JOIN sessions own_sessions ON own_sessions.sport = calibrated_sessions.sport
+                        WHERE calibrated_sessions.activity_id = calibrated.id
+                            AND own_sessions.activity_id = activities.id
+                    )
+            ) as footpod_calibration,
+            (
+                SELECT
+                    ARRAY_AGG(
+                        (
+                            ranked.segment_id,
+                            ranked.name,
+                            ranked.duration,
+                            ranked.average_heartrate,
+                            ranked.average_power,
+                            ranked.rank
+                        )
+                        ORDER BY ranked.start_time ASC
+                    )
+                FROM (
+                    SELECT
+                        segment_efforts.*,
+                        segments.name,
+                        RANK() OVER (
+                            PARTITION BY segment_efforts.segment_id
+                            ORDER BY segment_efforts.duration
+                        ) as rank
+                    FROM segment_efforts
+                    INNER JOIN segments ON segments.id = segment_efforts.segment_id
+                    WHERE segments.user_id = $1::bigint
+                ) ranked
+                WHERE ranked.activity_id = $2::bigint
+            ) as "efforts:Vec<SegmentEffortSummary>"
         FROM activities 
         JOIN sessions on sessions.activity_id=activities.id
+        LEFT JOIN sports on sports.id=sessions.sport_id
         WHERE activities.user_id = $1::bigint AND activities.id = $2::bigint
         GROUP BY activities.id
         "#,
@@ -130,17 +289,252 @@ pub async fn activity_details(id: i64) -> Result<ActivityDetail, ServerFnError>
     Ok(activity_detail)
 }
 
+/// Saves the user's notes on an activity and recalculates its load, which may depend on the RPE.
+#[server]
+pub async fn update_activity_notes(
+    activity_id: i64,
+    title: String,
+    notes: String,
+    rpe: String,
+    feeling: String,
+    tags: String,
+) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let rpe = parse_optional::<i16>(&rpe, "perceived exertion")?;
+    if rpe.is_some_and(|rpe| !(0..=10).contains(&rpe)) {
+        return Err(ServerFnError::new(
+            "Perceived exertion goes from 0 to 10".to_string(),
+        ));
+    }
+    let feeling = parse_optional::<i16>(&feeling, "feeling")?;
+    if feeling.is_some_and(|feeling| !(1..=5).contains(&feeling)) {
+        return Err(ServerFnError::new("Feeling goes from 1 to 5".to_string()));
+    }
+    let title = Some(title.trim().to_string()).filter(|t| !t.is_empty());
+    let notes = Some(notes.trim().to_string()).filter(|n| !n.is_empty());
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    let updated = sqlx::query!(
+        r#"
+        UPDATE activities
+        SET title=$3, notes=$4, rpe=$5, feeling=$6, tags=$7
+        WHERE id=$1 and user_id=$2::bigint
+        "#,
+        activity_id,
+        user.id,
+        title,
+        notes,
+        rpe,
+        feeling,
+        &parse_tags(&tags)[..]
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't update activity: {}", e)))?;
+    if updated.rows_affected() == 0 {
+        return Err(ServerFnError::new("Activity not found".to_string()));
+    }
+    update_activity_load(activity_id, user.id, &mut transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't calculate load: {}", e)))?;
+    update_completion_actuals(user.id, Some(activity_id), &mut transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't update workout completion: {}", e)))?;
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't update activity: {}", e)))?;
+    Ok(())
+}
+
 #[component]
-pub fn ActivityDetails(activity: RwSignal<Option<i64>>) -> impl IntoView {
-    let close = move |_| activity.set(None);
-    let detail = create_resource(activity, |id| async move {
-        if let Some(id) = id {
-            activity_details(id).await.ok()
-        } else {
-            None
+pub fn ActivityNotes(detail: ActivityDetail) -> impl IntoView {
+    let uploaded = use_context::<FitFileUploaded>().unwrap();
+    let update_action = create_server_action::<UpdateActivityNotes>();
+    create_effect(move |_| {
+        if let Some(Ok(_)) = update_action.value().get() {
+            uploaded.0.update(|v| *v += 1);
         }
     });
+    let rpe = detail.rpe;
+    let feeling = detail.feeling;
+    view! {
+        <ActionForm action=update_action>
+            <input type="hidden" name="activity_id" value=detail.id/>
+            <div class="field is-grouped">
+                <div class="control is-expanded">
+                    <label class="label is-small" for="title">
+                        Title
+                    </label>
+                    <input
+                        class="input is-small"
+                        id="title"
+                        name="title"
+                        type="text"
+                        placeholder=detail.sport
+                        value=detail.title
+                    />
+                </div>
+                <div class="control">
+                    <label class="label is-small" for="rpe">
+                        RPE
+                    </label>
+                    <div class="select is-small">
+                        <select id="rpe" name="rpe">
+                            <option value="" selected=rpe.is_none()>
+                                "-"
+                            </option>
+                            {(0..=10)
+                                .map(|r| {
+                                    view! {
+                                        <option value=r selected=rpe == Some(r)>
+                                            {r}
+                                        </option>
+                                    }
+                                })
+                                .collect_view()}
+                        </select>
+                    </div>
+                </div>
+                <div class="control">
+                    <label class="label is-small" for="feeling">
+                        Feeling
+                    </label>
+                    <div class="select is-small">
+                        <select id="feeling" name="feeling">
+                            <option value="" selected=feeling.is_none()>
+                                "-"
+                            </option>
+                            {(1..=5)
+                                .map(|f| {
+                                    view! {
+                                        <option value=f selected=feeling == Some(f)>
+                                            {feeling_label(f)}
+                                        </option>
+                                    }
+                                })
+                                .collect_view()}
+                        </select>
+                    </div>
+                </div>
+            </div>
+            <div class="field">
+                <label class="label is-small" for="tags">
+                    Tags
+                </label>
+                <input
+                    class="input is-small"
+                    id="tags"
+                    name="tags"
+                    type="text"
+                    placeholder="race, long run"
+                    value=detail.tags.join(", ")
+                />
+            </div>
+            <div class="field">
+                <label class="label is-small" for="notes">
+                    Notes
+                </label>
+                <textarea class="textarea is-small" id="notes" name="notes" rows="3">
+                    {detail.notes}
+                </textarea>
+            </div>
+            <div class="field is-grouped">
+                <div class="control">
+                    <button type="submit" class="button is-small is-success">
+                        Save
+                    </button>
+                </div>
+                <div class="control">
+                    <span class="is-size-7">
+                        "Load: " {detail.load.map(|l| l.to_string()).unwrap_or("-".to_string())}
+                    </span>
+                </div>
+            </div>
+            {move || {
+                update_action
+                    .value()
+                    .get()
+                    .and_then(|r| r.err())
+                    .map(|e| view! { <pre class="error">"Error: " {e.to_string()}</pre> })
+            }}
+
+        </ActionForm>
+    }
+}
+
+/// Per session breakdown of multisport activities, including transitions.
+#[component]
+pub fn SessionBreakdown(sessions: Vec<SessionSummary>) -> impl IntoView {
+    view! {
+        <div class="columns">
+            <div class="column is-fullwidth">
+                <table class="table is-striped is-hoverable is-fullwidth">
+                    <thead>
+                        <tr>
+                            <th>Sport</th>
+                            <th>Time</th>
+                            <th>Distance</th>
+                            <th>Avg. Heartrate</th>
+                            <th>Load</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {sessions
+                            .into_iter()
+                            .map(|session| {
+                                view! {
+                                    <tr>
+                                        <td>{session.sport}</td>
+                                        <td>
+                                            {(session.end_time - session.start_time)
+                                                .to_std()
+                                                .map(|d| {
+                                                    format_duration(Duration::new(d.as_secs(), 0)).to_string()
+                                                })
+                                                .unwrap_or_default()}
+                                        </td>
+                                        <td>
+                                            {session
+                                                .distance
+                                                .filter(|d| *d > 0.0)
+                                                .map(|d| format!("{:.2} km", d / 1000.0))}
+                                        </td>
+                                        <td>{session.average_heartrate}</td>
+                                        <td>{session.load.map(|l| l.round() as i32)}</td>
+                                    </tr>
+                                }
+                            })
+                            .collect_view()}
+                    </tbody>
+                </table>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+pub fn ActivityDetails(activity: RwSignal<Option<i64>>) -> impl IntoView {
+    let close = move |_| activity.set(None);
+    let uploaded = use_context::<FitFileUploaded>().unwrap();
+    let detail = create_resource(
+        move || (activity.get(), uploaded.0.get()),
+        |(id, _)| async move {
+            if let Some(id) = id {
+                activity_details(id).await.ok()
+            } else {
+                None
+            }
+        },
+    );
     let user_prefs = create_resource(move || (), |_| async move { get_preferences().await });
+    let show_intervals = create_rw_signal(false);
     let heartrate_chart_node = create_node_ref::<Div>();
     let UseElementSizeReturn { width, height: _ } = use_element_size(heartrate_chart_node);
     let _chart = create_local_resource(
@@ -161,6 +555,7 @@ pub fn ActivityDetails(activity: RwSignal<Option<i64>>) -> impl IntoView {
             if let Some(Some(detail)) = detail {
                 let (timestamps, heartrates) = detail
                     .records
+                    .unwrap_or_default()
                     .iter()
                     .filter_map(|r| {
                         r.heartrate.map(|h| {
@@ -248,7 +643,9 @@ pub fn ActivityDetails(activity: RwSignal<Option<i64>>) -> impl IntoView {
                                         <div class="modal-card is-full">
                                             <div class="modal-card-head">
                                                 <div class="modal-card-title">
-                                                    <p class="title is-4">{detail.sport}</p>
+                                                    <p class="title is-4">
+                                                        {detail.title.clone().unwrap_or(detail.sport.clone())}
+                                                    </p>
                                                     <p class="subtitle is-6">
                                                         {detail.start_time.format("%Y-%m-%d").to_string()} ,
                                                         {format_duration(
@@ -264,6 +661,15 @@ pub fn ActivityDetails(activity: RwSignal<Option<i64>>) -> impl IntoView {
                                                 ></button>
                                             </div>
                                             <div class="modal-card-body">
+                                                <div class="columns">
+                                                    <div class="column">
+                                                        <ActivityNotes detail=detail.clone()/>
+                                                        <ActivityEdit detail=detail.clone()/>
+                                                        <ActivitySegments detail=detail.clone()/>
+                                                        <ActivityComparison detail=detail.clone()/>
+                                                        <ActivityDownloads activity_id=detail.id/>
+                                                    </div>
+                                                </div>
                                                 <div class="columns">
                                                     <div class="column" node_ref=heartrate_chart_node>
                                                         <div id="heartrate_chart"></div>
@@ -273,13 +679,16 @@ pub fn ActivityDetails(activity: RwSignal<Option<i64>>) -> impl IntoView {
                                                     {
                                                         let coordinates: Option<Vec<(f64, f64)>> = detail
                                                             .records
-                                                            .into_iter()
+                                                            .iter()
+                                                            .flatten()
                                                             .filter_map(|r| {
                                                                 r.latitude.map(|lat| r.longitude.map(|long| (lat, long)))
                                                             })
                                                             .collect();
                                                         match coordinates {
-                                                            Some(coordinates) if !coordinates.is_empty() => {
+                                                            Some(
+                                                                coordinates,
+                                                            ) if !coordinates.is_empty() && !detail.indoor => {
                                                                 let num_coords = coordinates.len();
                                                                 let center = coordinates
                                                                     .clone()
@@ -313,7 +722,75 @@ pub fn ActivityDetails(activity: RwSignal<Option<i64>>) -> impl IntoView {
                                                     }
 
                                                 </div>
-                                                <div class="columns">
+                                                {detail
+                                                    .sessions
+                                                    .filter(|sessions| sessions.len() > 1)
+                                                    .map(|sessions| view! { <SessionBreakdown sessions/> })}
+                                                {detail
+                                                    .lengths
+                                                    .zip(detail.pool_length)
+                                                    .map(|(lengths, pool_length)| {
+                                                        view! { <SwimTables lengths pool_length/> }
+                                                    })}
+
+                                                {detail
+                                                    .records
+                                                    .clone()
+                                                    .map(|records| {
+                                                        let aerobic_threshold = Signal::derive(move || {
+                                                            user_prefs
+                                                                .get()
+                                                                .and_then(|p| p.ok())
+                                                                .map_or(155, |p| p.aerobic_threshold)
+                                                        });
+                                                        view! {
+                                                            <HeartrateRecoveryTable records aerobic_threshold/>
+                                                        }
+                                                    })}
+
+                                                {
+                                                    let intervals = detail
+                                                        .records
+                                                        .as_deref()
+                                                        .map(record_intervals)
+                                                        .unwrap_or_default();
+                                                    (!intervals.is_empty())
+                                                        .then(|| {
+                                                            view! {
+                                                                <div class="buttons has-addons">
+                                                                    <button
+                                                                        class="button is-small"
+                                                                        class:is-selected=move || !show_intervals.get()
+                                                                        class:is-info=move || !show_intervals.get()
+                                                                        on:click=move |_| show_intervals.set(false)
+                                                                    >
+                                                                        Device laps
+                                                                    </button>
+                                                                    <button
+                                                                        class="button is-small"
+                                                                        class:is-selected=move || show_intervals.get()
+                                                                        class:is-info=move || show_intervals.get()
+                                                                        on:click=move |_| show_intervals.set(true)
+                                                                    >
+                                                                        Detected intervals
+                                                                    </button>
+                                                                </div>
+                                                                <div
+                                                                    class="columns"
+                                                                    class:is-hidden=move || !show_intervals.get()
+                                                                >
+                                                                    <div class="column is-fullwidth">
+                                                                        <IntervalTable intervals/>
+                                                                    </div>
+                                                                </div>
+                                                            }
+                                                        })
+                                                }
+
+                                                <div
+                                                    class="columns"
+                                                    class:is-hidden=move || show_intervals.get()
+                                                >
                                                     <div class="column is-fullwidth">
                                                         <table class="table is-striped is-hoverable is-fullwidth">
                                                             <thead>
@@ -379,6 +856,71 @@ pub fn ActivityDetails(activity: RwSignal<Option<i64>>) -> impl IntoView {
                                                         </table>
                                                     </div>
                                                 </div>
+                                                {detail
+                                                    .records
+                                                    .filter(|records| {
+                                                        detail.pool_length.is_none()
+                                                            && records
+                                                                .iter()
+                                                                .any(|r| r.distance.is_some_and(|d| d > 0.0))
+                                                    })
+                                                    .map(|records| {
+                                                        view! {
+                                                            <div class="columns">
+                                                                <div class="column is-fullwidth">
+                                                                    <DistanceSplits records/>
+                                                                </div>
+                                                            </div>
+                                                        }
+                                                    })}
+
+                                                {detail
+                                                    .sets
+                                                    .map(|sets| {
+                                                        view! {
+                                                            <div class="columns">
+                                                                <div class="column is-fullwidth">
+                                                                    <table class="table is-striped is-hoverable is-fullwidth">
+                                                                        <thead>
+                                                                            <tr>
+                                                                                <th>Set</th>
+                                                                                <th>Exercise</th>
+                                                                                <th>Reps</th>
+                                                                                <th>Weight</th>
+                                                                                <th>Rest</th>
+                                                                            </tr>
+                                                                        </thead>
+                                                                        <tbody>
+                                                                            {sets
+                                                                                .into_iter()
+                                                                                .enumerate()
+                                                                                .map(|(i, set)| {
+                                                                                    view! {
+                                                                                        <tr>
+                                                                                            <td>{i + 1}</td>
+                                                                                            <td>{set.exercise}</td>
+                                                                                            <td>{set.repetitions}</td>
+                                                                                            <td>{set.weight.map(|w| format!("{} kg", w))}</td>
+                                                                                            <td>
+                                                                                                {set
+                                                                                                    .rest
+                                                                                                    .map(|r| {
+                                                                                                        format_duration(Duration::new(r.round() as u64, 0))
+                                                                                                            .to_string()
+                                                                                                    })}
+
+                                                                                            </td>
+                                                                                        </tr>
+                                                                                    }
+                                                                                })
+                                                                                .collect_view()}
+                                                                        </tbody>
+                                                                    </table>
+                                                                </div>
+                                                            </div>
+                                                        }
+                                                    })}
+
                                             </div>
                                         </div>
                                     }
@@ -0,0 +1,368 @@
+use super::activity_details::ActivityDetail;
+use crate::app::FitFileUploaded;
+#[cfg(feature = "ssr")]
+use crate::app::{auth, pool};
+#[cfg(feature = "ssr")]
+use crate::models::{
+    activity_edit::{
+        correct_elevation, merge_activities, set_indoor, split_activity, trim_activity,
+    },
+    calibration::calibrate_distance,
+    workout_completion::match_new_activity_to_workout,
+};
+#[cfg(feature = "ssr")]
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use leptos::*;
+use leptos_router::ActionForm;
+
+/// Format of `datetime-local` inputs.
+const INPUT_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+#[cfg(feature = "ssr")]
+fn parse_input_time(value: &str, field: &str) -> Result<DateTime<Local>, ServerFnError> {
+    NaiveDateTime::parse_from_str(value, INPUT_TIME_FORMAT)
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M"))
+        .ok()
+        .and_then(|t| Local.from_local_datetime(&t).earliest())
+        .ok_or(ServerFnError::new(format!("Couldn't parse {}", field)))
+}
+
+/// Cuts off the part of an activity before `start` and after `end`.
+#[server]
+pub async fn trim_activity_range(
+    activity_id: i64,
+    start: String,
+    end: String,
+) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let start = parse_input_time(&start, "start")?;
+    let end = parse_input_time(&end, "end")?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    trim_activity(activity_id, user.id, start, end, &mut transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't trim activity: {}", e)))?;
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't trim activity: {}", e)))?;
+    Ok(())
+}
+
+/// Splits an activity in two at `at`.
+#[server]
+pub async fn split_activity_at(activity_id: i64, at: String) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let at = parse_input_time(&at, "split time")?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    let new_id = split_activity(activity_id, user.id, at, &mut transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't split activity: {}", e)))?;
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't split activity: {}", e)))?;
+    match_new_activity_to_workout(new_id, user.id, &pool).await;
+    Ok(())
+}
+
+/// Merges the activity that follows `activity_id` into it.
+#[server]
+pub async fn merge_with_next_activity(activity_id: i64) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    let next_id = sqlx::query_scalar!(
+        r#"
+        SELECT next.id
+        FROM activities
+        INNER JOIN activities next ON next.user_id = activities.user_id
+            AND next.start_time > activities.start_time
+        WHERE activities.id=$1 and activities.user_id=$2::bigint
+        ORDER BY next.start_time
+        LIMIT 1
+        "#,
+        activity_id,
+        user.id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load activities: {}", e)))?
+    .ok_or(ServerFnError::new(
+        "There is no later activity to merge".to_string(),
+    ))?;
+    merge_activities(activity_id, next_id, user.id, &mut transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't merge activities: {}", e)))?;
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't merge activities: {}", e)))?;
+    Ok(())
+}
+
+/// Marks an activity as indoor and calibrates its recorded distance.
+#[server]
+pub async fn update_indoor_calibration(
+    activity_id: i64,
+    indoor: Option<String>,
+    calibration: f64,
+) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let indoor = indoor.is_some();
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    let current = sqlx::query!(
+        r#"
+        SELECT indoor, distance_calibration
+        FROM activities
+        WHERE id=$1 and user_id=$2::bigint
+        "#,
+        activity_id,
+        user.id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load activity: {}", e)))?
+    .ok_or(ServerFnError::new("Activity not found".to_string()))?;
+    if (calibration - current.distance_calibration).abs() > f64::EPSILON {
+        calibrate_distance(activity_id, user.id, calibration, &mut transaction)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Couldn't calibrate distance: {}", e)))?;
+    }
+    if indoor != current.indoor {
+        set_indoor(activity_id, user.id, indoor, &mut transaction)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Couldn't update activity: {}", e)))?;
+    }
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't update activity: {}", e)))?;
+    Ok(())
+}
+
+/// Takes the altitude of a GPS activity from the elevation model and recalculates its ascent and
+/// slope speeds.
+#[server]
+pub async fn correct_activity_elevation(activity_id: i64) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    let corrected = correct_elevation(activity_id, user.id, &mut transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't correct elevation: {}", e)))?;
+    if !corrected {
+        return Err(ServerFnError::new(
+            "The elevation model doesn't cover this activity".to_string(),
+        ));
+    }
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't correct elevation: {}", e)))?;
+    Ok(())
+}
+
+/// Trim, split and merge controls for fixing a bad recording.
+#[component]
+pub fn ActivityEdit(detail: ActivityDetail) -> impl IntoView {
+    let uploaded = use_context::<FitFileUploaded>().unwrap();
+    let trim_action = create_server_action::<TrimActivityRange>();
+    let split_action = create_server_action::<SplitActivityAt>();
+    let merge_action = create_server_action::<MergeWithNextActivity>();
+    let indoor_action = create_server_action::<UpdateIndoorCalibration>();
+    let elevation_action = create_server_action::<CorrectActivityElevation>();
+    create_effect(move |_| {
+        let trimmed = matches!(trim_action.value().get(), Some(Ok(_)));
+        let split = matches!(split_action.value().get(), Some(Ok(_)));
+        let merged = matches!(merge_action.value().get(), Some(Ok(_)));
+        let calibrated = matches!(indoor_action.value().get(), Some(Ok(_)));
+        let corrected = matches!(elevation_action.value().get(), Some(Ok(_)));
+        if trimmed || split || merged || calibrated || corrected {
+            uploaded.0.update(|v| *v += 1);
+        }
+    });
+    let start = detail.start_time.format(INPUT_TIME_FORMAT).to_string();
+    let end = detail.end_time.format(INPUT_TIME_FORMAT).to_string();
+    let split_at = start.clone();
+    let footpod_calibration = detail
+        .footpod_calibration
+        .map(|c| format!("Learned for this footpod from GPS runs: {:.3}", c));
+    let error = move || {
+        trim_action
+            .value()
+            .get()
+            .and_then(|r| r.err())
+            .or(split_action.value().get().and_then(|r| r.err()))
+            .or(merge_action.value().get().and_then(|r| r.err()))
+            .or(indoor_action.value().get().and_then(|r| r.err()))
+            .or(elevation_action.value().get().and_then(|r| r.err()))
+            .map(|e| view! { <pre class="error">"Error: " {e.to_string()}</pre> })
+    };
+    view! {
+        <details>
+            <summary class="is-size-7">Fix recording</summary>
+            <ActionForm action=trim_action>
+                <input type="hidden" name="activity_id" value=detail.id/>
+                <div class="field is-grouped is-grouped-multiline">
+                    <div class="control">
+                        <label class="label is-small" for="trim_start">
+                            Start
+                        </label>
+                        <input
+                            class="input is-small"
+                            id="trim_start"
+                            name="start"
+                            type="datetime-local"
+                            step="1"
+                            value=start
+                        />
+                    </div>
+                    <div class="control">
+                        <label class="label is-small" for="trim_end">
+                            End
+                        </label>
+                        <input
+                            class="input is-small"
+                            id="trim_end"
+                            name="end"
+                            type="datetime-local"
+                            step="1"
+                            value=end
+                        />
+                    </div>
+                    <div class="control">
+                        <label class="label is-small">"\u{a0}"</label>
+                        <button type="submit" class="button is-small">
+                            <i class="material-symbols-rounded">content_cut</i>
+                            Trim
+                        </button>
+                    </div>
+                </div>
+            </ActionForm>
+            <ActionForm action=split_action>
+                <input type="hidden" name="activity_id" value=detail.id/>
+                <div class="field is-grouped">
+                    <div class="control">
+                        <input
+                            class="input is-small"
+                            name="at"
+                            type="datetime-local"
+                            step="1"
+                            value=split_at
+                        />
+                    </div>
+                    <div class="control">
+                        <button type="submit" class="button is-small">
+                            <i class="material-symbols-rounded">call_split</i>
+                            Split here
+                        </button>
+                    </div>
+                    <div class="control">
+                        <button
+                            type="button"
+                            class="button is-small"
+                            on:click=move |_| {
+                                merge_action
+                                    .dispatch(MergeWithNextActivity {
+                                        activity_id: detail.id,
+                                    });
+                            }
+                        >
+
+                            <i class="material-symbols-rounded">merge</i>
+                            Merge with next activity
+                        </button>
+                    </div>
+                </div>
+            </ActionForm>
+            <ActionForm action=indoor_action>
+                <input type="hidden" name="activity_id" value=detail.id/>
+                <div class="field is-grouped is-grouped-multiline">
+                    <div class="control">
+                        <label class="checkbox is-size-7">
+                            <input type="checkbox" name="indoor" checked=detail.indoor/>
+                            " Indoor"
+                        </label>
+                    </div>
+                    <div class="control">
+                        <input
+                            class="input is-small"
+                            name="calibration"
+                            type="number"
+                            min="0.75"
+                            max="1.25"
+                            step="0.001"
+                            title="Distance calibration"
+                            value=detail.distance_calibration
+                        />
+                    </div>
+                    <div class="control">
+                        <button type="submit" class="button is-small">
+                            <i class="material-symbols-rounded">straighten</i>
+                            Calibrate
+                        </button>
+                    </div>
+                </div>
+                <p class="help">{footpod_calibration}</p>
+            </ActionForm>
+            {(!detail.indoor)
+                .then(|| {
+                    view! {
+                        <div class="field">
+                            <div class="control">
+                                <button
+                                    type="button"
+                                    class="button is-small"
+                                    on:click=move |_| {
+                                        elevation_action
+                                            .dispatch(CorrectActivityElevation {
+                                                activity_id: detail.id,
+                                            });
+                                    }
+                                >
+
+                                    <i class="material-symbols-rounded">landscape</i>
+                                    Correct elevation
+                                </button>
+                            </div>
+                        </div>
+                    }
+                })}
+
+            {error}
+        </details>
+    }
+}
@@ -0,0 +1,83 @@
+#[cfg(feature = "ssr")]
+use crate::authentication::User;
+#[cfg(feature = "ssr")]
+use crate::models::export::load_activity_export;
+use crate::models::export::ExportFormat;
+#[cfg(feature = "ssr")]
+use crate::state::AppState;
+#[cfg(feature = "ssr")]
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+#[cfg(feature = "ssr")]
+use axum_session_auth::{AuthSession, SessionPgPool};
+use leptos::*;
+#[cfg(feature = "ssr")]
+use sqlx::PgPool;
+
+/// Download of an activity regenerated from the stored data, as `fit`, `gpx`, `tcx` or `csv`.
+#[cfg(feature = "ssr")]
+pub async fn export_activity(
+    State(state): State<AppState>,
+    auth: AuthSession<User, i64, SessionPgPool, PgPool>,
+    Path((activity_id, format)): Path<(i64, String)>,
+) -> Response {
+    let user = if let Some(user) = auth.current_user {
+        user
+    } else {
+        return (StatusCode::FORBIDDEN, "Not logged in".to_string()).into_response();
+    };
+    let Some(format) = ExportFormat::from_extension(&format) else {
+        return (StatusCode::NOT_FOUND, format!("Unknown format {}", format)).into_response();
+    };
+    let mut connection = match state.pool.acquire().await {
+        Ok(connection) => connection,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    let export = match load_activity_export(activity_id, user.id, &mut connection).await {
+        Ok(export) => export,
+        Err(e) => return (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+    };
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"activity_{}.{}\"",
+                    activity_id,
+                    format.extension()
+                ),
+            ),
+        ],
+        export.export(format),
+    )
+        .into_response()
+}
+
+/// Links to download an activity in each export format.
+#[component]
+pub fn ActivityDownloads(activity_id: i64) -> impl IntoView {
+    view! {
+        <div class="buttons are-small">
+            {ExportFormat::ALL
+                .into_iter()
+                .map(|format| {
+                    let href = format!(
+                        "/api/export_activity/{}/{}",
+                        activity_id,
+                        format.extension(),
+                    );
+                    view! {
+                        <a class="button" href=href download>
+                            <i class="material-symbols-rounded">download</i>
+                            {format.extension().to_uppercase()}
+                        </a>
+                    }
+                })
+                .collect_view()}
+        </div>
+    }
+}
@@ -0,0 +1,394 @@
+#[cfg(feature = "ssr")]
+use crate::app::{auth, pool};
+use crate::pages::workout_schedule::manage_sports_dialog::{indented_name, list_sports};
+use chrono::NaiveDate;
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use super::FEELINGS;
+
+/// Column the activity list is sorted by.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Default,
+    strum::EnumString,
+    strum::Display,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum ActivitySort {
+    #[default]
+    Date,
+    Duration,
+    Distance,
+    Load,
+}
+
+/// Filters and sorting of the activity list.
+///
+/// Durations are in minutes and distances in kilometers, empty filters match everything.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ActivityFilter {
+    /// Matches title, notes and tags.
+    pub search: String,
+    pub tag: String,
+    pub feeling: Option<i16>,
+    /// Matches the sport and all its sub sports.
+    pub sport: Option<i64>,
+    pub device: String,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub min_duration: Option<u32>,
+    pub max_duration: Option<u32>,
+    pub min_distance: Option<f64>,
+    pub max_distance: Option<f64>,
+    pub min_load: Option<i32>,
+    pub max_load: Option<i32>,
+    pub sort: ActivitySort,
+    pub descending: bool,
+}
+
+impl Default for ActivityFilter {
+    fn default() -> Self {
+        Self {
+            search: String::new(),
+            tag: String::new(),
+            feeling: None,
+            sport: None,
+            device: String::new(),
+            from: None,
+            to: None,
+            min_duration: None,
+            max_duration: None,
+            min_distance: None,
+            max_distance: None,
+            min_load: None,
+            max_load: None,
+            sort: ActivitySort::Date,
+            descending: true,
+        }
+    }
+}
+
+impl ActivityFilter {
+    /// Sorts by a column, toggling the direction if the list is already sorted by it.
+    pub fn sort_by(&mut self, sort: ActivitySort) {
+        if self.sort == sort {
+            self.descending = !self.descending;
+        } else {
+            self.sort = sort;
+            self.descending = true;
+        }
+    }
+}
+
+/// Tags and devices of the user's activities, for the filter selects.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct FilterOptions {
+    pub tags: Vec<String>,
+    pub devices: Vec<String>,
+}
+
+#[server]
+pub async fn get_filter_options() -> Result<FilterOptions, ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let tags = sqlx::query_scalar!(
+        r#"
+        SELECT DISTINCT unnest(tags) as "tag!"
+        FROM activities
+        WHERE user_id=$1::bigint
+        ORDER BY 1
+        "#,
+        user.id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load tags: {}", e)))?;
+    let devices = sqlx::query_scalar!(
+        r#"
+        SELECT DISTINCT device as "device!"
+        FROM activities
+        WHERE user_id=$1::bigint and device IS NOT NULL
+        ORDER BY 1
+        "#,
+        user.id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load devices: {}", e)))?;
+    Ok(FilterOptions { tags, devices })
+}
+
+/// Header of a sortable column of the activity list.
+#[component]
+pub fn SortHeader(
+    filter: RwSignal<ActivityFilter>,
+    sort: ActivitySort,
+    label: &'static str,
+) -> impl IntoView {
+    view! {
+        <a href="#!" class="has-text-dark" on:click=move |_| filter.update(|f| f.sort_by(sort))>
+            <h6 class="title is-6">
+                {label}
+                {move || {
+                    filter
+                        .with(|f| {
+                            (f.sort == sort).then_some(if f.descending { " ▾" } else { " ▴" })
+                        })
+                }}
+
+            </h6>
+        </a>
+    }
+}
+
+#[component]
+pub fn ActivityFilterBar(
+    filter: RwSignal<ActivityFilter>,
+    #[prop(into)] reload: Signal<i32>,
+) -> impl IntoView {
+    let options = create_resource(move || reload.get(), |_| get_filter_options());
+    let sports = create_resource(|| (), |_| list_sports());
+    let show_more = create_rw_signal(false);
+    view! {
+        <div class="box">
+            <div class="field is-grouped is-grouped-multiline">
+                <div class="control is-expanded">
+                    <input
+                        class="input"
+                        type="search"
+                        placeholder="Search title, notes and tags"
+                        on:change=move |ev| filter.update(|f| f.search = event_target_value(&ev))
+                    />
+                </div>
+                <div class="control">
+                    <div class="select">
+                        <select on:change=move |ev| {
+                            filter.update(|f| f.sport = event_target_value(&ev).parse().ok())
+                        }>
+                            <option value="">All sports</option>
+                            {move || {
+                                sports
+                                    .get()
+                                    .and_then(|s| s.ok())
+                                    .unwrap_or_default()
+                                    .into_iter()
+                                    .map(|s| {
+                                        view! { <option value=s.id>{indented_name(&s)}</option> }
+                                    })
+                                    .collect_view()
+                            }}
+
+                        </select>
+                    </div>
+                </div>
+                <div class="control">
+                    <div class="select">
+                        <select on:change=move |ev| {
+                            filter.update(|f| f.tag = event_target_value(&ev))
+                        }>
+                            <option value="">All tags</option>
+                            {move || {
+                                options
+                                    .get()
+                                    .and_then(|o| o.ok())
+                                    .unwrap_or_default()
+                                    .tags
+                                    .into_iter()
+                                    .map(|t| view! { <option value=t.clone()>{t}</option> })
+                                    .collect_view()
+                            }}
+
+                        </select>
+                    </div>
+                </div>
+                <div class="control">
+                    <div class="select">
+                        <select on:change=move |ev| {
+                            filter.update(|f| f.feeling = event_target_value(&ev).parse().ok())
+                        }>
+                            <option value="">Any feeling</option>
+                            {FEELINGS
+                                .iter()
+                                .enumerate()
+                                .map(|(i, f)| view! { <option value=i + 1>{*f}</option> })
+                                .collect_view()}
+                        </select>
+                    </div>
+                </div>
+                <div class="control">
+                    <button class="button" on:click=move |_| show_more.update(|s| *s = !*s)>
+                        <i class="material-symbols-rounded">filter_list</i>
+                        More Filters
+                    </button>
+                </div>
+            </div>
+            <Show when=move || show_more.get() fallback=|| {}>
+                <div class="field is-grouped is-grouped-multiline">
+                    <div class="control">
+                        <label class="label is-small">From</label>
+                        <input
+                            class="input is-small"
+                            type="date"
+                            on:change=move |ev| {
+                                filter
+                                    .update(|f| {
+                                        f
+                                            .from = NaiveDate::parse_from_str(
+                                                &event_target_value(&ev),
+                                                "%Y-%m-%d",
+                                            )
+                                            .ok();
+                                    })
+                            }
+                        />
+
+                    </div>
+                    <div class="control">
+                        <label class="label is-small">To</label>
+                        <input
+                            class="input is-small"
+                            type="date"
+                            on:change=move |ev| {
+                                filter
+                                    .update(|f| {
+                                        f
+                                            .to = NaiveDate::parse_from_str(
+                                                &event_target_value(&ev),
+                                                "%Y-%m-%d",
+                                            )
+                                            .ok();
+                                    })
+                            }
+                        />
+
+                    </div>
+                    <div class="control">
+                        <label class="label is-small">Duration (min)</label>
+                        <div class="field has-addons">
+                            <input
+                                class="input is-small"
+                                type="number"
+                                min="0"
+                                placeholder="min"
+                                on:change=move |ev| {
+                                    filter
+                                        .update(|f| {
+                                            f.min_duration = event_target_value(&ev).parse().ok();
+                                        })
+                                }
+                            />
+
+                            <input
+                                class="input is-small"
+                                type="number"
+                                min="0"
+                                placeholder="max"
+                                on:change=move |ev| {
+                                    filter
+                                        .update(|f| {
+                                            f.max_duration = event_target_value(&ev).parse().ok();
+                                        })
+                                }
+                            />
+
+                        </div>
+                    </div>
+                    <div class="control">
+                        <label class="label is-small">Distance (km)</label>
+                        <div class="field has-addons">
+                            <input
+                                class="input is-small"
+                                type="number"
+                                min="0"
+                                step="0.1"
+                                placeholder="min"
+                                on:change=move |ev| {
+                                    filter
+                                        .update(|f| {
+                                            f.min_distance = event_target_value(&ev).parse().ok();
+                                        })
+                                }
+                            />
+
+                            <input
+                                class="input is-small"
+                                type="number"
+                                min="0"
+                                step="0.1"
+                                placeholder="max"
+                                on:change=move |ev| {
+                                    filter
+                                        .update(|f| {
+                                            f.max_distance = event_target_value(&ev).parse().ok();
+                                        })
+                                }
+                            />
+
+                        </div>
+                    </div>
+                    <div class="control">
+                        <label class="label is-small">Load</label>
+                        <div class="field has-addons">
+                            <input
+                                class="input is-small"
+                                type="number"
+                                min="0"
+                                placeholder="min"
+                                on:change=move |ev| {
+                                    filter
+                                        .update(|f| {
+                                            f.min_load = event_target_value(&ev).parse().ok();
+                                        })
+                                }
+                            />
+
+                            <input
+                                class="input is-small"
+                                type="number"
+                                min="0"
+                                placeholder="max"
+                                on:change=move |ev| {
+                                    filter
+                                        .update(|f| {
+                                            f.max_load = event_target_value(&ev).parse().ok();
+                                        })
+                                }
+                            />
+
+                        </div>
+                    </div>
+                    <div class="control">
+                        <label class="label is-small">Device</label>
+                        <div class="select is-small">
+                            <select on:change=move |ev| {
+                                filter.update(|f| f.device = event_target_value(&ev))
+                            }>
+                                <option value="">All devices</option>
+                                {move || {
+                                    options
+                                        .get()
+                                        .and_then(|o| o.ok())
+                                        .unwrap_or_default()
+                                        .devices
+                                        .into_iter()
+                                        .map(|d| view! { <option value=d.clone()>{d}</option> })
+                                        .collect_view()
+                                }}
+
+                            </select>
+                        </div>
+                    </div>
+                </div>
+            </Show>
+        </div>
+    }
+}
@@ -0,0 +1,85 @@
+use super::activity_details::Record;
+use crate::models::{
+    interval::{detect_intervals, Interval, IntervalSample},
+    swim_length::format_pace,
+};
+use leptos::*;
+
+/// Work and rest intervals detected in the records of an activity.
+pub fn record_intervals(records: &[Record]) -> Vec<Interval> {
+    let Some(start) = records.first().map(|r| r.timestamp) else {
+        return Vec::new();
+    };
+    let samples: Vec<IntervalSample> = records
+        .iter()
+        .map(|r| IntervalSample {
+            time: (r.timestamp - start).num_milliseconds() as f64 / 1000.0,
+            heartrate: r.heartrate.map(f64::from),
+            speed: r.speed,
+            power: r.power.map(f64::from),
+            distance: r.distance,
+        })
+        .collect();
+    detect_intervals(&samples)
+}
+
+/// Table of detected intervals with their averages, shown instead of the device laps.
+#[component]
+pub fn IntervalTable(intervals: Vec<Interval>) -> impl IntoView {
+    let has_power = intervals.iter().any(|i| i.average_power.is_some());
+    let mut work = 0;
+    view! {
+        <table class="table is-striped is-hoverable is-fullwidth">
+            <thead>
+                <tr>
+                    <th>Interval</th>
+                    <th>Time</th>
+                    <th>Distance</th>
+                    <th>Avg. Speed</th>
+                    <th>Avg. Heartrate</th>
+                    {has_power.then(|| view! { <th>Avg. Power</th> })}
+                </tr>
+            </thead>
+            <tbody>
+                {intervals
+                    .into_iter()
+                    .map(|interval| {
+                        let name = if interval.work {
+                            work += 1;
+                            format!("Work {}", work)
+                        } else {
+                            "Rest".to_string()
+                        };
+                        view! {
+                            <tr class:has-text-weight-semibold=interval.work>
+                                <td>{name}</td>
+                                <td>{format_pace(interval.duration())}</td>
+                                <td>
+                                    {interval
+                                        .distance
+                                        .filter(|d| *d > 0.0)
+                                        .map(|d| format!("{:.2} km", d / 1000.0))}
+                                </td>
+                                <td>
+                                    {interval.average_speed.map(|s| format!("{:.1} km/h", s * 3.6))}
+                                </td>
+                                <td>{interval.average_heartrate.map(|h| h.round() as i32)}</td>
+                                {has_power
+                                    .then(|| {
+                                        view! {
+                                            <td>
+                                                {interval
+                                                    .average_power
+                                                    .map(|p| format!("{} W", p.round() as i32))}
+                                            </td>
+                                        }
+                                    })}
+
+                            </tr>
+                        }
+                    })
+                    .collect_view()}
+            </tbody>
+        </table>
+    }
+}
@@ -0,0 +1,222 @@
+use super::activity_details::ActivityDetail;
+use crate::app::FitFileUploaded;
+#[cfg(feature = "ssr")]
+use crate::app::{auth, pool};
+#[cfg(feature = "ssr")]
+use crate::models::segment::{
+    insert_segment, match_segment_activities, simplify_track, SEGMENT_POINT_SPACING,
+};
+use crate::models::swim_length::format_pace;
+use leptos::*;
+use leptos_router::ActionForm;
+use serde::{Deserialize, Serialize};
+
+/// An effort of the activity on one of the user's segments.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::Type))]
+pub struct SegmentEffortSummary {
+    pub segment_id: i64,
+    pub name: String,
+    pub duration: f64,
+    pub average_heartrate: Option<i16>,
+    pub average_power: Option<i16>,
+    /// Place among all efforts on the segment, 1 is the fastest.
+    pub rank: i64,
+}
+
+/// Creates a segment from the stretch of the activity between `start` and `end` kilometers and
+/// finds the efforts on it in all activities.
+///
+/// The efforts are matched before returning, with a long history creating a segment takes a
+/// while.
+#[server]
+pub async fn create_segment(
+    activity_id: i64,
+    name: String,
+    start: f64,
+    end: f64,
+) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ServerFnError::new("The segment needs a name".to_string()));
+    }
+    let positions: Vec<(f64, f64)> = sqlx::query!(
+        r#"
+        SELECT records.latitude as "latitude!", records.longitude as "longitude!"
+        FROM records
+        INNER JOIN activities ON activities.id = records.activity_id
+        WHERE records.activity_id=$1
+            AND activities.user_id=$2::bigint
+            AND records.latitude IS NOT NULL
+            AND records.longitude IS NOT NULL
+            AND records.distance BETWEEN $3::float8 AND $4::float8
+        ORDER BY records.timestamp
+        "#,
+        activity_id as i32,
+        user.id,
+        start * 1000.0,
+        end * 1000.0
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load track: {}", e)))?
+    .into_iter()
+    .map(|r| (r.latitude, r.longitude))
+    .collect();
+    let points = simplify_track(&positions, SEGMENT_POINT_SPACING);
+    if points.len() < 2 {
+        return Err(ServerFnError::new(
+            "There is no GPS track between start and end".to_string(),
+        ));
+    }
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    let segment_id = insert_segment(user.id, name, &points, &mut transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't save segment: {}", e)))?;
+    match_segment_activities(segment_id, user.id, &points, &mut transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't match segment: {}", e)))?;
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't save segment: {}", e)))?;
+    Ok(())
+}
+
+/// Segment efforts of an activity and the form to create a segment from its track.
+#[component]
+pub fn ActivitySegments(detail: ActivityDetail) -> impl IntoView {
+    let uploaded = use_context::<FitFileUploaded>().unwrap();
+    let create_action = create_server_action::<CreateSegment>();
+    create_effect(move |_| {
+        if let Some(Ok(_)) = create_action.value().get() {
+            uploaded.0.update(|v| *v += 1);
+        }
+    });
+    let track_distance = detail
+        .records
+        .unwrap_or_default()
+        .iter()
+        .filter(|r| r.latitude.is_some())
+        .filter_map(|r| r.distance)
+        .fold(None, |max: Option<f64>, d| {
+            Some(max.map_or(d, |m| m.max(d)))
+        });
+    let efforts = detail.efforts.unwrap_or_default();
+    view! {
+        {(!efforts.is_empty())
+            .then(|| {
+                view! {
+                    <div class="columns">
+                        <div class="column is-fullwidth">
+                            <table class="table is-striped is-hoverable is-fullwidth">
+                                <thead>
+                                    <tr>
+                                        <th>Segment</th>
+                                        <th>Time</th>
+                                        <th>Avg. Heartrate</th>
+                                        <th>Avg. Power</th>
+                                        <th>Rank</th>
+                                    </tr>
+                                </thead>
+                                <tbody>
+                                    {efforts
+                                        .into_iter()
+                                        .map(|effort| {
+                                            view! {
+                                                <tr>
+                                                    <td>
+                                                        <a href=format!(
+                                                            "/segments?segment={}",
+                                                            effort.segment_id,
+                                                        )>{effort.name}</a>
+                                                    </td>
+                                                    <td>{format_pace(effort.duration)}</td>
+                                                    <td>{effort.average_heartrate}</td>
+                                                    <td>{effort.average_power}</td>
+                                                    <td>{effort.rank}</td>
+                                                </tr>
+                                            }
+                                        })
+                                        .collect_view()}
+                                </tbody>
+                            </table>
+                        </div>
+                    </div>
+                }
+            })}
+
+        {track_distance
+            .filter(|_| !detail.indoor)
+            .map(|distance| {
+                let distance = format!("{:.2}", distance / 1000.0);
+                view! {
+                    <details>
+                        <summary class="is-size-7">Create segment</summary>
+                        <ActionForm action=create_action>
+                            <input type="hidden" name="activity_id" value=detail.id/>
+                            <div class="field is-grouped is-grouped-multiline">
+                                <div class="control">
+                                    <input
+                                        class="input is-small"
+                                        name="name"
+                                        type="text"
+                                        placeholder="Name"
+                                        required
+                                    />
+                                </div>
+                                <div class="control">
+                                    <input
+                                        class="input is-small"
+                                        name="start"
+                                        type="number"
+                                        min="0"
+                                        max=distance.clone()
+                                        step="0.01"
+                                        title="Start in km"
+                                        value="0"
+                                    />
+                                </div>
+                                <div class="control">
+                                    <input
+                                        class="input is-small"
+                                        name="end"
+                                        type="number"
+                                        min="0"
+                                        max=distance.clone()
+                                        step="0.01"
+                                        title="End in km"
+                                        value=distance
+                                    />
+                                </div>
+                                <div class="control">
+                                    <button type="submit" class="button is-small">
+                                        <i class="material-symbols-rounded">route</i>
+                                        Create
+                                    </button>
+                                </div>
+                            </div>
+                        </ActionForm>
+                        {move || {
+                            create_action
+                                .value()
+                                .get()
+                                .and_then(|r| r.err())
+                                .map(|e| {
+                                    view! { <pre class="error">"Error: " {e.to_string()}</pre> }
+                                })
+                        }}
+
+                    </details>
+                }
+            })}
+    }
+}
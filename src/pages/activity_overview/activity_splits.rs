@@ -0,0 +1,166 @@
+use super::activity_details::Record;
+use crate::models::{
+    split::{distance_splits, DistanceSplit, SplitSample},
+    swim_length::format_pace,
+};
+use leptos::*;
+
+/// Meters in a mile.
+const MILE: f64 = 1609.344;
+
+/// Distance splits of an activity's records.
+fn record_splits(records: &[Record], split_length: f64) -> Vec<DistanceSplit> {
+    let Some(start) = records.first().map(|r| r.timestamp) else {
+        return Vec::new();
+    };
+    let samples: Vec<SplitSample> = records
+        .iter()
+        .map(|r| SplitSample {
+            time: (r.timestamp - start).num_milliseconds() as f64 / 1000.0,
+            distance: r.distance,
+            heartrate: r.heartrate.map(f64::from),
+            altitude: r.altitude,
+        })
+        .collect();
+    distance_splits(&samples, split_length)
+}
+
+/// Splits as CSV with the pace per `unit` meters.
+fn splits_csv(splits: &[DistanceSplit], unit: f64) -> String {
+    let optional =
+        |value: Option<f64>, format: fn(f64) -> String| value.map(format).unwrap_or_default();
+    let mut csv = String::from(
+        "split,distance_m,time_s,pace,average_heartrate,elevation_change_m,grade_adjusted_pace\n",
+    );
+    for (i, split) in splits.iter().enumerate() {
+        csv.push_str(&format!(
+            "{},{:.1},{:.1},{},{},{},{}\n",
+            i + 1,
+            split.distance,
+            split.duration,
+            optional(split.pace(unit), format_pace),
+            optional(split.average_heartrate, |h| format!("{:.0}", h)),
+            optional(split.elevation_change, |e| format!("{:.1}", e)),
+            optional(split.grade_adjusted_pace(unit), format_pace),
+        ));
+    }
+    csv
+}
+
+/// Data URL of a CSV document, so it can be downloaded with a plain link.
+fn csv_data_url(csv: &str) -> String {
+    let encoded: String = csv
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    format!("data:text/csv;charset=utf-8,{}", encoded)
+}
+
+/// Automatic splits over a configurable distance, computed from the recorded distance.
+#[component]
+pub fn DistanceSplits(records: Vec<Record>) -> impl IntoView {
+    let length = create_rw_signal(1.0_f64);
+    let miles = create_rw_signal(false);
+    let unit = move || if miles.get() { MILE } else { 1000.0 };
+    let unit_name = move || if miles.get() { "mi" } else { "km" };
+    let splits = create_memo(move |_| {
+        let split_length = length.get() * unit();
+        if split_length > 0.0 {
+            record_splits(&records, split_length)
+        } else {
+            Vec::new()
+        }
+    });
+    view! {
+        <div class="field is-grouped is-grouped-multiline">
+            <div class="control">
+                <label class="label is-small" for="split_length">
+                    Split length
+                </label>
+                <div class="field has-addons">
+                    <div class="control">
+                        <input
+                            class="input is-small"
+                            id="split_length"
+                            type="number"
+                            min="0.1"
+                            step="0.1"
+                            prop:value=move || length.get().to_string()
+                            on:change=move |ev| {
+                                if let Ok(value) = event_target_value(&ev).parse::<f64>() {
+                                    length.set(value.max(0.1));
+                                }
+                            }
+                        />
+
+                    </div>
+                    <div class="control">
+                        <div class="select is-small">
+                            <select on:change=move |ev| miles.set(event_target_value(&ev) == "mi")>
+                                <option value="km">km</option>
+                                <option value="mi">mi</option>
+                            </select>
+                        </div>
+                    </div>
+                </div>
+            </div>
+            <div class="control">
+                <label class="label is-small">"\u{a0}"</label>
+                <a
+                    class="button is-small"
+                    href=move || splits.with(|s| csv_data_url(&splits_csv(s, unit())))
+                    download="splits.csv"
+                >
+                    <i class="material-symbols-rounded">download</i>
+                    CSV
+                </a>
+            </div>
+        </div>
+        <table class="table is-striped is-hoverable is-fullwidth">
+            <thead>
+                <tr>
+                    <th>Split</th>
+                    <th>Distance</th>
+                    <th>Time</th>
+                    <th>{move || format!("Pace /{}", unit_name())}</th>
+                    <th>Avg. Heartrate</th>
+                    <th>Elevation</th>
+                    <th>{move || format!("GAP /{}", unit_name())}</th>
+                </tr>
+            </thead>
+            <tbody>
+                {move || {
+                    let unit = unit();
+                    splits
+                        .get()
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, split)| {
+                            view! {
+                                <tr>
+                                    <td>{i + 1}</td>
+                                    <td>
+                                        {format!("{:.2} {}", split.distance / unit, unit_name())}
+                                    </td>
+                                    <td>{format_pace(split.duration)}</td>
+                                    <td>{split.pace(unit).map(format_pace)}</td>
+                                    <td>{split.average_heartrate.map(|h| h.round() as i32)}</td>
+                                    <td>
+                                        {split.elevation_change.map(|e| format!("{:+.0} m", e))}
+                                    </td>
+                                    <td>{split.grade_adjusted_pace(unit).map(format_pace)}</td>
+                                </tr>
+                            }
+                        })
+                        .collect_view()
+                }}
+
+            </tbody>
+        </table>
+    }
+}
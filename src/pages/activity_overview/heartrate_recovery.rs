@@ -0,0 +1,91 @@
+use super::activity_details::Record;
+use crate::models::{
+    heartrate_recovery::{cardiac_drift, heartrate_recoveries},
+    swim_length::format_pace,
+};
+use leptos::*;
+
+/// Heart rate recovery after each hard effort of an activity and its cardiac drift.
+#[component]
+pub fn HeartrateRecoveryTable(
+    records: Vec<Record>,
+    #[prop(into)] aerobic_threshold: Signal<i32>,
+) -> impl IntoView {
+    let Some(start) = records.first().map(|r| r.timestamp) else {
+        return ().into_view();
+    };
+    let elapsed = |r: &Record| (r.timestamp - start).num_milliseconds() as f64 / 1000.0;
+    let heartrates: Vec<(f64, f64)> = records
+        .iter()
+        .filter_map(|r| Some((elapsed(r), r.heartrate? as f64)))
+        .collect();
+    let has_power = records.iter().any(|r| r.power.is_some_and(|p| p > 0));
+    let outputs: Vec<(f64, f64, f64)> = records
+        .iter()
+        .filter_map(|r| {
+            let output = if has_power { r.power? as f64 } else { r.speed? };
+            Some((elapsed(r), r.heartrate? as f64, output))
+        })
+        .collect();
+    let drift = cardiac_drift(&outputs);
+    let recoveries = move || heartrate_recoveries(&heartrates, aerobic_threshold.get() as f64);
+    view! {
+        {move || {
+            let recoveries = recoveries();
+            (!recoveries.is_empty() || drift.is_some())
+                .then(|| {
+                    view! {
+                        <div class="columns">
+                            <div class="column is-fullwidth">
+                                {(!recoveries.is_empty())
+                                    .then(|| {
+                                        view! {
+                                            <table class="table is-striped is-hoverable is-fullwidth is-narrow">
+                                                <thead>
+                                                    <tr>
+                                                        <th>Effort</th>
+                                                        <th>At</th>
+                                                        <th>Peak</th>
+                                                        <th>Recovery 60s</th>
+                                                        <th>Recovery 120s</th>
+                                                    </tr>
+                                                </thead>
+                                                <tbody>
+                                                    {recoveries
+                                                        .into_iter()
+                                                        .enumerate()
+                                                        .map(|(i, recovery)| {
+                                                            view! {
+                                                                <tr>
+                                                                    <td>{i + 1}</td>
+                                                                    <td>{format_pace(recovery.time)}</td>
+                                                                    <td>{recovery.peak.round() as i32}</td>
+                                                                    <td>{format!("-{:.0} bpm", recovery.recovery_60)}</td>
+                                                                    <td>
+                                                                        {recovery.recovery_120.map(|r| format!("-{:.0} bpm", r))}
+                                                                    </td>
+                                                                </tr>
+                                                            }
+                                                        })
+                                                        .collect_view()}
+                                                </tbody>
+                                            </table>
+                                        }
+                                    })}
+                                {drift
+                                    .map(|drift| {
+                                        view! {
+                                            <p class="is-size-7">
+                                                {format!("Cardiac drift: {:+.1}%", drift)}
+                                            </p>
+                                        }
+                                    })}
+
+                            </div>
+                        </div>
+                    }
+                })
+        }}
+    }
+    .into_view()
+}
@@ -0,0 +1,306 @@
+#[cfg(feature = "ssr")]
+use super::parse_optional;
+use crate::app::FitFileUploaded;
+#[cfg(feature = "ssr")]
+use crate::app::{auth, pool};
+#[cfg(feature = "ssr")]
+use crate::models::{
+    activity::{insert_activity, update_activity_load, Activity},
+    base::{DatabaseEntry, New},
+    session::{insert_sessions, update_session_loads, Session},
+    sport::find_sport,
+    user_preferences::get_user_preferences,
+    workout_completion::match_new_activity_to_workout,
+};
+use crate::pages::workout_schedule::manage_sports_dialog::{indented_name, list_sports};
+#[cfg(feature = "ssr")]
+use chrono::{Local, NaiveDateTime, TimeDelta, TimeZone};
+use leptos::*;
+use leptos_router::ActionForm;
+
+/// Longest distance of a manual activity in kilometers.
+#[cfg(feature = "ssr")]
+const MAX_DISTANCE: f64 = 1000.0;
+
+/// Adds an activity that wasn't recorded with a device.
+///
+/// `duration` is in minutes and `distance` in kilometers. The training load is estimated from
+/// the average heart rate or the perceived exertion, depending on the user's load model.
+#[server]
+pub async fn add_manual_activity(
+    sport: String,
+    start_time: String,
+    duration: u32,
+    distance: String,
+    heartrate: String,
+    rpe: String,
+) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let start_time = NaiveDateTime::parse_from_str(&start_time, "%Y-%m-%dT%H:%M")
+        .ok()
+        .and_then(|t| Local.from_local_datetime(&t).earliest())
+        .ok_or(ServerFnError::new("Couldn't parse start time".to_string()))?;
+    if duration == 0 || duration > 24 * 60 {
+        return Err(ServerFnError::new(
+            "The activity needs a duration of at most 24 hours".to_string(),
+        ));
+    }
+    let distance = parse_optional::<f64>(&distance, "distance")?;
+    if distance.is_some_and(|d| !(0.0..=MAX_DISTANCE).contains(&d)) {
+        return Err(ServerFnError::new(format!(
+            "The distance has to be between 0 and {}km",
+            MAX_DISTANCE
+        )));
+    }
+    let heartrate = parse_optional::<u16>(&heartrate, "heart rate")?;
+    if heartrate.is_some_and(|hr| hr > 250) {
+        return Err(ServerFnError::new(
+            "The heart rate can be at most 250".to_string(),
+        ));
+    }
+    let rpe = parse_optional::<i16>(&rpe, "perceived exertion")?;
+    if rpe.is_some_and(|rpe| !(0..=10).contains(&rpe)) {
+        return Err(ServerFnError::new(
+            "Perceived exertion goes from 0 to 10".to_string(),
+        ));
+    }
+    let sport_id = match sport.parse::<i64>() {
+        Ok(sport_id) => Some(
+            find_sport(user.id, sport_id, &pool)
+                .await
+                .map_err(|e| ServerFnError::new(format!("Couldn't load sport: {}", e)))?
+                .ok_or(ServerFnError::new("Unknown sport".to_string()))?,
+        ),
+        Err(_) if sport.is_empty() => None,
+        Err(_) => return Err(ServerFnError::new("Couldn't parse sport".to_string())),
+    };
+    let seconds = duration as f64 * 60.0;
+    let end_time = start_time + TimeDelta::minutes(duration as i64);
+    let preferences = get_user_preferences(user.id, start_time, &pool).await;
+    // without a heart rate the load model falls back to session-RPE
+    let load = heartrate.map(|hr| preferences.constant_load(&[(hr as u32, seconds)]));
+    let activity = DatabaseEntry {
+        state: Box::new(Activity {
+            user_id: Some(user.id),
+            start_time,
+            end_time,
+            duration: seconds,
+            load,
+            avg_heartrate: heartrate,
+            rpe,
+            device: None,
+            indoor: false,
+            footpod: None,
+            gps_distance_ratio: None,
+        }),
+        extra: New,
+    };
+    let session = DatabaseEntry {
+        state: Box::new(Session {
+            start_time,
+            end_time,
+            sport: None,
+            sub_sport: None,
+            distance: distance.map(|d| d * 1000.0),
+            calories: None,
+            average_heartrate: heartrate.map(|hr| hr as i16),
+            min_heartrate: None,
+            max_heartrate: None,
+            average_power: None,
+            ascent: None,
+            descent: None,
+            average_speed: distance.map(|d| d * 1000.0 / seconds),
+            max_speed: None,
+            pool_length: None,
+        }),
+        extra: New,
+    };
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    let activity = insert_activity(activity, user.id, &mut *transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't save activity: {}", e)))?;
+    let activity_id = activity.extra.activity_id;
+    insert_sessions(vec![session], activity_id, &mut *transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't save session: {}", e)))?;
+    sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET sport_id=$2
+        WHERE activity_id=$1
+        "#,
+        activity_id as i32,
+        sport_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't save sport: {}", e)))?;
+    update_activity_load(activity_id, user.id, &mut transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't calculate load: {}", e)))?;
+    update_session_loads(activity_id, user.id, &mut transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't calculate session loads: {}", e)))?;
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't save activity: {}", e)))?;
+    match_new_activity_to_workout(activity_id, user.id, &pool).await;
+    Ok(())
+}
+
+#[component]
+pub fn ManualActivityDialog(show: RwSignal<bool>) -> impl IntoView {
+    let uploaded = use_context::<FitFileUploaded>().unwrap();
+    let add_action = create_server_action::<AddManualActivity>();
+    let sports = create_rw_signal(Vec::new());
+    create_effect(move |_| {
+        if show() {
+            spawn_local(async move {
+                sports.set(list_sports().await.unwrap_or_default());
+            });
+        }
+    });
+    create_effect(move |_| {
+        if let Some(Ok(_)) = add_action.value().get() {
+            show.set(false);
+            uploaded.0.update(|v| *v += 1);
+        }
+    });
+    let close = move |_| show.set(false);
+    view! {
+        <Show when=move || { show() } fallback=|| {}>
+            <ActionForm action=add_action>
+                <div class="modal is-active">
+                    <div class="modal-background" on:click=close></div>
+                    <div class="modal-card">
+                        <div class="modal-card-head">
+                            <p class="modal-card-title">"Add Activity"</p>
+                            <button class="delete" aria-label="close" on:click=close></button>
+                        </div>
+                        <div class="modal-card-body">
+                            <div class="field">
+                                <label class="label" for="sport">
+                                    Sport
+                                </label>
+                                <div class="control">
+                                    <div class="select">
+                                        <select id="sport" name="sport">
+                                            <option value="">General</option>
+                                            {move || {
+                                                sports
+                                                    .get()
+                                                    .into_iter()
+                                                    .map(|s| {
+                                                        view! { <option value=s.id>{indented_name(&s)}</option> }
+                                                    })
+                                                    .collect_view()
+                                            }}
+
+                                        </select>
+                                    </div>
+                                </div>
+                            </div>
+                            <div class="field is-grouped">
+                                <div class="control is-expanded">
+                                    <label class="label" for="start_time">
+                                        Start
+                                    </label>
+                                    <input
+                                        class="input"
+                                        id="start_time"
+                                        name="start_time"
+                                        type="datetime-local"
+                                        required
+                                    />
+                                </div>
+                                <div class="control">
+                                    <label class="label" for="duration">
+                                        Duration (min)
+                                    </label>
+                                    <input
+                                        class="input"
+                                        id="duration"
+                                        name="duration"
+                                        type="number"
+                                        min="1"
+                                        value="60"
+                                    />
+                                </div>
+                            </div>
+                            <div class="field is-grouped">
+                                <div class="control">
+                                    <label class="label" for="distance">
+                                        Distance (km)
+                                    </label>
+                                    <input
+                                        class="input"
+                                        id="distance"
+                                        name="distance"
+                                        type="number"
+                                        min="0"
+                                        step="0.01"
+                                    />
+                                </div>
+                                <div class="control">
+                                    <label class="label" for="heartrate">
+                                        Avg. Heartrate
+                                    </label>
+                                    <input
+                                        class="input"
+                                        id="heartrate"
+                                        name="heartrate"
+                                        type="number"
+                                        min="30"
+                                        max="250"
+                                    />
+                                </div>
+                                <div class="control">
+                                    <label class="label" for="rpe">
+                                        RPE (0-10)
+                                    </label>
+                                    <input
+                                        class="input"
+                                        id="rpe"
+                                        name="rpe"
+                                        type="number"
+                                        min="0"
+                                        max="10"
+                                    />
+                                </div>
+                            </div>
+                            <p class="help">
+                                "Training load is estimated from the heart rate or from RPE × duration, depending on the load model in the settings."
+                            </p>
+                            {move || {
+                                add_action
+                                    .value()
+                                    .get()
+                                    .and_then(|r| r.err())
+                                    .map(|e| {
+                                        view! { <pre class="error">"Error: " {e.to_string()}</pre> }
+                                    })
+                            }}
+
+                        </div>
+                        <div class="modal-card-foot">
+                            <button type="submit" class="button is-success">
+                                Save
+                            </button>
+                            <button type="button" class="button" on:click=close>
+                                Cancel
+                            </button>
+                        </div>
+                    </div>
+                </div>
+            </ActionForm>
+        </Show>
+    }
+}
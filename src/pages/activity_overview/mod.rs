@@ -4,15 +4,29 @@ use std::time::Duration;
 use crate::app::{auth, pool};
 use crate::{app::FitFileUploaded, error_template::ErrorTemplate};
 use activity_details::ActivityDetails;
+use activity_filter::{ActivityFilter, ActivityFilterBar, ActivitySort, SortHeader};
 use bigdecimal::{BigDecimal, ToPrimitive};
 use chrono::{DateTime, Local};
 use humantime::format_duration;
 use leptos::*;
+use manual_activity_dialog::ManualActivityDialog;
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "ssr")]
 use sqlx::*;
+use strength_log_dialog::StrengthLogDialog;
 
+pub mod activity_compare;
 pub mod activity_details;
+pub mod activity_edit;
+pub mod activity_export;
+pub mod activity_filter;
+pub mod activity_intervals;
+pub mod activity_segments;
+pub mod activity_splits;
+pub mod heartrate_recovery;
+pub mod manual_activity_dialog;
+pub mod strength_log_dialog;
+pub mod swim_details;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActivityListEntry {
@@ -21,36 +35,223 @@ pub struct ActivityListEntry {
     pub duration: BigDecimal,
     pub load: Option<i32>,
     pub sport: String,
+    pub title: Option<String>,
+    pub feeling: Option<i16>,
+    pub tags: Vec<String>,
+    pub device: Option<String>,
+    /// Distance in meters.
+    pub distance: Option<f64>,
 }
 
+/// Position in the activity list after which the next page starts.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ActivityCursor {
+    /// Value of the sorted column, `None` for activities without one, they come last.
+    pub key: Option<f64>,
+    pub id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityPage {
+    pub activities: Vec<ActivityListEntry>,
+    /// `None` if this is the last page.
+    pub next: Option<ActivityCursor>,
+}
+
+/// Number of activities loaded at once.
+#[cfg(feature = "ssr")]
+const PAGE_SIZE: i64 = 50;
+
+/// Parses an optional number from a form field, an empty field is `None`.
+#[cfg(feature = "ssr")]
+fn parse_optional<T: std::str::FromStr>(
+    value: &str,
+    field: &str,
+) -> Result<Option<T>, ServerFnError> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    value
+        .parse::<T>()
+        .map(Some)
+        .map_err(|_| ServerFnError::new(format!("Couldn't parse {}", field)))
+}
+
+/// Splits comma separated tags, dropping empty and duplicate ones.
+pub fn parse_tags(tags: &str) -> Vec<String> {
+    let mut parsed: Vec<String> = Vec::new();
+    for tag in tags.split(',').map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        if !parsed.iter().any(|p| p.eq_ignore_ascii_case(tag)) {
+            parsed.push(tag.to_string());
+        }
+    }
+    parsed
+}
+
+/// How an activity felt, indexed by the feeling score minus one.
+pub const FEELINGS: [&str; 5] = ["Very weak", "Weak", "Normal", "Strong", "Very strong"];
+
+pub fn feeling_label(feeling: i16) -> &'static str {
+    FEELINGS
+        .get((feeling - 1) as usize)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Lists a page of the user's activities, filtered and sorted by `filter`.
+///
+/// Pages are addressed with a cursor instead of an offset, so activities uploaded while
+/// scrolling don't shift the following pages.
 #[server(ActivityList, "/api")]
-pub async fn get_activity_list() -> Result<Vec<ActivityListEntry>, ServerFnError> {
+pub async fn get_activity_list(
+    filter: ActivityFilter,
+    cursor: Option<ActivityCursor>,
+) -> Result<ActivityPage, ServerFnError> {
     let auth = auth()?;
     if auth.current_user.is_none() {
         return Err(ServerFnError::new("Not logged in".to_string()));
     }
     let user = auth.current_user.unwrap();
     let pool = pool()?;
-    let activities = query_as!(
-        ActivityListEntry,
+    let mut activities = query!(
         r#"
-        SELECT 
-            activities.id, 
-            activities.start_time, 
-            activities.duration,
-            activities.load,
-            COALESCE(string_agg(sessions.sport,', '),'General') as "sport!" 
-        FROM activities 
-        JOIN sessions on sessions.activity_id=activities.id
-        WHERE activities.user_id = $1::bigint
-        GROUP BY activities.id
-        ORDER BY activities.start_time DESC"#,
-        user.id
+        WITH entries AS (
+            SELECT 
+                activities.id, 
+                activities.start_time, 
+                activities.duration,
+                activities.load,
+                COALESCE(string_agg(COALESCE(sports.name, sessions.sport),', '),'General') as sport,
+                activities.title,
+                activities.feeling,
+                activities.tags,
+                activities.device,
+                SUM(sessions.distance)::float8 as distance
+            FROM activities 
+            JOIN sessions on sessions.activity_id=activities.id
+            LEFT JOIN sports on sports.id=sessions.sport_id
+            WHERE activities.user_id = $1::bigint
+                AND (
+                    $2::bigint IS NULL
+                    OR EXISTS (
+                        SELECT 1 FROM sessions s
+                        WHERE s.activity_id = activities.id AND s.sport_id IN (SELECT sport_subtree($2::bigint))
+                    )
+                )
+                AND (
+                    $3 = ''
+                    OR activities.title ILIKE '%' || $3 || '%'
+                    OR activities.notes ILIKE '%' || $3 || '%'
+                    OR EXISTS (SELECT 1 FROM unnest(activities.tags) t WHERE t ILIKE '%' || $3 || '%')
+                )
+                AND ($4 = '' OR $4 = ANY(activities.tags))
+                AND ($5::smallint IS NULL OR activities.feeling = $5)
+                AND ($6 = '' OR activities.device = $6)
+                AND ($7::date IS NULL OR activities.start_time >= $7::date)
+                AND ($8::date IS NULL OR activities.start_time < $8::date + 1)
+                AND ($9::float8 IS NULL OR activities.duration >= $9 * 60)
+                AND ($10::float8 IS NULL OR activities.duration <= $10 * 60)
+                AND ($11::int IS NULL OR activities.load >= $11)
+                AND ($12::int IS NULL OR activities.load <= $12)
+            GROUP BY activities.id
+            HAVING ($13::float8 IS NULL OR SUM(sessions.distance) >= $13 * 1000)
+                AND ($14::float8 IS NULL OR SUM(sessions.distance) <= $14 * 1000)
+        ), keyed AS (
+            SELECT
+                *,
+                CASE $15
+                    WHEN 'duration' THEN duration::float8
+                    WHEN 'distance' THEN distance
+                    WHEN 'load' THEN load::float8
+                    ELSE extract(epoch from start_time)::float8
+                END as sort_key
+            FROM entries
+        )
+        SELECT
+            id as "id!",
+            start_time as "start_time!",
+            duration as "duration!",
+            load,
+            sport as "sport!",
+            title,
+            feeling,
+            tags as "tags!",
+            device,
+            distance,
+            sort_key
+        FROM keyed
+        WHERE $18::bigint IS NULL
+            OR CASE
+                WHEN $17::float8 IS NULL THEN
+                    sort_key IS NULL AND CASE WHEN $16 THEN id < $18 ELSE id > $18 END
+                ELSE
+                    sort_key IS NULL
+                    OR CASE
+                        WHEN $16 THEN (sort_key, id) < ($17, $18)
+                        ELSE (sort_key, id) > ($17, $18)
+                    END
+            END
+        ORDER BY
+            -- activities without a value come last in both directions
+            sort_key IS NULL,
+            CASE WHEN $16 THEN sort_key END DESC,
+            CASE WHEN $16 THEN id END DESC,
+            CASE WHEN NOT $16 THEN sort_key END ASC,
+            CASE WHEN NOT $16 THEN id END ASC
+        LIMIT $19"#,
+        user.id,
+        filter.sport,
+        filter.search.trim(),
+        filter.tag,
+        filter.feeling,
+        filter.device,
+        filter.from,
+        filter.to,
+        filter.min_duration.map(f64::from),
+        filter.max_duration.map(f64::from),
+        filter.min_load,
+        filter.max_load,
+        filter.min_distance,
+        filter.max_distance,
+        filter.sort.to_string(),
+        filter.descending,
+        cursor.and_then(|c| c.key),
+        cursor.map(|c| c.id),
+        PAGE_SIZE + 1
     )
     .fetch_all(&pool)
     .await?;
-    Ok(activities)
+    // one more than a page tells us whether there is a next page
+    let next = if activities.len() as i64 > PAGE_SIZE {
+        activities.truncate(PAGE_SIZE as usize);
+        activities.last().map(|a| ActivityCursor {
+            key: a.sort_key,
+            id: a.id,
+        })
+    } else {
+        None
+    };
+    Ok(ActivityPage {
+        activities: activities
+            .into_iter()
+            .map(|a| ActivityListEntry {
+                id: a.id,
+                start_time: a.start_time.into(),
+                duration: a.duration,
+                load: a.load,
+                sport: a.sport,
+                title: a.title,
+                feeling: a.feeling,
+                tags: a.tags,
+                device: a.device,
+                distance: a.distance,
+            })
+            .collect(),
+        next,
+    })
 }
+
 #[server]
 pub async fn delete_activity(activity_id: i64) -> Result<(), ServerFnError> {
     let pool = pool()?;
@@ -76,71 +277,162 @@ pub async fn delete_activity(activity_id: i64) -> Result<(), ServerFnError> {
 #[component]
 pub fn ActivityList() -> impl IntoView {
     let uploaded = use_context::<FitFileUploaded>().unwrap();
-    let activities = create_resource(move || (uploaded.0.get()), move |_| get_activity_list());
-    let show_activity = create_rw_signal(None);
+    let filter = create_rw_signal(ActivityFilter::default());
     let delete_activity = create_server_action::<DeleteActivity>();
+    let first_page = create_resource(
+        move || {
+            (
+                uploaded.0.get(),
+                filter.get(),
+                delete_activity.version().get(),
+            )
+        },
+        move |(_, filter, _)| get_activity_list(filter, None),
+    );
+    // pages loaded with "Load more" are appended here until the first page reloads
+    let more_activities = create_rw_signal(Vec::<ActivityListEntry>::new());
+    let next = create_rw_signal(None::<ActivityCursor>);
+    let loading_more = create_rw_signal(false);
+    create_effect(move |_| {
+        if let Some(Ok(page)) = first_page.get() {
+            more_activities.set(Vec::new());
+            next.set(page.next);
+        }
+    });
+    let load_more = move |_| {
+        if let Some(cursor) = next.get_untracked() {
+            loading_more.set(true);
+            spawn_local(async move {
+                if let Ok(page) = get_activity_list(filter.get_untracked(), Some(cursor)).await {
+                    more_activities.update(|a| a.extend(page.activities));
+                    next.set(page.next);
+                }
+                loading_more.set(false);
+            });
+        }
+    };
+    let show_activity = create_rw_signal(None);
+    let show_strength_log = create_rw_signal(false);
+    let show_manual_activity = create_rw_signal(false);
     view! {
         <div class="container">
+            <div class="level">
+                <div class="level-left"></div>
+                <div class="level-right">
+                    <div class="buttons">
+                        <button class="button" on:click=move |_| show_manual_activity.set(true)>
+                            <i class="material-symbols-rounded">add</i>
+                            Add Activity
+                        </button>
+                        <button class="button" on:click=move |_| show_strength_log.set(true)>
+                            <i class="material-symbols-rounded">fitness_center</i>
+                            Log Strength Session
+                        </button>
+                    </div>
+                </div>
+            </div>
+            <ActivityFilterBar filter reload=uploaded.0/>
+            <ManualActivityDialog show=show_manual_activity/>
+            <StrengthLogDialog show=show_strength_log/>
             <Transition fallback=move || view! { <p>"Loading..."</p> }>
                 <ErrorBoundary fallback=|errors| {
                     view! { <ErrorTemplate errors=errors/> }
                 }>
                     {move || {
-                        activities
+                        first_page
                             .get()
-                            .map(move |activities| match activities {
+                            .map(move |page| match page {
                                 Err(e) => {
                                     view! { <pre class="error">"Error: " {e.to_string()}</pre> }
                                         .into_view()
                                 }
-                                Ok(activities) => {
+                                Ok(page) => {
                                     view! {
                                         <div class="box columns">
                                             <div class="column is-auto">
                                                 <div class="columns is-full">
-                                                    <div class="column is-two-fifths">
+                                                    <div class="column is-4">
                                                         <h6 class="title is-6">Activity</h6>
                                                     </div>
-                                                    <div class="column is-one-fifth">
-                                                        <h6 class="title is-6">Date</h6>
+                                                    <div class="column is-2">
+                                                        <SortHeader filter sort=ActivitySort::Date label="Date"/>
                                                     </div>
-                                                    <div class="column is-one-fifth">
-                                                        <h6 class="title is-6">Duration</h6>
+                                                    <div class="column is-2">
+                                                        <SortHeader
+                                                            filter
+                                                            sort=ActivitySort::Duration
+                                                            label="Duration"
+                                                        />
                                                     </div>
-                                                    <div class="column is-one-fifth">
-                                                        <h6 class="title is-6">Load</h6>
+                                                    <div class="column is-2">
+                                                        <SortHeader
+                                                            filter
+                                                            sort=ActivitySort::Distance
+                                                            label="Distance"
+                                                        />
+                                                    </div>
+                                                    <div class="column is-2">
+                                                        <SortHeader filter sort=ActivitySort::Load label="Load"/>
                                                     </div>
                                                 </div>
                                             </div>
                                             <div class="column is-1"></div>
                                         </div>
                                         <For
-                                            each=move || activities.clone()
+                                            each=move || {
+                                                page.activities
+                                                    .clone()
+                                                    .into_iter()
+                                                    .chain(more_activities.get())
+                                            }
+
                                             key=|e| e.id
                                             let:activity
                                         >
                                             <div class="box columns">
                                                 <div class="column is-auto">
                                                     <div class="columns is-full">
-                                                        <div class="column is-two-fifths">
+                                                        <div class="column is-4">
                                                             <a
                                                                 class="is-title"
                                                                 href="#!"
                                                                 on:click=move |_| show_activity.set(Some(activity.id))
                                                             >
-                                                                {activity.sport}
+                                                                {activity.title.clone().unwrap_or(activity.sport.clone())}
                                                             </a>
+                                                            <p class="is-size-7">
+                                                                {activity.title.is_some().then_some(activity.sport)}
+                                                                {activity
+                                                                    .feeling
+                                                                    .map(|f| format!(" · {}", feeling_label(f)))}
+                                                                {activity.device.map(|d| format!(" · {}", d))}
+                                                            </p>
+                                                            <div class="tags">
+                                                                {activity
+                                                                    .tags
+                                                                    .into_iter()
+                                                                    .map(|t| {
+                                                                        view! { <span class="tag is-info is-light">{t}</span> }
+                                                                    })
+                                                                    .collect_view()}
+                                                            </div>
                                                         </div>
-                                                        <div class="column is-one-fifth">
+                                                        <div class="column is-2">
                                                             {activity.start_time.format("%Y-%m-%d").to_string()}
                                                         </div>
-                                                        <div class="column is-one-fifth">
+                                                        <div class="column is-2">
                                                             {format_duration(
                                                                     Duration::new(activity.duration.to_u64().unwrap(), 0),
                                                                 )
                                                                 .to_string()}
                                                         </div>
-                                                        <div class="column is-one-fifth">{activity.load}</div>
+                                                        <div class="column is-2">
+                                                            {activity
+                                                                .distance
+                                                                .filter(|d| *d > 0.0)
+                                                                .map(|d| format!("{:.2} km", d / 1000.0))}
+                                                        </div>
+                                                        <div class="column is-2">{activity.load}</div>
                                                     </div>
                                                 </div>
                                                 <div class="column is-1">
@@ -165,10 +457,46 @@ pub fn ActivityList() -> impl IntoView {
                                 }
                             })
                     }}
-                    <ActivityDetails activity=show_activity/>
+                    <Show when=move || next.with(Option::is_some) fallback=|| {}>
+                        <div class="has-text-centered">
+                            <button class="button" class:is-loading=loading_more on:click=load_more>
+                                Load more
+                            </button>
+                        </div>
+                    </Show> <ActivityDetails activity=show_activity/>
 
                 </ErrorBoundary>
             </Transition>
         </div>
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{parse_tags, ActivityCursor};
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(
+            parse_tags(" race, Race ,,long run,"),
+            vec!["race".to_string(), "long run".to_string()]
+        );
+        assert!(parse_tags("").is_empty());
+    }
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_cursor_roundtrip() {
+        // activities without a load or distance have no key
+        for key in [Some(12.5), None] {
+            let cursor = ActivityCursor { key, id: 7 };
+            let json = serde_json::to_string(&cursor).unwrap();
+            assert_eq!(
+                serde_json::from_str::<ActivityCursor>(&json).unwrap(),
+                cursor
+            );
+        }
+    }
+}
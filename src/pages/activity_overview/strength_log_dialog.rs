@@ -0,0 +1,312 @@
+use crate::app::FitFileUploaded;
+#[cfg(feature = "ssr")]
+use crate::app::{auth, pool};
+#[cfg(feature = "ssr")]
+use crate::models::{
+    activity::{insert_activity, Activity},
+    base::{DatabaseEntry, New},
+    session::{insert_sessions, Session},
+    sport::assign_sports,
+    strength_set::{insert_strength_sets, strength_load, StrengthSet},
+    user_preferences::get_user_preferences,
+    workout_completion::match_new_activity_to_workout,
+};
+#[cfg(feature = "ssr")]
+use chrono::{Local, NaiveDateTime, TimeDelta, TimeZone};
+use leptos::*;
+use leptos_router::ActionForm;
+use serde::{Deserialize, Serialize};
+
+/// A set as entered in the strength log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetEntry {
+    exercise: String,
+    repetitions: i32,
+    /// Weight in kilograms.
+    weight: f64,
+    /// Rest after the set in seconds.
+    rest: f64,
+}
+
+#[server]
+pub async fn get_exercises() -> Result<Vec<String>, ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    sqlx::query_scalar!(
+        r#"
+        SELECT name
+        FROM exercises
+        WHERE user_id=$1::bigint
+        ORDER BY name
+        "#,
+        user.id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load exercises: {}", e)))
+}
+
+/// Records a strength session that wasn't tracked with a watch.
+///
+/// `duration` is in minutes, the training load is estimated from the sets.
+#[server]
+pub async fn log_strength_session(
+    start_time: String,
+    duration: u32,
+    set: Vec<SetEntry>,
+) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let start_time = NaiveDateTime::parse_from_str(&start_time, "%Y-%m-%dT%H:%M")
+        .ok()
+        .and_then(|t| Local.from_local_datetime(&t).earliest())
+        .ok_or(ServerFnError::new("Couldn't parse start time".to_string()))?;
+    if duration == 0 || duration > 24 * 60 {
+        return Err(ServerFnError::new(
+            "The session needs a duration of at most 24 hours".to_string(),
+        ));
+    }
+    let end_time = start_time + TimeDelta::minutes(duration as i64);
+    let sets: Vec<DatabaseEntry<New, StrengthSet>> = set
+        .into_iter()
+        .filter(|s| !s.exercise.trim().is_empty())
+        .map(|s| DatabaseEntry {
+            state: Box::new(StrengthSet::new(
+                s.exercise.trim().to_string(),
+                s.repetitions,
+                s.weight,
+                s.rest,
+            )),
+            extra: New,
+        })
+        .collect();
+    if sets.is_empty() {
+        return Err(ServerFnError::new(
+            "The session needs at least one set".to_string(),
+        ));
+    }
+    for set in sets.iter() {
+        set.state.validate().map_err(ServerFnError::new)?;
+    }
+    let preferences = get_user_preferences(user.id, start_time, &pool).await;
+    let activity = DatabaseEntry {
+        state: Box::new(Activity {
+            user_id: Some(user.id),
+            start_time,
+            end_time,
+            duration: duration as f64 * 60.0,
+            load: Some(strength_load(&sets, &preferences)),
+            avg_heartrate: None,
+            rpe: None,
+            device: None,
+            indoor: false,
+            footpod: None,
+            gps_distance_ratio: None,
+        }),
+        extra: New,
+    };
+    let session = DatabaseEntry {
+        state: Box::new(Session {
+            start_time,
+            end_time,
+            sport: Some("training".to_string()),
+            sub_sport: Some("strength_training".to_string()),
+            distance: None,
+            calories: None,
+            average_heartrate: None,
+            min_heartrate: None,
+            max_heartrate: None,
+            average_power: None,
+            ascent: None,
+            descent: None,
+            average_speed: None,
+            max_speed: None,
+            pool_length: None,
+        }),
+        extra: New,
+    };
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    let activity = insert_activity(activity, user.id, &mut *transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't save activity: {}", e)))?;
+    let activity_id = activity.extra.activity_id;
+    insert_sessions(vec![session], activity_id, &mut *transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't save session: {}", e)))?;
+    insert_strength_sets(sets, activity_id, user.id, &mut transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't save sets: {}", e)))?;
+    assign_sports(user.id, Some(activity_id), &mut transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't assign sports: {}", e)))?;
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't save activity: {}", e)))?;
+    match_new_activity_to_workout(activity_id, user.id, &pool).await;
+    Ok(())
+}
+
+#[component]
+pub fn StrengthLogDialog(show: RwSignal<bool>) -> impl IntoView {
+    let uploaded = use_context::<FitFileUploaded>().unwrap();
+    let log_action = create_server_action::<LogStrengthSession>();
+    let set_count = create_rw_signal(1);
+    let exercises = create_rw_signal(Vec::new());
+    create_effect(move |_| {
+        if show() {
+            set_count.set(1);
+            spawn_local(async move {
+                exercises.set(get_exercises().await.unwrap_or_default());
+            });
+        }
+    });
+    create_effect(move |_| {
+        if let Some(Ok(_)) = log_action.value().get() {
+            show.set(false);
+            uploaded.0.update(|v| *v += 1);
+        }
+    });
+    let close = move |_| show.set(false);
+    view! {
+        <Show when=move || { show() } fallback=|| {}>
+            <ActionForm action=log_action>
+                <div class="modal is-active">
+                    <div class="modal-background" on:click=close></div>
+                    <div class="modal-card">
+                        <div class="modal-card-head">
+                            <p class="modal-card-title">"Log Strength Session"</p>
+                            <button class="delete" aria-label="close" on:click=close></button>
+                        </div>
+                        <div class="modal-card-body">
+                            <div class="field is-grouped">
+                                <div class="control is-expanded">
+                                    <label class="label" for="start_time">
+                                        Start
+                                    </label>
+                                    <input
+                                        class="input"
+                                        id="start_time"
+                                        name="start_time"
+                                        type="datetime-local"
+                                        required
+                                    />
+                                </div>
+                                <div class="control">
+                                    <label class="label" for="duration">
+                                        Duration (min)
+                                    </label>
+                                    <input
+                                        class="input"
+                                        id="duration"
+                                        name="duration"
+                                        type="number"
+                                        min="1"
+                                        value="45"
+                                    />
+                                </div>
+                            </div>
+                            <datalist id="exercises">
+                                {move || {
+                                    exercises
+                                        .get()
+                                        .into_iter()
+                                        .map(|e| view! { <option value=e></option> })
+                                        .collect_view()
+                                }}
+
+                            </datalist>
+                            <table class="table is-narrow is-fullwidth">
+                                <thead>
+                                    <tr>
+                                        <th>Exercise</th>
+                                        <th>Reps</th>
+                                        <th>Weight (kg)</th>
+                                        <th>Rest (s)</th>
+                                    </tr>
+                                </thead>
+                                <tbody>
+                                    <For each=move || 0..set_count.get() key=|i| *i let:i>
+                                        <tr>
+                                            <td>
+                                                <input
+                                                    class="input is-small"
+                                                    type="text"
+                                                    list="exercises"
+                                                    name=format!("set[{}][exercise]", i)
+                                                />
+                                            </td>
+                                            <td>
+                                                <input
+                                                    class="input is-small"
+                                                    type="number"
+                                                    min="0"
+                                                    value="10"
+                                                    name=format!("set[{}][repetitions]", i)
+                                                />
+                                            </td>
+                                            <td>
+                                                <input
+                                                    class="input is-small"
+                                                    type="number"
+                                                    min="0"
+                                                    step="0.5"
+                                                    value="0"
+                                                    name=format!("set[{}][weight]", i)
+                                                />
+                                            </td>
+                                            <td>
+                                                <input
+                                                    class="input is-small"
+                                                    type="number"
+                                                    min="0"
+                                                    value="90"
+                                                    name=format!("set[{}][rest]", i)
+                                                />
+                                            </td>
+                                        </tr>
+                                    </For>
+                                </tbody>
+                            </table>
+                            <button
+                                type="button"
+                                class="button is-small"
+                                on:click=move |_| set_count.update(|c| *c += 1)
+                            >
+                                <i class="material-symbols-rounded">add</i>
+                                Add Set
+                            </button>
+                            {move || {
+                                log_action
+                                    .value()
+                                    .get()
+                                    .and_then(|r| r.err())
+                                    .map(|e| {
+                                        view! { <pre class="error">"Error: " {e.to_string()}</pre> }
+                                    })
+                            }}
+
+                        </div>
+                        <div class="modal-card-foot">
+                            <button type="submit" class="button is-success">
+                                Save
+                            </button>
+                            <button type="button" class="button" on:click=close>
+                                Cancel
+                            </button>
+                        </div>
+                    </div>
+                </div>
+            </ActionForm>
+        </Show>
+    }
+}
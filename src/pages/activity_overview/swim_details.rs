@@ -0,0 +1,204 @@
+use crate::models::swim_length::format_pace;
+use chrono::{DateTime, Local};
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::Type))]
+pub struct SwimLength {
+    pub start_time: DateTime<Local>,
+    pub duration: f64,
+    pub active: bool,
+    pub stroke: Option<String>,
+    pub strokes: Option<i32>,
+}
+
+impl SwimLength {
+    /// Seconds plus strokes of a length, lower is more efficient.
+    pub fn swolf(&self) -> Option<f64> {
+        self.strokes.map(|s| self.duration.round() + s as f64)
+    }
+}
+
+/// Consecutive active lengths between two rests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwimInterval {
+    pub lengths: usize,
+    /// Time swum in seconds.
+    pub duration: f64,
+    pub swolf: Option<f64>,
+    /// Rest after the interval in seconds.
+    pub rest: f64,
+}
+
+/// Groups the lengths of a swim into intervals separated by rests.
+pub fn swim_intervals(lengths: &[SwimLength]) -> Vec<SwimInterval> {
+    let mut intervals: Vec<SwimInterval> = Vec::new();
+    let mut swolfs: Vec<Vec<f64>> = Vec::new();
+    let mut resting = true;
+    for length in lengths {
+        if !length.active {
+            if let Some(interval) = intervals.last_mut() {
+                interval.rest += length.duration;
+            }
+            resting = true;
+            continue;
+        }
+        if resting {
+            intervals.push(SwimInterval {
+                lengths: 0,
+                duration: 0.0,
+                swolf: None,
+                rest: 0.0,
+            });
+            swolfs.push(Vec::new());
+            resting = false;
+        }
+        let interval = intervals.last_mut().unwrap();
+        interval.lengths += 1;
+        interval.duration += length.duration;
+        if let (Some(swolf), Some(interval_swolfs)) = (length.swolf(), swolfs.last_mut()) {
+            interval_swolfs.push(swolf);
+        }
+    }
+    for (interval, swolfs) in intervals.iter_mut().zip(swolfs) {
+        if !swolfs.is_empty() {
+            interval.swolf = Some(swolfs.iter().sum::<f64>() / swolfs.len() as f64);
+        }
+    }
+    intervals
+}
+
+/// Pace per 100m over `distance` meters swum in `duration` seconds.
+fn pace_per_100m(duration: f64, distance: f64) -> String {
+    if distance > 0.0 {
+        format_pace(duration / distance * 100.0)
+    } else {
+        String::new()
+    }
+}
+
+/// Per length and per interval tables of a pool swim.
+#[component]
+pub fn SwimTables(lengths: Vec<SwimLength>, pool_length: f64) -> impl IntoView {
+    let intervals = swim_intervals(&lengths);
+    view! {
+        <div class="columns">
+            <div class="column is-fullwidth">
+                <table class="table is-striped is-hoverable is-fullwidth">
+                    <thead>
+                        <tr>
+                            <th>Interval</th>
+                            <th>Lengths</th>
+                            <th>Distance</th>
+                            <th>Time</th>
+                            <th>Pace</th>
+                            <th>Avg. SWOLF</th>
+                            <th>Rest</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {intervals
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, interval)| {
+                                let distance = interval.lengths as f64 * pool_length;
+                                view! {
+                                    <tr>
+                                        <td>{i + 1}</td>
+                                        <td>{interval.lengths}</td>
+                                        <td>{format!("{} m", distance.round())}</td>
+                                        <td>{format_pace(interval.duration)}</td>
+                                        <td>
+                                            {pace_per_100m(interval.duration, distance)} " /100m"
+                                        </td>
+                                        <td>{interval.swolf.map(|s| format!("{:.0}", s))}</td>
+                                        <td>{format_pace(interval.rest)}</td>
+                                    </tr>
+                                }
+                            })
+                            .collect_view()}
+                    </tbody>
+                </table>
+            </div>
+        </div>
+        <details>
+            <summary class="is-size-7">Lengths</summary>
+            <table class="table is-striped is-hoverable is-fullwidth is-narrow">
+                <thead>
+                    <tr>
+                        <th>Length</th>
+                        <th>Stroke</th>
+                        <th>Time</th>
+                        <th>Strokes</th>
+                        <th>Pace</th>
+                        <th>SWOLF</th>
+                    </tr>
+                </thead>
+                <tbody>
+                    {lengths
+                        .into_iter()
+                        .filter(|length| length.active)
+                        .enumerate()
+                        .map(|(i, length)| {
+                            view! {
+                                <tr>
+                                    <td>{i + 1}</td>
+                                    <td>{length.stroke.clone()}</td>
+                                    <td>{format_pace(length.duration)}</td>
+                                    <td>{length.strokes}</td>
+                                    <td>{pace_per_100m(length.duration, pool_length)} " /100m"</td>
+                                    <td>{length.swolf()}</td>
+                                </tr>
+                            }
+                        })
+                        .collect_view()}
+                </tbody>
+            </table>
+        </details>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{swim_intervals, SwimInterval, SwimLength};
+    #[cfg(feature = "ssr")]
+    use chrono::Local;
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_swim_intervals() {
+        let length = |duration: f64, active: bool, strokes: Option<i32>| SwimLength {
+            start_time: Local::now(),
+            duration,
+            active,
+            stroke: None,
+            strokes,
+        };
+        let lengths = vec![
+            length(5.0, false, None),
+            length(20.0, true, Some(10)),
+            length(22.0, true, Some(12)),
+            length(30.0, false, None),
+            length(25.0, true, None),
+        ];
+        assert_eq!(
+            swim_intervals(&lengths),
+            vec![
+                SwimInterval {
+                    lengths: 2,
+                    duration: 42.0,
+                    swolf: Some(32.0),
+                    rest: 30.0
+                },
+                SwimInterval {
+                    lengths: 1,
+                    duration: 25.0,
+                    swolf: None,
+                    rest: 0.0
+                }
+            ]
+        );
+    }
+}
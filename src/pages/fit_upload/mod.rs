@@ -1,5 +1,3 @@
-#[cfg(feature = "ssr")]
-use itertools::Itertools;
 use std::time::Duration;
 
 #[cfg(feature = "ssr")]
@@ -23,21 +21,35 @@ use crate::app::FitFileUploaded;
 #[cfg(feature = "ssr")]
 use crate::authentication::User;
 #[cfg(feature = "ssr")]
+use crate::config::Config;
+#[cfg(feature = "ssr")]
 use crate::models::{
-    activity::{insert_activity, Activity},
+    activity::{device_name, insert_activity, Activity},
+    activity_edit::recalculate_session_stats,
     base::{DatabaseEntry, New},
+    calibration::{
+        calibrate_distance, footpod_calibration, footpod_name, gps_distance_ratio,
+        is_calibrated_sport, is_indoor_sub_sport,
+    },
+    elevation::{corrected_altitudes, dem_elevation, load_dem_tiles, update_activity_climb},
+    heartrate_recovery::update_heartrate_recovery,
+    heatmap::update_heatmap_cells,
     lap::{insert_laps, Lap},
     record::{insert_records, Record},
-    session::{insert_sessions, Session},
-    slope_speed::{insert_slopes, slope_speed_from_records, SlopeSpeed},
+    segment::match_activity_segments,
+    session::{insert_sessions, update_session_loads, Session},
+    slope_speed::{insert_slopes, slopes_from_records},
+    sport::assign_sports,
+    strength_set::{insert_strength_sets, merge_rest, strength_load, FitSet},
+    swim_length::{insert_swim_lengths, swim_load, SwimLength},
+    track_cleanup::clean_track,
     user_preferences::get_user_preferences,
+    workout_completion::match_new_activity_to_workout,
 };
 #[cfg(feature = "ssr")]
 use crate::state::AppState;
 #[cfg(feature = "ssr")]
 use axum_session_auth::{AuthSession, SessionPgPool};
-#[cfg(feature = "ssr")]
-use chrono::TimeDelta;
 
 #[cfg(feature = "ssr")]
 pub async fn upload_fit_file(
@@ -65,7 +77,11 @@ async fn process_fit_file<'a>(data: Bytes, user_id: i64, executor: PgPool) -> Re
     let mut records: Vec<DatabaseEntry<New, Record>> = Vec::new();
     let mut sessions: Vec<DatabaseEntry<New, Session>> = Vec::new();
     let mut laps: Vec<DatabaseEntry<New, Lap>> = Vec::new();
+    let mut sets: Vec<FitSet> = Vec::new();
+    let mut lengths: Vec<DatabaseEntry<New, SwimLength>> = Vec::new();
     let mut activity: Option<DatabaseEntry<New, Activity>> = None;
+    let mut device: Option<String> = None;
+    let mut footpod: Option<String> = None;
     for data in fitparser::from_bytes(&data).context("Failed to read fit file")? {
         match data.kind() {
             fitparser::profile::MesgNum::Record => {
@@ -86,6 +102,16 @@ async fn process_fit_file<'a>(data: Bytes, user_id: i64, executor: PgPool) -> Re
                     .map(|lap| laps.push(lap))
                     .context("Couldn't parse record")?;
             }
+            fitparser::profile::MesgNum::Set => {
+                FitSet::try_from(data)
+                    .map(|set| sets.push(set))
+                    .context("Couldn't parse record")?;
+            }
+            fitparser::profile::MesgNum::Length => {
+                DatabaseEntry::<New, SwimLength>::try_from(data)
+                    .map(|length| lengths.push(length))
+                    .context("Couldn't parse record")?;
+            }
             fitparser::profile::MesgNum::Activity => {
                 if activity.is_some() {
                     bail!("Found more than one activity");
@@ -95,7 +121,11 @@ async fn process_fit_file<'a>(data: Bytes, user_id: i64, executor: PgPool) -> Re
                         .expect("no activity entry found"),
                 );
             }
+            fitparser::profile::MesgNum::FileId => {
+                device = device_name(&data);
+            }
             fitparser::profile::MesgNum::DeviceInfo => {
+                footpod = footpod.or(footpod_name(&data));
                 leptos::logging::log!("Device Info: {:?}", data);
             }
             _ => {
@@ -104,6 +134,50 @@ async fn process_fit_file<'a>(data: Bytes, user_id: i64, executor: PgPool) -> Re
         }
     }
     if let Some(mut activity) = activity {
+        activity.state.device = device;
+        let track_cleaned = clean_track(&mut records, &Config::global().gps_cleanup);
+        // without any position the distance comes from a footpod or the accelerometer
+        let has_position = records.iter().any(|r| r.state.latitude.is_some());
+        let has_distance = records.iter().any(|r| r.state.distance.is_some());
+        activity.state.indoor = sessions.iter().any(|s| {
+            s.state
+                .sub_sport
+                .as_deref()
+                .is_some_and(is_indoor_sub_sport)
+        }) || (has_distance && !has_position);
+        // footpods are only calibrated for sports they measure the distance of
+        let calibration_sport = sessions
+            .iter()
+            .find_map(|s| s.state.sport.clone())
+            .filter(|sport| is_calibrated_sport(sport))
+            .filter(|_| footpod.is_some());
+        activity.state.footpod = footpod;
+        if calibration_sport.is_some() && !activity.state.indoor {
+            activity.state.gps_distance_ratio = gps_distance_ratio(&records);
+        }
+        let indoor = activity.state.indoor;
+        // the elevation model is less noisy than the recorded altitude of a GPS track
+        let positions: Vec<_> = records
+            .iter()
+            .map(|r| r.state.latitude.zip(r.state.longitude))
+            .collect();
+        let altitudes = if !indoor && has_position {
+            let tiles = load_dem_tiles(&positions).await;
+            corrected_altitudes(&positions, |lat, long| dem_elevation(&tiles, lat, long))
+        } else {
+            None
+        };
+        let altitude_corrected = altitudes.is_some();
+        for (record, altitude) in records.iter_mut().zip(altitudes.unwrap_or_default()) {
+            record.state.raw_altitude = record.state.altitude;
+            record.state.altitude = Some(altitude);
+        }
+        let calibration = activity
+            .state
+            .footpod
+            .clone()
+            .zip(calibration_sport)
+            .filter(|_| indoor);
         let preferences = get_user_preferences(user_id, activity.state.start_time, &executor).await;
         let hr_measurements: Vec<_> = records
             .iter()
@@ -115,6 +189,19 @@ async fn process_fit_file<'a>(data: Bytes, user_id: i64, executor: PgPool) -> Re
             // calculate training load
             activity.state.load = Some(preferences.calculate_load(hr_measurements));
         }
+        let sets = merge_rest(sets);
+        if !sets.is_empty() {
+            // heart rate underestimates strength sessions, so we take the higher load
+            let load = strength_load(&sets, &preferences);
+            activity.state.load = Some(activity.state.load.map_or(load, |l| l.max(load)));
+        }
+        let pool_length = sessions.iter().find_map(|s| s.state.pool_length);
+        if let (Some(pool_length), false) = (pool_length, lengths.is_empty()) {
+            // heart rate is unreliable in the water, so pace gives the swim load
+            let lengths: Vec<_> = lengths.iter().map(|l| (*l.state).clone()).collect();
+            let load = swim_load(&lengths, pool_length, preferences.critical_swim_speed);
+            activity.state.load = Some(activity.state.load.map_or(load, |l| l.max(load)));
+        }
 
         let mut tx = executor.begin().await?;
         let result = insert_activity(activity, user_id, &mut *tx).await;
@@ -126,6 +213,10 @@ async fn process_fit_file<'a>(data: Bytes, user_id: i64, executor: PgPool) -> Re
         if let Err(x) = result {
             bail!("couldn't insert records: {}", x);
         }
+        let result = update_heatmap_cells(activity.extra.activity_id, &mut tx).await;
+        if let Err(x) = result {
+            bail!("couldn't update heatmap: {}", x);
+        }
         let result = insert_sessions(sessions.clone(), activity.extra.activity_id, &mut *tx).await;
         if let Err(x) = result {
             bail!("couldn't insert sessions: {}", x);
@@ -134,47 +225,88 @@ async fn process_fit_file<'a>(data: Bytes, user_id: i64, executor: PgPool) -> Re
         if let Err(x) = result {
             bail!("couldn't insert laps: {}", x);
         }
-        //calculate slope
-        let slopes = records
-            .iter()
-            .filter(|r| {
-                r.state.timestamp
-                    > activity
-                        .state
-                        .start_time
-                        .checked_add_signed(TimeDelta::minutes(10))
-                        .unwrap()
-                    && r.state.distance.is_some()
-                    && r.state.altitude.is_some()
-                    && r.state.speed.is_some()
-                    && r.state.heartrate.is_some()
-            })
-            .group_by(|r| (r.state.distance.unwrap() / 100.0).floor())
-            .into_iter()
-            .map(|(_, group)| {
-                slope_speed_from_records(
-                    group.cloned().collect::<Vec<_>>(),
-                    &sessions,
-                    user_id,
-                    &preferences,
-                )
-            })
-            .filter_map(|s| s.ok())
-            .map(|s| DatabaseEntry {
-                state: Box::new(s),
-                extra: New,
-            })
-            .collect::<Vec<DatabaseEntry<New, SlopeSpeed>>>();
+        if track_cleaned {
+            // distances and speeds of the device include the glitches that were removed
+            let result = recalculate_session_stats(activity.extra.activity_id, &mut tx).await;
+            if let Err(x) = result {
+                bail!("couldn't update session statistics: {}", x);
+            }
+        }
+        if altitude_corrected {
+            let result = update_activity_climb(activity.extra.activity_id, &mut tx).await;
+            if let Err(x) = result {
+                bail!("couldn't update ascent: {}", x);
+            }
+        }
+        if !sets.is_empty() {
+            let result =
+                insert_strength_sets(sets, activity.extra.activity_id, user_id, &mut tx).await;
+            if let Err(x) = result {
+                bail!("couldn't insert strength sets: {}", x);
+            }
+        }
+        if !lengths.is_empty() {
+            let result = insert_swim_lengths(lengths, activity.extra.activity_id, &mut tx).await;
+            if let Err(x) = result {
+                bail!("couldn't insert swim lengths: {}", x);
+            }
+        }
+        //calculate slope, indoor activities have no usable altitude
+        let slopes = if indoor {
+            Vec::new()
+        } else {
+            slopes_from_records(
+                &records,
+                &sessions,
+                activity.state.start_time,
+                user_id,
+                &preferences,
+            )
+        };
         if slopes.len() > 0 {
             let result = insert_slopes(slopes, activity.extra.activity_id, &mut *tx).await;
             if let Err(x) = result {
                 bail!("couldn't insert slope: {}", x)
             }
         }
+        if let Some((footpod, sport)) = calibration {
+            let result = footpod_calibration(user_id, &footpod, &sport, &mut tx).await;
+            let calibration = match result {
+                Ok(calibration) => calibration,
+                Err(x) => bail!("couldn't load footpod calibration: {}", x),
+            };
+            if let Some(calibration) = calibration {
+                let result =
+                    calibrate_distance(activity.extra.activity_id, user_id, calibration, &mut tx)
+                        .await;
+                if let Err(x) = result {
+                    bail!("couldn't calibrate distance: {}", x)
+                }
+            }
+        }
+        let result = assign_sports(user_id, Some(activity.extra.activity_id), &mut tx).await;
+        if let Err(x) = result {
+            bail!("couldn't assign sports: {}", x)
+        }
+        let result = update_session_loads(activity.extra.activity_id, user_id, &mut tx).await;
+        if let Err(x) = result {
+            bail!("couldn't calculate session loads: {}", x)
+        }
+        let result = update_heartrate_recovery(activity.extra.activity_id, user_id, &mut tx).await;
+        if let Err(x) = result {
+            bail!("couldn't calculate heart rate recovery: {}", x)
+        }
         let tx_result = tx.commit().await;
         if let Err(x) = tx_result {
             bail!("Transaction failed, try again: {}", x);
         };
+        match_new_activity_to_workout(activity.extra.activity_id, user_id, &executor).await;
+        let mut connection = executor.acquire().await?;
+        let result =
+            match_activity_segments(activity.extra.activity_id, user_id, &mut connection).await;
+        if let Err(x) = result {
+            leptos::logging::log!("couldn't match activity to segments: {}", x);
+        }
     } else {
         bail!("No activity found in fit file, may be corrupt");
     };
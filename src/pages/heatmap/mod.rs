@@ -0,0 +1,291 @@
+use crate::app::FitFileUploaded;
+#[cfg(feature = "ssr")]
+use crate::app::{auth, pool};
+#[cfg(feature = "ssr")]
+use crate::models::heatmap::HEATMAP_PRECISION;
+use crate::models::heatmap::{geohash_bounds, MapBounds};
+use crate::pages::workout_schedule::manage_sports_dialog::{indented_name, list_sports};
+use chrono::NaiveDate;
+use leptos::*;
+use leptos_leaflet::*;
+use serde::{Deserialize, Serialize};
+
+/// Number of recorded positions of all activities within a geohash cell.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeatmapCell {
+    pub geohash: String,
+    pub points: i64,
+}
+
+/// Most cells sent to the browser, the densest ones are kept.
+#[cfg(feature = "ssr")]
+const MAX_CELLS: i64 = 5000;
+
+/// Heatmap cells of all outdoor activities, aggregated to geohashes of length `precision`.
+///
+/// With `bounds` only cells around the visible part of the map are loaded, so the limit applies
+/// to what's on screen.
+#[server]
+pub async fn get_heatmap(
+    sport: Option<i64>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    precision: usize,
+    bounds: Option<MapBounds>,
+) -> Result<Vec<HeatmapCell>, ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let precision = precision.clamp(4, HEATMAP_PRECISION);
+    let cover = bounds.map(|b| b.geohash_cover(HEATMAP_PRECISION));
+    let cover_chars = cover
+        .as_ref()
+        .and_then(|c| c.first())
+        .map_or(0, |c| c.len());
+    let cells = sqlx::query_as!(
+        HeatmapCell,
+        r#"
+        SELECT LEFT(heatmap_cells.geohash, $2) as "geohash!", SUM(heatmap_cells.points)::int8 as "points!"
+        FROM heatmap_cells
+        INNER JOIN activities ON activities.id = heatmap_cells.activity_id
+        WHERE activities.user_id = $1::bigint
+            AND NOT activities.indoor
+            AND ($3::date IS NULL OR activities.start_time >= $3::date)
+            AND ($4::date IS NULL OR activities.start_time < $4::date + 1)
+            AND (
+                $5::bigint IS NULL
+                OR EXISTS (
+                    SELECT 1
+                    FROM sessions
+                    WHERE sessions.activity_id = activities.id
+                        AND sessions.sport_id IN (SELECT sport_subtree($5::bigint))
+                )
+            )
+            AND ($7::text[] IS NULL OR LEFT(heatmap_cells.geohash, $8) = ANY($7::text[]))
+        GROUP BY 1
+        ORDER BY 2 DESC
+        LIMIT $6
+        "#,
+        user.id,
+        precision as i32,
+        from,
+        to,
+        sport,
+        MAX_CELLS,
+        cover.as_deref(),
+        cover_chars as i32
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load heatmap: {}", e)))?;
+    Ok(cells)
+}
+
+/// Map of everywhere the user has trained, colored by how often.
+#[component]
+pub fn Heatmap() -> impl IntoView {
+    let uploaded = use_context::<FitFileUploaded>().unwrap();
+    let sport = create_rw_signal(None::<i64>);
+    let from = create_rw_signal(None::<NaiveDate>);
+    let to = create_rw_signal(None::<NaiveDate>);
+    let precision = create_rw_signal(6_usize);
+    let bounds = create_rw_signal(None::<MapBounds>);
+    let sports = create_resource(|| (), |_| list_sports());
+    let cells = create_resource(
+        move || {
+            (
+                sport.get(),
+                from.get(),
+                to.get(),
+                precision.get(),
+                bounds.get(),
+                uploaded.0.get(),
+            )
+        },
+        |(sport, from, to, precision, bounds, _)| get_heatmap(sport, from, to, precision, bounds),
+    );
+    let loaded_cells =
+        Signal::derive(move || cells.get().and_then(|cells| cells.ok()).unwrap_or_default());
+    // the map is centered on the densest cell once, afterwards it loads what's in view
+    let center = create_memo(move |previous: Option<&Option<(f64, f64)>>| {
+        previous.copied().flatten().or_else(|| {
+            loaded_cells.with(|cells| {
+                cells.first().and_then(|c| geohash_bounds(&c.geohash)).map(
+                    |((south, west), (north, east))| ((south + north) / 2.0, (west + east) / 2.0),
+                )
+            })
+        })
+    });
+    let parse_date =
+        |ev: ev::Event| NaiveDate::parse_from_str(&event_target_value(&ev), "%Y-%m-%d").ok();
+    view! {
+        <div class="container is-fluid">
+            <div class="field is-grouped is-grouped-multiline">
+                <div class="control">
+                    <label class="label is-small" for="heatmap_sport">
+                        Sport
+                    </label>
+                    <div class="select is-small">
+                        <select
+                            id="heatmap_sport"
+                            on:change=move |ev| sport.set(event_target_value(&ev).parse().ok())
+                        >
+                            <option value="">All sports</option>
+                            {move || {
+                                sports
+                                    .get()
+                                    .and_then(|s| s.ok())
+                                    .unwrap_or_default()
+                                    .into_iter()
+                                    .map(|s| {
+                                        view! { <option value=s.id>{indented_name(&s)}</option> }
+                                    })
+                                    .collect_view()
+                            }}
+
+                        </select>
+                    </div>
+                </div>
+                <div class="control">
+                    <label class="label is-small" for="heatmap_from">
+                        From
+                    </label>
+                    <input
+                        class="input is-small"
+                        id="heatmap_from"
+                        type="date"
+                        on:change=move |ev| from.set(parse_date(ev))
+                    />
+                </div>
+                <div class="control">
+                    <label class="label is-small" for="heatmap_to">
+                        To
+                    </label>
+                    <input
+                        class="input is-small"
+                        id="heatmap_to"
+                        type="date"
+                        on:change=move |ev| to.set(parse_date(ev))
+                    />
+                </div>
+                <div class="control">
+                    <label class="label is-small" for="heatmap_precision">
+                        Detail
+                    </label>
+                    <div class="select is-small">
+                        <select
+                            id="heatmap_precision"
+                            on:change=move |ev| {
+                                if let Ok(value) = event_target_value(&ev).parse() {
+                                    precision.set(value);
+                                }
+                            }
+                        >
+
+                            <option value="5">Coarse</option>
+                            <option value="6" selected>
+                                Medium
+                            </option>
+                            <option value="7">Fine</option>
+                        </select>
+                    </div>
+                </div>
+            </div>
+            <Transition fallback=move || {
+                view! { <p>"Loading..."</p> }
+            }>
+                {move || {
+                    cells
+                        .get()
+                        .map(|cells| match cells {
+                            Err(e) => {
+                                view! { <pre class="error">"Error: " {e.to_string()}</pre> }
+                                    .into_view()
+                            }
+                            Ok(cells) if cells.is_empty() => {
+                                view! { <p>"No GPS tracks recorded for these filters."</p> }
+                                    .into_view()
+                            }
+                            Ok(_) => ().into_view(),
+                        })
+                }}
+                {move || {
+                    center
+                        .get()
+                        .map(|center| {
+                            view! { <HeatmapLayer cells=loaded_cells center bounds/> }
+                        })
+                }}
+
+            </Transition>
+        </div>
+    }
+}
+
+/// Draws heatmap cells on a map, `bounds` follows the visible part of the map.
+#[component]
+fn HeatmapLayer(
+    #[prop(into)] cells: Signal<Vec<HeatmapCell>>,
+    center: (f64, f64),
+    bounds: RwSignal<Option<MapBounds>>,
+) -> impl IntoView {
+    let (map, set_map) = create_signal(None::<leaflet::Map>);
+    let events = MapEvents::new().move_end(move |_| {
+        map.with_untracked(|map| {
+            if let Some(map) = map {
+                let visible = map.get_bounds();
+                let (south_west, north_east) = (visible.get_south_west(), visible.get_north_east());
+                bounds.set(Some(MapBounds {
+                    south: south_west.lat(),
+                    west: south_west.lng(),
+                    north: north_east.lat(),
+                    east: north_east.lng(),
+                }));
+            }
+        })
+    });
+    view! {
+        <MapContainer
+            style="height:75vh;"
+            center=Position::new(center.0, center.1)
+            zoom=12.0
+            set_view=true
+            map=set_map
+            events
+        >
+            <TileLayer url="https://tile.openstreetmap.org/{z}/{x}/{y}.png"/>
+            {move || {
+                let cells = cells.get();
+                let max_points = cells.iter().map(|c| c.points).max().unwrap_or(1) as f64;
+                cells
+                    .into_iter()
+                    .filter_map(|cell| {
+                        let ((south, west), (north, east)) = geohash_bounds(&cell.geohash)?;
+                        let density = (cell.points as f64).ln_1p() / max_points.ln_1p();
+                        Some(
+                            view! {
+                                // logarithmic, so a single pass still shows up next to the daily commute
+                                <Polygon
+                                    positions=vec![
+                                        Position::new(south, west),
+                                        Position::new(north, west),
+                                        Position::new(north, east),
+                                        Position::new(south, east),
+                                    ]
+
+                                    stroke=false
+                                    fill_color="#ff3860"
+                                    fill_opacity=0.2 + 0.7 * density
+                                    interactive=false
+                                />
+                            },
+                        )
+                    })
+                    .collect_view()
+            }}
+
+        </MapContainer>
+    }
+}
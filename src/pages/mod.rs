@@ -1,8 +1,11 @@
 pub mod activity_overview;
 pub mod auth;
 pub mod fit_upload;
+pub mod heatmap;
 pub mod home;
 pub mod landing;
 pub mod overview;
+pub mod route_planner;
+pub mod segments;
 pub mod user;
 pub mod workout_schedule;
@@ -24,17 +24,18 @@ pub struct TrainingLoad {
 pub async fn daily_training_load(
     user_id: i64,
     to: DateTime<Local>,
+    sport: Option<i64>,
     executor: sqlx::PgPool,
 ) -> Result<Vec<TrainingLoad>, sqlx::Error> {
     let result: Vec<TrainingLoad> = sqlx::query_as!(
         TrainingLoad,
         r#"
             SELECT 
-                SUM(a.load) as "load!",
+                ROUND(SUM(a.load))::int8 as "load!",
                 a.date as "date!"
             FROM (
                 SELECT 
-                    COALESCE(activities.load, 0) as load,
+                    COALESCE(session_loads.load, 0) as load,
                     d.dt as date
                 FROM
                 (
@@ -47,7 +48,9 @@ pub async fn daily_training_load(
                             '1 day') dt
                 ) d
                 
-                LEFT JOIN activities on date_trunc('day',activities.start_time) = d.dt AND activities.user_id=$1::bigint
+                LEFT JOIN session_loads on date_trunc('day',session_loads.start_time) = d.dt
+                    AND session_loads.user_id=$1::bigint
+                    AND ($3::bigint IS NULL OR session_loads.sport_id IN (SELECT sport_subtree($3::bigint)))
              ) a
             
             GROUP BY a.date
@@ -55,7 +58,8 @@ pub async fn daily_training_load(
             "#
 ,
         &user_id,
-        &to
+        &to,
+        sport
     )
     .fetch_all(&executor)
     .await?;
@@ -65,6 +69,7 @@ pub async fn daily_training_load(
 #[server]
 pub async fn daily_training_load_action(
     to: Option<DateTime<Local>>,
+    sport: Option<i64>,
 ) -> Result<Vec<TrainingLoad>, ServerFnError> {
     let auth = auth()?;
     if auth.current_user.is_none() {
@@ -72,7 +77,7 @@ pub async fn daily_training_load_action(
     }
     let user = auth.current_user.expect("the user to be logged in");
     let pool = pool()?;
-    let summary = daily_training_load(user.id, to.unwrap_or(Local::now()), pool).await?;
+    let summary = daily_training_load(user.id, to.unwrap_or(Local::now()), sport, pool).await?;
     Ok(summary)
 }
 
@@ -80,11 +85,12 @@ pub async fn daily_training_load_action(
 pub fn FitnessLevelChart(
     #[prop(into)] from: Memo<Option<DateTime<Local>>>,
     #[prop(into)] to: Memo<Option<DateTime<Local>>>,
+    #[prop(into)] sport: Signal<Option<i64>>,
 ) -> impl IntoView {
     let uploaded = use_context::<FitFileUploaded>().unwrap();
     let training_load = create_resource(
-        move || (to.get(), uploaded.0()),
-        move |(to, _)| daily_training_load_action(to),
+        move || (to.get(), sport.get(), uploaded.0()),
+        move |(to, sport, _)| daily_training_load_action(to, sport),
     );
     let fitness_chart = create_node_ref::<Div>();
     let UseElementSizeReturn { width, height: _ } = use_element_size(fitness_chart);
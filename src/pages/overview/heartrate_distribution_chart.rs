@@ -29,6 +29,7 @@ pub async fn heartrate_zone_summary(
     user_id: i64,
     from: DateTime<Local>,
     to: DateTime<Local>,
+    sport: Option<i64>,
     executor: sqlx::PgPool,
 ) -> Result<Vec<HeartrateDistributionEntry>, sqlx::Error> {
     let result = sqlx::query_as!(HeartrateDistributionEntry, r#"
@@ -40,9 +41,14 @@ pub async fn heartrate_zone_summary(
     LEFT JOIN user_preferences up ON up.user_id=a.user_id
     WHERE a.user_id = $1::bigint AND a.start_time >= $2::timestamptz AND a.end_time <= $3::timestamptz
         AND r.heartrate IS NOT NULL AND r.heartrate >= COALESCE(up.max_heartrate * 0.55, 100)
+        AND ($4::bigint IS NULL OR EXISTS (
+            SELECT 1 FROM sessions s
+            WHERE s.activity_id = r.activity_id AND r.timestamp >= s.start_time AND r.timestamp <= s.end_time
+                AND s.sport_id IN (SELECT sport_subtree($4::bigint))
+        ))
     GROUP BY r.heartrate
     ORDER BY r.heartrate ASC
-"#, &user_id, &from,&to).fetch_all(&executor).await?;
+"#, &user_id, &from,&to, sport).fetch_all(&executor).await?;
     Ok(result)
 }
 
@@ -50,6 +56,7 @@ pub async fn heartrate_zone_summary(
 pub async fn heartrate_distribution_action(
     from: Option<DateTime<Local>>,
     to: Option<DateTime<Local>>,
+    sport: Option<i64>,
 ) -> Result<Vec<HeartrateDistributionEntry>, ServerFnError> {
     let auth = auth()?;
     if auth.current_user.is_none() {
@@ -61,6 +68,7 @@ pub async fn heartrate_distribution_action(
         user.id,
         from.unwrap_or(Local::now() - Duration::try_days(120).unwrap()),
         to.unwrap_or(Local::now()),
+        sport,
         pool,
     )
     .await?;
@@ -71,11 +79,12 @@ pub async fn heartrate_distribution_action(
 pub fn HeartrateDistributionChart(
     #[prop(into)] from: Memo<Option<DateTime<Local>>>,
     #[prop(into)] to: Memo<Option<DateTime<Local>>>,
+    #[prop(into)] sport: Signal<Option<i64>>,
 ) -> impl IntoView {
     let uploaded = use_context::<FitFileUploaded>().unwrap();
     let zone_distribution = create_resource(
-        move || (from(), to(), uploaded.0()),
-        move |(from, to, _)| heartrate_distribution_action(from, to),
+        move || (from(), to(), sport(), uploaded.0()),
+        move |(from, to, sport, _)| heartrate_distribution_action(from, to, sport),
     );
     let user_prefs = create_resource(move || (), |_| async move { get_preferences().await });
     let heartrate_distibution_chart = create_node_ref::<Div>();
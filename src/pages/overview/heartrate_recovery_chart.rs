@@ -0,0 +1,175 @@
+#[cfg(feature = "ssr")]
+use crate::app::{auth, pool};
+use charming::{
+    component::{Axis, Grid, Legend},
+    datatype::CompositeValue,
+    element::{AxisType, LineStyle, LineStyleType, Tooltip, Trigger},
+    series::Line,
+    Chart, WasmRenderer,
+};
+#[cfg(feature = "ssr")]
+use chrono::Duration;
+use chrono::{DateTime, Local};
+use leptos::{html::Div, *};
+use leptos_use::{use_element_size, UseElementSizeReturn};
+use serde::{Deserialize, Serialize};
+use std::cmp;
+
+use crate::{app::FitFileUploaded, error_template::ErrorTemplate};
+
+/// Weekly averages of heart rate recovery and cardiac drift.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WeeklyRecovery {
+    pub week: DateTime<Local>,
+    pub recovery_60: Option<f64>,
+    pub recovery_120: Option<f64>,
+    pub cardiac_drift: Option<f64>,
+}
+
+#[cfg(feature = "ssr")]
+pub async fn heartrate_recovery(
+    user_id: i64,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+    sport: Option<i64>,
+    executor: sqlx::PgPool,
+) -> Result<Vec<WeeklyRecovery>, sqlx::Error> {
+    let result: Vec<WeeklyRecovery> = sqlx::query_as!(
+        WeeklyRecovery,
+        r#"
+    WITH weeks as (
+        SELECT generate_series(
+            date_trunc('week', $2::timestamptz),
+            date_trunc('week', $3::timestamptz),
+            '1 week'
+        ) as start
+    ), recoveries as (
+        SELECT
+            date_trunc('week', activities.start_time) as week,
+            heartrate_recoveries.recovery_60,
+            heartrate_recoveries.recovery_120,
+            heartrate_recoveries.cardiac_drift
+        FROM heartrate_recoveries
+        INNER JOIN activities ON activities.id = heartrate_recoveries.activity_id
+        WHERE activities.user_id = $1::bigint
+            AND (
+                $4::bigint IS NULL
+                OR EXISTS (
+                    SELECT 1 FROM sessions
+                    WHERE sessions.activity_id = activities.id
+                        AND sessions.sport_id IN (SELECT sport_subtree($4::bigint))
+                )
+            )
+    )
+    SELECT
+        weeks.start as "week!",
+        AVG(recoveries.recovery_60) as recovery_60,
+        AVG(recoveries.recovery_120) as recovery_120,
+        AVG(recoveries.cardiac_drift) as cardiac_drift
+    FROM weeks
+    LEFT JOIN recoveries ON recoveries.week = weeks.start
+    GROUP BY weeks.start
+    ORDER BY weeks.start
+"#,
+        &user_id,
+        &from,
+        &to,
+        sport
+    )
+    .fetch_all(&executor)
+    .await?;
+    Ok(result)
+}
+
+#[server(HeartrateRecoveryAction, "/api")]
+pub async fn heartrate_recovery_action(
+    from: Option<DateTime<Local>>,
+    to: Option<DateTime<Local>>,
+    sport: Option<i64>,
+) -> Result<Vec<WeeklyRecovery>, ServerFnError> {
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let pool = pool()?;
+    let from = from.unwrap_or(Local::now() - Duration::try_days(120).unwrap());
+    let to = to.unwrap_or(Local::now());
+    let recovery = heartrate_recovery(user.id, from, to, sport, pool).await?;
+    Ok(recovery)
+}
+
+#[component]
+pub fn HeartrateRecoveryChart(
+    #[prop(into)] from: Memo<Option<DateTime<Local>>>,
+    #[prop(into)] to: Memo<Option<DateTime<Local>>>,
+    #[prop(into)] sport: Signal<Option<i64>>,
+) -> impl IntoView {
+    let uploaded = use_context::<FitFileUploaded>().unwrap();
+    let recovery = create_resource(
+        move || (from(), to(), sport(), uploaded.0()),
+        move |(from, to, sport, _)| heartrate_recovery_action(from, to, sport),
+    );
+    let recovery_chart = create_node_ref::<Div>();
+    let UseElementSizeReturn { width, height: _ } = use_element_size(recovery_chart);
+    let _chart = create_local_resource(
+        move || (recovery.get(), width()),
+        move |(recovery, width)| async move {
+            if let Some(Ok(recovery)) = recovery {
+                // weeks without efforts are gaps in the lines
+                let values = |value: fn(&WeeklyRecovery) -> Option<f64>| {
+                    recovery
+                        .iter()
+                        .map(|r| match value(r) {
+                            Some(value) => CompositeValue::from((value * 10.0).round() / 10.0),
+                            None => CompositeValue::from("-"),
+                        })
+                        .collect::<Vec<_>>()
+                };
+                let chart = Chart::new()
+                    .grid(Grid::new().top(30).bottom(20))
+                    .legend(Legend::new())
+                    .tooltip(Tooltip::new().trigger(Trigger::Axis))
+                    .x_axis(
+                        Axis::new().type_(AxisType::Category).data(
+                            recovery
+                                .iter()
+                                .map(|r| format!("{}", r.week.format("%Y-%m-%d")))
+                                .collect::<Vec<_>>(),
+                        ),
+                    )
+                    .y_axis(Axis::new().type_(AxisType::Value).name("bpm"))
+                    .y_axis(Axis::new().type_(AxisType::Value).name("%"))
+                    .series(
+                        Line::new()
+                            .name("Recovery 60s")
+                            .data(values(|r| r.recovery_60)),
+                    )
+                    .series(
+                        Line::new()
+                            .name("Recovery 120s")
+                            .data(values(|r| r.recovery_120)),
+                    )
+                    .series(
+                        Line::new()
+                            .name("Cardiac Drift")
+                            .y_axis_index(1)
+                            .line_style(LineStyle::new().type_(LineStyleType::Dashed))
+                            .data(values(|r| r.cardiac_drift)),
+                    );
+                let renderer = WasmRenderer::new(cmp::max(width as u32, 300), 200);
+                let _rendered = renderer.render("heartrate_recovery_chart", &chart);
+            }
+        },
+    );
+
+    view! {
+        <Transition fallback=move || view! { <p>"Loading..."</p> }>
+            <ErrorBoundary fallback=|errors| {
+                view! { <ErrorTemplate errors=errors/> }
+            }>
+                <div node_ref=recovery_chart id="heartrate_recovery_chart"></div>
+
+            </ErrorBoundary>
+        </Transition>
+    }
+}
@@ -30,9 +30,11 @@ pub async fn heartrate_zone_summary(
     user_id: i64,
     from: DateTime<Local>,
     to: DateTime<Local>,
+    sport: Option<i64>,
     executor: sqlx::PgPool,
 ) -> Result<HeartrateSummary, sqlx::Error> {
-    let result = sqlx::query_as!(HeartrateSummary, r#"SELECT
+    let result = sqlx::query_as!(HeartrateSummary, r#"
+SELECT
     COUNT(*) FILTER (WHERE m.zone = 3) AS zone3,
     COUNT(*) FILTER (WHERE m.zone = 2) AS zone2,
     COUNT(*) FILTER (WHERE m.zone = 1) AS zone1
@@ -48,8 +50,13 @@ FROM (
     LEFT JOIN user_preferences up ON up.user_id=activities.user_id
     WHERE activities.user_id = $1::bigint AND activities.start_time >= $2::timestamptz AND activities.end_time <= $3::timestamptz
         AND record.heartrate IS NOT NULL AND record.heartrate >= COALESCE(up.max_heartrate * 0.55, 100)
+        AND ($4::bigint IS NULL OR EXISTS (
+            SELECT 1 FROM sessions s
+            WHERE s.activity_id = record.activity_id AND record.timestamp >= s.start_time AND record.timestamp <= s.end_time
+                AND s.sport_id IN (SELECT sport_subtree($4::bigint))
+        ))
 ) m
-"#, &user_id, &from,&to).fetch_one(&executor).await?;
+"#, &user_id, &from,&to, sport).fetch_one(&executor).await?;
     Ok(result)
 }
 
@@ -57,6 +64,7 @@ FROM (
 pub async fn heartrate_zone_summary_action(
     from: Option<DateTime<Local>>,
     to: Option<DateTime<Local>>,
+    sport: Option<i64>,
 ) -> Result<HeartrateSummary, ServerFnError> {
     let auth = auth()?;
     if auth.current_user.is_none() {
@@ -68,6 +76,7 @@ pub async fn heartrate_zone_summary_action(
         user.id,
         from.unwrap_or(Local::now() - Duration::try_days(120).unwrap()),
         to.unwrap_or(Local::now()),
+        sport,
         pool,
     )
     .await?;
@@ -78,11 +87,12 @@ pub async fn heartrate_zone_summary_action(
 pub fn HeartrateZoneSummaryChart(
     #[prop(into)] from: Memo<Option<DateTime<Local>>>,
     #[prop(into)] to: Memo<Option<DateTime<Local>>>,
+    #[prop(into)] sport: Signal<Option<i64>>,
 ) -> impl IntoView {
     let uploaded = use_context::<FitFileUploaded>().unwrap();
     let zone_summary = create_resource(
-        move || (from(), to(), uploaded.0()),
-        move |(from, to, _)| heartrate_zone_summary_action(from, to),
+        move || (from(), to(), sport(), uploaded.0()),
+        move |(from, to, sport, _)| heartrate_zone_summary_action(from, to, sport),
     );
     let heartrate_summary_chart = create_node_ref::<Div>();
     let UseElementSizeReturn { width, height: _ } = use_element_size(heartrate_summary_chart);
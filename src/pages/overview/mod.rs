@@ -1,14 +1,21 @@
 mod fitness_level_chart;
 mod heartrate_distribution_chart;
+mod heartrate_recovery_chart;
 mod heartrate_summary_chart;
+mod planned_load_chart;
 mod slope_speed_chart;
+mod strength_volume_chart;
 mod training_load_chart;
 
+use crate::pages::workout_schedule::manage_sports_dialog::{indented_name, list_sports};
 use chrono::{Duration, Local, NaiveDate, TimeZone};
 use fitness_level_chart::FitnessLevelChart;
 use heartrate_distribution_chart::HeartrateDistributionChart;
+use heartrate_recovery_chart::HeartrateRecoveryChart;
 use heartrate_summary_chart::HeartrateZoneSummaryChart;
 use leptos::*;
+use planned_load_chart::PlannedLoadChart;
+use strength_volume_chart::StrengthVolumeChart;
 use training_load_chart::TrainingLoadChart;
 
 use slope_speed_chart::SlopeSpeedChart;
@@ -46,6 +53,8 @@ pub fn Overview() -> impl IntoView {
                 .ok()
         })
     });
+    let sport = create_rw_signal(None::<i64>);
+    let sports = create_resource(|| (), |_| list_sports());
     view! {
         <div class="container is-fluid">
             <div class="columns">
@@ -89,6 +98,36 @@ pub fn Overview() -> impl IntoView {
                     </div>
 
                 </div>
+                <div class="column">
+                    <div class="field">
+                        <label for="sport">Sport</label>
+                        <div class="control">
+                            <div class="select is-fullwidth">
+                                <select
+                                    id="sport"
+                                    on:change=move |ev| {
+                                        sport.set(event_target_value(&ev).parse().ok())
+                                    }
+                                >
+
+                                    <option value="">All sports</option>
+                                    {move || {
+                                        sports
+                                            .get()
+                                            .and_then(|s| s.ok())
+                                            .unwrap_or_default()
+                                            .into_iter()
+                                            .map(|s| {
+                                                view! { <option value=s.id>{indented_name(&s)}</option> }
+                                            })
+                                            .collect_view()
+                                    }}
+
+                                </select>
+                            </div>
+                        </div>
+                    </div>
+                </div>
             </div>
             <div class="columns is-multiline is-variable is-1">
                 <div class="column is-full-mobile is-half-desktop is-one-third-fullhd">
@@ -98,7 +137,7 @@ pub fn Overview() -> impl IntoView {
                         </div>
                         <div class="card-content">
                             <div class="content">
-                                <HeartrateZoneSummaryChart from=from_memo to=to_memo/>
+                                <HeartrateZoneSummaryChart from=from_memo to=to_memo sport/>
                             </div>
                         </div>
                     </div>
@@ -109,7 +148,17 @@ pub fn Overview() -> impl IntoView {
                             <p class="card-header-title">Training Load</p>
                         </div>
                         <div class="card-content ">
-                            <TrainingLoadChart from=from_memo to=to_memo/>
+                            <TrainingLoadChart from=from_memo to=to_memo sport/>
+                        </div>
+                    </div>
+                </div>
+                <div class="column is-full-mobile is-half-desktop is-one-third-fullhd">
+                    <div class="card is-fullwidth">
+                        <div class="card-header">
+                            <p class="card-header-title">Planned vs. Completed</p>
+                        </div>
+                        <div class="card-content ">
+                            <PlannedLoadChart from=from_memo to=to_memo/>
                         </div>
                     </div>
                 </div>
@@ -119,7 +168,7 @@ pub fn Overview() -> impl IntoView {
                             <p class="card-header-title">Fitness Level</p>
                         </div>
                         <div class="card-content ">
-                            <FitnessLevelChart from=from_memo to=to_memo/>
+                            <FitnessLevelChart from=from_memo to=to_memo sport/>
                         </div>
                     </div>
                 </div>
@@ -129,7 +178,17 @@ pub fn Overview() -> impl IntoView {
                             <p class="card-header-title">Heartrate Distribution</p>
                         </div>
                         <div class="card-content ">
-                            <HeartrateDistributionChart from=from_memo to=to_memo/>
+                            <HeartrateDistributionChart from=from_memo to=to_memo sport/>
+                        </div>
+                    </div>
+                </div>
+                <div class="column is-full-mobile is-half-desktop is-one-third-fullhd">
+                    <div class="card is-fullwidth">
+                        <div class="card-header">
+                            <p class="card-header-title">Heartrate Recovery</p>
+                        </div>
+                        <div class="card-content ">
+                            <HeartrateRecoveryChart from=from_memo to=to_memo sport/>
                         </div>
                     </div>
                 </div>
@@ -139,7 +198,17 @@ pub fn Overview() -> impl IntoView {
                             <p class="card-header-title">Slope Speed</p>
                         </div>
                         <div class="card-content ">
-                            <SlopeSpeedChart from=from_memo to=to_memo/>
+                            <SlopeSpeedChart from=from_memo to=to_memo sport/>
+                        </div>
+                    </div>
+                </div>
+                <div class="column is-full-mobile is-half-desktop is-one-third-fullhd">
+                    <div class="card is-fullwidth">
+                        <div class="card-header">
+                            <p class="card-header-title">Strength Volume</p>
+                        </div>
+                        <div class="card-content ">
+                            <StrengthVolumeChart from=from_memo to=to_memo/>
                         </div>
                     </div>
                 </div>
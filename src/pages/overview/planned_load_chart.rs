@@ -0,0 +1,169 @@
+use charming::{
+    component::{Axis, Grid, Legend},
+    datatype::CompositeValue,
+    element::{AxisType, LineStyle, LineStyleType, Tooltip, Trigger},
+    series::{Bar, Line},
+    Chart, WasmRenderer,
+};
+use chrono::{DateTime, Local};
+#[cfg(feature = "ssr")]
+use chrono::{Datelike, Duration, Weekday};
+use leptos::{html::Div, *};
+use leptos_use::{use_element_size, UseElementSizeReturn};
+use std::cmp;
+
+use crate::{
+    app::FitFileUploaded, error_template::ErrorTemplate, pages::workout_schedule::WeeklyVolume,
+};
+
+#[server(PlannedLoadAction, "/api")]
+pub async fn planned_load_action(
+    from: Option<DateTime<Local>>,
+    to: Option<DateTime<Local>>,
+) -> Result<Vec<WeeklyVolume>, ServerFnError> {
+    use crate::pages::workout_schedule::get_week_workouts;
+    use std::collections::HashMap;
+
+    let from = from
+        .unwrap_or(Local::now() - Duration::try_days(120).unwrap())
+        .date_naive()
+        .week(Weekday::Mon)
+        .first_day();
+    let to = to
+        .unwrap_or(Local::now())
+        .date_naive()
+        .week(Weekday::Mon)
+        .last_day();
+    let weeks: HashMap<(i32, u32), WeeklyVolume> =
+        get_week_workouts(from, to + Duration::try_days(1).unwrap())
+            .await?
+            .iter()
+            .map(|w| {
+                let volume = w.volume();
+                (volume.week, volume)
+            })
+            .collect();
+    // fill weeks without workouts or activities, so the chart has a continuous axis
+    Ok(from
+        .iter_weeks()
+        .take_while(|d| *d <= to)
+        .map(|d| {
+            let week = (d.iso_week().year(), d.iso_week().week());
+            weeks.get(&week).cloned().unwrap_or(WeeklyVolume {
+                week,
+                planned_load: None,
+                planned_duration: None,
+                planned_distance: None,
+                actual_load: 0,
+                actual_duration: 0,
+                actual_distance: 0,
+            })
+        })
+        .collect())
+}
+
+#[component]
+pub fn PlannedLoadChart(
+    #[prop(into)] from: Memo<Option<DateTime<Local>>>,
+    #[prop(into)] to: Memo<Option<DateTime<Local>>>,
+) -> impl IntoView {
+    let uploaded = use_context::<FitFileUploaded>().unwrap();
+    let planned_load = create_resource(
+        move || (from(), to(), uploaded.0()),
+        move |(from, to, _)| planned_load_action(from, to),
+    );
+    let planned_load_chart = create_node_ref::<Div>();
+    let UseElementSizeReturn { width, height: _ } = use_element_size(planned_load_chart);
+    let _chart = create_local_resource(
+        move || (planned_load.get(), width()),
+        move |(volume, width)| async move {
+            if let Some(Ok(volume)) = volume {
+                // duration and distance are only shown for weeks without planned load
+                let fallback = |value: fn(&WeeklyVolume) -> Option<f64>| {
+                    volume
+                        .iter()
+                        .map(|v| match (v.planned_load, value(v)) {
+                            (None, Some(value)) => CompositeValue::from(value),
+                            _ => CompositeValue::from("-"),
+                        })
+                        .collect::<Vec<_>>()
+                };
+                let chart = Chart::new()
+                    .grid(Grid::new().top(30).bottom(20))
+                    .legend(Legend::new())
+                    .tooltip(Tooltip::new().trigger(Trigger::Axis))
+                    .x_axis(
+                        Axis::new().type_(AxisType::Category).data(
+                            volume
+                                .iter()
+                                .map(|v| format!("{}", v.start_date().format("%Y-%m-%d")))
+                                .collect::<Vec<_>>(),
+                        ),
+                    )
+                    .y_axis(Axis::new().type_(AxisType::Value).name("Load"))
+                    .y_axis(Axis::new().type_(AxisType::Value).name("h / km"))
+                    .series(
+                        Bar::new().name("Planned Load").data(
+                            volume
+                                .iter()
+                                .map(|v| match v.planned_load {
+                                    Some(load) => CompositeValue::from(load),
+                                    None => CompositeValue::from("-"),
+                                })
+                                .collect::<Vec<_>>(),
+                        ),
+                    )
+                    .series(
+                        Bar::new()
+                            .name("Completed Load")
+                            .data(volume.iter().map(|v| v.actual_load).collect::<Vec<_>>()),
+                    )
+                    .series(
+                        Line::new()
+                            .name("Planned Duration")
+                            .y_axis_index(1)
+                            .data(fallback(|v| v.planned_duration.map(|d| d as f64 / 3600.0))),
+                    )
+                    .series(
+                        Line::new()
+                            .name("Completed Duration")
+                            .y_axis_index(1)
+                            .line_style(LineStyle::new().type_(LineStyleType::Dashed))
+                            .data(fallback(|v| {
+                                v.planned_duration
+                                    .map(|_| v.actual_duration as f64 / 3600.0)
+                            })),
+                    )
+                    .series(
+                        Line::new()
+                            .name("Planned Distance")
+                            .y_axis_index(1)
+                            .data(fallback(|v| v.planned_distance.map(|d| d as f64 / 1000.0))),
+                    )
+                    .series(
+                        Line::new()
+                            .name("Completed Distance")
+                            .y_axis_index(1)
+                            .line_style(LineStyle::new().type_(LineStyleType::Dashed))
+                            .data(fallback(|v| {
+                                v.planned_distance
+                                    .map(|_| v.actual_distance as f64 / 1000.0)
+                            })),
+                    );
+                let renderer = WasmRenderer::new(cmp::max(width as u32, 300), 200);
+                let _rendered = renderer.render("planned_load_chart", &chart);
+            }
+        },
+    );
+
+    view! {
+        <Transition fallback=move || view! { <p>"Loading..."</p> }>
+            <ErrorBoundary fallback=|errors| {
+                view! { <ErrorTemplate errors=errors/> }
+            }>
+                <div node_ref=planned_load_chart id="planned_load_chart"></div>
+
+            </ErrorBoundary>
+        </Transition>
+    }
+}
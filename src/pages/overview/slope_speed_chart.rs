@@ -44,6 +44,7 @@ pub async fn slope_speed(
     user_id: i64,
     from: DateTime<Local>,
     to: DateTime<Local>,
+    sport: Option<i64>,
     executor: sqlx::PgPool,
 ) -> Result<Vec<SlopeSpeed>, sqlx::Error> {
     let result: Vec<SlopeSpeed> = sqlx::query_as(
@@ -54,11 +55,13 @@ pub async fn slope_speed(
                 sp.heartrate_zone::text as zone
             FROM slope_speed sp
             WHERE sp.user_id = $1::bigint and sp.start_time >= $2::timestamptz and sp.start_time <= $3::timestamptz
+                and ($4::bigint IS NULL OR sp.sport_id IN (SELECT sport_subtree($4::bigint)))
             GROUP BY sp.slope, sp.heartrate_zone
         "#)
          .bind(&user_id)
          .bind(&from )
          .bind(&to)
+         .bind(sport)
     .fetch_all(&executor).await?;
     Ok(result)
 }
@@ -67,6 +70,7 @@ pub async fn slope_speed(
 pub async fn slope_speed_action(
     from: Option<DateTime<Local>>,
     to: Option<DateTime<Local>>,
+    sport: Option<i64>,
 ) -> Result<Vec<SlopeSpeed>, ServerFnError> {
     let auth = auth()?;
     if auth.current_user.is_none() {
@@ -78,6 +82,7 @@ pub async fn slope_speed_action(
         user.id,
         from.unwrap_or(Local::now() - Duration::try_days(120).unwrap()),
         to.unwrap_or(Local::now()),
+        sport,
         pool,
     )
     .await?;
@@ -87,11 +92,12 @@ pub async fn slope_speed_action(
 pub fn SlopeSpeedChart(
     #[prop(into)] from: Memo<Option<DateTime<Local>>>,
     #[prop(into)] to: Memo<Option<DateTime<Local>>>,
+    #[prop(into)] sport: Signal<Option<i64>>,
 ) -> impl IntoView {
     let uploaded = use_context::<FitFileUploaded>().unwrap();
     let slope_speed = create_resource(
-        move || (from(), to(), uploaded.0()),
-        move |(from, to, _)| slope_speed_action(from, to),
+        move || (from(), to(), sport(), uploaded.0()),
+        move |(from, to, sport, _)| slope_speed_action(from, to, sport),
     );
     let slope_speed_chart = create_node_ref::<Div>();
     let UseElementSizeReturn { width, height: _ } = use_element_size(slope_speed_chart);
@@ -0,0 +1,166 @@
+#[cfg(feature = "ssr")]
+use crate::app::{auth, pool};
+use charming::{
+    component::{Axis, Grid, Legend},
+    element::{AxisType, Tooltip, Trigger},
+    series::Line,
+    Chart, WasmRenderer,
+};
+#[cfg(feature = "ssr")]
+use chrono::Duration;
+use chrono::{DateTime, Local};
+use itertools::Itertools;
+use leptos::{html::Div, *};
+use leptos_use::{use_element_size, UseElementSizeReturn};
+use serde::{Deserialize, Serialize};
+use std::cmp;
+
+use crate::{app::FitFileUploaded, error_template::ErrorTemplate};
+
+/// Number of exercises shown in the chart, the ones with the most volume win.
+#[cfg(feature = "ssr")]
+const SHOWN_EXERCISES: i64 = 5;
+
+/// Volume load, repetitions times weight, of an exercise in a week.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExerciseVolume {
+    pub exercise: String,
+    pub week: DateTime<Local>,
+    pub volume: f64,
+}
+
+#[cfg(feature = "ssr")]
+pub async fn strength_volume(
+    user_id: i64,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+    executor: sqlx::PgPool,
+) -> Result<Vec<ExerciseVolume>, sqlx::Error> {
+    let result: Vec<ExerciseVolume> = sqlx::query_as!(
+        ExerciseVolume,
+        r#"
+    WITH weeks as (
+        SELECT generate_series(
+            date_trunc('week', $2::timestamptz),
+            date_trunc('week', $3::timestamptz),
+            '1 week'
+        ) as start
+    ), sets as (
+        SELECT
+            strength_sets.exercise_id,
+            strength_sets.repetitions * strength_sets.weight as volume,
+            date_trunc('week', activities.start_time) as week
+        FROM strength_sets
+        INNER JOIN activities ON activities.id = strength_sets.activity_id
+        WHERE activities.user_id = $1::bigint
+            AND activities.start_time >= date_trunc('week', $2::timestamptz)
+            AND activities.start_time < date_trunc('week', $3::timestamptz) + '1 week'
+    ), top as (
+        SELECT exercises.id, exercises.name
+        FROM exercises
+        INNER JOIN sets ON sets.exercise_id = exercises.id
+        GROUP BY exercises.id
+        HAVING SUM(sets.volume) > 0
+        ORDER BY SUM(sets.volume) DESC
+        LIMIT $4
+    )
+    SELECT
+        top.name as "exercise!",
+        weeks.start as "week!",
+        COALESCE(SUM(sets.volume), 0)::float8 as "volume!"
+    FROM top
+    CROSS JOIN weeks
+    LEFT JOIN sets ON sets.exercise_id = top.id AND sets.week = weeks.start
+    GROUP BY top.name, weeks.start
+    ORDER BY top.name, weeks.start
+"#,
+        &user_id,
+        &from,
+        &to,
+        SHOWN_EXERCISES
+    )
+    .fetch_all(&executor)
+    .await?;
+    Ok(result)
+}
+
+#[server(StrengthVolumeAction, "/api")]
+pub async fn strength_volume_action(
+    from: Option<DateTime<Local>>,
+    to: Option<DateTime<Local>>,
+) -> Result<Vec<ExerciseVolume>, ServerFnError> {
+    let auth = auth()?;
+    if auth.current_user.is_none() {
+        return Err(ServerFnError::new("Not logged in".to_string()));
+    }
+    let user = auth.current_user.expect("the user to be logged in");
+    let pool = pool()?;
+    let volume = strength_volume(
+        user.id,
+        from.unwrap_or(Local::now() - Duration::try_days(120).unwrap()),
+        to.unwrap_or(Local::now()),
+        pool,
+    )
+    .await?;
+    Ok(volume)
+}
+
+#[component]
+pub fn StrengthVolumeChart(
+    #[prop(into)] from: Memo<Option<DateTime<Local>>>,
+    #[prop(into)] to: Memo<Option<DateTime<Local>>>,
+) -> impl IntoView {
+    let uploaded = use_context::<FitFileUploaded>().unwrap();
+    let strength_volume = create_resource(
+        move || (from(), to(), uploaded.0()),
+        move |(from, to, _)| strength_volume_action(from, to),
+    );
+    let strength_volume_chart = create_node_ref::<Div>();
+    let UseElementSizeReturn { width, height: _ } = use_element_size(strength_volume_chart);
+    let _chart = create_local_resource(
+        move || (strength_volume.get(), width()),
+        move |(volume, width)| async move {
+            if let Some(Ok(volume)) = volume {
+                let weeks = volume
+                    .iter()
+                    .map(|v| v.week)
+                    .unique()
+                    .sorted()
+                    .map(|w| format!("{}", w.format("%Y-%m-%d")))
+                    .collect::<Vec<_>>();
+                let chart = volume
+                    .into_iter()
+                    .group_by(|v| v.exercise.clone())
+                    .into_iter()
+                    .fold(
+                        Chart::new()
+                            .grid(Grid::new().top(30).bottom(20))
+                            .legend(Legend::new())
+                            .tooltip(Tooltip::new().trigger(Trigger::Axis))
+                            .x_axis(Axis::new().type_(AxisType::Category).data(weeks))
+                            .y_axis(Axis::new().type_(AxisType::Value)),
+                        |chart, (exercise, volume)| {
+                            chart.series(
+                                Line::new()
+                                    .name(exercise)
+                                    .data(volume.map(|v| v.volume).collect::<Vec<_>>()),
+                            )
+                        },
+                    );
+                let renderer = WasmRenderer::new(cmp::max(width as u32, 300), 155);
+                let _rendered = renderer.render("strength_volume_chart", &chart);
+            }
+        },
+    );
+
+    view! {
+        <Transition fallback=move || view! { <p>"Loading..."</p> }>
+            <ErrorBoundary fallback=|errors| {
+                view! { <ErrorTemplate errors=errors/> }
+            }>
+                <div node_ref=strength_volume_chart id="strength_volume_chart"></div>
+
+            </ErrorBoundary>
+        </Transition>
+    }
+}
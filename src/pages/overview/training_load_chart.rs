@@ -26,6 +26,7 @@ pub async fn training_load(
     user_id: i64,
     from: DateTime<Local>,
     to: DateTime<Local>,
+    sport: Option<i64>,
     executor: sqlx::PgPool,
 ) -> Result<Vec<TrainingLoad>, sqlx::Error> {
     let result: Vec<TrainingLoad> = sqlx::query_as!(
@@ -40,24 +41,24 @@ pub async fn training_load(
     )
     SELECT
         weeks.start as "date!",
-        COALESCE(SUM(activities.load), 0)::int8 as "load!"
+        ROUND(COALESCE(SUM(activities.load), 0))::int8 as "load!"
     FROM weeks
     LEFT JOIN (
         SELECT 
-            activities.load as load,
-            date_trunc('week', activities.start_time ) as date
-        FROM activities as activities
-        LEFT JOIN records as record ON record.activity_id = activities.id
-        WHERE activities.user_id = $1::bigint 
-            AND record.heartrate IS NOT NULL
-        GROUP BY activities.id
+            session_loads.load as load,
+            date_trunc('week', session_loads.start_time ) as date
+        FROM session_loads
+        WHERE session_loads.user_id = $1::bigint 
+            AND session_loads.load IS NOT NULL
+            AND ($4::bigint IS NULL OR session_loads.sport_id IN (SELECT sport_subtree($4::bigint)))
     ) activities ON activities.date = weeks.start
     GROUP BY weeks.start
     ORDER BY weeks.start
 "#,
         &user_id,
         &from,
-        &to
+        &to,
+        sport
     )
     .fetch_all(&executor)
     .await?;
@@ -68,6 +69,7 @@ pub async fn training_load(
 pub async fn training_load_action(
     from: Option<DateTime<Local>>,
     to: Option<DateTime<Local>>,
+    sport: Option<i64>,
 ) -> Result<Vec<TrainingLoad>, ServerFnError> {
     let auth = auth()?;
     if auth.current_user.is_none() {
@@ -79,6 +81,7 @@ pub async fn training_load_action(
         user.id,
         from.unwrap_or(Local::now() - Duration::try_days(120).unwrap()),
         to.unwrap_or(Local::now()),
+        sport,
         pool,
     )
     .await?;
@@ -89,11 +92,12 @@ pub async fn training_load_action(
 pub fn TrainingLoadChart(
     #[prop(into)] from: Memo<Option<DateTime<Local>>>,
     #[prop(into)] to: Memo<Option<DateTime<Local>>>,
+    #[prop(into)] sport: Signal<Option<i64>>,
 ) -> impl IntoView {
     let uploaded = use_context::<FitFileUploaded>().unwrap();
     let training_load = create_resource(
-        move || (from(), to(), uploaded.0()),
-        move |(from, to, _)| training_load_action(from, to),
+        move || (from(), to(), sport(), uploaded.0()),
+        move |(from, to, sport, _)| training_load_action(from, to, sport),
     );
     let trainingload_chart = create_node_ref::<Div>();
     let UseElementSizeReturn { width, height: _ } = use_element_size(trainingload_chart);
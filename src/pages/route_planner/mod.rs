@@ -0,0 +1,286 @@
+use crate::app::FitFileUploaded;
+#[cfg(feature = "ssr")]
+use crate::app::{auth, pool};
+use crate::models::{
+    route_plan::{parse_gpx, plan_route, RoutePoint, RouteSplit},
+    swim_length::format_pace,
+};
+use crate::pages::workout_schedule::manage_sports_dialog::{indented_name, list_sports};
+use humantime::format_duration;
+use leptos::*;
+use leptos_leaflet::*;
+use std::time::Duration;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::HtmlInputElement;
+
+/// Average speed per grade within a heart rate zone over the last year, sorted by grade.
+///
+/// Grades with only a few recorded sections are left out, they're too noisy to plan with.
+#[server]
+pub async fn slope_speed_curve(
+    sport: Option<i64>,
+    zone: String,
+) -> Result<Vec<(f64, f64)>, ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let curve = sqlx::query!(
+        r#"
+        SELECT slope::float8 as "slope!", AVG(average_speed)::float8 as "speed!"
+        FROM slope_speed
+        WHERE user_id = $1::bigint
+            AND heartrate_zone::text = $2
+            AND start_time >= NOW() - INTERVAL '1 year'
+            AND ($3::bigint IS NULL OR sport_id IN (SELECT sport_subtree($3::bigint)))
+        GROUP BY slope
+        HAVING COUNT(*) >= 3
+        ORDER BY slope
+        "#,
+        user.id,
+        zone,
+        sport
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load slope speeds: {}", e)))?
+    .into_iter()
+    .map(|r| (r.slope, r.speed))
+    .collect();
+    Ok(curve)
+}
+
+/// Plans a route from a GPX file or by clicking on the map and estimates its split times from the
+/// user's own slope speeds.
+#[component]
+pub fn RoutePlanner() -> impl IntoView {
+    let uploaded = use_context::<FitFileUploaded>().unwrap();
+    let sport = create_rw_signal(None::<i64>);
+    let zone = create_rw_signal("zone2".to_string());
+    let route = create_rw_signal(Vec::<RoutePoint>::new());
+    // bumped when a file is loaded, so the map is recentered on it
+    let loaded = create_rw_signal(0_usize);
+    let sports = create_resource(|| (), |_| list_sports());
+    let curve = create_resource(
+        move || (sport.get(), zone.get(), uploaded.0.get()),
+        |(sport, zone, _)| slope_speed_curve(sport, zone),
+    );
+    let on_file = move |ev: ev::Event| {
+        let input = event_target::<HtmlInputElement>(&ev);
+        if let Some(file) = input.files().and_then(|f| f.get(0)) {
+            spawn_local(async move {
+                if let Some(text) = JsFuture::from(file.text())
+                    .await
+                    .ok()
+                    .and_then(|t| t.as_string())
+                {
+                    route.set(parse_gpx(&text));
+                    loaded.update(|v| *v += 1);
+                }
+            });
+        }
+    };
+    let splits = move || -> Option<Vec<RouteSplit>> {
+        let curve = curve.get()?.ok()?;
+        route.with(|route| plan_route(route, &curve, 1000.0))
+    };
+    view! {
+        <div class="container is-fluid">
+            <div class="field is-grouped is-grouped-multiline">
+                <div class="control">
+                    <label class="label is-small" for="planner_file">
+                        GPX route
+                    </label>
+                    <input
+                        class="input is-small"
+                        id="planner_file"
+                        type="file"
+                        accept=".gpx"
+                        on:change=on_file
+                    />
+                </div>
+                <div class="control">
+                    <label class="label is-small" for="planner_sport">
+                        Sport
+                    </label>
+                    <div class="select is-small">
+                        <select
+                            id="planner_sport"
+                            on:change=move |ev| sport.set(event_target_value(&ev).parse().ok())
+                        >
+                            <option value="">All sports</option>
+                            {move || {
+                                sports
+                                    .get()
+                                    .and_then(|s| s.ok())
+                                    .unwrap_or_default()
+                                    .into_iter()
+                                    .map(|s| {
+                                        view! { <option value=s.id>{indented_name(&s)}</option> }
+                                    })
+                                    .collect_view()
+                            }}
+
+                        </select>
+                    </div>
+                </div>
+                <div class="control">
+                    <label class="label is-small" for="planner_zone">
+                        Heartrate zone
+                    </label>
+                    <div class="select is-small">
+                        <select
+                            id="planner_zone"
+                            on:change=move |ev| zone.set(event_target_value(&ev))
+                        >
+                            <option value="zone1">Zone 1</option>
+                            <option value="zone2" selected>
+                                Zone 2
+                            </option>
+                            <option value="zone3">Zone 3</option>
+                        </select>
+                    </div>
+                </div>
+                <div class="control">
+                    <label class="label is-small">"\u{a0}"</label>
+                    <button class="button is-small" on:click=move |_| route.set(Vec::new())>
+                        Clear route
+                    </button>
+                </div>
+            </div>
+            <p class="help">"Click on the map to draw a route, drawn routes are taken as flat."</p>
+            <div class="columns">
+                <div class="column is-three-fifths">
+                    {move || {
+                        loaded.track();
+                        let center = route
+                            .with_untracked(|r| r.first().map(|p| p.position))
+                            .map(|(lat, lon)| Position::new(lat, lon));
+                        let events = MapEvents::new()
+                            .mouse_click(move |e| {
+                                let position = e.lat_lng();
+                                route
+                                    .update(|r| {
+                                        r.push(RoutePoint {
+                                            position: (position.lat(), position.lng()),
+                                            elevation: None,
+                                        })
+                                    });
+                            });
+                        view! {
+                            <MapContainer
+                                style="height:70vh;"
+                                center=center.unwrap_or(Position::new(47.0, 8.0))
+                                zoom=13.0
+                                locate=center.is_none()
+                                set_view=true
+                                events
+                            >
+                                <TileLayer url="https://tile.openstreetmap.org/{z}/{x}/{y}.png"/>
+                                <Polyline positions=Signal::derive(move || {
+                                    route
+                                        .with(|r| {
+                                            r.iter()
+                                                .map(|p| Position::new(p.position.0, p.position.1))
+                                                .collect::<Vec<_>>()
+                                        })
+                                })/>
+                            </MapContainer>
+                        }
+                    }}
+
+                </div>
+                <div class="column">
+                    <Transition fallback=move || {
+                        view! { <p>"Loading..."</p> }
+                    }>
+                        {move || match (curve.get(), splits()) {
+                            (Some(Err(e)), _) => {
+                                view! { <pre class="error">"Error: " {e.to_string()}</pre> }
+                                    .into_view()
+                            }
+                            (Some(Ok(curve)), _) if curve.is_empty() => {
+                                view! {
+                                    <p>
+                                        "No slope speeds recorded in this zone yet, upload some activities with GPS, altitude and heartrate first."
+                                    </p>
+                                }
+                                    .into_view()
+                            }
+                            (_, Some(splits)) => view! { <RouteSplits splits/> }.into_view(),
+                            _ => ().into_view(),
+                        }}
+
+                    </Transition>
+                </div>
+            </div>
+        </div>
+    }
+}
+
+/// Summary and table of the estimated splits of a route.
+#[component]
+fn RouteSplits(splits: Vec<RouteSplit>) -> impl IntoView {
+    let last = splits.last().cloned();
+    let distance = last.as_ref().map_or(0.0, |s| s.distance);
+    let elapsed = last.as_ref().map_or(0.0, |s| s.elapsed);
+    let ascent: f64 = splits.iter().map(|s| s.ascent).sum();
+    let descent: f64 = splits.iter().map(|s| s.descent).sum();
+    view! {
+        <nav class="level">
+            <div class="level-item has-text-centered">
+                <div>
+                    <p class="heading">Distance</p>
+                    <p class="title is-5">{format!("{:.2} km", distance / 1000.0)}</p>
+                </div>
+            </div>
+            <div class="level-item has-text-centered">
+                <div>
+                    <p class="heading">Ascent / Descent</p>
+                    <p class="title is-5">{format!("{:.0} m / {:.0} m", ascent, descent)}</p>
+                </div>
+            </div>
+            <div class="level-item has-text-centered">
+                <div>
+                    <p class="heading">Estimated time</p>
+                    <p class="title is-5">
+                        {format_duration(Duration::from_secs(elapsed.round() as u64)).to_string()}
+                    </p>
+                </div>
+            </div>
+        </nav>
+        <table class="table is-striped is-hoverable is-fullwidth">
+            <thead>
+                <tr>
+                    <th>Km</th>
+                    <th>Split</th>
+                    <th>Elapsed</th>
+                    <th>Ascent</th>
+                    <th>Descent</th>
+                </tr>
+            </thead>
+            <tbody>
+                {splits
+                    .into_iter()
+                    .map(|split| {
+                        view! {
+                            <tr>
+                                <td>{format!("{:.1}", split.distance / 1000.0)}</td>
+                                <td>{format_pace(split.duration)}</td>
+                                <td>
+                                    {format_duration(
+                                            Duration::from_secs(split.elapsed.round() as u64),
+                                        )
+                                        .to_string()}
+                                </td>
+                                <td>{format!("{:.0} m", split.ascent)}</td>
+                                <td>{format!("{:.0} m", split.descent)}</td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()}
+            </tbody>
+        </table>
+    }
+}
@@ -0,0 +1,283 @@
+use crate::app::FitFileUploaded;
+#[cfg(feature = "ssr")]
+use crate::app::{auth, pool};
+use crate::models::swim_length::format_pace;
+use chrono::{DateTime, Local};
+use leptos::*;
+use leptos_router::use_query_map;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentSummary {
+    pub id: i64,
+    pub name: String,
+    /// Meters.
+    pub distance: f64,
+    pub efforts: i64,
+    /// Fastest effort in seconds.
+    pub best: Option<f64>,
+}
+
+/// An effort on a segment, the leaderboard lists them fastest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub activity_id: i32,
+    pub start_time: DateTime<Local>,
+    pub duration: f64,
+    pub average_heartrate: Option<i16>,
+    pub average_power: Option<i16>,
+}
+
+#[server]
+pub async fn list_segments() -> Result<Vec<SegmentSummary>, ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let segments = sqlx::query_as!(
+        SegmentSummary,
+        r#"
+        SELECT
+            segments.id,
+            segments.name,
+            segments.distance,
+            COUNT(segment_efforts.id) as "efforts!",
+            MIN(segment_efforts.duration) as best
+        FROM segments
+        LEFT JOIN segment_efforts ON segment_efforts.segment_id = segments.id
+        WHERE segments.user_id = $1::bigint
+        GROUP BY segments.id
+        ORDER BY segments.name
+        "#,
+        user.id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load segments: {}", e)))?;
+    Ok(segments)
+}
+
+#[server]
+pub async fn segment_leaderboard(segment_id: i64) -> Result<Vec<LeaderboardEntry>, ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let efforts = sqlx::query_as!(
+        LeaderboardEntry,
+        r#"
+        SELECT
+            segment_efforts.activity_id,
+            segment_efforts.start_time as "start_time:DateTime<Local>",
+            segment_efforts.duration,
+            segment_efforts.average_heartrate,
+            segment_efforts.average_power
+        FROM segment_efforts
+        INNER JOIN segments ON segments.id = segment_efforts.segment_id
+        WHERE segments.id = $1 AND segments.user_id = $2::bigint
+        ORDER BY segment_efforts.duration, segment_efforts.start_time
+        "#,
+        segment_id,
+        user.id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load segment efforts: {}", e)))?;
+    Ok(efforts)
+}
+
+#[server]
+pub async fn delete_segment(segment_id: i64) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    sqlx::query!(
+        r#"
+        DELETE FROM segments
+        WHERE id = $1 AND user_id = $2::bigint
+        "#,
+        segment_id,
+        user.id
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't delete segment: {}", e)))?;
+    Ok(())
+}
+
+/// The user's segments with a leaderboard of their efforts on the selected one.
+#[component]
+pub fn Segments() -> impl IntoView {
+    let uploaded = use_context::<FitFileUploaded>().unwrap();
+    let query = use_query_map();
+    let selected = create_rw_signal(
+        query.with_untracked(|q| q.get("segment").and_then(|s| s.parse::<i64>().ok())),
+    );
+    let delete_action = create_server_action::<DeleteSegment>();
+    let segments = create_resource(
+        move || (uploaded.0.get(), delete_action.version().get()),
+        |_| list_segments(),
+    );
+    let leaderboard = create_resource(
+        move || (selected.get(), uploaded.0.get()),
+        |(selected, _)| async move {
+            match selected {
+                Some(segment_id) => segment_leaderboard(segment_id).await.map(Some),
+                None => Ok(None),
+            }
+        },
+    );
+    let distance = move || {
+        segments
+            .get()
+            .and_then(|s| s.ok())
+            .and_then(|s| s.into_iter().find(|s| Some(s.id) == selected.get()))
+            .map(|s| s.distance)
+    };
+    view! {
+        <div class="container is-fluid">
+            <div class="columns">
+                <div class="column is-two-fifths">
+                    <Transition fallback=move || {
+                        view! { <p>"Loading..."</p> }
+                    }>
+                        {move || {
+                            segments
+                                .get()
+                                .map(|segments| match segments {
+                                    Err(e) => {
+                                        view! { <pre class="error">"Error: " {e.to_string()}</pre> }
+                                            .into_view()
+                                    }
+                                    Ok(segments) if segments.is_empty() => {
+                                        view! {
+                                            <p>
+                                                "No segments yet, create one from the track of an activity."
+                                            </p>
+                                        }
+                                            .into_view()
+                                    }
+                                    Ok(segments) => {
+                                        view! {
+                                            <table class="table is-hoverable is-fullwidth">
+                                                <thead>
+                                                    <tr>
+                                                        <th>Segment</th>
+                                                        <th>Distance</th>
+                                                        <th>Efforts</th>
+                                                        <th>Best</th>
+                                                        <th></th>
+                                                    </tr>
+                                                </thead>
+                                                <tbody>
+                                                    {segments
+                                                        .into_iter()
+                                                        .map(|segment| {
+                                                            let id = segment.id;
+                                                            view! {
+                                                                <tr
+                                                                    class:is-selected=move || selected.get() == Some(id)
+                                                                    on:click=move |_| selected.set(Some(id))
+                                                                >
+                                                                    <td>{segment.name}</td>
+                                                                    <td>{format!("{:.2} km", segment.distance / 1000.0)}</td>
+                                                                    <td>{segment.efforts}</td>
+                                                                    <td>{segment.best.map(format_pace)}</td>
+                                                                    <td>
+                                                                        <button
+                                                                            class="delete"
+                                                                            aria-label="delete"
+                                                                            on:click=move |ev| {
+                                                                                ev.stop_propagation();
+                                                                                if selected.get_untracked() == Some(id) {
+                                                                                    selected.set(None);
+                                                                                }
+                                                                                delete_action.dispatch(DeleteSegment { segment_id: id });
+                                                                            }
+                                                                        >
+                                                                        </button>
+                                                                    </td>
+                                                                </tr>
+                                                            }
+                                                        })
+                                                        .collect_view()}
+                                                </tbody>
+                                            </table>
+                                        }
+                                            .into_view()
+                                    }
+                                })
+                        }}
+
+                    </Transition>
+                </div>
+                <div class="column">
+                    <Transition fallback=move || {
+                        view! { <p>"Loading..."</p> }
+                    }>
+                        {move || {
+                            leaderboard
+                                .get()
+                                .map(|efforts| match efforts {
+                                    Err(e) => {
+                                        view! { <pre class="error">"Error: " {e.to_string()}</pre> }
+                                            .into_view()
+                                    }
+                                    Ok(None) => ().into_view(),
+                                    Ok(Some(efforts)) => {
+                                        let distance = distance().unwrap_or_default();
+                                        view! {
+                                            <table class="table is-striped is-hoverable is-fullwidth">
+                                                <thead>
+                                                    <tr>
+                                                        <th>Rank</th>
+                                                        <th>Date</th>
+                                                        <th>Time</th>
+                                                        <th>Speed</th>
+                                                        <th>Avg. Heartrate</th>
+                                                        <th>Avg. Power</th>
+                                                    </tr>
+                                                </thead>
+                                                <tbody>
+                                                    {efforts
+                                                        .into_iter()
+                                                        .enumerate()
+                                                        .map(|(i, effort)| {
+                                                            view! {
+                                                                <tr>
+                                                                    <td>{i + 1}</td>
+                                                                    <td>
+                                                                        {effort.start_time.format("%Y-%m-%d %H:%M").to_string()}
+                                                                    </td>
+                                                                    <td>{format_pace(effort.duration)}</td>
+                                                                    <td>
+                                                                        {(effort.duration > 0.0)
+                                                                            .then(|| {
+                                                                                format!("{:.1} km/h", distance / effort.duration * 3.6)
+                                                                            })}
+
+                                                                    </td>
+                                                                    <td>{effort.average_heartrate}</td>
+                                                                    <td>{effort.average_power}</td>
+                                                                </tr>
+                                                            }
+                                                        })
+                                                        .collect_view()}
+                                                </tbody>
+                                            </table>
+                                        }
+                                            .into_view()
+                                    }
+                                })
+                        }}
+
+                    </Transition>
+                </div>
+            </div>
+        </div>
+    }
+}
@@ -1,8 +1,15 @@
 #[cfg(feature = "ssr")]
 use crate::app::{auth, pool};
 #[cfg(feature = "ssr")]
+use crate::models::activity::update_rpe_loads;
+use crate::models::swim_length::format_pace;
+#[cfg(feature = "ssr")]
+use crate::models::swim_length::parse_pace;
+#[cfg(feature = "ssr")]
 use crate::models::user_preferences::get_user_preferences;
-use crate::models::user_preferences::UserPreferences;
+use crate::models::user_preferences::{LoadModel, UserPreferences};
+#[cfg(feature = "ssr")]
+use crate::models::workout_completion::update_completion_actuals;
 #[cfg(feature = "ssr")]
 use chrono::Local;
 #[cfg(feature = "ssr")]
@@ -11,6 +18,8 @@ use leptos::*;
 use leptos_router::*;
 #[cfg(feature = "ssr")]
 use sqlx::*;
+#[cfg(feature = "ssr")]
+use std::str::FromStr;
 
 #[cfg(feature = "ssr")]
 use nalgebra::DVector;
@@ -58,17 +67,34 @@ pub async fn update_user_preferences(
     aerobic_threshold: u32,
     anaerobic_threshold: u32,
     max_heartrate: u32,
+    load_model: String,
+    critical_swim_pace: String,
 ) -> Result<(), ServerFnError> {
     let pool = pool()?;
     let auth = auth()?;
     let user = auth
         .current_user
         .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let load_model = LoadModel::from_str(&load_model)
+        .map_err(|_| ServerFnError::new("Couldn't parse load model".to_string()))?;
+    // the pace is entered per 100m
+    let critical_swim_speed = parse_pace(&critical_swim_pace)
+        .map(|pace| 100.0 / pace)
+        .ok_or(ServerFnError::new(
+            "Couldn't parse critical swim pace".to_string(),
+        ))?;
+    let previous_model = get_user_preferences(user.id, Local::now(), &pool)
+        .await
+        .load_model;
     let (tau, c) = curve_fit(
         aerobic_threshold as f64,
         anaerobic_threshold as f64,
         max_heartrate as f64,
     );
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
     let current = sqlx::query!(
         r#"
         SELECT id
@@ -77,19 +103,15 @@ pub async fn update_user_preferences(
         "#,
         user.id as _
     )
-    .fetch_optional(&pool)
+    .fetch_optional(&mut *transaction)
     .await
     .map_err(|e| ServerFnError::new(format!("Couldn't query user preferences:{}", e)))?;
     match current {
         Some(current) => {
-            let mut transaction = pool
-                .begin()
-                .await
-                .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
             sqlx::query!(
                 r#"
-                INSERT INTO user_preferences (user_id,start_time,end_time,aerobic_threshold, anaerobic_threshold,max_heartrate, tau, c)
-                VALUES ($1,$2,$3,$4,$5,$6,$7,$8)
+                INSERT INTO user_preferences (user_id,start_time,end_time,aerobic_threshold, anaerobic_threshold,max_heartrate, tau, c, load_model, critical_swim_speed)
+                VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10)
                 "#,
                 user.id as _,
                 Utc::now(),
@@ -98,7 +120,9 @@ pub async fn update_user_preferences(
                 anaerobic_threshold as i32,
                 max_heartrate as i32,
                 tau,
-                c
+                c,
+                load_model as _,
+                critical_swim_speed
             ).execute(&mut *transaction).await.map_err(|e|ServerFnError::new(format!("Couldn't update preferences:{}",e)))?;
 
             sqlx::query!(
@@ -113,16 +137,12 @@ pub async fn update_user_preferences(
             .execute(&mut *transaction)
             .await
             .map_err(|e| ServerFnError::new(format!("Couldn't update preferences:{}", e)))?;
-            transaction
-                .commit()
-                .await
-                .map_err(|e| ServerFnError::new(format!("Couldn't commit changes:{}", e)))?;
         }
         None => {
             sqlx::query!(
                 r#"
-                INSERT INTO user_preferences (user_id,start_time,end_time,aerobic_threshold, anaerobic_threshold,max_heartrate, tau, c)
-                VALUES ($1,$2,$3,$4,$5,$6,$7,$8)
+                INSERT INTO user_preferences (user_id,start_time,end_time,aerobic_threshold, anaerobic_threshold,max_heartrate, tau, c, load_model, critical_swim_speed)
+                VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10)
                 "#,
                 user.id as _,
                 Option::<DateTime<Utc>>::None,
@@ -131,10 +151,26 @@ pub async fn update_user_preferences(
                 anaerobic_threshold as i32,
                 max_heartrate as i32,
                 tau,
-                c
-            ).execute(&pool).await.map_err(|e|ServerFnError::new(format!("Couldn't update preferences:{}",e)))?;
+                c,
+                load_model as _,
+                critical_swim_speed
+            ).execute(&mut *transaction).await.map_err(|e|ServerFnError::new(format!("Couldn't update preferences:{}",e)))?;
         }
     }
+    if load_model != previous_model {
+        update_rpe_loads(user.id, load_model, &mut transaction)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Couldn't calculate load:{}", e)))?;
+        update_completion_actuals(user.id, None, &mut transaction)
+            .await
+            .map_err(|e| {
+                ServerFnError::new(format!("Couldn't update workout completions:{}", e))
+            })?;
+    }
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't commit changes:{}", e)))?;
     Ok(())
 }
 
@@ -155,6 +191,8 @@ pub fn UserSettings(show: RwSignal<bool>) -> impl IntoView {
     let aerobic_threshold = create_rw_signal(140);
     let anaerobic_threshold = create_rw_signal(160);
     let max_heartrate = create_rw_signal(180);
+    let load_model = create_rw_signal(LoadModel::default());
+    let critical_swim_pace = create_rw_signal(format_pace(100.0));
     let update_user_preferences = create_server_action::<UpdateUserPreferences>();
     spawn_local(async move {
         let preferences = get_preferences().await;
@@ -163,6 +201,8 @@ pub fn UserSettings(show: RwSignal<bool>) -> impl IntoView {
             aerobic_threshold.set(preferences.aerobic_threshold as u32);
             anaerobic_threshold.set(preferences.anaerobic_threshold as u32);
             max_heartrate.set(preferences.max_heartrate as u32);
+            load_model.set(preferences.load_model);
+            critical_swim_pace.set(format_pace(100.0 / preferences.critical_swim_speed));
         }
     });
     view! {
@@ -284,6 +324,58 @@ pub fn UserSettings(show: RwSignal<bool>) -> impl IntoView {
                                     </div>
                                 </div>
                             </div>
+                            <div class="columns">
+                                <div class="column is-full">
+                                    <div class="field">
+                                        <label class="label">Load Model</label>
+                                        <div class="control">
+                                            <div class="select">
+                                                <select name="load_model">
+                                                    <option
+                                                        value=LoadModel::Heartrate.to_string()
+                                                        selected=move || load_model.get() == LoadModel::Heartrate
+                                                    >
+                                                        "Heart rate"
+                                                    </option>
+                                                    <option
+                                                        value=LoadModel::SessionRpe.to_string()
+                                                        selected=move || load_model.get() == LoadModel::SessionRpe
+                                                    >
+                                                        "Session-RPE (Foster)"
+                                                    </option>
+                                                </select>
+                                            </div>
+                                        </div>
+                                        <p class="help">
+                                            "Session-RPE uses RPE × duration for activities with a perceived exertion."
+                                        </p>
+                                    </div>
+                                </div>
+                            </div>
+                            <div class="columns">
+                                <div class="column is-full">
+                                    <div class="field">
+                                        <label class="label">Critical Swim Speed</label>
+                                        <div class="field has-addons">
+                                            <div class="control">
+                                                <input
+                                                    class="input"
+                                                    type="text"
+                                                    name="critical_swim_pace"
+                                                    pattern="[0-9]+:[0-5][0-9]"
+                                                    value=critical_swim_pace
+                                                />
+                                            </div>
+                                            <div class="control">
+                                                <span class="button is-static">"min/100m"</span>
+                                            </div>
+                                        </div>
+                                        <p class="help">
+                                            "Swim load is calculated from the pace relative to this one."
+                                        </p>
+                                    </div>
+                                </div>
+                            </div>
 
                         </div>
                         <div class="modal-card-foot">
@@ -1,17 +1,25 @@
 #[cfg(feature = "ssr")]
 use crate::app::{auth, pool};
 #[cfg(feature = "ssr")]
+use chrono::Local;
+#[cfg(feature = "ssr")]
 use itertools::Itertools;
 use leptos::*;
 use leptos_router::*;
+#[cfg(feature = "ssr")]
+use rrule::{RRule, RRuleSet, Tz, Unvalidated};
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "ssr")]
 use sqlx::*;
 use wasm_bindgen::JsCast;
 use web_sys::{DragEvent, HtmlElement};
 
+use super::add_workout_dialog::WorkoutTemplate;
+use super::manage_sports_dialog::{indented_name, list_sports};
+#[cfg(feature = "ssr")]
+use super::{local_midnight, WorkoutType};
 #[cfg(feature = "ssr")]
-use super::WorkoutType;
+use crate::models::sport::find_sport;
 
 #[derive(Clone, Debug)]
 pub struct Parameter {
@@ -87,9 +95,24 @@ pub fn WorkoutParameter(param: Parameter) -> impl IntoView {
                                         name=select_name
                                         id="parameter_type"
                                     >
-                                        <option value="time_s">Time</option>
-                                        <option value="distance_m">Distance(m)</option>
-                                        <option value="trainingload">TrainingLoad</option>
+                                        <option
+                                            value="time_s"
+                                            selected=move || param.param_type.get() == "time_s"
+                                        >
+                                            Time
+                                        </option>
+                                        <option
+                                            value="distance_m"
+                                            selected=move || param.param_type.get() == "distance_m"
+                                        >
+                                            Distance(m)
+                                        </option>
+                                        <option
+                                            value="trainingload"
+                                            selected=move || param.param_type.get() == "trainingload"
+                                        >
+                                            TrainingLoad
+                                        </option>
                                     </select>
                                 </div>
                             </p>
@@ -121,40 +144,31 @@ pub fn WorkoutParameter(param: Parameter) -> impl IntoView {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkoutParam {
-    name: String,
-    value: i32,
-    param_type: String,
-    scaling: bool,
-    position: i32,
+    pub(super) name: String,
+    pub(super) value: i32,
+    pub(super) param_type: String,
+    pub(super) scaling: bool,
+    pub(super) position: i32,
 }
 
-#[server]
-pub async fn create_workout(
-    name: String,
-    workout_type: String,
+/// Maps errors of template queries, so that duplicate names get a readable message.
+#[cfg(feature = "ssr")]
+pub(super) fn template_error(context: &str, e: sqlx::Error) -> ServerFnError {
+    match e.as_database_error().and_then(|d| d.code()) {
+        Some(code) if code == "23505" => {
+            ServerFnError::new("A template with this name already exists".to_string())
+        }
+        _ => ServerFnError::new(format!("{}: {}", context, e)),
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub(super) async fn insert_parameters(
+    template_id: i64,
     param: Vec<WorkoutParam>,
+    executor: &mut PgConnection,
 ) -> Result<(), ServerFnError> {
-    let pool = pool()?;
-    let auth = auth()?;
-    let user = auth
-        .current_user
-        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
-    let result = sqlx::query!(
-        r#"
-        INSERT INTO workout_templates (user_id, template_name, workout_type)
-        VALUES ($1, $2,$3)
-        RETURNING id
-        "#,
-        user.id as _,
-        name,
-        TryInto::<WorkoutType>::try_into(workout_type)
-            .map_err(|_| ServerFnError::new("Couldn't parse workout type".to_string()))?
-            as _
-    )
-    .fetch_one(&pool)
-    .await
-    .map_err(|e| ServerFnError::new(format!("Error saving workout template: {}", e)))?;
-    let template_ids: Vec<i64> = std::iter::repeat(result.id).take(param.len()).collect();
+    let template_ids: Vec<i64> = std::iter::repeat(template_id).take(param.len()).collect();
     let (names, types, values, scalings, positions): (Vec<_>, Vec<_>, Vec<_>, Vec<_>, Vec<_>) =
         param
             .into_iter()
@@ -173,28 +187,375 @@ pub async fn create_workout(
         &scalings[..],
         &positions
     )
-    .execute(&pool)
+    .execute(&mut *executor)
     .await
     .map_err(|e| ServerFnError::new(format!("Couldn't insert workout parameters: {}", e)))?;
+    Ok(())
+}
+
+/// Copies the parameter overrides of an instance to the parameters of another template version,
+/// matching parameters by position and type.
+#[cfg(feature = "ssr")]
+async fn copy_parameter_links(
+    from_instance: i64,
+    to_instance: i64,
+    template_id: i64,
+    executor: &mut PgConnection,
+) -> Result<(), ServerFnError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO parameter_links (instance_id, parameter_id, value_override)
+        SELECT $2, new_param.id, l.value_override
+        FROM parameter_links l
+        INNER JOIN workout_parameters old_param ON old_param.id=l.parameter_id
+        INNER JOIN workout_parameters new_param
+            ON new_param.position=old_param.position and new_param.parameter_type=old_param.parameter_type
+        WHERE l.instance_id=$1 and new_param.workout_template_id=$3
+        "#,
+        from_instance as i32,
+        to_instance as i32,
+        template_id as i32
+    )
+    .execute(&mut *executor)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't copy parameter overrides: {}", e)))?;
+    Ok(())
+}
+
+/// Moves scheduled workouts of a template to its new version.
+///
+/// Instances are split at the start of today, so past occurences keep the old version and
+/// future ones use the new one.
+#[cfg(feature = "ssr")]
+async fn move_instances_to_version(
+    old_template: i64,
+    new_template: i64,
+    executor: &mut PgConnection,
+) -> Result<(), ServerFnError> {
+    let cutoff = local_midnight(Local::now().date_naive()).map_err(ServerFnError::new)?;
+    let instances = sqlx::query!(
+        r#"
+        SELECT id, start_date, rrule, valid_from, valid_until
+        FROM workout_instances
+        WHERE workout_template_id=$1
+        "#,
+        old_template as i32
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load workout instances: {}", e)))?;
+    for instance in instances {
+        if instance.valid_until.is_some_and(|until| until <= cutoff) {
+            continue;
+        }
+        if instance.start_date >= cutoff || instance.valid_from.is_some_and(|from| from >= cutoff) {
+            // nothing happened yet, so the whole instance can use the new version
+            sqlx::query!(
+                r#"
+                UPDATE workout_instances
+                SET workout_template_id=$2
+                WHERE id=$1
+                "#,
+                instance.id,
+                new_template as i32
+            )
+            .execute(&mut *executor)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Couldn't update workout instance: {}", e)))?;
+            copy_parameter_links(instance.id, instance.id, new_template, executor).await?;
+            sqlx::query!(
+                r#"
+                DELETE FROM parameter_links l
+                USING workout_parameters p
+                WHERE l.parameter_id=p.id and l.instance_id=$1 and p.workout_template_id=$2
+                "#,
+                instance.id as i32,
+                old_template as i32
+            )
+            .execute(&mut *executor)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Couldn't update parameter overrides: {}", e)))?;
+            continue;
+        }
+        let start = instance.start_date.with_timezone(&Tz::LOCAL);
+        let rrule = instance
+            .rrule
+            .parse::<RRule<Unvalidated>>()
+            .and_then(|r| r.validate(start))
+            .map_err(|e| ServerFnError::new(format!("Invalid rrule: {}", e)))?;
+        let upcoming = RRuleSet::new(start)
+            .rrule(rrule)
+            .after(cutoff.with_timezone(&Tz::LOCAL))
+            .all(1)
+            .dates;
+        if upcoming
+            .first()
+            .map_or(true, |d| instance.valid_until.is_some_and(|until| *d >= until))
+        {
+            continue;
+        }
+        sqlx::query!(
+            r#"
+            UPDATE workout_instances
+            SET valid_until=$2
+            WHERE id=$1
+            "#,
+            instance.id,
+            cutoff
+        )
+        .execute(&mut *executor)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't update workout instance: {}", e)))?;
+        let new_instance = sqlx::query!(
+            r#"
+            INSERT INTO workout_instances (user_id, workout_template_id, start_date, rrule, active, valid_from, valid_until)
+            SELECT user_id, $2, start_date, rrule, active, $3, $4
+            FROM workout_instances
+            WHERE id=$1
+            RETURNING id
+            "#,
+            instance.id,
+            new_template as i32,
+            cutoff,
+            instance.valid_until
+        )
+        .fetch_one(&mut *executor)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't split workout instance: {}", e)))?
+        .id;
+        sqlx::query!(
+            r#"
+            INSERT INTO workout_exclusion_dates (workout_instance_id, exclusion_date)
+            SELECT $2, exclusion_date
+            FROM workout_exclusion_dates
+            WHERE workout_instance_id=$1 and exclusion_date >= $3
+            "#,
+            instance.id as i32,
+            new_instance as i32,
+            cutoff
+        )
+        .execute(&mut *executor)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't copy exclusion dates: {}", e)))?;
+        sqlx::query!(
+            r#"
+            UPDATE workout_completions
+            SET workout_instance_id=$2
+            WHERE workout_instance_id=$1 and occurence_date >= $3
+            "#,
+            instance.id as i32,
+            new_instance as i32,
+            cutoff
+        )
+        .execute(&mut *executor)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't move workout completions: {}", e)))?;
+        copy_parameter_links(instance.id, new_instance, new_template, executor).await?;
+    }
+    Ok(())
+}
+
+/// Creates a workout template, or saves a new version of an existing one if `template_id` is set.
+///
+/// Templates that aren't scheduled yet are changed in place, otherwise a new version is created
+/// so that past occurences aren't changed.
+#[server]
+pub async fn create_workout(
+    name: String,
+    workout_type: String,
+    sport: String,
+    param: Vec<WorkoutParam>,
+    template_id: Option<i64>,
+) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let workout_type = TryInto::<WorkoutType>::try_into(workout_type)
+        .map_err(|_| ServerFnError::new("Couldn't parse workout type".to_string()))?;
+    // an empty sport means any sport of the workout type
+    let sport_id = match sport.parse::<i64>() {
+        Ok(sport_id) => Some(
+            find_sport(user.id, sport_id, &pool)
+                .await
+                .map_err(|e| ServerFnError::new(format!("Couldn't load sport: {}", e)))?
+                .ok_or(ServerFnError::new("Unknown sport".to_string()))?,
+        ),
+        Err(_) if sport.is_empty() => None,
+        Err(_) => return Err(ServerFnError::new("Couldn't parse sport".to_string())),
+    };
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    let Some(template_id) = template_id else {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO workout_templates (user_id, template_name, workout_type, sport_id)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#,
+            user.id as _,
+            name,
+            workout_type as _,
+            sport_id
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|e| template_error("Error saving workout template", e))?;
+        insert_parameters(result.id, param, &mut transaction).await?;
+        transaction
+            .commit()
+            .await
+            .map_err(|e| ServerFnError::new(format!("Couldn't save workout template: {}", e)))?;
+        return Ok(());
+    };
+    let template = sqlx::query!(
+        r#"
+        SELECT
+            version,
+            superseded,
+            archived,
+            EXISTS(SELECT 1 FROM workout_instances i WHERE i.workout_template_id=t.id) as "scheduled!"
+        FROM workout_templates t
+        WHERE id=$1 and user_id=$2::bigint
+        "#,
+        template_id,
+        user.id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Unknown workout template: {}", e)))?;
+    if template.superseded {
+        return Err(ServerFnError::new(
+            "Only the latest version of a template can be edited".to_string(),
+        ));
+    }
+    if !template.scheduled {
+        sqlx::query!(
+            r#"
+            UPDATE workout_templates
+            SET template_name=$2, workout_type=$3, sport_id=$4
+            WHERE id=$1
+            "#,
+            template_id,
+            name,
+            workout_type as _,
+            sport_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| template_error("Couldn't update workout template", e))?;
+        sqlx::query!(
+            r#"
+            DELETE FROM workout_parameters
+            WHERE workout_template_id=$1
+            "#,
+            template_id as i32
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't update workout parameters: {}", e)))?;
+        insert_parameters(template_id, param, &mut transaction).await?;
+    } else {
+        sqlx::query!(
+            r#"
+            UPDATE workout_templates
+            SET superseded=TRUE
+            WHERE id=$1
+            "#,
+            template_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't update workout template: {}", e)))?;
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO workout_templates (user_id, template_name, workout_type, sport_id, version, previous_version_id, archived)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id
+            "#,
+            user.id as _,
+            name,
+            workout_type as _,
+            sport_id,
+            template.version + 1,
+            template_id as i32,
+            template.archived
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|e| template_error("Error saving workout template", e))?;
+        insert_parameters(result.id, param, &mut transaction).await?;
+        move_instances_to_version(template_id, result.id, &mut transaction).await?;
+    }
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't save workout template: {}", e)))?;
 
     Ok(())
 }
 
+/// Dialog to create a workout template.
+///
+/// If `template` is set, the dialog is filled with it and saves a new version of it instead.
 #[component]
-pub fn CreateWorkoutDialog(show: RwSignal<bool>) -> impl IntoView {
+pub fn CreateWorkoutDialog(
+    show: RwSignal<bool>,
+    #[prop(optional)] template: Option<RwSignal<Option<WorkoutTemplate>>>,
+    #[prop(optional, into)] on_save: Option<Callback<()>>,
+) -> impl IntoView {
     let create_workout_action = create_server_action::<CreateWorkout>();
     let workout_parameter_index = create_rw_signal(0);
     let workout_parameters = create_rw_signal(vec![Parameter::default()]);
+    let template = template.unwrap_or(create_rw_signal(None));
+    let sports = create_rw_signal(Vec::new());
+    create_effect(move |_| {
+        if show() {
+            spawn_local(async move {
+                sports.set(list_sports().await.unwrap_or_default());
+            });
+        }
+    });
     let on_submit = move |_| {
         show.set(false);
     };
+    create_effect(move |_| {
+        // run callback if server action was run
+        if let Some(Ok(_)) = create_workout_action.value().get() {
+            if let Some(on_save) = on_save {
+                on_save(());
+            }
+        }
+    });
     let owner = Owner::current().unwrap();
     let _ = watch(
         move || show.get(),
         move |cur, prev, _| {
             if *cur && !*prev.unwrap_or(&false) {
-                workout_parameters.set(Vec::new());
-                workout_parameter_index.set(0);
+                let params = template
+                    .get_untracked()
+                    .map(|t| t.parameters)
+                    .unwrap_or_default();
+                with_owner(owner, || {
+                    workout_parameters.set(
+                        params
+                            .iter()
+                            .enumerate()
+                            .map(|(i, p)| Parameter {
+                                key: i as u32,
+                                name: create_rw_signal(p.name.clone()),
+                                value: create_rw_signal(p.value as u32),
+                                param_type: create_rw_signal(p.parameter_type.clone()),
+                                scaling: create_rw_signal(p.scaling),
+                                order: create_rw_signal(i as u32),
+                            })
+                            .collect(),
+                    );
+                });
+                workout_parameter_index.set(params.len() as u32);
             }
         },
         false,
@@ -222,7 +583,16 @@ pub fn CreateWorkoutDialog(show: RwSignal<bool>) -> impl IntoView {
                     <div class="modal-background" on:click=close></div>
                     <div class="modal-card">
                         <div class="modal-card-head">
-                            <p class="modal-card-title">"Create workout"</p>
+                            <p class="modal-card-title">
+                                {move || {
+                                    if template.with(|t| t.is_some()) {
+                                        "Edit workout"
+                                    } else {
+                                        "Create workout"
+                                    }
+                                }}
+
+                            </p>
                             <button class="delete" aria-label="close" on:click=close></button>
 
                         </div>
@@ -232,7 +602,24 @@ pub fn CreateWorkoutDialog(show: RwSignal<bool>) -> impl IntoView {
                                     Name
                                 </label>
                                 <div class="control">
-                                    <input class="input" id="name" name="name" type="text"/>
+                                    <input
+                                        class="input"
+                                        id="name"
+                                        name="name"
+                                        type="text"
+                                        value=move || {
+                                            template
+                                                .with(|t| t.as_ref().map(|t| t.template_name.clone()))
+                                        }
+                                    />
+
+                                    {move || {
+                                        template
+                                            .with(|t| t.as_ref().map(|t| t.id))
+                                            .map(|id| {
+                                                view! { <input type="hidden" name="template_id" value=id/> }
+                                            })
+                                    }}
 
                                 </div>
                             </div>
@@ -243,14 +630,76 @@ pub fn CreateWorkoutDialog(show: RwSignal<bool>) -> impl IntoView {
                                 <div class="control">
                                     <div class="select">
                                         <select name="workout_type">
-                                            <option value="" disabled selected>
+                                            <option
+                                                value=""
+                                                disabled
+                                                selected=move || template.with(|t| t.is_none())
+                                            >
                                                 Choose Workout Type
                                             </option>
-                                            <option value="run">Run</option>
-                                            <option value="strength">Strength</option>
-                                            <option value="cycling">Cycling</option>
-                                            <option value="hiking">Hiking</option>
-                                            <option value="endurance">General Endurance</option>
+
+                                            {[
+                                                ("run", "Run"),
+                                                ("strength", "Strength"),
+                                                ("cycling", "Cycling"),
+                                                ("hiking", "Hiking"),
+                                                ("endurance", "General Endurance"),
+                                            ]
+                                                .into_iter()
+                                                .map(|(value, label)| {
+                                                    view! {
+                                                        <option
+                                                            value=value
+                                                            selected=move || {
+                                                                template
+                                                                    .with(|t| {
+                                                                        t.as_ref()
+                                                                            .is_some_and(|t| t.workout_type.to_string() == value)
+                                                                    })
+                                                            }
+                                                        >
+
+                                                            {label}
+                                                        </option>
+                                                    }
+                                                })
+                                                .collect_view()}
+                                        </select>
+                                    </div>
+                                </div>
+                            </div>
+                            <div class="field">
+                                <label class="label" for="sport">
+                                    Sport
+                                </label>
+                                <div class="control">
+                                    <div class="select">
+                                        <select name="sport">
+                                            <option value="">Any sport of this type</option>
+                                            {move || {
+                                                sports
+                                                    .get()
+                                                    .into_iter()
+                                                    .map(|s| {
+                                                        let sport_id = s.id;
+                                                        view! {
+                                                            <option
+                                                                value=sport_id
+                                                                selected=move || {
+                                                                    template
+                                                                        .with(|t| {
+                                                                            t.as_ref().and_then(|t| t.sport_id) == Some(sport_id)
+                                                                        })
+                                                                }
+                                                            >
+
+                                                                {indented_name(&s)}
+                                                            </option>
+                                                        }
+                                                    })
+                                                    .collect_view()
+                                            }}
+
                                         </select>
                                     </div>
                                 </div>
@@ -403,7 +852,10 @@ pub fn CreateWorkoutDialog(show: RwSignal<bool>) -> impl IntoView {
                             </button>
                             <button type="submit" class="button is-success">
                                 <i class="material-symbols-rounded right">save</i>
-                                Create
+                                {move || {
+                                    if template.with(|t| t.is_some()) { "Save" } else { "Create" }
+                                }}
+
                             </button>
                         </div>
                     </div>
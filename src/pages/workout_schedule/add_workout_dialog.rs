@@ -48,6 +48,10 @@ pub struct WorkoutTemplate {
     pub template_name: String,
     /// the type of workout this is.
     pub workout_type: WorkoutType, // /// The steps that make up this workout.
+    /// the sport of the workout, any sport of the workout type fulfills it if unset.
+    pub sport_id: Option<i64>,
+    /// name of the sport of the workout.
+    pub sport: Option<String>,
     pub parameters: Vec<WorkoutParameter>,
 }
 
@@ -59,6 +63,8 @@ impl sqlx::FromRow<'_, PgRow> for WorkoutTemplate {
             user_id: row.get("user_id"),
             template_name: row.get("template_name"),
             workout_type: WorkoutType::from_str(&row.get::<&str, _>("workout_type")).unwrap(),
+            sport_id: row.get("sport_id"),
+            sport: row.get("sport"),
             parameters: row.get::<Vec<WorkoutParameter>, _>("parameters"),
         })
     }
@@ -77,11 +83,14 @@ pub async fn get_workout_templates() -> Result<Vec<WorkoutTemplate>, ServerFnErr
             templates.user_id,
             templates.template_name,
             templates.workout_type::text,
+            templates.sport_id,
+            sports.name as sport,
             ARRAY_AGG((params.id, params.name, params.value, params.parameter_type::TEXT, params.scaling, params.position) ORDER BY params.position) as "parameters" 
         FROM workout_templates as templates 
         INNER JOIN workout_parameters as params ON params.workout_template_id = templates.id
-        WHERE templates.user_id = $1::bigint
-        GROUP BY templates.id"#
+        LEFT JOIN sports ON sports.id = templates.sport_id
+        WHERE templates.user_id = $1::bigint and NOT templates.superseded and NOT templates.archived
+        GROUP BY templates.id, sports.id"#
     )
         .bind(user.id)
     .fetch_all(&pool)
@@ -107,6 +116,26 @@ pub async fn add_workout(
     let user = auth
         .current_user
         .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    // archived and superseded templates are hidden in the dialog, but could still be sent
+    let schedulable = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM workout_templates
+            WHERE id=$1 and user_id=$2::bigint and NOT archived and NOT superseded
+        ) as "schedulable!"
+        "#,
+        workout_type as i64,
+        user.id
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load workout template: {}", e)))?;
+    if !schedulable {
+        return Err(ServerFnError::new(
+            "Workout template not found or archived".to_string(),
+        ));
+    }
     let result = sqlx::query!(
         r#"INSERT INTO workout_instances (user_id, workout_template_id, start_date, rrule)
         VALUES ($1,$2,$3,$4)
@@ -0,0 +1,830 @@
+use std::collections::HashMap;
+#[cfg(feature = "ssr")]
+use std::collections::HashSet;
+
+#[cfg(feature = "ssr")]
+use crate::app::{auth, pool};
+use chrono::NaiveDate;
+#[cfg(feature = "ssr")]
+use chrono::{Local, NaiveDateTime, TimeZone, Utc};
+#[cfg(feature = "ssr")]
+use itertools::Itertools;
+use leptos::*;
+#[cfg(feature = "ssr")]
+use rrule::{Frequency, RRule, Tz, Unvalidated};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "ssr")]
+use sqlx::*;
+use strum::IntoEnumIterator;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::HtmlInputElement;
+
+use super::add_workout_dialog::{get_workout_templates, WorkoutTemplate};
+#[cfg(feature = "ssr")]
+use super::local_midnight;
+use super::WorkoutType;
+
+/// A single VEVENT of an iCalendar file, reduced to what's needed to schedule a workout.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CalendarEvent {
+    /// The summary of the event, used to match it to a workout template.
+    pub summary: String,
+    /// The day of the first occurence.
+    pub start_date: NaiveDate,
+    /// Duration of the event in seconds, if it has a start time.
+    pub duration: Option<i32>,
+    /// The RRULE of the event, if it repeats.
+    pub rrule: Option<String>,
+    /// Days excluded from the recurrence.
+    pub exclusion_dates: Vec<NaiveDate>,
+}
+
+/// All events of an imported calendar that share the same summary.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CalendarImportGroup {
+    pub summary: String,
+    pub events: Vec<CalendarEvent>,
+    /// The existing template whose name matches the summary.
+    pub template_id: Option<i64>,
+}
+
+/// What to do with the events of a summary when importing.
+/// If neither a template nor a workout type are set, the events are skipped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateAssignment {
+    pub summary: String,
+    /// Schedule the events for this existing template.
+    pub template_id: Option<i64>,
+    /// Create a new template named after the summary with this workout type.
+    pub workout_type: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Default)]
+struct RawEvent {
+    uid: Option<String>,
+    summary: Option<String>,
+    start: Option<(NaiveDateTime, bool)>,
+    end: Option<NaiveDateTime>,
+    duration: Option<i32>,
+    rrule: Option<String>,
+    rdates: Vec<NaiveDate>,
+    exclusion_dates: Vec<NaiveDate>,
+    recurrence_id: Option<NaiveDate>,
+    cancelled: bool,
+}
+
+/// Joins folded lines, continuation lines start with a space or tab.
+#[cfg(feature = "ssr")]
+fn unfold_lines(calendar: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in calendar.lines() {
+        if let Some(rest) = line.strip_prefix(&[' ', '\t'][..]) {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(rest);
+                continue;
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line.to_string());
+        }
+    }
+    lines
+}
+
+/// Splits a content line into its upper-cased name, parameters and value.
+#[cfg(feature = "ssr")]
+fn parse_content_line(line: &str) -> Option<(String, HashMap<String, String>, String)> {
+    let mut in_quotes = false;
+    let split = line.char_indices().find(|(_, c)| {
+        if *c == '"' {
+            in_quotes = !in_quotes;
+        }
+        *c == ':' && !in_quotes
+    })?;
+    let (head, value) = (&line[..split.0], &line[split.0 + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.to_uppercase();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.to_uppercase(), v.trim_matches('"').to_string()))
+        .collect();
+    Some((name, params, value.to_string()))
+}
+
+#[cfg(feature = "ssr")]
+fn unescape_text(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => result.push(' '),
+                Some(c) => result.push(c),
+                None => {}
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result.trim().to_string()
+}
+
+/// Parses a DATE or DATE-TIME value into a local datetime.
+/// The returned flag is true if the value only contained a date.
+/// Times with a TZID are treated as local time, since only the day is used for scheduling.
+#[cfg(feature = "ssr")]
+fn parse_datetime(value: &str) -> Result<(NaiveDateTime, bool), String> {
+    let value = value.trim();
+    if value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .map(|d| (d.and_hms_opt(0, 0, 0).unwrap(), true))
+            .map_err(|e| format!("invalid date '{}': {}", value, e));
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .map(|d| {
+                (
+                    Utc.from_utc_datetime(&d)
+                        .with_timezone(&Local)
+                        .naive_local(),
+                    false,
+                )
+            })
+            .map_err(|e| format!("invalid date '{}': {}", value, e));
+    }
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .map(|d| (d, false))
+        .map_err(|e| format!("invalid date '{}': {}", value, e))
+}
+
+/// Parses an iCalendar DURATION such as `PT1H30M` or `P1W` into seconds.
+#[cfg(feature = "ssr")]
+fn parse_duration(value: &str) -> Result<i32, String> {
+    let invalid = || format!("invalid duration '{}'", value);
+    let (sign, rest) = match value.trim().strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.trim().trim_start_matches('+')),
+    };
+    let rest = rest.strip_prefix('P').ok_or_else(invalid)?;
+    let mut seconds: i32 = 0;
+    let mut number = String::new();
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            'W' | 'D' | 'H' | 'M' | 'S' => {
+                let n: i32 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+                let unit = match c {
+                    'W' => 7 * 24 * 3600,
+                    'D' => 24 * 3600,
+                    'H' => 3600,
+                    'M' => 60,
+                    _ => 1,
+                };
+                seconds = n
+                    .checked_mul(unit)
+                    .and_then(|n| n.checked_add(seconds))
+                    .ok_or_else(invalid)?;
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+    Ok(sign * seconds)
+}
+
+#[cfg(feature = "ssr")]
+fn parse_date_list(value: &str) -> Result<Vec<NaiveDate>, String> {
+    value
+        .split(',')
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| parse_datetime(v).map(|(d, _)| d.date()))
+        .collect()
+}
+
+/// Parses all VEVENTs of an iCalendar file.
+///
+/// Modified occurences (events with a RECURRENCE-ID) are excluded from their recurring event and returned as single events,
+/// cancelled events are dropped. RDATEs are returned as additional single events.
+#[cfg(feature = "ssr")]
+pub fn parse_calendar(calendar: &str) -> Result<Vec<CalendarEvent>, String> {
+    let mut raw_events: Vec<RawEvent> = Vec::new();
+    let mut current: Option<RawEvent> = None;
+    for line in unfold_lines(calendar) {
+        let (name, params, value) = match parse_content_line(&line) {
+            Some(content) => content,
+            None => continue,
+        };
+        match (name.as_str(), current.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VEVENT") => {
+                current = Some(RawEvent::default())
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                raw_events.push(current.take().unwrap())
+            }
+            ("UID", Some(event)) => event.uid = Some(value),
+            ("SUMMARY", Some(event)) => event.summary = Some(unescape_text(&value)),
+            ("DTSTART", Some(event)) => event.start = Some(parse_datetime(&value)?),
+            ("DTEND", Some(event)) => event.end = Some(parse_datetime(&value)?.0),
+            ("DURATION", Some(event)) => event.duration = Some(parse_duration(&value)?),
+            ("RRULE", Some(event)) => event.rrule = Some(value),
+            ("RDATE", Some(event)) if params.get("VALUE").map(|v| v.as_str()) != Some("PERIOD") => {
+                event.rdates.extend(parse_date_list(&value)?)
+            }
+            ("EXDATE", Some(event)) => event.exclusion_dates.extend(parse_date_list(&value)?),
+            ("RECURRENCE-ID", Some(event)) => {
+                event.recurrence_id = Some(parse_datetime(&value)?.0.date())
+            }
+            ("STATUS", Some(event)) => event.cancelled = value.eq_ignore_ascii_case("CANCELLED"),
+            _ => {}
+        }
+    }
+    if current.is_some() {
+        return Err("VEVENT is missing its END".to_string());
+    }
+
+    // exclude modified occurences from the event they belong to
+    let overrides: Vec<(String, NaiveDate)> = raw_events
+        .iter()
+        .filter_map(|e| e.uid.clone().zip(e.recurrence_id))
+        .collect();
+    for (uid, date) in overrides {
+        raw_events
+            .iter_mut()
+            .filter(|e| e.recurrence_id.is_none() && e.uid.as_ref() == Some(&uid))
+            .for_each(|e| e.exclusion_dates.push(date));
+    }
+
+    let mut events = Vec::new();
+    for event in raw_events.into_iter().filter(|e| !e.cancelled) {
+        let summary = event
+            .summary
+            .filter(|s| !s.is_empty())
+            .ok_or("VEVENT without SUMMARY".to_string())?;
+        let (start, all_day) = event
+            .start
+            .ok_or(format!("VEVENT '{}' without DTSTART", summary))?;
+        let duration = if all_day {
+            None
+        } else {
+            event
+                .duration
+                .or(event.end.map(|end| (end - start).num_seconds() as i32))
+                .filter(|d| *d > 0)
+        };
+        let rrule = event.rrule.filter(|_| event.recurrence_id.is_none());
+        events.extend(event.rdates.iter().map(|date| CalendarEvent {
+            summary: summary.clone(),
+            start_date: *date,
+            duration,
+            rrule: None,
+            exclusion_dates: Vec::new(),
+        }));
+        events.push(CalendarEvent {
+            summary,
+            start_date: start.date(),
+            duration,
+            exclusion_dates: if rrule.is_some() {
+                event.exclusion_dates
+            } else {
+                Vec::new()
+            },
+            rrule,
+        });
+    }
+    Ok(events)
+}
+
+/// Converts the recurrence of an event into the validated rrule stored with workout instances.
+/// Single events become a rule with exactly one occurence.
+#[cfg(feature = "ssr")]
+fn event_rrule(event: &CalendarEvent) -> Result<String, String> {
+    let rrule = match &event.rrule {
+        Some(rule) => rule
+            .parse::<RRule<Unvalidated>>()
+            .map_err(|e| format!("Invalid RRULE for '{}': {}", event.summary, e))?,
+        None => RRule::new(Frequency::Daily).count(1),
+    };
+    rrule
+        .validate(local_midnight(event.start_date)?.with_timezone(&Tz::LOCAL))
+        .map(|r| r.to_string())
+        .map_err(|e| format!("Invalid RRULE for '{}': {}", event.summary, e))
+}
+
+#[server]
+pub async fn preview_calendar_import(
+    calendar: String,
+) -> Result<Vec<CalendarImportGroup>, ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let events = parse_calendar(&calendar)
+        .map_err(|e| ServerFnError::new(format!("Couldn't read calendar: {}", e)))?;
+    for event in events.iter() {
+        event_rrule(event).map_err(ServerFnError::new)?;
+    }
+    let templates = sqlx::query!(
+        r#"
+        SELECT id, template_name
+        FROM workout_templates
+        WHERE user_id=$1::bigint and NOT superseded
+        "#,
+        user.id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load workout templates: {}", e)))?;
+    let templates: HashMap<String, i64> = templates
+        .into_iter()
+        .map(|t| (t.template_name.to_lowercase(), t.id))
+        .collect();
+    let groups = events
+        .into_iter()
+        .into_group_map_by(|e| e.summary.clone())
+        .into_iter()
+        .map(|(summary, mut events)| {
+            events.sort_by_key(|e| e.start_date);
+            CalendarImportGroup {
+                template_id: templates.get(&summary.to_lowercase()).copied(),
+                summary,
+                events,
+            }
+        })
+        .sorted_by_key(|g| g.events.first().map(|e| e.start_date))
+        .collect();
+    Ok(groups)
+}
+
+#[server]
+pub async fn import_calendar(
+    calendar: String,
+    assignments: Vec<TemplateAssignment>,
+) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let events = parse_calendar(&calendar)
+        .map_err(|e| ServerFnError::new(format!("Couldn't read calendar: {}", e)))?
+        .into_iter()
+        .into_group_map_by(|e| e.summary.clone());
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    let mut imported = HashSet::new();
+    for assignment in assignments {
+        let events = match events.get(&assignment.summary) {
+            Some(events) if imported.insert(assignment.summary.clone()) => events,
+            _ => continue,
+        };
+        let template_id = match (assignment.template_id, assignment.workout_type) {
+            (Some(template_id), _) => {
+                sqlx::query!(
+                    r#"
+                    SELECT id
+                    FROM workout_templates
+                    WHERE id=$1 and user_id=$2::bigint
+                    "#,
+                    template_id,
+                    user.id
+                )
+                .fetch_one(&mut *transaction)
+                .await
+                .map_err(|e| ServerFnError::new(format!("Unknown workout template: {}", e)))?
+                .id
+            }
+            (None, Some(workout_type)) => {
+                let template = sqlx::query!(
+                    r#"
+                    INSERT INTO workout_templates (user_id, template_name, workout_type)
+                    VALUES ($1, $2, $3)
+                    RETURNING id
+                    "#,
+                    user.id as _,
+                    assignment.summary,
+                    TryInto::<WorkoutType>::try_into(workout_type).map_err(|_| {
+                        ServerFnError::new("Couldn't parse workout type".to_string())
+                    })? as _
+                )
+                .fetch_one(&mut *transaction)
+                .await
+                .map_err(|e| ServerFnError::new(format!("Error saving workout template: {}", e)))?;
+                let duration = events.iter().find_map(|e| e.duration).unwrap_or(3600);
+                sqlx::query!(
+                    r#"
+                    INSERT INTO workout_parameters(workout_template_id, name, parameter_type, value, scaling, position)
+                    VALUES ($1, 'Duration', 'time_s', $2, TRUE, 0)
+                    "#,
+                    template.id as _,
+                    duration
+                )
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| ServerFnError::new(format!("Couldn't insert workout parameters: {}", e)))?;
+                template.id
+            }
+            (None, None) => continue,
+        };
+        for event in events {
+            let rrule = event_rrule(event).map_err(ServerFnError::new)?;
+            let instance = sqlx::query!(
+                r#"
+                INSERT INTO workout_instances (user_id, workout_template_id, start_date, rrule)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id
+                "#,
+                user.id as _,
+                template_id as _,
+                local_midnight(event.start_date).map_err(ServerFnError::new)?,
+                rrule
+            )
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Error saving workout: {}", e)))?;
+            if !event.exclusion_dates.is_empty() {
+                let instance_ids: Vec<i64> = std::iter::repeat(instance.id)
+                    .take(event.exclusion_dates.len())
+                    .collect();
+                let dates: Vec<_> = event
+                    .exclusion_dates
+                    .iter()
+                    .map(|d| local_midnight(*d))
+                    .collect::<Result<_, _>>()
+                    .map_err(ServerFnError::new)?;
+                sqlx::query!(
+                    r#"
+                    INSERT INTO workout_exclusion_dates(workout_instance_id, exclusion_date)
+                    SELECT *
+                    FROM UNNEST($1::bigint[], $2::timestamptz[])
+                    "#,
+                    &instance_ids[..],
+                    &dates[..]
+                )
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| ServerFnError::new(format!("Error saving exclusion dates: {}", e)))?;
+            }
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't commit changes:{}", e)))?;
+    Ok(())
+}
+
+#[component]
+pub fn ImportCalendarDialog(
+    show: RwSignal<bool>,
+    #[prop(into)] on_save: Callback<()>,
+) -> impl IntoView {
+    let calendar = create_rw_signal(None::<String>);
+    // selected template per summary, either a template id, "new" or "skip"
+    let assignments = create_rw_signal(HashMap::<String, String>::new());
+    let workout_types = create_rw_signal(HashMap::<String, String>::new());
+    let templates = create_resource(show, |show| async move {
+        if show {
+            get_workout_templates().await.unwrap_or_default()
+        } else {
+            Vec::new()
+        }
+    });
+    let preview = create_resource(
+        move || calendar.get(),
+        |calendar| async move {
+            match calendar {
+                Some(calendar) => preview_calendar_import(calendar).await.map(Some),
+                None => Ok(None),
+            }
+        },
+    );
+    create_effect(move |_| {
+        if let Some(Ok(Some(groups))) = preview.get() {
+            assignments.set(
+                groups
+                    .iter()
+                    .map(|g| {
+                        (
+                            g.summary.clone(),
+                            g.template_id
+                                .map(|id| id.to_string())
+                                .unwrap_or("new".to_string()),
+                        )
+                    })
+                    .collect(),
+            );
+            workout_types.set(
+                groups
+                    .iter()
+                    .map(|g| (g.summary.clone(), "run".to_string()))
+                    .collect(),
+            );
+        }
+    });
+    let import_action = create_server_action::<ImportCalendar>();
+    create_effect(move |_| {
+        // run callback if server action was run
+        if let Some(Ok(_)) = import_action.value().get() {
+            calendar.set(None);
+            show.set(false);
+            on_save(());
+        }
+    });
+    let close = move |_| {
+        calendar.set(None);
+        show.set(false);
+    };
+    let on_file = move |ev: leptos::ev::Event| {
+        let input = event_target::<HtmlInputElement>(&ev);
+        if let Some(file) = input.files().and_then(|f| f.get(0)) {
+            spawn_local(async move {
+                if let Ok(text) = JsFuture::from(file.text()).await {
+                    calendar.set(text.as_string());
+                }
+            });
+        }
+    };
+    let on_import = move |_| {
+        if let Some(calendar) = calendar.get_untracked() {
+            let types = workout_types.get_untracked();
+            import_action.dispatch(ImportCalendar {
+                calendar,
+                assignments: assignments
+                    .get_untracked()
+                    .into_iter()
+                    .map(|(summary, choice)| TemplateAssignment {
+                        template_id: choice.parse::<i64>().ok(),
+                        workout_type: if choice == "new" {
+                            types.get(&summary).cloned()
+                        } else {
+                            None
+                        },
+                        summary,
+                    })
+                    .collect(),
+            });
+        }
+    };
+    view! {
+        <Show when=move || { show.get() } fallback=|| {}>
+            <div class="modal is-active">
+                <div class="modal-background" on:click=close></div>
+                <div class="modal-card">
+                    <div class="modal-card-head">
+                        <p class="modal-card-title">"Import training plan"</p>
+                        <button class="delete" aria-label="close" on:click=close></button>
+                    </div>
+                    <div class="modal-card-body">
+                        <div class="file">
+                            <label class="file-label">
+                                <input
+                                    class="file-input"
+                                    type="file"
+                                    name="calendar"
+                                    accept=".ics,text/calendar"
+                                    on:change=on_file
+                                />
+                                <span class="file-cta">
+                                    <span class="file-icon">
+                                        <i class="fas fa-upload"></i>
+                                    </span>
+                                    <span class="file-label">Choose iCalendar File...</span>
+                                </span>
+                            </label>
+                        </div>
+                        <Transition fallback=move || {
+                            view! { <p>"Loading..."</p> }
+                        }>
+                            {move || match preview.get() {
+                                Some(Err(e)) => {
+                                    view! { <pre class="error">"Error: " {e.to_string()}</pre> }
+                                        .into_view()
+                                }
+                                Some(Ok(Some(groups))) => {
+                                    view! {
+                                        <table class="table is-striped is-fullwidth">
+                                            <thead>
+                                                <tr>
+                                                    <th>Workout</th>
+                                                    <th>Starts</th>
+                                                    <th>Entries</th>
+                                                    <th>Template</th>
+                                                </tr>
+                                            </thead>
+                                            <tbody>
+                                                <For
+                                                    each=move || groups.clone()
+                                                    key=|g| g.summary.clone()
+                                                    let:group
+                                                >
+                                                    <CalendarImportRow
+                                                        group=group
+                                                        templates=templates
+                                                        assignments=assignments
+                                                        workout_types=workout_types
+                                                    />
+                                                </For>
+                                            </tbody>
+                                        </table>
+                                    }
+                                        .into_view()
+                                }
+                                _ => view! {}.into_view(),
+                            }}
+
+                        </Transition>
+                        {move || {
+                            import_action
+                                .value()
+                                .get()
+                                .and_then(|r| r.err())
+                                .map(|e| {
+                                    view! { <pre class="error">"Error: " {e.to_string()}</pre> }
+                                })
+                        }}
+
+                    </div>
+                    <div class="modal-card-foot">
+                        <button class="button" on:click=close>
+                            Cancel
+                        </button>
+                        <button
+                            class="button is-success"
+                            disabled=move || !matches!(preview.get(), Some(Ok(Some(_))))
+                            on:click=on_import
+                        >
+                            <i class="material-symbols-rounded right">save</i>
+                            Import
+                        </button>
+                    </div>
+                </div>
+            </div>
+        </Show>
+    }
+}
+
+#[component]
+fn CalendarImportRow(
+    group: CalendarImportGroup,
+    templates: Resource<bool, Vec<WorkoutTemplate>>,
+    assignments: RwSignal<HashMap<String, String>>,
+    workout_types: RwSignal<HashMap<String, String>>,
+) -> impl IntoView {
+    let summary = group.summary.clone();
+    let choice = {
+        let summary = summary.clone();
+        move || {
+            assignments
+                .with(|a| a.get(&summary).cloned())
+                .unwrap_or("new".to_string())
+        }
+    };
+    let set_choice = {
+        let summary = summary.clone();
+        move |ev| {
+            let value = event_target_value(&ev);
+            assignments.update(|a| {
+                a.insert(summary.clone(), value);
+            })
+        }
+    };
+    let set_type = {
+        let summary = summary.clone();
+        move |ev| {
+            let value = event_target_value(&ev);
+            workout_types.update(|t| {
+                t.insert(summary.clone(), value);
+            })
+        }
+    };
+    let type_options = || {
+        WorkoutType::iter()
+            .map(|t| view! { <option value=t.to_string()>{t.label()}</option> })
+            .collect_view()
+    };
+    let is_new = {
+        let choice = choice.clone();
+        move || choice() == "new"
+    };
+    view! {
+        <tr>
+            <td>{group.summary.clone()}</td>
+            <td>{group.events.first().map(|e| e.start_date.format("%Y-%m-%d").to_string())}</td>
+            <td>
+                {group.events.len()}
+                {if group.events.iter().any(|e| e.rrule.is_some()) { " (repeating)" } else { "" }}
+            </td>
+            <td>
+                <div class="field is-grouped">
+                    <div class="control">
+                        <div class="select is-small">
+                            <select on:change=set_choice>
+                                <option value="new" selected=choice() == "new">
+                                    "Create new template"
+                                </option>
+                                <option value="skip" selected=choice() == "skip">
+                                    "Skip"
+                                </option>
+                                {move || {
+                                    let choice = choice.clone();
+                                    templates
+                                        .get()
+                                        .unwrap_or_default()
+                                        .into_iter()
+                                        .map(|t| {
+                                            view! {
+                                                <option
+                                                    value=t.id.to_string()
+                                                    selected=choice() == t.id.to_string()
+                                                >
+                                                    {t.template_name}
+                                                </option>
+                                            }
+                                        })
+                                        .collect_view()
+                                }}
+
+                            </select>
+                        </div>
+                    </div>
+                    <Show when=is_new.clone() fallback=|| {}>
+                        <div class="control">
+                            <div class="select is-small">
+                                <select on:change=set_type.clone()>{type_options}</select>
+                            </div>
+                        </div>
+                    </Show>
+                </div>
+            </td>
+        </tr>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{parse_calendar, parse_duration};
+    #[cfg(feature = "ssr")]
+    use chrono::NaiveDate;
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_parse_calendar() {
+        let calendar = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:1\r\n\
+            SUMMARY:Easy\r\n  Run\r\n\
+            DTSTART:20240101T070000\r\n\
+            DTEND:20240101T080000\r\n\
+            RRULE:FREQ=WEEKLY;COUNT=4\r\n\
+            EXDATE:20240108T070000,20240115T070000\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:1\r\n\
+            SUMMARY:Long Run\r\n\
+            RECURRENCE-ID:20240122T070000\r\n\
+            DTSTART:20240121T070000\r\n\
+            DURATION:PT2H\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            SUMMARY:Rest\\, really\r\n\
+            DTSTART;VALUE=DATE:20240103\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        let events = parse_calendar(calendar).unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].summary, "Easy Run");
+        assert_eq!(events[0].duration, Some(3600));
+        assert_eq!(events[0].rrule.as_deref(), Some("FREQ=WEEKLY;COUNT=4"));
+        assert_eq!(
+            events[0].exclusion_dates,
+            vec![
+                NaiveDate::from_ymd_opt(2024, 1, 8).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 1, 22).unwrap(),
+            ]
+        );
+        assert_eq!(events[1].summary, "Long Run");
+        assert_eq!(events[1].duration, Some(7200));
+        assert_eq!(events[1].rrule, None);
+        assert_eq!(events[2].summary, "Rest, really");
+        assert_eq!(events[2].duration, None);
+    }
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("PT1H30M"), Ok(5400));
+        assert_eq!(parse_duration("P1DT1S"), Ok(86401));
+        assert!(parse_duration("1H").is_err());
+        assert!(parse_duration("P99999W").is_err());
+    }
+}
@@ -0,0 +1,352 @@
+#[cfg(feature = "ssr")]
+use crate::app::{auth, pool};
+use crate::models::sport::Sport;
+#[cfg(feature = "ssr")]
+use crate::models::sport::{assign_sports, find_sport, get_sports};
+use leptos::*;
+use leptos_router::ActionForm;
+
+#[cfg(feature = "ssr")]
+use super::WorkoutType;
+
+/// Whether a string looks like a FIT sport or sub sport name, e.g. `indoor_cycling`.
+#[cfg(feature = "ssr")]
+fn is_fit_code(code: &str) -> bool {
+    !code.is_empty()
+        && code
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+#[server]
+pub async fn list_sports() -> Result<Vec<Sport>, ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    get_sports(user.id, &pool)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't load sports: {}", e)))
+}
+
+/// Adds a user defined sport, optionally recorded for a FIT sport and sub sport.
+///
+/// Existing activities are assigned to the new sport if it matches them better.
+#[server]
+pub async fn add_sport(
+    name: String,
+    parent: String,
+    workout_type: String,
+    fit_sport: String,
+    fit_sub_sport: String,
+) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ServerFnError::new("The sport needs a name".to_string()));
+    }
+    let workout_type = TryInto::<WorkoutType>::try_into(workout_type)
+        .map_err(|_| ServerFnError::new("Couldn't parse workout type".to_string()))?;
+    let parent_id = match parent.parse::<i64>() {
+        Ok(parent_id) => Some(
+            find_sport(user.id, parent_id, &pool)
+                .await
+                .map_err(|e| ServerFnError::new(format!("Couldn't load sport: {}", e)))?
+                .ok_or(ServerFnError::new("Unknown parent sport".to_string()))?,
+        ),
+        Err(_) if parent.is_empty() => None,
+        Err(_) => return Err(ServerFnError::new("Couldn't parse sport".to_string())),
+    };
+    let fit_sport = fit_sport.trim().to_lowercase();
+    let fit_sub_sport = fit_sub_sport.trim().to_lowercase();
+    if (!fit_sport.is_empty() && !is_fit_code(&fit_sport))
+        || (!fit_sub_sport.is_empty() && !is_fit_code(&fit_sub_sport))
+    {
+        return Err(ServerFnError::new(
+            "FIT sports are written in snake case, e.g. indoor_cycling".to_string(),
+        ));
+    }
+    if fit_sport.is_empty() && !fit_sub_sport.is_empty() {
+        return Err(ServerFnError::new(
+            "A FIT sub sport needs a FIT sport".to_string(),
+        ));
+    }
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    let taken = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM sports
+            WHERE lower(name)=lower($1) and (user_id IS NULL or user_id=$2::bigint)
+        ) as "taken!"
+        "#,
+        name,
+        user.id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load sports: {}", e)))?;
+    if taken {
+        return Err(ServerFnError::new(
+            "A sport with this name already exists".to_string(),
+        ));
+    }
+    let sport_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO sports (user_id, parent_id, name, workout_type)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+        user.id as i32,
+        parent_id,
+        name,
+        workout_type as _
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't save sport: {}", e)))?;
+    if !fit_sport.is_empty() {
+        sqlx::query!(
+            r#"
+            INSERT INTO sport_fit_mappings (sport_id, fit_sport, fit_sub_sport)
+            VALUES ($1, $2, $3)
+            "#,
+            sport_id,
+            fit_sport,
+            Some(fit_sub_sport).filter(|s| !s.is_empty())
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't save FIT sport: {}", e)))?;
+        assign_sports(user.id, None, &mut transaction)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Couldn't assign sports: {}", e)))?;
+    }
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't save sport: {}", e)))?;
+    Ok(())
+}
+
+/// Deletes a user defined sport and its sub sports.
+///
+/// Activities fall back to the built-in sports, templates to any sport of their workout type.
+#[server]
+pub async fn delete_sport(sport_id: i64) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    sqlx::query!(
+        r#"
+        DELETE FROM sports
+        WHERE id=$1 and user_id=$2::bigint
+        "#,
+        sport_id,
+        user.id
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't delete sport: {}", e)))?;
+    assign_sports(user.id, None, &mut transaction)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't assign sports: {}", e)))?;
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't delete sport: {}", e)))?;
+    Ok(())
+}
+
+/// Name of a sport indented by its depth, for select options.
+pub fn indented_name(sport: &Sport) -> String {
+    format!(
+        "{}{}",
+        "\u{a0}\u{a0}".repeat(sport.depth as usize),
+        sport.name
+    )
+}
+
+#[component]
+pub fn ManageSportsDialog(show: RwSignal<bool>) -> impl IntoView {
+    let add_action = create_server_action::<AddSport>();
+    let delete_action = create_server_action::<DeleteSport>();
+    let sports = create_resource(
+        move || {
+            (
+                show.get(),
+                add_action.version().get(),
+                delete_action.version().get(),
+            )
+        },
+        |(show, _, _)| async move {
+            if show {
+                list_sports().await.map(Some)
+            } else {
+                Ok(None)
+            }
+        },
+    );
+    let close = move |_| show.set(false);
+    let error = move || {
+        [add_action.value().get(), delete_action.value().get()]
+            .into_iter()
+            .find_map(|r| r.and_then(|r| r.err()))
+            .map(|e| view! { <pre class="error">"Error: " {e.to_string()}</pre> })
+    };
+    view! {
+        <Show when=move || { show.get() } fallback=|| {}>
+            <div class="modal is-active">
+                <div class="modal-background" on:click=close></div>
+                <div class="modal-card">
+                    <div class="modal-card-head">
+                        <p class="modal-card-title">"Sports"</p>
+                        <button class="delete" aria-label="close" on:click=close></button>
+                    </div>
+                    <div class="modal-card-body">
+                        <Transition fallback=move || {
+                            view! { <p>"Loading..."</p> }
+                        }>
+                            {move || match sports.get() {
+                                Some(Err(e)) => {
+                                    view! { <pre class="error">"Error: " {e.to_string()}</pre> }
+                                        .into_view()
+                                }
+                                Some(Ok(Some(sports))) => {
+                                    let parents = sports.clone();
+                                    view! {
+                                        <table class="table is-striped is-narrow is-fullwidth">
+                                            <thead>
+                                                <tr>
+                                                    <th>Sport</th>
+                                                    <th>Type</th>
+                                                    <th>FIT Sports</th>
+                                                    <th></th>
+                                                </tr>
+                                            </thead>
+                                            <tbody>
+                                                {sports
+                                                    .into_iter()
+                                                    .map(|s| {
+                                                        let sport_id = s.id;
+                                                        view! {
+                                                            <tr>
+                                                                <td class:has-text-weight-semibold=s.depth
+                                                                    == 0>{indented_name(&s)}</td>
+                                                                <td>{s.workout_type.to_string()}</td>
+                                                                <td>{s.fit_codes.join(", ")}</td>
+                                                                <td>
+                                                                    <Show when=move || s.user_defined fallback=|| {}>
+                                                                        <button
+                                                                            class="button is-small is-danger"
+                                                                            title="Delete"
+                                                                            on:click=move |_| {
+                                                                                delete_action.dispatch(DeleteSport { sport_id });
+                                                                            }
+                                                                        >
+
+                                                                            <i class="material-symbols-rounded">delete</i>
+                                                                        </button>
+                                                                    </Show>
+                                                                </td>
+                                                            </tr>
+                                                        }
+                                                    })
+                                                    .collect_view()}
+                                            </tbody>
+                                        </table>
+                                        <ActionForm action=add_action>
+                                            <p class="subtitle is-6">"Add Sport"</p>
+                                            <div class="field is-grouped">
+                                                <div class="control is-expanded">
+                                                    <input
+                                                        class="input"
+                                                        type="text"
+                                                        name="name"
+                                                        placeholder="Name"
+                                                    />
+                                                </div>
+                                                <div class="control">
+                                                    <div class="select">
+                                                        <select name="parent">
+                                                            <option value="">"No parent sport"</option>
+                                                            {parents
+                                                                .into_iter()
+                                                                .map(|s| {
+                                                                    view! { <option value=s.id>{indented_name(&s)}</option> }
+                                                                })
+                                                                .collect_view()}
+                                                        </select>
+                                                    </div>
+                                                </div>
+                                                <div class="control">
+                                                    <div class="select">
+                                                        <select name="workout_type">
+                                                            <option value="run">Run</option>
+                                                            <option value="strength">Strength</option>
+                                                            <option value="cycling">Cycling</option>
+                                                            <option value="hiking">Hiking</option>
+                                                            <option value="endurance" selected>
+                                                                General Endurance
+                                                            </option>
+                                                        </select>
+                                                    </div>
+                                                </div>
+                                            </div>
+                                            <div class="field is-grouped">
+                                                <div class="control is-expanded">
+                                                    <input
+                                                        class="input"
+                                                        type="text"
+                                                        name="fit_sport"
+                                                        placeholder="FIT sport, e.g. racket"
+                                                    />
+                                                </div>
+                                                <div class="control is-expanded">
+                                                    <input
+                                                        class="input"
+                                                        type="text"
+                                                        name="fit_sub_sport"
+                                                        placeholder="FIT sub sport, e.g. padel"
+                                                    />
+                                                </div>
+                                                <div class="control">
+                                                    <button type="submit" class="button is-success">
+                                                        <i class="material-symbols-rounded">add</i>
+                                                        Add
+                                                    </button>
+                                                </div>
+                                            </div>
+                                        </ActionForm>
+                                    }
+                                        .into_view()
+                                }
+                                _ => view! {}.into_view(),
+                            }}
+
+                        </Transition>
+                        {error}
+                    </div>
+                    <div class="modal-card-foot">
+                        <button class="button" on:click=close>
+                            Close
+                        </button>
+                    </div>
+                </div>
+            </div>
+        </Show>
+    }
+}
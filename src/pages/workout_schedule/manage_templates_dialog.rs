@@ -0,0 +1,438 @@
+#[cfg(feature = "ssr")]
+use crate::app::{auth, pool};
+use leptos::*;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "ssr")]
+use sqlx::{postgres::*, *};
+
+#[cfg(feature = "ssr")]
+use super::add_template_dialog::template_error;
+use super::add_workout_dialog::WorkoutTemplate;
+use super::template_library::{export_url, TemplateLibrary};
+
+/// A template together with its management state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateSummary {
+    pub template: WorkoutTemplate,
+    pub version: i32,
+    pub archived: bool,
+    /// How many workout instances use any version of the template.
+    pub scheduled: i64,
+}
+
+#[cfg(feature = "ssr")]
+impl sqlx::FromRow<'_, PgRow> for TemplateSummary {
+    fn from_row(row: &PgRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            template: WorkoutTemplate::from_row(row)?,
+            version: row.get("version"),
+            archived: row.get("archived"),
+            scheduled: row.get("scheduled"),
+        })
+    }
+}
+
+#[server]
+pub async fn get_template_summaries() -> Result<Vec<TemplateSummary>, ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let templates: Vec<TemplateSummary> = sqlx::query_as(
+        r#"
+        WITH RECURSIVE versions AS (
+            SELECT id, id as current_id
+            FROM workout_templates
+            WHERE user_id = $1::bigint and NOT superseded
+            UNION ALL
+            SELECT t.previous_version_id::bigint, v.current_id
+            FROM versions v
+            INNER JOIN workout_templates t ON t.id = v.id
+            WHERE t.previous_version_id IS NOT NULL
+        )
+        SELECT templates.id,
+            templates.user_id,
+            templates.template_name,
+            templates.workout_type::text,
+            templates.sport_id,
+            sports.name as sport,
+            templates.version,
+            templates.archived,
+            (
+                SELECT COUNT(*)
+                FROM workout_instances i
+                INNER JOIN versions v ON v.id = i.workout_template_id
+                WHERE v.current_id = templates.id
+            ) as scheduled,
+            COALESCE(
+                ARRAY_AGG((params.id, params.name, params.value, params.parameter_type::TEXT, params.scaling, params.position) ORDER BY params.position)
+                    FILTER (WHERE params.id IS NOT NULL),
+                '{}'
+            ) as "parameters"
+        FROM workout_templates as templates
+        LEFT JOIN workout_parameters as params ON params.workout_template_id = templates.id
+        LEFT JOIN sports ON sports.id = templates.sport_id
+        WHERE templates.user_id = $1::bigint and NOT templates.superseded
+        GROUP BY templates.id, sports.id
+        ORDER BY templates.archived, templates.template_name"#,
+    )
+    .bind(user.id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load workout templates: {}", e)))?;
+    Ok(templates)
+}
+
+#[server]
+pub async fn duplicate_workout_template(template_id: i64) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    let names: Vec<String> = sqlx::query_scalar!(
+        r#"
+        SELECT template_name
+        FROM workout_templates
+        WHERE user_id=$1::bigint and NOT superseded
+        "#,
+        user.id
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load workout templates: {}", e)))?;
+    let template = sqlx::query!(
+        r#"
+        SELECT template_name
+        FROM workout_templates
+        WHERE id=$1 and user_id=$2::bigint
+        "#,
+        template_id,
+        user.id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Unknown workout template: {}", e)))?;
+    let name = (1..)
+        .map(|i| match i {
+            1 => format!("{} (Copy)", template.template_name),
+            i => format!("{} (Copy {})", template.template_name, i),
+        })
+        .find(|n| !names.contains(n))
+        .unwrap();
+    let copy = sqlx::query!(
+        r#"
+        INSERT INTO workout_templates (user_id, template_name, workout_type, sport_id)
+        SELECT user_id, $2, workout_type, sport_id
+        FROM workout_templates
+        WHERE id=$1
+        RETURNING id
+        "#,
+        template_id,
+        name
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|e| template_error("Couldn't copy workout template", e))?;
+    sqlx::query!(
+        r#"
+        INSERT INTO workout_parameters (workout_template_id, name, parameter_type, value, scaling, position)
+        SELECT $2, name, parameter_type, value, scaling, position
+        FROM workout_parameters
+        WHERE workout_template_id=$1
+        "#,
+        template_id as i32,
+        copy.id as i32
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't copy workout parameters: {}", e)))?;
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't copy workout template: {}", e)))?;
+    Ok(())
+}
+
+/// Archived templates can't be scheduled anymore, but existing workouts stay in the calendar.
+#[server]
+pub async fn set_template_archived(template_id: i64, archived: bool) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    sqlx::query!(
+        r#"
+        UPDATE workout_templates
+        SET archived=$3
+        WHERE id=$1 and user_id=$2::bigint
+        "#,
+        template_id,
+        user.id,
+        archived
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't archive workout template: {}", e)))?;
+    Ok(())
+}
+
+/// Deletes a template with all its versions, as long as none of them is scheduled.
+#[server]
+pub async fn delete_workout_template(template_id: i64) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    let versions: Vec<i64> = sqlx::query_scalar!(
+        r#"
+        WITH RECURSIVE versions AS (
+            SELECT id, previous_version_id
+            FROM workout_templates
+            WHERE id=$1 and user_id=$2::bigint
+            UNION ALL
+            SELECT t.id, t.previous_version_id
+            FROM versions v
+            INNER JOIN workout_templates t ON t.id = v.previous_version_id
+        )
+        SELECT id as "id!"
+        FROM versions
+        "#,
+        template_id,
+        user.id
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load template versions: {}", e)))?;
+    let scheduled = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(SELECT 1 FROM workout_instances WHERE workout_template_id = ANY($1::bigint[])) as "scheduled!"
+        "#,
+        &versions[..]
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load workout instances: {}", e)))?;
+    if scheduled {
+        return Err(ServerFnError::new(
+            "The template is still scheduled, archive it instead".to_string(),
+        ));
+    }
+    sqlx::query!(
+        r#"
+        DELETE FROM workout_templates
+        WHERE id = ANY($1)
+        "#,
+        &versions[..]
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't delete workout template: {}", e)))?;
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't delete workout template: {}", e)))?;
+    Ok(())
+}
+
+#[component]
+pub fn ManageTemplatesDialog(
+    show: RwSignal<bool>,
+    #[prop(into)] on_edit: Callback<WorkoutTemplate>,
+    #[prop(into)] on_change: Callback<()>,
+) -> impl IntoView {
+    let duplicate_action = create_server_action::<DuplicateWorkoutTemplate>();
+    let archive_action = create_server_action::<SetTemplateArchived>();
+    let delete_action = create_server_action::<DeleteWorkoutTemplate>();
+    let imported = create_rw_signal(0);
+    let templates = create_resource(
+        move || {
+            (
+                show.get(),
+                duplicate_action.version().get(),
+                archive_action.version().get(),
+                delete_action.version().get(),
+                imported.get(),
+            )
+        },
+        |(show, _, _, _, _)| async move {
+            if show {
+                get_template_summaries().await.map(Some)
+            } else {
+                Ok(None)
+            }
+        },
+    );
+    create_effect(move |_| {
+        // run callback if server action was run
+        if let Some(Ok(_)) = archive_action.value().get() {
+            on_change(());
+        }
+    });
+    let close = move |_| show.set(false);
+    let error = move || {
+        [
+            duplicate_action.value().get(),
+            archive_action.value().get(),
+            delete_action.value().get(),
+        ]
+        .into_iter()
+        .find_map(|r| r.and_then(|r| r.err()))
+        .map(|e| view! { <pre class="error">"Error: " {e.to_string()}</pre> })
+    };
+    view! {
+        <Show when=move || { show.get() } fallback=|| {}>
+            <div class="modal is-active">
+                <div class="modal-background" on:click=close></div>
+                <div class="modal-card">
+                    <div class="modal-card-head">
+                        <p class="modal-card-title">"Workout templates"</p>
+                        <button class="delete" aria-label="close" on:click=close></button>
+                    </div>
+                    <div class="modal-card-body">
+                        <Transition fallback=move || {
+                            view! { <p>"Loading..."</p> }
+                        }>
+                            {move || match templates.get() {
+                                Some(Err(e)) => {
+                                    view! { <pre class="error">"Error: " {e.to_string()}</pre> }
+                                        .into_view()
+                                }
+                                Some(Ok(Some(templates))) => {
+                                    view! {
+                                        <table class="table is-striped is-fullwidth">
+                                            <thead>
+                                                <tr>
+                                                    <th>Name</th>
+                                                    <th>Type</th>
+                                                    <th>Version</th>
+                                                    <th>Scheduled</th>
+                                                    <th></th>
+                                                </tr>
+                                            </thead>
+                                            <tbody>
+                                                {templates
+                                                    .into_iter()
+                                                    .map(|t| {
+                                                        let template = t.template.clone();
+                                                        let template_id = t.template.id;
+                                                        view! {
+                                                            <tr class:has-text-grey-light=t.archived>
+                                                                <td>{t.template.template_name.clone()}</td>
+                                                                <td>
+                                                                    {t
+                                                                        .template
+                                                                        .sport
+                                                                        .clone()
+                                                                        .unwrap_or(t.template.workout_type.to_string())}
+                                                                </td>
+                                                                <td>{t.version}</td>
+                                                                <td>{t.scheduled}</td>
+                                                                <td>
+                                                                    <div class="buttons are-small">
+                                                                        <button
+                                                                            class="button"
+                                                                            title="Edit"
+                                                                            on:click=move |_| {
+                                                                                show.set(false);
+                                                                                on_edit(template.clone());
+                                                                            }
+                                                                        >
+
+                                                                            <i class="material-symbols-rounded">edit</i>
+                                                                        </button>
+                                                                        <a
+                                                                            class="button"
+                                                                            title="Export"
+                                                                            href=export_url(&[template_id])
+                                                                            download=format!("{}.json", t.template.template_name)
+                                                                        >
+
+                                                                            <i class="material-symbols-rounded">download</i>
+                                                                        </a>
+                                                                        <button
+                                                                            class="button"
+                                                                            title="Duplicate"
+                                                                            on:click=move |_| {
+                                                                                duplicate_action
+                                                                                    .dispatch(DuplicateWorkoutTemplate {
+                                                                                        template_id,
+                                                                                    });
+                                                                            }
+                                                                        >
+
+                                                                            <i class="material-symbols-rounded">content_copy</i>
+                                                                        </button>
+                                                                        <button
+                                                                            class="button"
+                                                                            title=if t.archived { "Unarchive" } else { "Archive" }
+                                                                            on:click=move |_| {
+                                                                                archive_action
+                                                                                    .dispatch(SetTemplateArchived {
+                                                                                        template_id,
+                                                                                        archived: !t.archived,
+                                                                                    });
+                                                                            }
+                                                                        >
+
+                                                                            <i class="material-symbols-rounded">
+                                                                                {if t.archived { "unarchive" } else { "archive" }}
+                                                                            </i>
+                                                                        </button>
+                                                                        <button
+                                                                            class="button is-danger"
+                                                                            title="Delete"
+                                                                            disabled=t.scheduled != 0
+                                                                            on:click=move |_| {
+                                                                                delete_action
+                                                                                    .dispatch(DeleteWorkoutTemplate {
+                                                                                        template_id,
+                                                                                    });
+                                                                            }
+                                                                        >
+
+                                                                            <i class="material-symbols-rounded">delete</i>
+                                                                        </button>
+                                                                    </div>
+                                                                </td>
+                                                            </tr>
+                                                        }
+                                                    })
+                                                    .collect_view()}
+                                            </tbody>
+                                        </table>
+                                    }
+                                        .into_view()
+                                }
+                                _ => view! {}.into_view(),
+                            }}
+
+                        </Transition>
+                        {error}
+                        <TemplateLibrary on_change=move |_| imported.update(|i| *i += 1)/>
+                    </div>
+                    <div class="modal-card-foot">
+                        <button class="button" on:click=close>
+                            Close
+                        </button>
+                        <a class="button" href=export_url(&[]) download="workout-templates.json">
+                            <i class="material-symbols-rounded">download</i>
+                            Export All
+                        </a>
+                    </div>
+                </div>
+            </div>
+        </Show>
+    }
+}
@@ -4,9 +4,14 @@ use std::{
     iter,
 };
 
-use self::{add_template_dialog::CreateWorkoutDialog, add_workout_dialog::AddWorkoutDialog};
+use self::{
+    add_template_dialog::CreateWorkoutDialog, add_workout_dialog::AddWorkoutDialog,
+    import_calendar_dialog::ImportCalendarDialog, manage_sports_dialog::ManageSportsDialog,
+    manage_templates_dialog::ManageTemplatesDialog, plan_generator_dialog::PlanGeneratorDialog,
+};
 #[cfg(feature = "ssr")]
 use crate::app::{auth, pool};
+use crate::models::workout_completion::WorkoutCompletion;
 use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Weekday};
 #[cfg(feature = "ssr")]
 use chrono::{IsoWeek, TimeZone};
@@ -26,6 +31,11 @@ use self::add_workout_dialog::WorkoutParameter;
 
 pub mod add_template_dialog;
 pub mod add_workout_dialog;
+pub mod import_calendar_dialog;
+pub mod manage_sports_dialog;
+pub mod manage_templates_dialog;
+pub mod plan_generator_dialog;
+pub mod template_library;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[cfg_attr(feature = "ssr", derive(sqlx::Type, sqlx::FromRow))]
@@ -72,6 +82,15 @@ pub struct ScheduleOverride {
     pub instance: u16,
 }
 
+/// Start of the day in local time, fails if midnight is skipped by a DST change.
+#[cfg(feature = "ssr")]
+pub fn local_midnight(date: NaiveDate) -> Result<DateTime<Local>, String> {
+    Local
+        .from_local_datetime(&date.and_time(chrono::NaiveTime::MIN))
+        .earliest()
+        .ok_or_else(|| format!("midnight of {} doesn't exist in local time", date))
+}
+
 /// A scaling entry for a week of a workout plan.
 /// Defines how the volume of workouts should be increased\decreased during this week.
 pub struct ScalingEntry {
@@ -146,6 +165,51 @@ pub async fn delete_workout_occurence(
     Ok(())
 }
 
+#[server]
+pub async fn link_activity_to_occurence(
+    activity_id: i64,
+    instance_id: i64,
+    week: (i32, u32),
+    day: Weekday,
+) -> Result<(), ServerFnError> {
+    use crate::models::workout_completion::link_activity_to_workout;
+
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let date = NaiveDate::from_isoywd_opt(week.0, week.1, day)
+        .ok_or(ServerFnError::new("Invalid date".to_string()))?;
+    let mut connection = pool.acquire().await?;
+    link_activity_to_workout(activity_id, instance_id, date, user.id, &mut connection)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't link activity: {}", e)))?;
+    Ok(())
+}
+
+#[server]
+pub async fn unlink_activity(activity_id: i64) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    sqlx::query!(
+        r#"
+        DELETE FROM workout_completions
+        WHERE user_id=$1 and activity_id=$2
+        "#,
+        user.id as _,
+        activity_id as i32
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't unlink activity: {}", e)))?;
+
+    Ok(())
+}
+
 #[component]
 pub fn WorkoutDay(
     week: WorkoutWeek,
@@ -155,6 +219,8 @@ pub fn WorkoutDay(
 ) -> impl IntoView {
     let delete_instance = create_server_action::<DeleteWorkoutInstance>();
     let delete_occurence = create_server_action::<DeleteWorkoutOccurence>();
+    let link_activity = create_server_action::<LinkActivityToOccurence>();
+    let unlink_activity = create_server_action::<UnlinkActivity>();
     create_effect(move |_| {
         // run callback if server action was run
         if delete_instance.value().get().is_some() {
@@ -167,6 +233,20 @@ pub fn WorkoutDay(
             on_change(());
         }
     });
+    create_effect(move |_| {
+        // run callback if server action was run
+        if link_activity.value().get().is_some() {
+            on_change(());
+        }
+    });
+    create_effect(move |_| {
+        // run callback if server action was run
+        if unlink_activity.value().get().is_some() {
+            on_change(());
+        }
+    });
+    let date = NaiveDate::from_isoywd_opt(week.week.0, week.week.1, day).unwrap();
+    let activities = week.activities.get(&day).cloned().unwrap_or_default();
     view! {
         <div class="column">
             <div class="columns" style="margin-bottom:0px;">
@@ -209,13 +289,27 @@ pub fn WorkoutDay(
                                             let mut s = DefaultHasher::new();
                                             e.hash(&mut s);
                                             let color = s.finish() % 360;
+                                            let completion = e.completion.clone();
+                                            let status_color = match completion
+                                                .as_ref()
+                                                .map(|c| c.compliance())
+                                            {
+                                                Some(Some(ratio)) if !(0.8..=1.2).contains(&ratio) => {
+                                                    "#eed49f"
+                                                }
+                                                Some(_) => "#a6da95",
+                                                None if date < today.date_naive() => "#ed8796",
+                                                None => "transparent",
+                                            };
+                                            let activities = activities.clone();
                                             view! {
                                                 <div class="column is-full center-align valign-wrapper py-1">
                                                     <div
                                                         class="box center-align valign-wrapper p-2 level"
                                                         style=format!(
-                                                            "position:relative;background-color:hsl({},80%,85%)",
+                                                            "position:relative;background-color:hsl({},80%,85%);border-left:6px solid {}",
                                                             color,
+                                                            status_color,
                                                         )
                                                     >
 
@@ -280,6 +374,59 @@ pub fn WorkoutDay(
 
                                                                             Delete Occurence
                                                                         </a>
+                                                                        <hr class="dropdown-divider"/>
+                                                                        {match completion.clone() {
+                                                                            Some(c) => {
+                                                                                view! {
+                                                                                    <a
+                                                                                        href="#"
+                                                                                        class="dropdown-item"
+                                                                                        on:click=move |_| {
+                                                                                            unlink_activity
+                                                                                                .dispatch(UnlinkActivity {
+                                                                                                    activity_id: c.activity_id,
+                                                                                                });
+                                                                                        }
+                                                                                    >
+
+                                                                                        Unlink Activity
+                                                                                    </a>
+                                                                                }
+                                                                                    .into_view()
+                                                                            }
+                                                                            None => {
+                                                                                activities
+                                                                                    .iter()
+                                                                                    .map(|a| {
+                                                                                        let activity_id = a.id;
+                                                                                        view! {
+                                                                                            <a
+                                                                                                href="#"
+                                                                                                class="dropdown-item"
+                                                                                                on:click=move |_| {
+                                                                                                    link_activity
+                                                                                                        .dispatch(LinkActivityToOccurence {
+                                                                                                            activity_id,
+                                                                                                            instance_id: e.id,
+                                                                                                            week: week.week,
+                                                                                                            day,
+                                                                                                        });
+                                                                                                }
+                                                                                            >
+
+                                                                                                {format!(
+                                                                                                    "Completed by {} ({})",
+                                                                                                    a.sport,
+                                                                                                    a.start_time.format("%H:%M"),
+                                                                                                )}
+
+                                                                                            </a>
+                                                                                        }
+                                                                                    })
+                                                                                    .collect_view()
+                                                                            }
+                                                                        }}
+
                                                                     </div>
                                                                 </div>
                                                             </div>
@@ -313,6 +460,33 @@ pub fn WorkoutDay(
                                                                             })
                                                                             .collect::<Vec<_>>()
                                                                     }
+                                                                    {completion
+                                                                        .clone()
+                                                                        .map(|c| {
+                                                                            view! {
+                                                                                <div class="columns">
+                                                                                    <div class="column">Completed</div>
+                                                                                    <div class="column">
+                                                                                        {format_duration(
+                                                                                                std::time::Duration::new(c.actual_duration as _, 0),
+                                                                                            )
+                                                                                            .to_string()}
+                                                                                        {c
+                                                                                            .planned_duration
+                                                                                            .map(|p| {
+                                                                                                format!(
+                                                                                                    " / {}",
+                                                                                                    format_duration(std::time::Duration::new(p as _, 0)),
+                                                                                                )
+                                                                                            })}
+
+                                                                                    </div>
+                                                                                    <div class="column">
+                                                                                        {c.compliance().map(|r| format!("{:.0}%", r * 100.0))}
+                                                                                    </div>
+                                                                                </div>
+                                                                            }
+                                                                        })}
 
                                                                 </div>
                                                             </div>
@@ -343,6 +517,10 @@ pub struct WorkoutInstance {
     active: bool,
     template: WorkoutTemplate,
     exclusion_dates: Vec<DateTime<Local>>,
+    /// Occurences before this are handled by an older version of the template.
+    valid_from: Option<DateTime<Local>>,
+    /// Occurences from this on are handled by a newer version of the template.
+    valid_until: Option<DateTime<Local>>,
 }
 #[cfg(feature = "ssr")]
 impl sqlx::FromRow<'_, PgRow> for WorkoutInstance {
@@ -364,6 +542,8 @@ impl sqlx::FromRow<'_, PgRow> for WorkoutInstance {
             active: row.get("active"),
             template: template,
             exclusion_dates: row.try_get("exclusion_dates").unwrap_or_default(),
+            valid_from: row.get("valid_from"),
+            valid_until: row.get("valid_until"),
         })
     }
 }
@@ -465,7 +645,7 @@ pub async fn get_week_scaling(
 
 #[cfg(feature = "ssr")]
 pub async fn get_workout_instances(
-    _from: DateTime<Local>,
+    from: DateTime<Local>,
     to: DateTime<Local>,
 ) -> Result<Vec<WorkoutInstance>, ServerFnError> {
     let pool = pool()?;
@@ -481,6 +661,8 @@ pub async fn get_workout_instances(
                 i.start_date,
                 i.rrule,
                 i.active,
+                i.valid_from,
+                i.valid_until,
                 (
                     t.id, 
                     t.user_id,
@@ -492,15 +674,92 @@ pub async fn get_workout_instances(
             INNER JOIN workout_templates t ON i.workout_template_id=t.id
             LEFT JOIN workout_exclusion_dates ex ON ex.workout_instance_id=i.id
             WHERE i.user_id=$1::bigint and i.active and i.start_date < $2
+                and (i.valid_from IS NULL or i.valid_from < $2)
+                and (i.valid_until IS NULL or i.valid_until > $3)
             GROUP BY i.id, i.user_id, i.start_date, i.rrule, i.active, t.id, t.user_id, t.template_name, t.workout_type
         "#,
     )
     .bind(user.id as i32)
     .bind(to)
+    .bind(from)
     .fetch_all(&pool)
     .await?;
     Ok(rrules)
 }
+
+#[cfg(feature = "ssr")]
+pub async fn get_workout_completions(
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+) -> Result<Vec<WorkoutCompletion>, ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let completions = sqlx::query_as!(
+        WorkoutCompletion,
+        r#"
+        SELECT
+            activity_id::int8 as "activity_id!",
+            workout_instance_id::int8 as "workout_instance_id!",
+            occurence_date as "occurence_date:DateTime<Local>",
+            manual,
+            planned_duration,
+            planned_distance,
+            planned_load,
+            actual_duration,
+            actual_distance,
+            actual_load
+        FROM workout_completions
+        WHERE user_id=$1::bigint and occurence_date >= $2 and occurence_date < $3
+        "#,
+        user.id,
+        from,
+        to
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load workout completions: {}", e)))?;
+    Ok(completions)
+}
+
+#[cfg(feature = "ssr")]
+pub async fn get_day_activities(
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+) -> Result<Vec<DayActivity>, ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let activities = sqlx::query_as!(
+        DayActivity,
+        r#"
+        SELECT
+            activities.id,
+            activities.start_time,
+            COALESCE(string_agg(COALESCE(sports.name, sessions.sport),', '),'General') as "sport!",
+            ROUND(activities.duration)::int4 as "duration!",
+            ROUND(SUM(sessions.distance))::int4 as distance,
+            activities.load
+        FROM activities
+        LEFT JOIN sessions on sessions.activity_id=activities.id
+        LEFT JOIN sports on sports.id=sessions.sport_id
+        WHERE activities.user_id = $1::bigint and activities.start_time >= $2 and activities.start_time < $3
+        GROUP BY activities.id
+        ORDER BY activities.start_time
+        "#,
+        user.id,
+        from,
+        to
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load activities: {}", e)))?;
+    Ok(activities)
+}
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkoutInstanceWithScaling {
     id: i64,
@@ -605,6 +864,66 @@ pub struct Workout {
     id: i64,
     name: String,
     steps: Vec<WorkoutStep>,
+    /// The activity that fulfilled this occurence, if any.
+    completion: Option<WorkoutCompletion>,
+}
+
+/// An activity done on a day of the calendar, that can be linked to a workout.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DayActivity {
+    id: i64,
+    start_time: DateTime<Local>,
+    sport: String,
+    duration: i32,
+    distance: Option<i32>,
+    load: Option<i32>,
+}
+
+/// Planned and completed volume of a week.
+///
+/// Planned values are `None` if no workout of the week specifies them.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WeeklyVolume {
+    pub week: (i32, u32),
+    pub planned_load: Option<i64>,
+    pub planned_duration: Option<i64>,
+    pub planned_distance: Option<i64>,
+    pub actual_load: i64,
+    pub actual_duration: i64,
+    pub actual_distance: i64,
+}
+
+impl WeeklyVolume {
+    pub fn start_date(&self) -> NaiveDate {
+        NaiveDate::from_isoywd_opt(self.week.0, self.week.1, Weekday::Mon).unwrap()
+    }
+
+    /// Completed vs. planned volume as short labels, falling back to duration and distance if
+    /// no load was planned.
+    pub fn summary(&self) -> Vec<String> {
+        if let Some(planned_load) = self.planned_load {
+            return vec![format!("Load {} / {}", self.actual_load, planned_load)];
+        }
+        let mut summary = Vec::new();
+        if let Some(planned_duration) = self.planned_duration {
+            summary.push(format!(
+                "{:.1}h / {:.1}h",
+                self.actual_duration as f64 / 3600.0,
+                planned_duration as f64 / 3600.0
+            ));
+        }
+        if let Some(planned_distance) = self.planned_distance {
+            summary.push(format!(
+                "{:.1}km / {:.1}km",
+                self.actual_distance as f64 / 1000.0,
+                planned_distance as f64 / 1000.0
+            ));
+        }
+        if summary.is_empty() && self.actual_load > 0 {
+            summary.push(format!("Load {}", self.actual_load));
+        }
+        summary
+    }
 }
 
 impl Hash for Workout {
@@ -619,9 +938,12 @@ pub struct WorkoutWeek {
     week: (i32, u32),
     workouts: HashMap<Weekday, Vec<Workout>>,
     scaling: i32,
+    activities: HashMap<Weekday, Vec<DayActivity>>,
+    /// Percentage of workouts up to today that were completed.
+    compliance: Option<i32>,
 }
 impl WorkoutWeek {
-    pub fn key(&self) -> (i32, u32, usize, i32, usize) {
+    pub fn key(&self) -> (i32, u32, usize, i32, usize, usize, Option<i32>, usize) {
         (
             self.week.0,
             self.week.1,
@@ -635,8 +957,40 @@ impl WorkoutWeek {
                         .sum::<usize>()
                 })
                 .sum::<usize>(),
+            self.workouts
+                .values()
+                .flatten()
+                .filter(|w| w.completion.is_some())
+                .count(),
+            self.compliance,
+            self.activities.values().map(|v| v.len()).sum(),
         )
     }
+
+    pub fn volume(&self) -> WeeklyVolume {
+        let planned = |param_type: &str| {
+            self.workouts
+                .values()
+                .flatten()
+                .flat_map(|w| &w.steps)
+                .filter(|s| s.param_type == param_type)
+                .map(|s| s.value as i64)
+                .reduce(|a, b| a + b)
+        };
+        let activities = || self.activities.values().flatten();
+        WeeklyVolume {
+            week: self.week,
+            planned_load: planned("trainingload"),
+            planned_duration: planned("time_s"),
+            planned_distance: planned("distance_m"),
+            actual_load: activities().filter_map(|a| a.load).map(|l| l as i64).sum(),
+            actual_duration: activities().map(|a| a.duration as i64).sum(),
+            actual_distance: activities()
+                .filter_map(|a| a.distance)
+                .map(|d| d as i64)
+                .sum(),
+        }
+    }
 }
 #[server]
 pub async fn get_week_workouts(
@@ -648,24 +1002,39 @@ pub async fn get_week_workouts(
         .current_user
         .ok_or(ServerFnError::new("Not logged in".to_string()))?;
 
-    let instances = get_workout_instances(
-        Local
-            .from_local_datetime(&from.and_hms_opt(0, 0, 0).unwrap())
-            .unwrap(),
-        Local
-            .from_local_datetime(&to.and_hms_opt(0, 0, 0).unwrap())
-            .unwrap(),
-    )
-    .await
-    .unwrap();
+    let from_date = Local
+        .from_local_datetime(&from.and_hms_opt(0, 0, 0).unwrap())
+        .unwrap();
+    let to_date = Local
+        .from_local_datetime(&to.and_hms_opt(0, 0, 0).unwrap())
+        .unwrap();
+    let instances = get_workout_instances(from_date, to_date).await.unwrap();
     let scalings = get_week_scaling(from.iso_week(), to.iso_week())
         .await
         .unwrap();
+    let completions: HashMap<(i64, NaiveDate), WorkoutCompletion> =
+        get_workout_completions(from_date, to_date)
+            .await?
+            .into_iter()
+            .map(|c| ((c.workout_instance_id, c.occurence_date.date_naive()), c))
+            .collect();
+    let mut activities: HashMap<IsoWeek, HashMap<Weekday, Vec<DayActivity>>> = HashMap::new();
+    for activity in get_day_activities(from_date, to_date).await? {
+        activities
+            .entry(activity.start_time.iso_week())
+            .or_default()
+            .entry(activity.start_time.weekday())
+            .or_default()
+            .push(activity);
+    }
     let mut weeks: HashMap<IsoWeek, HashMap<Weekday, Vec<Workout>>> = HashMap::new();
     // ensure each week has an entry
     for scaling in scalings.keys() {
         weeks.entry(*scaling).or_default();
     }
+    for week in activities.keys() {
+        weeks.entry(*week).or_default();
+    }
 
     for instance in instances {
         let rrule = RRuleSet::new(instance.start_date.with_timezone(&Tz::Local(Local)))
@@ -695,17 +1064,17 @@ pub async fn get_week_workouts(
         )
         .await
         .unwrap();
+        let after = instance
+            .valid_from
+            .map_or(from_date, |valid_from| valid_from.max(from_date));
+        let before = instance
+            .valid_until
+            .map_or(to_date, |valid_until| {
+                (valid_until - Duration::try_seconds(1).unwrap()).min(to_date)
+            });
         let occurences = rrule
-            .after(
-                Tz::LOCAL
-                    .from_local_datetime(&from.and_hms_opt(0, 0, 0).unwrap())
-                    .unwrap(),
-            )
-            .before(
-                Tz::LOCAL
-                    .from_local_datetime(&to.and_hms_opt(0, 0, 0).unwrap())
-                    .unwrap(),
-            )
+            .after(after.with_timezone(&Tz::LOCAL))
+            .before(before.with_timezone(&Tz::LOCAL))
             .all_unchecked();
         for occurence in occurences {
             let steps: Vec<WorkoutStep> = steps_and_scaling
@@ -735,6 +1104,9 @@ pub async fn get_week_workouts(
                 id: instance.id,
                 name: instance.template.template_name.clone(),
                 steps,
+                completion: completions
+                    .get(&(instance.id, occurence.date_naive()))
+                    .cloned(),
             };
 
             weeks
@@ -745,12 +1117,31 @@ pub async fn get_week_workouts(
                 .push(workout);
         }
     }
+    let today = Local::now().date_naive();
     let mut result: Vec<WorkoutWeek> = weeks
         .iter()
-        .map(|(week, m)| WorkoutWeek {
-            week: (week.year(), week.week()),
-            workouts: m.clone(),
-            scaling: *scalings.get(&week).unwrap_or(&0),
+        .map(|(week, m)| {
+            let due: Vec<_> = m
+                .iter()
+                .filter(|(day, _)| {
+                    NaiveDate::from_isoywd_opt(week.year(), week.week(), **day).unwrap() <= today
+                })
+                .flat_map(|(_, workouts)| workouts)
+                .collect();
+            WorkoutWeek {
+                week: (week.year(), week.week()),
+                workouts: m.clone(),
+                scaling: *scalings.get(week).unwrap_or(&0),
+                activities: activities.remove(week).unwrap_or_default(),
+                compliance: if due.is_empty() {
+                    None
+                } else {
+                    Some(
+                        (due.iter().filter(|w| w.completion.is_some()).count() * 100 / due.len())
+                            as i32,
+                    )
+                },
+            }
         })
         .collect();
     result.sort_by(|a, b| a.week.partial_cmp(&b.week).unwrap());
@@ -817,6 +1208,11 @@ pub fn WorkoutCalendar() -> impl IntoView {
     let set_scaling = create_server_action::<SetWeekScaling>();
     let show_add_workout = create_rw_signal(false);
     let show_create_workout = create_rw_signal(false);
+    let show_manage_templates = create_rw_signal(false);
+    let show_manage_sports = create_rw_signal(false);
+    let edit_template = create_rw_signal(None);
+    let show_import_calendar = create_rw_signal(false);
+    let show_plan_generator = create_rw_signal(false);
 
     let reload_calendar = move |_| {
         spawn_local(async move {
@@ -1006,6 +1402,15 @@ pub fn WorkoutCalendar() -> impl IntoView {
                                         </option>
                                     </select>
                                 </div>
+                                {item
+                                    .volume()
+                                    .summary()
+                                    .into_iter()
+                                    .map(|l| view! { <p class="help">{l}</p> })
+                                    .collect_view()}
+                                <p class="help" title="Completed workouts">
+                                    {item.compliance.map(|c| format!("{}% done", c))}
+                                </p>
                             </div>
 
                         </div>
@@ -1026,13 +1431,37 @@ pub fn WorkoutCalendar() -> impl IntoView {
                         <a
                             class="button dropdown-item"
                             alt="Add workout template"
-                            on:click=move |_| { show_create_workout.set(true) }
+                            on:click=move |_| {
+                                edit_template.set(None);
+                                show_create_workout.set(true)
+                            }
                         >
+
                             <span class="icon is-small">
                                 <i class="fas fa-dumbbell"></i>
                             </span>
                             <span>Add Template</span>
                         </a>
+                        <a
+                            class="button dropdown-item"
+                            alt="Manage workout templates"
+                            on:click=move |_| { show_manage_templates.set(true) }
+                        >
+                            <span class="icon is-small">
+                                <i class="fas fa-list"></i>
+                            </span>
+                            <span>Manage Templates</span>
+                        </a>
+                        <a
+                            class="button dropdown-item"
+                            alt="Manage sports"
+                            on:click=move |_| { show_manage_sports.set(true) }
+                        >
+                            <span class="icon is-small">
+                                <i class="fas fa-running"></i>
+                            </span>
+                            <span>Manage Sports</span>
+                        </a>
                         <a
                             class="button dropdown-item"
                             alt="Add workout entry"
@@ -1043,22 +1472,58 @@ pub fn WorkoutCalendar() -> impl IntoView {
                             </span>
                             <span>Add Workout Entry</span>
                         </a>
+                        <a
+                            class="button dropdown-item"
+                            alt="Import training plan"
+                            on:click=move |_| { show_import_calendar.set(true) }
+                        >
+                            <span class="icon is-small">
+                                <i class="fas fa-file-import"></i>
+                            </span>
+                            <span>Import Calendar</span>
+                        </a>
+                        <a
+                            class="button dropdown-item"
+                            alt="Generate training plan"
+                            on:click=move |_| { show_plan_generator.set(true) }
+                        >
+                            <span class="icon is-small">
+                                <i class="fas fa-chart-line"></i>
+                            </span>
+                            <span>Generate Plan</span>
+                        </a>
                     </div>
                 </div>
             </div>
-            <CreateWorkoutDialog show=show_create_workout/>
+            <CreateWorkoutDialog
+                show=show_create_workout
+                template=edit_template
+                on_save=reload_calendar
+            />
+            <ManageTemplatesDialog
+                show=show_manage_templates
+                on_edit=move |template| {
+                    edit_template.set(Some(template));
+                    show_create_workout.set(true);
+                }
+
+                on_change=reload_calendar
+            />
+            <ManageSportsDialog show=show_manage_sports/>
             <AddWorkoutDialog show=show_add_workout on_save=reload_calendar/>
+            <ImportCalendarDialog show=show_import_calendar on_save=reload_calendar/>
+            <PlanGeneratorDialog show=show_plan_generator on_save=reload_calendar/>
         </div>
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "ssr", derive(sqlx::Type))]
 #[cfg_attr(
     feature = "ssr",
     sqlx(type_name = "workout_type", rename_all = "snake_case")
 )]
-#[derive(strum::EnumString)]
+#[derive(strum::EnumString, strum::Display, strum::EnumIter)]
 #[strum(serialize_all = "snake_case")]
 pub enum WorkoutType {
     Run,
@@ -1068,6 +1533,18 @@ pub enum WorkoutType {
     Endurance,
 }
 
+impl WorkoutType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            WorkoutType::Run => "Run",
+            WorkoutType::Strength => "Strength",
+            WorkoutType::Cycling => "Cycling",
+            WorkoutType::Hiking => "Hiking",
+            WorkoutType::Endurance => "General Endurance",
+        }
+    }
+}
+
 #[cfg(feature = "ssr")]
 impl TryFrom<String> for WorkoutType {
     type Error = String;
@@ -0,0 +1,489 @@
+#[cfg(feature = "ssr")]
+use crate::app::{auth, pool};
+use chrono::{Datelike, Duration, Local, NaiveDate, Weekday};
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+/// Longest plan that can be generated, in weeks.
+pub const MAX_PLAN_WEEKS: i64 = 104;
+
+/// Settings for generating a periodized plan.
+///
+/// All percentages are of the volume before the plan, as weekly scaling adds up. Build weeks
+/// increase volume by `ramp_rate` percent each, so they don't compound, followed by
+/// `recovery_weeks` with `recovery_reduction` percent less volume than the last build week. The
+/// ramp rate is the maximum increase from one build week to the next, recovery weeks aren't
+/// counted. The plan ends with `taper_weeks` that reduce volume by `taper_reduction` percent per
+/// week, the last one being the week of the goal event. The week after the goal restores the
+/// volume from before the plan.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct PlanSettings {
+    pub build_weeks: u32,
+    pub recovery_weeks: u32,
+    pub ramp_rate: i32,
+    pub recovery_reduction: i32,
+    pub taper_weeks: u32,
+    pub taper_reduction: i32,
+}
+
+impl Default for PlanSettings {
+    fn default() -> Self {
+        Self {
+            build_weeks: 3,
+            recovery_weeks: 1,
+            ramp_rate: 10,
+            recovery_reduction: 30,
+            taper_weeks: 2,
+            taper_reduction: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum PlanPhase {
+    Build,
+    Recovery,
+    Taper,
+    /// The week after the goal, back to the volume before the plan.
+    Restore,
+}
+
+/// A week of a generated plan.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlannedWeek {
+    pub week: (i32, u32),
+    pub phase: PlanPhase,
+    /// Volume in percent relative to the week before the plan starts.
+    pub level: i32,
+    /// Change compared to the previous week, as stored in `weekly_scaling`.
+    pub scaling: i32,
+}
+
+/// Generates weekly scaling from the week of `start` up to the week after `goal`.
+///
+/// Cycles are aligned to the goal, so the taper directly follows a full build block. A partial
+/// cycle at the start of the plan is filled with build weeks first.
+pub fn generate_plan(
+    start: NaiveDate,
+    goal: NaiveDate,
+    settings: PlanSettings,
+) -> Result<Vec<PlannedWeek>, String> {
+    let start = start.week(Weekday::Mon).first_day();
+    let goal = goal.week(Weekday::Mon).first_day();
+    if goal < start {
+        return Err("The goal has to be after the start of the plan".to_string());
+    }
+    if settings.build_weeks == 0 {
+        return Err("There has to be at least one build week per cycle".to_string());
+    }
+    if (goal - start).num_weeks() >= MAX_PLAN_WEEKS {
+        return Err(format!(
+            "A plan can be at most {} weeks long",
+            MAX_PLAN_WEEKS
+        ));
+    }
+    let week_counts = [
+        settings.build_weeks,
+        settings.recovery_weeks,
+        settings.taper_weeks,
+    ];
+    if week_counts.iter().any(|&w| w as i64 > MAX_PLAN_WEEKS) {
+        return Err(format!(
+            "Phases can be at most {} weeks long",
+            MAX_PLAN_WEEKS
+        ));
+    }
+    let percentages = [
+        settings.ramp_rate,
+        settings.recovery_reduction,
+        settings.taper_reduction,
+    ];
+    if percentages.iter().any(|p| !(0..=50).contains(p)) {
+        return Err("Ramp rate and reductions have to be between 0% and 50%".to_string());
+    }
+    let weeks = ((goal - start).num_weeks() + 1) as u32;
+    let taper_weeks = settings.taper_weeks.min(weeks);
+    let pre_taper = weeks - taper_weeks;
+    let cycle = settings.build_weeks + settings.recovery_weeks;
+    let partial_cycle = (pre_taper + settings.recovery_weeks) % cycle;
+
+    let mut plan: Vec<PlannedWeek> = Vec::new();
+    let mut build_level = 0;
+    let mut previous_level = 0;
+    for index in 0..weeks {
+        let date = start + Duration::try_weeks(index as i64).unwrap();
+        let (phase, level) = if index >= pre_taper {
+            let taper_week = (index - pre_taper + 1) as i32;
+            (
+                PlanPhase::Taper,
+                build_level - taper_week * settings.taper_reduction,
+            )
+        } else {
+            // position counted back from the taper, which takes the place of the last recovery
+            let position = (pre_taper - 1 - index + settings.recovery_weeks) % cycle;
+            if index >= partial_cycle && position < settings.recovery_weeks {
+                (
+                    PlanPhase::Recovery,
+                    build_level - settings.recovery_reduction,
+                )
+            } else {
+                if plan.iter().any(|w| w.phase == PlanPhase::Build) {
+                    build_level += settings.ramp_rate;
+                }
+                (PlanPhase::Build, build_level)
+            }
+        };
+        let scaling = level - previous_level;
+        if !(-50..=50).contains(&scaling) {
+            return Err(format!(
+                "Week {} would change volume by {}%, at most 50% are possible",
+                date.iso_week().week(),
+                scaling
+            ));
+        }
+        plan.push(PlannedWeek {
+            week: (date.iso_week().year(), date.iso_week().week()),
+            phase,
+            level,
+            scaling,
+        });
+        previous_level = level;
+    }
+    // scaling adds up, so without this the taper would stay in force after the goal
+    let after_goal = goal + Duration::try_weeks(1).unwrap();
+    plan.push(PlannedWeek {
+        week: (after_goal.iso_week().year(), after_goal.iso_week().week()),
+        phase: PlanPhase::Restore,
+        level: 0,
+        scaling: -previous_level,
+    });
+    Ok(plan)
+}
+
+#[server]
+pub async fn apply_training_plan(
+    start: NaiveDate,
+    goal: NaiveDate,
+    settings: PlanSettings,
+) -> Result<(), ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let plan = generate_plan(start, goal, settings).map_err(ServerFnError::new)?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    let years: Vec<i32> = plan.iter().map(|w| w.week.0).collect();
+    let weeks: Vec<i32> = plan.iter().map(|w| w.week.1 as i32).collect();
+    let scalings: Vec<i32> = plan.iter().map(|w| w.scaling).collect();
+    sqlx::query!(
+        r#"
+        DELETE FROM weekly_scaling
+        WHERE user_id=$1::bigint and (year, week) IN (SELECT * FROM UNNEST($2::int4[], $3::int4[]))
+        "#,
+        user.id,
+        &years[..],
+        &weeks[..]
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't replace scaling: {}", e)))?;
+    sqlx::query!(
+        r#"
+        INSERT INTO weekly_scaling (user_id, year, week, scaling)
+        SELECT $1, * FROM UNNEST($2::int4[], $3::int4[], $4::int4[])
+        "#,
+        user.id as _,
+        &years[..],
+        &weeks[..],
+        &scalings[..]
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't save scaling: {}", e)))?;
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't save plan: {}", e)))?;
+    Ok(())
+}
+
+#[component]
+fn PlanSettingInput(
+    label: &'static str,
+    value: Signal<i32>,
+    #[prop(into)] on_input: Callback<i32>,
+    #[prop(optional)] step: Option<i32>,
+) -> impl IntoView {
+    view! {
+        <div class="column field">
+            <label class="label is-small">{label}</label>
+            <div class="control">
+                <input
+                    class="input is-small"
+                    type="number"
+                    min="0"
+                    step=step.unwrap_or(1)
+                    prop:value=value
+                    on:input=move |ev| {
+                        if let Ok(value) = event_target_value(&ev).parse::<i32>() {
+                            on_input(value);
+                        }
+                    }
+                />
+
+            </div>
+        </div>
+    }
+}
+
+#[component]
+pub fn PlanGeneratorDialog(
+    show: RwSignal<bool>,
+    #[prop(into)] on_save: Callback<()>,
+) -> impl IntoView {
+    let start = create_rw_signal(Local::now().date_naive());
+    let goal = create_rw_signal(None::<NaiveDate>);
+    let settings = create_rw_signal(PlanSettings::default());
+    let plan = create_memo(move |_| {
+        goal.get()
+            .map(|goal| generate_plan(start.get(), goal, settings.get()))
+    });
+    let apply_action = create_server_action::<ApplyTrainingPlan>();
+    create_effect(move |_| {
+        // run callback if server action was run
+        if let Some(Ok(_)) = apply_action.value().get() {
+            show.set(false);
+            on_save(());
+        }
+    });
+    let close = move |_| show.set(false);
+    let on_apply = move |_| {
+        if let Some(goal) = goal.get_untracked() {
+            apply_action.dispatch(ApplyTrainingPlan {
+                start: start.get_untracked(),
+                goal,
+                settings: settings.get_untracked(),
+            });
+        }
+    };
+    view! {
+        <Show when=move || { show.get() } fallback=|| {}>
+            <div class="modal is-active">
+                <div class="modal-background" on:click=close></div>
+                <div class="modal-card">
+                    <div class="modal-card-head">
+                        <p class="modal-card-title">"Generate training plan"</p>
+                        <button class="delete" aria-label="close" on:click=close></button>
+                    </div>
+                    <div class="modal-card-body">
+                        <div class="columns">
+                            <div class="column field">
+                                <label class="label is-small">Start</label>
+                                <div class="control">
+                                    <input
+                                        class="input is-small"
+                                        type="date"
+                                        prop:value=move || {
+                                            start.get().format("%Y-%m-%d").to_string()
+                                        }
+
+                                        on:change=move |ev| {
+                                            if let Ok(date) = NaiveDate::parse_from_str(
+                                                &event_target_value(&ev),
+                                                "%Y-%m-%d",
+                                            ) {
+                                                start.set(date);
+                                            }
+                                        }
+                                    />
+
+                                </div>
+                            </div>
+                            <div class="column field">
+                                <label class="label is-small">Goal Event</label>
+                                <div class="control">
+                                    <input
+                                        class="input is-small"
+                                        type="date"
+                                        on:change=move |ev| {
+                                            goal.set(
+                                                NaiveDate::parse_from_str(
+                                                        &event_target_value(&ev),
+                                                        "%Y-%m-%d",
+                                                    )
+                                                    .ok(),
+                                            );
+                                        }
+                                    />
+
+                                </div>
+                            </div>
+                        </div>
+                        <div class="columns">
+                            <PlanSettingInput
+                                label="Build Weeks"
+                                value=Signal::derive(move || settings.get().build_weeks as i32)
+                                on_input=move |v: i32| settings.update(|s| s.build_weeks = v as u32)
+                            />
+                            <PlanSettingInput
+                                label="Recovery Weeks"
+                                value=Signal::derive(move || settings.get().recovery_weeks as i32)
+                                on_input=move |v: i32| {
+                                    settings.update(|s| s.recovery_weeks = v as u32)
+                                }
+                            />
+
+                            <PlanSettingInput
+                                label="Taper Weeks"
+                                value=Signal::derive(move || settings.get().taper_weeks as i32)
+                                on_input=move |v: i32| settings.update(|s| s.taper_weeks = v as u32)
+                            />
+                        </div>
+                        <div class="columns">
+                            <PlanSettingInput
+                                label="Max. Ramp Rate %"
+                                value=Signal::derive(move || settings.get().ramp_rate)
+                                on_input=move |v| settings.update(|s| s.ramp_rate = v)
+                                step=5
+                            />
+                            <PlanSettingInput
+                                label="Recovery Reduction %"
+                                value=Signal::derive(move || settings.get().recovery_reduction)
+                                on_input=move |v| settings.update(|s| s.recovery_reduction = v)
+                                step=5
+                            />
+                            <PlanSettingInput
+                                label="Taper Reduction %"
+                                value=Signal::derive(move || settings.get().taper_reduction)
+                                on_input=move |v| settings.update(|s| s.taper_reduction = v)
+                                step=5
+                            />
+                        </div>
+                        <p class="help">
+                            "Percentages are of the volume before the plan and add up without compounding, a 10% ramp rate gives 110%, 120%, 130%."
+                        </p>
+                        {move || match plan.get() {
+                            Some(Err(e)) => {
+                                view! { <pre class="error">"Error: " {e}</pre> }.into_view()
+                            }
+                            Some(Ok(weeks)) => {
+                                view! {
+                                    <table class="table is-striped is-fullwidth is-narrow">
+                                        <thead>
+                                            <tr>
+                                                <th>Week</th>
+                                                <th>Phase</th>
+                                                <th>Volume</th>
+                                                <th>Scaling</th>
+                                            </tr>
+                                        </thead>
+                                        <tbody>
+                                            {weeks
+                                                .into_iter()
+                                                .map(|w| {
+                                                    view! {
+                                                        <tr>
+                                                            <td>{w.week.0} - {w.week.1}</td>
+                                                            <td>{format!("{:?}", w.phase)}</td>
+                                                            <td>{format!("{}%", 100 + w.level)}</td>
+                                                            <td>{format!("{:+}%", w.scaling)}</td>
+                                                        </tr>
+                                                    }
+                                                })
+                                                .collect_view()}
+                                        </tbody>
+                                    </table>
+                                }
+                                    .into_view()
+                            }
+                            None => view! {}.into_view(),
+                        }}
+
+                        {move || {
+                            apply_action
+                                .value()
+                                .get()
+                                .and_then(|r| r.err())
+                                .map(|e| {
+                                    view! { <pre class="error">"Error: " {e.to_string()}</pre> }
+                                })
+                        }}
+
+                    </div>
+                    <div class="modal-card-foot">
+                        <button class="button" on:click=close>
+                            Cancel
+                        </button>
+                        <button
+                            class="button is-success"
+                            disabled=move || !matches!(plan.get(), Some(Ok(_)))
+                            on:click=on_apply
+                        >
+                            <i class="material-symbols-rounded right">save</i>
+                            Apply
+                        </button>
+                    </div>
+                </div>
+            </div>
+        </Show>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{generate_plan, PlanPhase, PlanSettings};
+    #[cfg(feature = "ssr")]
+    use chrono::NaiveDate;
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_generate_plan() {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let goal = NaiveDate::from_ymd_opt(2024, 3, 9).unwrap();
+        let plan = generate_plan(start, goal, PlanSettings::default()).unwrap();
+        assert_eq!(plan.len(), 11);
+        assert_eq!(
+            plan.iter().map(|w| w.phase).collect::<Vec<_>>(),
+            vec![
+                PlanPhase::Build,
+                PlanPhase::Build,
+                PlanPhase::Build,
+                PlanPhase::Build,
+                PlanPhase::Recovery,
+                PlanPhase::Build,
+                PlanPhase::Build,
+                PlanPhase::Build,
+                PlanPhase::Taper,
+                PlanPhase::Taper,
+                PlanPhase::Restore,
+            ]
+        );
+        assert_eq!(
+            plan.iter().map(|w| w.level).collect::<Vec<_>>(),
+            vec![0, 10, 20, 30, 0, 40, 50, 60, 40, 20, 0]
+        );
+        // scaling is cumulative, so it has to add up to the level of each week
+        let mut level = 0;
+        for week in &plan {
+            level += week.scaling;
+            assert_eq!(level, week.level);
+        }
+        // after the goal week the volume is back to where it was before the plan
+        assert_eq!(plan[9].week, (2024, 10));
+        assert_eq!(plan.iter().map(|w| w.scaling).sum::<i32>(), 0);
+        assert_eq!(plan.last().unwrap().week, (2024, 11));
+        assert!(generate_plan(goal, start, PlanSettings::default()).is_err());
+        let negative = PlanSettings {
+            ramp_rate: -10,
+            ..PlanSettings::default()
+        };
+        assert!(generate_plan(start, goal, negative).is_err());
+        let far_goal = NaiveDate::from_ymd_opt(2030, 1, 1).unwrap();
+        assert!(generate_plan(start, far_goal, PlanSettings::default()).is_err());
+    }
+}
@@ -0,0 +1,514 @@
+#[cfg(feature = "ssr")]
+use std::collections::HashSet;
+
+#[cfg(feature = "ssr")]
+use crate::app::{auth, pool};
+use leptos::{server_fn::ServerFn, *};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "ssr")]
+use sqlx::PgConnection;
+#[cfg(feature = "ssr")]
+use std::str::FromStr;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::HtmlInputElement;
+
+#[cfg(feature = "ssr")]
+use super::add_template_dialog::{insert_parameters, template_error, WorkoutParam};
+#[cfg(feature = "ssr")]
+use super::add_workout_dialog::WorkoutTemplate;
+#[cfg(feature = "ssr")]
+use super::WorkoutType;
+#[cfg(feature = "ssr")]
+use crate::models::sport::find_sport_by_name;
+
+/// Identifies template documents, so other JSON files are rejected on import.
+pub const TEMPLATE_FORMAT: &str = "toedi-workout-templates";
+/// Version of the template document, increased on incompatible changes.
+pub const TEMPLATE_FORMAT_VERSION: u32 = 1;
+
+/// A set of workout templates that can be shared between users.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TemplateDocument {
+    pub format: String,
+    pub version: u32,
+    pub templates: Vec<TemplateExport>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TemplateExport {
+    pub name: String,
+    /// The workout type in snake case, e.g. `run`.
+    pub workout_type: String,
+    /// Name of the sport, e.g. `Trail Running`. Unknown sports are dropped on import.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sport: Option<String>,
+    /// Parameters in the order of the workout.
+    pub parameters: Vec<ParameterExport>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ParameterExport {
+    pub name: String,
+    pub parameter_type: String,
+    pub value: i32,
+    pub scaling: bool,
+}
+
+/// Outcome of importing a template document.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TemplateImport {
+    /// Names the templates were imported with.
+    pub imported: Vec<String>,
+    /// Templates that were skipped because the name already exists.
+    pub skipped: Vec<String>,
+}
+
+#[cfg(feature = "ssr")]
+impl From<WorkoutTemplate> for TemplateExport {
+    fn from(template: WorkoutTemplate) -> Self {
+        Self {
+            name: template.template_name,
+            workout_type: template.workout_type.to_string(),
+            sport: template.sport,
+            parameters: template
+                .parameters
+                .into_iter()
+                .map(|p| ParameterExport {
+                    name: p.name,
+                    parameter_type: p.parameter_type,
+                    value: p.value,
+                    scaling: p.scaling,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Parses and validates a template document.
+#[cfg(feature = "ssr")]
+pub fn parse_template_document(document: &str) -> Result<TemplateDocument, String> {
+    let document: TemplateDocument =
+        serde_json::from_str(document).map_err(|e| format!("Invalid template document: {}", e))?;
+    if document.format != TEMPLATE_FORMAT {
+        return Err(format!("Unknown document format '{}'", document.format));
+    }
+    if document.version == 0 || document.version > TEMPLATE_FORMAT_VERSION {
+        return Err(format!("Unsupported document version {}", document.version));
+    }
+    if document.templates.is_empty() {
+        return Err("The document doesn't contain any templates".to_string());
+    }
+    for template in document.templates.iter() {
+        if template.name.trim().is_empty() {
+            return Err("Templates need a name".to_string());
+        }
+        WorkoutType::from_str(&template.workout_type).map_err(|_| {
+            format!(
+                "Template '{}' has unknown workout type '{}'",
+                template.name, template.workout_type
+            )
+        })?;
+        if template.parameters.is_empty() {
+            return Err(format!("Template '{}' has no parameters", template.name));
+        }
+        for parameter in template.parameters.iter() {
+            if !["time_s", "distance_m", "trainingload"]
+                .contains(&parameter.parameter_type.as_str())
+            {
+                return Err(format!(
+                    "Template '{}' has unknown parameter type '{}'",
+                    template.name, parameter.parameter_type
+                ));
+            }
+            if parameter.value <= 0 {
+                return Err(format!(
+                    "Parameter '{}' of template '{}' needs a positive value",
+                    parameter.name, template.name
+                ));
+            }
+        }
+    }
+    Ok(document)
+}
+
+/// Returns `name`, or the first of `name (2)`, `name (3)`, ... that isn't taken yet.
+#[cfg(feature = "ssr")]
+pub fn resolve_name(name: &str, taken: &HashSet<String>) -> String {
+    let name = name.trim();
+    (1..)
+        .map(|i| match i {
+            1 => name.to_string(),
+            i => format!("{} ({})", name, i),
+        })
+        .find(|n| !taken.contains(&n.to_lowercase()))
+        .unwrap()
+}
+
+#[cfg(feature = "ssr")]
+fn starter_library() -> TemplateDocument {
+    parse_template_document(include_str!("starter_templates.json"))
+        .expect("the starter library to be valid")
+}
+
+/// Stores the templates of a document for a user.
+///
+/// Templates whose name is taken are renamed, or skipped if `skip_existing` is set.
+#[cfg(feature = "ssr")]
+async fn import_templates(
+    user_id: i64,
+    document: TemplateDocument,
+    skip_existing: bool,
+    executor: &mut PgConnection,
+) -> Result<TemplateImport, ServerFnError> {
+    let mut taken: HashSet<String> = sqlx::query_scalar!(
+        r#"
+        SELECT template_name
+        FROM workout_templates
+        WHERE user_id=$1::bigint and NOT superseded
+        "#,
+        user_id
+    )
+    .fetch_all(&mut *executor)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load workout templates: {}", e)))?
+    .into_iter()
+    .map(|n| n.to_lowercase())
+    .collect();
+    let mut result = TemplateImport {
+        imported: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for template in document.templates {
+        if skip_existing && taken.contains(&template.name.trim().to_lowercase()) {
+            result.skipped.push(template.name);
+            continue;
+        }
+        let name = resolve_name(&template.name, &taken);
+        let workout_type = WorkoutType::from_str(&template.workout_type)
+            .map_err(|_| ServerFnError::new("Couldn't parse workout type".to_string()))?;
+        let sport_id = match template.sport {
+            Some(sport) => find_sport_by_name(user_id, &sport, &mut *executor)
+                .await
+                .map_err(|e| ServerFnError::new(format!("Couldn't load sport: {}", e)))?,
+            None => None,
+        };
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO workout_templates (user_id, template_name, workout_type, sport_id)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#,
+            user_id as i32,
+            name,
+            workout_type as _,
+            sport_id
+        )
+        .fetch_one(&mut *executor)
+        .await
+        .map_err(|e| template_error("Error saving workout template", e))?;
+        insert_parameters(
+            inserted.id,
+            template
+                .parameters
+                .into_iter()
+                .enumerate()
+                .map(|(position, p)| WorkoutParam {
+                    name: p.name,
+                    value: p.value,
+                    param_type: p.parameter_type,
+                    scaling: p.scaling,
+                    position: position as i32,
+                })
+                .collect(),
+            executor,
+        )
+        .await?;
+        taken.insert(name.to_lowercase());
+        result.imported.push(name);
+    }
+    Ok(result)
+}
+
+/// Exports templates as a document, all current templates if `template_ids` isn't set.
+///
+/// Uses GET so the document can be downloaded with a plain link.
+#[server(ExportWorkoutTemplates, "/api", "GetJson", "export_workout_templates")]
+pub async fn export_workout_templates(
+    template_ids: Option<Vec<i64>>,
+) -> Result<TemplateDocument, ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let templates: Vec<WorkoutTemplate> = sqlx::query_as(
+        r#"
+        SELECT templates.id,
+            templates.user_id,
+            templates.template_name,
+            templates.workout_type::text,
+            templates.sport_id,
+            sports.name as sport,
+            ARRAY_AGG((params.id, params.name, params.value, params.parameter_type::TEXT, params.scaling, params.position) ORDER BY params.position) as "parameters"
+        FROM workout_templates as templates
+        INNER JOIN workout_parameters as params ON params.workout_template_id = templates.id
+        LEFT JOIN sports ON sports.id = templates.sport_id
+        WHERE templates.user_id = $1::bigint and NOT templates.superseded
+            and ($2::bigint[] IS NULL or templates.id = ANY($2))
+        GROUP BY templates.id, sports.id
+        ORDER BY templates.template_name"#,
+    )
+    .bind(user.id)
+    .bind(template_ids)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| ServerFnError::new(format!("Couldn't load workout templates: {}", e)))?;
+    Ok(TemplateDocument {
+        format: TEMPLATE_FORMAT.to_string(),
+        version: TEMPLATE_FORMAT_VERSION,
+        templates: templates.into_iter().map(TemplateExport::from).collect(),
+    })
+}
+
+#[server]
+pub async fn import_workout_templates(
+    document: String,
+    skip_existing: bool,
+) -> Result<TemplateImport, ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let document = parse_template_document(&document).map_err(ServerFnError::new)?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't start transaction:{}", e)))?;
+    let result = import_templates(user.id, document, skip_existing, &mut transaction).await?;
+    transaction
+        .commit()
+        .await
+        .map_err(|e| ServerFnError::new(format!("Couldn't import workout templates: {}", e)))?;
+    Ok(result)
+}
+
+#[server]
+pub async fn get_starter_templates() -> Result<Vec<TemplateExport>, ServerFnError> {
+    Ok(starter_library().templates)
+}
+
+#[server]
+pub async fn clone_starter_template(name: String) -> Result<TemplateImport, ServerFnError> {
+    let pool = pool()?;
+    let auth = auth()?;
+    let user = auth
+        .current_user
+        .ok_or(ServerFnError::new("Not logged in".to_string()))?;
+    let mut document = starter_library();
+    document.templates.retain(|t| t.name == name);
+    if document.templates.is_empty() {
+        return Err(ServerFnError::new(format!(
+            "Unknown starter template '{}'",
+            name
+        )));
+    }
+    let mut connection = pool.acquire().await?;
+    import_templates(user.id, document, false, &mut connection).await
+}
+
+/// Link to download the given templates, or all of them, as a document.
+pub fn export_url(template_ids: &[i64]) -> String {
+    let query = template_ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| format!("template_ids[{}]={}", i, id))
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{}?{}", ExportWorkoutTemplates::PATH, query)
+}
+
+/// Import of template documents and the starter library.
+#[component]
+pub fn TemplateLibrary(#[prop(into)] on_change: Callback<()>) -> impl IntoView {
+    let skip_existing = create_rw_signal(false);
+    let import_action = create_server_action::<ImportWorkoutTemplates>();
+    let clone_action = create_server_action::<CloneStarterTemplate>();
+    let starter_templates = create_resource(|| (), |_| get_starter_templates());
+    create_effect(move |_| {
+        // run callback if server action was run
+        if let Some(Ok(_)) = import_action.value().get() {
+            on_change(());
+        }
+    });
+    create_effect(move |_| {
+        // run callback if server action was run
+        if let Some(Ok(_)) = clone_action.value().get() {
+            on_change(());
+        }
+    });
+    let on_file = move |ev: leptos::ev::Event| {
+        let input = event_target::<HtmlInputElement>(&ev);
+        if let Some(file) = input.files().and_then(|f| f.get(0)) {
+            spawn_local(async move {
+                if let Some(document) = JsFuture::from(file.text())
+                    .await
+                    .ok()
+                    .and_then(|t| t.as_string())
+                {
+                    import_action.dispatch(ImportWorkoutTemplates {
+                        document,
+                        skip_existing: skip_existing.get_untracked(),
+                    });
+                }
+            });
+        }
+    };
+    let result = move || {
+        [import_action.value().get(), clone_action.value().get()]
+            .into_iter()
+            .flatten()
+            .next()
+            .map(|r| match r {
+                Ok(result) => view! {
+                    <p class="help is-success">
+                        {format!("Imported: {}", result.imported.join(", "))}
+                        {(!result.skipped.is_empty())
+                            .then(|| format!(" Skipped: {}", result.skipped.join(", ")))}
+                    </p>
+                }
+                .into_view(),
+                Err(e) => view! { <pre class="error">"Error: " {e.to_string()}</pre> }.into_view(),
+            })
+    };
+    view! {
+        <div class="box">
+            <div class="field is-grouped">
+                <div class="control">
+                    <div class="file is-small">
+                        <label class="file-label">
+                            <input
+                                class="file-input"
+                                type="file"
+                                name="templates"
+                                accept=".json,application/json"
+                                on:change=on_file
+                            />
+                            <span class="file-cta">
+                                <span class="file-icon">
+                                    <i class="fas fa-upload"></i>
+                                </span>
+                                <span class="file-label">Import Templates...</span>
+                            </span>
+                        </label>
+                    </div>
+                </div>
+                <div class="control">
+                    <label class="checkbox is-small">
+                        <input
+                            type="checkbox"
+                            prop:checked=skip_existing
+                            on:change=move |_| skip_existing.update(|v| *v = !*v)
+                        />
+                        " Skip existing names instead of renaming"
+                    </label>
+                </div>
+            </div>
+            {result}
+        </div>
+        <p class="has-text-weight-semibold">Starter Library</p>
+        <Transition fallback=move || {
+            view! { <p>"Loading..."</p> }
+        }>
+            {move || {
+                starter_templates
+                    .get()
+                    .and_then(|t| t.ok())
+                    .map(|templates| {
+                        view! {
+                            <table class="table is-narrow is-fullwidth">
+                                <tbody>
+                                    {templates
+                                        .into_iter()
+                                        .map(|t| {
+                                            let name = t.name.clone();
+                                            view! {
+                                                <tr>
+                                                    <td>{t.name.clone()}</td>
+                                                    <td>{t.sport.clone().unwrap_or(t.workout_type.clone())}</td>
+                                                    <td>
+                                                        {t
+                                                            .parameters
+                                                            .iter()
+                                                            .map(|p| p.name.clone())
+                                                            .collect::<Vec<_>>()
+                                                            .join(", ")}
+                                                    </td>
+                                                    <td>
+                                                        <button
+                                                            class="button is-small"
+                                                            title="Add to my templates"
+                                                            on:click=move |_| {
+                                                                clone_action
+                                                                    .dispatch(CloneStarterTemplate {
+                                                                        name: name.clone(),
+                                                                    });
+                                                            }
+                                                        >
+
+                                                            <i class="material-symbols-rounded">add</i>
+                                                        </button>
+                                                    </td>
+                                                </tr>
+                                            }
+                                        })
+                                        .collect_view()}
+                                </tbody>
+                            </table>
+                        }
+                    })
+            }}
+
+        </Transition>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "ssr")]
+    use super::{parse_template_document, resolve_name, starter_library};
+    #[cfg(feature = "ssr")]
+    use std::collections::HashSet;
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_parse_template_document() {
+        assert!(!starter_library().templates.is_empty());
+        let document = r#"{
+            "format": "toedi-workout-templates",
+            "version": 1,
+            "templates": [{
+                "name": "Easy Run",
+                "workout_type": "run",
+                "parameters": [{"name": "Easy", "parameter_type": "time_s", "value": 1800, "scaling": true}]
+            }]
+        }"#;
+        let parsed = parse_template_document(document).unwrap();
+        assert_eq!(parsed.templates[0].parameters[0].value, 1800);
+        assert!(parse_template_document(&document.replace("\"run\"", "\"swim\"")).is_err());
+        assert!(parse_template_document(&document.replace("time_s", "pace")).is_err());
+        assert!(parse_template_document(&document.replace("1800", "0")).is_err());
+        assert!(
+            parse_template_document(&document.replace("\"version\": 1", "\"version\": 2")).is_err()
+        );
+        assert!(parse_template_document("{}").is_err());
+    }
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn test_resolve_name() {
+        let taken: HashSet<String> = ["easy run".to_string(), "easy run (2)".to_string()].into();
+        assert_eq!(resolve_name("Long Run", &taken), "Long Run");
+        assert_eq!(resolve_name("Easy Run ", &taken), "Easy Run (3)");
+    }
+}